@@ -0,0 +1,47 @@
+//! Compares restoring a cache from a snapshot of `(key, value, frequency)`
+//! entries with `insert_with_frequency()` in a loop against `bulk_load()`.
+//! 
+//! Run with: cargo bench --bench bulk_load
+//! 
+
+use std::time::Instant;
+
+use lfu_cache::LfuCache;
+
+const ENTRIES : u64 = 1_000_000;
+const FREQS   : u64 = 1_000;
+
+/// A snapshot with its entries in no particular frequency order, the way one
+/// read back from a hash map would be.
+/// 
+fn snapshot() -> Vec<(u64, u64, usize)> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+
+    (0..ENTRIES).map(|key| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (key, key, (state % FREQS + 1) as usize)
+    }).collect()
+}
+
+fn main() {
+    let entries = snapshot();
+    let start   = Instant::now();
+    let mut cache = LfuCache::new(ENTRIES as usize);
+
+    for &(key, value, freq) in &entries {
+        cache.insert_with_frequency(key, value, freq);
+    }
+    let naive = start.elapsed();
+    drop(cache);
+
+    let start = Instant::now();
+    let cache = LfuCache::bulk_load(ENTRIES as usize, entries);
+    let bulk  = start.elapsed();
+
+    assert_eq!(cache.len(), ENTRIES as usize);
+
+    println!("insert_with_frequency: {naive:>10.2?}");
+    println!("            bulk_load: {bulk:>10.2?}");
+}
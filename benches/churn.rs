@@ -0,0 +1,97 @@
+//! Measures inserts of new keys into a full cache, each of which evicts the
+//! LFU item, with a stream of keys that are read once and then never again
+//! going past a set of hot keys. Each new key evicts the one before, which is
+//! alone in its queue, so the new key can take that queue over instead of it
+//! being dropped and a frequency 1 queue made. Without reads, the emptied 
+//! frequency 1 queue is reused either way. With string keys, the copy of the
+//! new key the queue keeps is cloned into the evicted key's buffer, so it
+//! takes no allocation either.
+//! 
+//! Run with: cargo bench --bench churn
+//! 
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::time::Instant;
+
+use lfu_cache::LfuCache;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) 
+        -> *mut u8 
+    {
+        ALLOCS.fetch_add(1, Relaxed);
+        System.realloc(ptr, layout, size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const CAPACITY : u64 = 10_000;
+const INSERTS  : u64 = 2_000_000;
+
+fn run(name: &str, reads: usize) {
+    let mut cache = LfuCache::with_seed(CAPACITY as usize, 1);
+
+    for key in 0..CAPACITY {
+        cache.insert(key, key);
+        for _ in 0..3 { cache.get(&key); }
+    }
+    let before = ALLOCS.load(Relaxed);
+    let start  = Instant::now();
+
+    for key in CAPACITY..CAPACITY + INSERTS {
+        cache.insert(key, key);
+        for _ in 0..reads { cache.get(&key); }
+    }
+    let elapsed = start.elapsed();
+    let allocs  = ALLOCS.load(Relaxed) - before;
+
+    println!("{name:>8}: {:>6.3} allocations/insert, {:>6.2?}/insert",
+             allocs as f64 / INSERTS as f64, elapsed / INSERTS as u32);
+}
+
+/// Like `run()` without reads, for string keys, which are made up front so
+/// only the allocations the cache makes are counted.
+/// 
+fn run_strings(name: &str) {
+    let mut cache = LfuCache::with_seed(CAPACITY as usize, 1);
+    let key       = |n: u64| format!("key number {n:012}");
+
+    for n in 0..CAPACITY {
+        cache.insert(key(n), n);
+        for _ in 0..3 { cache.get(&key(n)); }
+    }
+    let keys   = (CAPACITY..CAPACITY + INSERTS).map(key).collect::<Vec<_>>();
+    let before = ALLOCS.load(Relaxed);
+    let start  = Instant::now();
+
+    for (key, n) in keys.into_iter().zip(CAPACITY..) {
+        cache.insert(key, n);
+    }
+    let elapsed = start.elapsed();
+    let allocs  = ALLOCS.load(Relaxed) - before;
+
+    println!("{name:>8}: {:>6.3} allocations/insert, {:>6.2?}/insert",
+             allocs as f64 / INSERTS as f64, elapsed / INSERTS as u32);
+}
+
+fn main() {
+    run("no reads", 0);
+    run("one read", 1);
+    run_strings("strings");
+}
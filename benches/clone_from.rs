@@ -0,0 +1,82 @@
+//! Compares repeatedly snapshotting a cache with `clone()` against 
+//! `clone_from()` into a reused snapshot, counting allocations with a wrapper
+//! around the system allocator.
+//! 
+//! Run with: cargo bench --bench clone_from
+//! 
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::time::Instant;
+
+use lfu_cache::LfuCache;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) 
+        -> *mut u8 
+    {
+        ALLOCS.fetch_add(1, Relaxed);
+        System.realloc(ptr, layout, size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ENTRIES : u64   = 10_000;
+const ROUNDS  : usize = 50;
+
+/// Mutates the cache a little between snapshots, the way a live cache would.
+/// 
+fn churn(cache: &mut LfuCache<String, String>, round: usize) {
+    for i in 0..500 {
+        let key = format!("key-{}", (i * 7919 + round as u64 * 104_729) 
+                                    % (ENTRIES * 2));
+        if i % 2 == 0 {
+            cache.get(&key);
+        } else {
+            cache.insert(key, format!("value-{round}-{i}"));
+        }
+    }
+}
+
+fn run(name: &str, snapshot: impl Fn(&mut LfuCache<String, String>, 
+                                      &LfuCache<String, String>)) 
+{
+    let mut cache = LfuCache::new(ENTRIES as usize);
+
+    for i in 0..ENTRIES * 2 {
+        cache.insert(format!("key-{i}"), format!("value-{i}"));
+    }
+    let mut buffer = cache.clone();
+    let mut allocs = 0;
+    let     start  = Instant::now();
+
+    for round in 0..ROUNDS {
+        churn(&mut cache, round);
+
+        let before = ALLOCS.load(Relaxed);
+        snapshot(&mut buffer, &cache);
+        allocs += ALLOCS.load(Relaxed) - before;
+    }
+    println!("{name:>10}: {:>8} allocations/snapshot, {:>10.2?}/round", 
+             allocs / ROUNDS, start.elapsed() / ROUNDS as u32);
+}
+
+fn main() {
+    run("clone", |buffer, cache| *buffer = cache.clone());
+    run("clone_from", |buffer, cache| buffer.clone_from(cache));
+}
@@ -0,0 +1,47 @@
+//! Measures lookups and inserts with the standard library's `RandomState`
+//! against `SeededState`, the default hasher with the `fast-hash` feature,
+//! on `u64` keys and on short `&str` keys.
+//! 
+//! Run with: cargo bench --bench hashers
+//! 
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::time::Instant;
+
+use lfu_cache::{LfuCache, SeededState};
+
+const KEYS     : usize = 10_000;
+const ACCESSES : usize = 5_000_000;
+
+fn run<K, S>(name: &str, keys: &[K], hash_builder: S)
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    let mut cache = LfuCache::with_hasher(KEYS / 2, hash_builder);
+    let start     = Instant::now();
+
+    // Half the keys fit, so about half the accesses miss and insert.
+    for i in 0..ACCESSES {
+        let key = &keys[i.wrapping_mul(7919) % keys.len()];
+
+        if cache.get(key).is_none() {
+            cache.insert(key.clone(), i);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    println!("{name:>16}: {:>6.2?}/access", elapsed / ACCESSES as u32);
+}
+
+fn main() {
+    let ints    = (0..KEYS as u64).map(|i| i * 31).collect::<Vec<_>>();
+    let strings = (0..KEYS).map(|i| format!("k{i}")).collect::<Vec<_>>();
+    let strs    = strings.iter().map(String::as_str).collect::<Vec<_>>();
+
+    run("u64 random",  &ints, RandomState::new());
+    run("u64 seeded",  &ints, SeededState::new(0));
+    run("&str random", &strs, RandomState::new());
+    run("&str seeded", &strs, SeededState::new(0));
+}
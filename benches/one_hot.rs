@@ -0,0 +1,65 @@
+//! Measures frequency increments on a key that's alone in its queue, the way
+//! a single very hot key is, against two hot keys that take turns and so 
+//! keep sharing queues. The lone key's queue is relabeled with each access,
+//! with no queue made or dropped, which shows up as no allocations.
+//! 
+//! Run with: cargo bench --bench one_hot
+//! 
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::time::Instant;
+
+use lfu_cache::LfuCache;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) 
+        -> *mut u8 
+    {
+        ALLOCS.fetch_add(1, Relaxed);
+        System.realloc(ptr, layout, size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const KEYS     : u64 = 1_000;
+const ACCESSES : u64 = 5_000_000;
+
+fn run(name: &str, hot: &[u64]) {
+    let mut cache = LfuCache::with_seed(KEYS as usize, 1);
+
+    for key in 0..KEYS {
+        cache.insert(key, key);
+    }
+    let before = ALLOCS.load(Relaxed);
+    let start  = Instant::now();
+
+    for i in 0..ACCESSES {
+        cache.get(&hot[i as usize % hot.len()]);
+    }
+    let elapsed = start.elapsed();
+    let allocs  = ALLOCS.load(Relaxed) - before;
+
+    println!("{name:>8}: {:>6.3} allocations/access, {:>6.2?}/access",
+             allocs as f64 / ACCESSES as f64, elapsed / ACCESSES as u32);
+}
+
+fn main() {
+    run("one hot", &[0]);
+    run("two hot", &[0, 1]);
+}
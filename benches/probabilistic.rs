@@ -0,0 +1,61 @@
+//! Compares exact frequency counting against probabilistic increments on a
+//! skewed workload. Every increment moves a key to another frequency queue,
+//! and often creates or removes a queue along the way; this counts how many
+//! of those moves each mode makes, and how long the workload takes.
+//! 
+//! Run with: cargo bench --bench probabilistic
+//! 
+
+use std::time::Instant;
+
+use lfu_cache::{LfuCache, LfuCacheBuilder, SeededState};
+
+const KEYS     : u64 = 10_000;
+const ACCESSES : u64 = 2_000_000;
+
+/// A skewed stream of keys: low keys are accessed far more often than high
+/// ones.
+/// 
+fn keys() -> impl Iterator<Item = u64> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+
+    (0..ACCESSES).map(move |_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        let a = state % KEYS;
+        let b = (state >> 32) % KEYS;
+        a * b / KEYS
+    })
+}
+
+fn run(name: &str, mut cache: LfuCache<u64, u64, SeededState>) {
+    for key in 0..KEYS {
+        cache.insert(key, key);
+    }
+    let start = Instant::now();
+
+    for key in keys() {
+        cache.get(&key);
+    }
+    let elapsed = start.elapsed();
+    let hist    = cache.frequency_histogram();
+    let moves   = hist.iter().map(|(freq, n)| (freq - 1) * n).sum::<usize>();
+
+    println!("{name:>13}: {moves:>8} queue moves, {:>4} queues, {elapsed:>10.2?}",
+             hist.len());
+}
+
+fn main() {
+    run("exact", LfuCache::with_seed(KEYS as usize, 1));
+
+    for factor in [1, 10, 100] {
+        run(&format!("factor = {factor}"),
+            LfuCacheBuilder::new(KEYS as usize)
+                .hasher(SeededState::new(1))
+                .probabilistic_increments(factor)
+                .rng_seed(1)
+                .build());
+    }
+}
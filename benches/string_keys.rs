@@ -0,0 +1,94 @@
+//! Measures lookups, inserts and removals on 64-byte `String` keys, which are
+//! slow to hash and to compare, so the time goes mostly to the map. The map
+//! keeps each key's hash, and the frequency queues keep it too, so an
+//! operation hashes its key once, an insert into a full cache doesn't hash
+//! the key it evicts, and growing the map hashes nothing. Each phase counts
+//! the hashes it takes to show it.
+//! 
+//! Run with: cargo bench --bench string_keys
+//! 
+
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::time::Instant;
+
+use lfu_cache::LfuCache;
+
+const KEYS     : usize = 20_000;
+const ACCESSES : usize = 2_000_000;
+
+static HASHED: AtomicUsize = AtomicUsize::new(0);
+
+/// A `String` key that counts the times it's hashed.
+/// 
+#[derive(Clone, PartialEq, Eq)]
+struct Key(String);
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        HASHED.fetch_add(1, Relaxed);
+        self.0.hash(state);
+    }
+}
+
+/// Returns a 64-byte key, whose first 48 bytes all keys share, so comparing
+/// keys that aren't equal takes a while too.
+/// 
+fn key(i: usize) -> Key {
+    Key(format!("{:-<48}{i:016}", "tenant/acme/region/eu-west/objects/"))
+}
+
+/// Runs `f`, which does `ops` operations, and prints the time and the hashes
+/// each took.
+/// 
+fn phase(name: &str, ops: usize, f: impl FnOnce()) {
+    let hashed = HASHED.load(Relaxed);
+    let start  = Instant::now();
+
+    f();
+
+    let elapsed = start.elapsed();
+    let hashes  = HASHED.load(Relaxed) - hashed;
+
+    println!("{name:>8}: {:>6.2?}/op, {:.2} hashes/op",
+             elapsed / ops as u32, hashes as f64 / ops as f64);
+}
+
+fn main() {
+    let keys = (0..KEYS).map(key).collect::<Vec<_>>();
+
+    // Filling the cache grows the map from nothing.
+    let mut cache = LfuCache::new(KEYS / 2);
+
+    phase("fill", KEYS / 2, || {
+        for (i, key) in keys.iter().take(KEYS / 2).enumerate() {
+            cache.insert(key.clone(), i);
+        }
+    });
+
+    // Each insert of the other half evicts a key of the first.
+    phase("evict", KEYS / 2, || {
+        for (i, key) in keys.iter().skip(KEYS / 2).enumerate() {
+            cache.insert(key.clone(), i);
+        }
+    });
+
+    // Half the keys fit, so about half the accesses miss and insert.
+    phase("mixed", ACCESSES, || {
+        for i in 0..ACCESSES {
+            let key = &keys[i.wrapping_mul(7919) % KEYS];
+
+            if cache.get(key).is_none() {
+                cache.insert(key.clone(), i);
+            }
+        }
+    });
+
+    let mut removed = 0;
+
+    phase("remove", KEYS, || {
+        removed = keys.iter().filter(|key| cache.remove(key).is_some())
+                             .count();
+    });
+    assert_eq!(removed, KEYS / 2);
+}
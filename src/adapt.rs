@@ -0,0 +1,300 @@
+//! Adaptive capacity, for caches that should be as small as they can be while
+//! still hitting often enough, see `LfuCache::enable_adaptive_capacity()`.
+//! The cache counts its hits and misses, and remembers the hashes of the keys
+//! it evicted most recently, its ghosts. A miss on a ghost is a sign that a
+//! bigger cache would have hit. Every so many lookups, `LfuCache::adapt()`
+//! compares the hit ratio since the last time with the target, and grows the
+//! capacity if it's short and the ghosts say more room would help, or shrinks
+//! it if the ratio is well above the target.
+//! 
+
+use std::collections::{HashSet, VecDeque};
+
+/// The hit ratio an adaptive cache aims for, and the bounds and pace of its
+/// capacity changes, see `LfuCache::enable_adaptive_capacity()`.
+/// 
+/// ```
+/// use lfu_cache::AdaptiveCapacity;
+/// 
+/// let adaptive = AdaptiveCapacity::target_hit_ratio(0.9)
+///                                 .bounds(100, 10_000)
+///                                 .step(0.25);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveCapacity {
+    target      : f64,
+    min         : usize,
+    max         : usize,
+    step        : f64,
+    min_lookups : u64,
+}
+
+impl AdaptiveCapacity {
+    /// Aims for a hit ratio of `ratio`, within the default bounds of 1 and
+    /// `usize::MAX` items, changing the capacity by a tenth at most once in
+    /// 1000 lookups.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `ratio` isn't more than 0 and less than 1.
+    /// 
+    pub fn target_hit_ratio(ratio: f64) -> Self {
+        assert!(ratio > 0.0 && ratio < 1.0,
+                "the target hit ratio must be in (0, 1)");
+        Self {
+            target      : ratio,
+            min         : 1,
+            max         : usize::MAX,
+            step        : 0.1,
+            min_lookups : 1000,
+        }
+    }
+
+    /// Keeps the capacity between `min` and `max` items.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `min` is 0 or more than `max`.
+    /// 
+    pub fn bounds(mut self, min: usize, max: usize) -> Self {
+        assert!(min > 0 && min <= max, "the bounds must be 0 < min <= max");
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    /// Changes the capacity by `fraction` of itself at a time, or by at
+    /// least one item.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `fraction` isn't more than 0 and at most 1.
+    /// 
+    pub fn step(mut self, fraction: f64) -> Self {
+        assert!(fraction > 0.0 && fraction <= 1.0,
+                "the step must be in (0, 1]");
+        self.step = fraction;
+        self
+    }
+
+    /// Only changes the capacity once there have been `lookups` since the
+    /// last change was considered, so it's judged on enough of the workload.
+    /// 
+    pub fn min_lookups(mut self, lookups: u64) -> Self {
+        self.min_lookups = lookups.max(1);
+        self
+    }
+
+    /// Returns the hit ratio aimed for.
+    /// 
+    pub fn target(&self) -> f64 {
+        self.target
+    }
+}
+
+/// A capacity change made by `LfuCache::adapt()`, and the lookups it was
+/// based on.
+/// 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapacityChange {
+    /// The capacity before the change.
+    pub from    : usize,
+    /// The capacity after it.
+    pub to      : usize,
+    /// The hits since the last change was considered.
+    pub hits    : u64,
+    /// The lookups since the last change was considered.
+    pub lookups : u64,
+}
+
+impl CapacityChange {
+    /// Returns the hit ratio the change was based on.
+    /// 
+    pub fn hit_ratio(&self) -> f64 {
+        self.hits as f64 / self.lookups as f64
+    }
+
+    /// Returns `true` if the capacity was raised.
+    /// 
+    pub fn grew(&self) -> bool {
+        self.to > self.from
+    }
+}
+
+/// The state of adaptive capacity: the settings, the lookups counted since
+/// the last change was considered, and the ghosts.
+/// 
+#[derive(Clone, Debug)]
+pub(crate) struct Adapter {
+    config     : AdaptiveCapacity,
+    hits       : u64,
+    lookups    : u64,
+    ghost_hits : u64,
+    ghosts     : VecDeque<u64>,
+    ghost_set  : HashSet<u64>,
+}
+
+impl Adapter {
+    pub(crate) fn new(config: AdaptiveCapacity) -> Self {
+        Self {
+            config,
+            hits       : 0,
+            lookups    : 0,
+            ghost_hits : 0,
+            ghosts     : VecDeque::new(),
+            ghost_set  : HashSet::new(),
+        }
+    }
+
+    /// Returns the capacity clamped to the bounds.
+    /// 
+    pub(crate) fn clamp(&self, capacity: usize) -> usize {
+        capacity.clamp(self.config.min, self.config.max)
+    }
+
+    /// Counts a lookup of the key with the hash `hash`, and whether it hit.
+    /// 
+    pub(crate) fn lookup(&mut self, hit: bool, hash: u64) {
+        self.lookups += 1;
+
+        if hit {
+            self.hits += 1;
+        } else if self.ghost_set.remove(&hash) {
+            self.ghost_hits += 1;
+        }
+    }
+
+    /// Remembers an evicted key by its hash. As many ghosts are kept as the
+    /// cache holds items, the oldest going first.
+    /// 
+    pub(crate) fn evicted(&mut self, hash: u64, capacity: usize) {
+        if !self.ghost_set.insert(hash) { return; }
+
+        self.ghosts.push_back(hash);
+
+        while self.ghosts.len() > capacity {
+            if let Some(oldest) = self.ghosts.pop_front() {
+                self.ghost_set.remove(&oldest);
+            }
+        }
+    }
+
+    /// Decides on a new capacity, once there have been enough lookups, and
+    /// starts counting them over. The capacity grows if the hit ratio is
+    /// short of the target and ghost hits make up at least a tenth of the
+    /// shortfall, and shrinks if the ratio is more than halfway from the
+    /// target to 1. Returns the change, if any.
+    /// 
+    pub(crate) fn decide(&mut self, capacity: usize)
+        -> Option<CapacityChange>
+    {
+        if self.lookups < self.config.min_lookups { return None; }
+
+        let AdaptiveCapacity { target, min, max, step, .. } = self.config;
+
+        let (hits, lookups) = (self.hits, self.lookups);
+        let ratio     = hits as f64 / lookups as f64;
+        let shortfall = target * lookups as f64 - hits as f64;
+        let delta     = ((capacity as f64 * step) as usize).max(1);
+        let from      = capacity;
+        let capacity  = self.clamp(capacity);
+
+        let to = if shortfall > 0.0
+                    && self.ghost_hits as f64 * 10.0 >= shortfall
+        {
+            capacity.saturating_add(delta).min(max)
+        } else if ratio > target + (1.0 - target) / 2.0 {
+            capacity.saturating_sub(delta).max(min)
+        } else {
+            capacity
+        };
+        self.hits       = 0;
+        self.lookups    = 0;
+        self.ghost_hits = 0;
+
+        (to != from).then_some(CapacityChange { from, to, hits, lookups })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LfuCacheBuilder, MaintenanceBudget};
+    use crate::rng::Rng;
+
+    #[test]
+    fn capacity_follows_the_workload() {
+        let adaptive  = AdaptiveCapacity::target_hit_ratio(0.9)
+                                         .bounds(20, 1000)
+                                         .min_lookups(500);
+        let mut cache = LfuCacheBuilder::new(50).stats()
+                                                .adaptive_capacity(adaptive)
+                                                .build();
+        let mut rng   = Rng::new(7);
+        let mut run   = |cache: &mut crate::LfuCache<u64, ()>, keys: u64| {
+            let mut peak = 0;
+
+            for i in 0..40_000 {
+                let key = rng.next_u64() % keys;
+
+                if cache.get(&key).is_none() { cache.insert(key, ()); }
+
+                if i % 100 == 0 {
+                    cache.run_maintenance(MaintenanceBudget::unlimited());
+                    peak = peak.max(cache.capacity());
+                }
+                assert!(cache.len() <= cache.capacity());
+            }
+            peak
+        };
+        // 300 keys in turn don't fit, so the cache grows until enough of them
+        // do, but not much further.
+        let peak = run(&mut cache, 300);
+
+        assert!(peak > 250 && peak < 400, "{peak}");
+        assert!(cache.capacity() > 250, "{}", cache.capacity());
+
+        let grows = cache.stats().unwrap().grows;
+
+        assert!(grows > 10);
+
+        // Then the workload narrows to 10 keys, and the cache shrinks to its
+        // lower bound.
+        let peak = run(&mut cache, 10);
+
+        assert!(peak > 250);
+        assert_eq!(cache.capacity(), 20);
+        assert_eq!(cache.stats().unwrap().grows, grows);
+        assert!(cache.stats().unwrap().shrinks > 10);
+    }
+
+    #[test]
+    fn changes_are_paced_and_bounded() {
+        let config  = AdaptiveCapacity::target_hit_ratio(0.5).bounds(10, 12)
+                                                            .min_lookups(4);
+        let mut adapter = Adapter::new(config);
+
+        // Too few lookups to go by.
+        for _ in 0..3 { adapter.lookup(true, 0); }
+        assert_eq!(adapter.decide(10), None);
+
+        // Every lookup hit: shrink, but not past the bound.
+        adapter.lookup(true, 0);
+        assert_eq!(adapter.decide(11),
+                   Some(CapacityChange { from: 11, to: 10, hits: 4,
+                                         lookups: 4 }));
+
+        // Every lookup missed, but not on ghosts: no change.
+        for hash in 0..4 { adapter.lookup(false, hash); }
+        assert_eq!(adapter.decide(10), None);
+
+        // Misses on ghosts: grow, up to the bound.
+        for hash in 0..4 { adapter.evicted(hash, 10); }
+        for hash in 0..4 { adapter.lookup(false, hash); }
+        assert_eq!(adapter.decide(11).map(|change| change.to), Some(12));
+
+        // A capacity outside the bounds comes back within them.
+        for _ in 0..2 { adapter.lookup(true, 0); adapter.lookup(false, 9); }
+        assert_eq!(adapter.decide(40).map(|change| change.to), Some(12));
+    }
+}
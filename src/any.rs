@@ -0,0 +1,229 @@
+//! `AnyLfuCache`, for one bounded cache shared by parts of a program that
+//! cache values of different types. It's an `LfuCache` whose values are boxed
+//! as `dyn Any`, with accessors that take the type they expect. The items of
+//! all types share the capacity and one set of frequency queues, so a busy
+//! type crowds out the items of the others.
+//! 
+
+use std::any::Any;
+use std::hash::{BuildHasher, Hash};
+
+use crate::{DefaultState, EvictionOrder, LfuCache};
+
+/// A value of any type, as an `AnyLfuCache` holds it.
+/// 
+type AnyValue = Box<dyn Any + Send>;
+
+/// A Least Frequently Used cache whose values can be of any type, see the
+/// `any` module.
+/// 
+/// ```
+/// use lfu_cache::AnyLfuCache;
+/// 
+/// let mut cache = AnyLfuCache::new(2);
+/// 
+/// cache.insert_typed("port", 8080u16);
+/// cache.insert_typed("host", String::from("localhost"));
+/// 
+/// assert_eq!(cache.get_typed::<u16>(&"port"), Some(&8080));
+/// assert_eq!(cache.get_typed::<u32>(&"port"), None);
+/// ```
+pub struct AnyLfuCache<K, S = DefaultState> {
+    cache: LfuCache<K, AnyValue, S>,
+}
+
+impl<K> AnyLfuCache<K> {
+    /// Creates a new cache with the given capacity.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self { cache: LfuCache::new(capacity) }
+    }
+}
+
+impl<K, S> AnyLfuCache<K, S> {
+    /// Creates a new cache with the given capacity that uses `hash_builder`
+    /// to hash keys.
+    /// 
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self { cache: LfuCache::with_hasher(capacity, hash_builder) }
+    }
+
+    /// Returns the number of items in the cache, of all types.
+    /// 
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the maximum number of items the cache holds, of all types.
+    /// 
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// Returns an iterator over the keys in eviction order: the key
+    /// `pop_lfu()` would remove first comes first.
+    /// 
+    pub fn eviction_order(&self) -> EvictionOrder<'_, K> {
+        self.cache.eviction_order()
+    }
+}
+
+impl<K, S> AnyLfuCache<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns `true` if the cache holds the key, whatever the type of its
+    /// value, without counting an access.
+    /// 
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns `true` if the cache holds the key with a value of type `T`,
+    /// without counting an access.
+    /// 
+    pub fn contains_typed<T: Any>(&self, key: &K) -> bool {
+        self.cache.contains_key(key) && self.holds::<T>(key)
+    }
+
+    /// Returns the frequency count of the key, or `None` if it isn't cached.
+    /// 
+    pub fn frequency(&self, key: &K) -> Option<usize> {
+        self.cache.frequency(key)
+    }
+
+    /// Returns `true` if the key's value, if any, is a `T`.
+    /// 
+    fn holds<T: Any>(&self, key: &K) -> bool {
+        self.cache.peek_value(key).is_some_and(|value| value.is::<T>())
+    }
+}
+
+impl<K, S> AnyLfuCache<K, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the cache, evicting the LFU item, of
+    /// whatever type, if the cache is full. A value already cached under the
+    /// key is replaced, even if it's of another type, and the item's
+    /// frequency count is incremented as with `LfuCache::insert()`.
+    /// 
+    pub fn insert_typed<T: Any + Send>(&mut self, key: K, value: T) {
+        self.cache.insert(key, Box::new(value));
+    }
+
+    /// Returns a reference to the key's value if it's a `T`, counting an
+    /// access. If the value is of another type, this returns `None` and
+    /// counts nothing: the caller asked for a value the cache doesn't have,
+    /// so neither the item's frequency count nor the stats are changed.
+    /// 
+    pub fn get_typed<T: Any>(&mut self, key: &K) -> Option<&T> {
+        if self.cache.peek_value(key).is_some_and(|value| !value.is::<T>()) {
+            return None;
+        }
+        self.cache.get(key).and_then(|value| value.downcast_ref())
+    }
+
+    /// Removes the key's value and returns it if it's a `T`. If it's of
+    /// another type, the item is left in the cache and `None` is returned.
+    /// 
+    pub fn remove_typed<T: Any>(&mut self, key: &K) -> Option<T> {
+        if !self.holds::<T>(key) { return None; }
+
+        self.cache.remove(key)?.downcast().ok().map(|value| *value)
+    }
+
+    /// Removes the key from the cache, whatever the type of its value, and
+    /// returns `true` if it was there.
+    /// 
+    pub fn remove(&mut self, key: &K) -> bool {
+        self.cache.remove(key).is_some()
+    }
+
+    /// Removes the Least Frequently Used item from the cache, of whatever
+    /// type, and returns it, its value still boxed.
+    /// 
+    pub fn pop_lfu(&mut self) -> Option<(K, Box<dyn Any + Send>)> {
+        self.cache.pop_lfu()
+    }
+
+    /// Removes all items from the cache.
+    /// 
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+impl<K, S> std::fmt::Debug for AnyLfuCache<K, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnyLfuCache").field("len", &self.len())
+                                     .field("capacity", &self.capacity())
+                                     .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn types_share_the_capacity() {
+        let mut cache = AnyLfuCache::new(3);
+
+        cache.insert_typed(1, 1u32);
+        cache.insert_typed(2, "two");
+        cache.insert_typed(3, 3u32);
+
+        // The strings are busier, so the numbers give way to them.
+        for _ in 0..2 { cache.get_typed::<&str>(&2); }
+        cache.get_typed::<u32>(&3);
+        cache.insert_typed(4, "four");
+        cache.get_typed::<&str>(&4);
+        cache.insert_typed(5, "five");
+
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.contains_key(&1));
+        assert!(!cache.contains_key(&3));
+        assert!(cache.eviction_order().eq([5, 4, 2].iter()));
+        assert_eq!(cache.get_typed::<&str>(&5), Some(&"five"));
+
+        // A key can change type.
+        cache.insert_typed(5, 5u32);
+        assert_eq!(cache.get_typed::<u32>(&5), Some(&5));
+        assert_eq!(cache.pop_lfu().map(|(key, _)| key), Some(4));
+    }
+
+    #[test]
+    fn mismatched_types_count_for_nothing() {
+        let mut cache = AnyLfuCache::new(2);
+
+        cache.insert_typed("a", 1u32);
+        cache.insert_typed("b", vec![2u8]);
+
+        assert_eq!(cache.get_typed::<u64>(&"a"), None);
+        assert_eq!(cache.frequency(&"a"), Some(1));
+        assert!(cache.contains_typed::<u32>(&"a"));
+        assert!(!cache.contains_typed::<u64>(&"a"));
+
+        assert_eq!(cache.remove_typed::<String>(&"b"), None);
+        assert!(cache.contains_key(&"b"));
+        assert_eq!(cache.remove_typed::<Vec<u8>>(&"b"), Some(vec![2]));
+        assert!(!cache.contains_key(&"b"));
+
+        // Misses of either kind leave the eviction order alone.
+        cache.insert_typed("c", 3u32);
+        cache.get_typed::<i32>(&"a");
+        cache.get_typed::<u32>(&"z");
+        assert!(cache.eviction_order().eq([&"a", &"c"]));
+        assert!(cache.remove(&"a"));
+        assert_eq!(cache.len(), 1);
+    }
+}
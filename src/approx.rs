@@ -0,0 +1,417 @@
+//! An approximate LFU cache in the style of Redis's LFU eviction. Instead of
+//! frequency queues, each item has an 8-bit counter that grows roughly
+//! logarithmically with its accesses, and shrinks as time passes without any.
+//! To evict, a few items are picked at random and the one with the lowest
+//! counter goes. Nothing is kept in order, so an access only ever touches its
+//! own item, which makes this cheaper than `LfuCache` for very large caches at
+//! the cost of sometimes evicting an item that isn't the least used.
+//! 
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::rng::Rng;
+use crate::{Clock, DefaultState, SystemClock};
+
+/// The counter new items start with, so they aren't the first to be evicted
+/// before they've had a chance to be used again.
+/// 
+const INIT_COUNTER: u8 = 5;
+
+/// An item of an `ApproxLfuCache`. `touched` is when its counter was last
+/// brought up to date.
+/// 
+struct Entry<K, V> {
+    key     : K,
+    value   : V,
+    counter : u8,
+    touched : Duration,
+}
+
+/// A Least Frequently Used cache that approximates LFU eviction with
+/// logarithmic counters and random sampling, like Redis does, see the
+/// module docs.
+/// 
+/// An item's counter goes up with a probability of
+/// `1 / ((counter - 5) * log_factor + 1)` on each access, so with the default
+/// factor of 10 it takes about a million accesses to max it out. It goes down
+/// by one for each `decay_time` that passes without an access. The default is
+/// a minute.
+/// 
+pub struct ApproxLfuCache<K, V, S = DefaultState> {
+    index      : HashMap<K, usize, S>,
+    entries    : Vec<Entry<K, V>>,
+    capacity   : usize,
+    samples    : usize,
+    log_factor : u64,
+    decay_time : Duration,
+    rng        : Rng,
+    clock      : Arc<dyn Clock>,
+}
+
+impl<K, V> ApproxLfuCache<K, V> {
+    /// Creates a new approximate LFU cache with the given capacity.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, DefaultState::default())
+    }
+
+    /// Creates a new approximate LFU cache with the given capacity whose
+    /// random choices are seeded with `seed`. Two caches created with the
+    /// same seed, and fed the same operations at the same times, evict the
+    /// same items.
+    /// 
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        let mut cache = Self::new(capacity);
+
+        cache.rng = Rng::new(seed);
+        cache
+    }
+}
+
+impl<K, V, S> ApproxLfuCache<K, V, S> {
+    /// Creates a new approximate LFU cache with the given capacity that uses
+    /// `hash_builder` to hash keys.
+    /// 
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            index      : HashMap::with_capacity_and_hasher(capacity,
+                                                           hash_builder),
+            entries    : Vec::with_capacity(capacity),
+            capacity,
+            samples    : 5,
+            log_factor : 10,
+            decay_time : Duration::from_secs(60),
+            rng        : Rng::new(RandomState::new().hash_one(0)),
+            clock      : Arc::new(SystemClock::new()),
+        }
+    }
+
+    /// Returns the number of items in the cache.
+    /// 
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the cache is empty.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the maximum number of items the cache holds.
+    /// 
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets how many items are sampled to pick one to evict. More samples
+    /// get closer to exact LFU, but make evictions slower. The default is 5.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `samples` is 0.
+    /// 
+    pub fn set_samples(&mut self, samples: usize) {
+        assert!(samples > 0, "eviction needs at least one sample");
+        self.samples = samples;
+    }
+
+    /// Sets how quickly counters stop growing, see `ApproxLfuCache`. A factor
+    /// of 0 makes every access count, until the counter reaches 255.
+    /// 
+    pub fn set_log_factor(&mut self, factor: u64) {
+        self.log_factor = factor;
+    }
+
+    /// Sets how long an item goes without an access before its counter drops
+    /// by one. `Duration::ZERO` turns decay off.
+    /// 
+    pub fn set_decay_time(&mut self, decay_time: Duration) {
+        self.decay_time = decay_time;
+    }
+
+    /// Has the cache tell time with `clock` instead of the system's clock,
+    /// such as a `ManualClock` in tests. Counters only decay by the time that
+    /// passes on the new clock, so this is best done while it's empty.
+    /// 
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        let now = clock.now();
+
+        for entry in &mut self.entries {
+            entry.touched = now;
+        }
+        self.clock = Arc::new(clock);
+    }
+
+    /// Removes all items from the cache.
+    /// 
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.entries.clear();
+    }
+
+    /// Returns an iterator over the items in the cache, in arbitrary order.
+    /// Counters aren't changed.
+    /// 
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Returns the entry's counter, less what it's lost to decay since it
+    /// was last brought up to date.
+    /// 
+    fn decayed(&self, entry: &Entry<K, V>, now: Duration) -> u8 {
+        if self.decay_time.is_zero() { return entry.counter; }
+
+        let idle    = now.saturating_sub(entry.touched).as_nanos();
+        let periods = idle / self.decay_time.as_nanos();
+
+        entry.counter.saturating_sub(periods.min(255) as u8)
+    }
+
+    /// Applies decay to the counter of the entry at `i`, then counts one
+    /// access to it, with a probability that falls as the counter grows.
+    /// 
+    fn touch(&mut self, i: usize) {
+        let now     = self.clock.now();
+        let counter = self.decayed(&self.entries[i], now);
+        let base    = counter.saturating_sub(INIT_COUNTER) as u64;
+        let entry   = &mut self.entries[i];
+
+        entry.counter = counter;
+        entry.touched = now;
+
+        if counter < u8::MAX
+            && self.rng.one_in(base.saturating_mul(self.log_factor) + 1) {
+            entry.counter += 1;
+        }
+    }
+}
+
+impl<K, V, S> ApproxLfuCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns `true` if the key is in the cache. Its counter isn't changed.
+    /// 
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Returns the key's counter, with decay applied, if it's in the cache.
+    /// The counter isn't changed.
+    /// 
+    pub fn counter(&self, key: &K) -> Option<u8> {
+        let entry = &self.entries[*self.index.get(key)?];
+
+        Some(self.decayed(entry, self.clock.now()))
+    }
+
+    /// Returns a reference to the value corresponding to the key, counting
+    /// an access to it.
+    /// 
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// counting an access to it.
+    /// 
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let i = *self.index.get(key)?;
+
+        self.touch(i);
+        Some(&mut self.entries[i].value)
+    }
+
+    /// Removes the key from the cache and returns its value, if it was
+    /// present.
+    /// 
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let i = self.index.remove(key)?;
+
+        Some(self.remove_at(i).1)
+    }
+
+    /// Removes the entry at `i`, which is already out of the index, moving
+    /// the last entry into its place.
+    /// 
+    fn remove_at(&mut self, i: usize) -> (K, V) {
+        let entry = self.entries.swap_remove(i);
+
+        if let Some(moved) = self.entries.get(i) {
+            *self.index.get_mut(&moved.key).unwrap() = i;
+        }
+        (entry.key, entry.value)
+    }
+
+    /// Samples entries at random and evicts the one with the lowest counter,
+    /// returning it.
+    /// 
+    fn evict(&mut self) -> Option<(K, V)> {
+        if self.entries.is_empty() { return None; }
+
+        let now    = self.clock.now();
+        let len    = self.entries.len() as u64;
+        let mut lo = None;
+
+        for _ in 0..self.samples {
+            let i       = (self.rng.next_u64() % len) as usize;
+            let counter = self.decayed(&self.entries[i], now);
+
+            if lo.is_none_or(|(_, c)| counter < c) {
+                lo = Some((i, counter));
+            }
+        }
+        let (i, _) = lo.unwrap();
+
+        self.index.remove(&self.entries[i].key);
+        Some(self.remove_at(i))
+    }
+}
+
+impl<K, V, S> ApproxLfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the cache. A key that's already cached
+    /// gets the new value, and the write counts as an access. Otherwise, if
+    /// the cache is full, it samples items to evict one first.
+    /// 
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 { return; }
+
+        if let Some(&i) = self.index.get(&key) {
+            self.entries[i].value = value;
+            self.touch(i);
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.index.insert(key.clone(), self.entries.len());
+        self.entries.push(Entry {
+            key,
+            value,
+            counter : INIT_COUNTER,
+            touched : self.clock.now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LfuCache, ManualClock};
+
+    #[test]
+    fn counters_grow_and_decay() {
+        let clock     = ManualClock::new();
+        let mut cache = ApproxLfuCache::with_seed(4, 7);
+
+        cache.set_clock(clock.clone());
+        cache.set_log_factor(0);
+        cache.insert("a", 1);
+        for _ in 0..10 {
+            cache.get(&"a");
+        }
+        assert_eq!(cache.counter(&"a"), Some(15));
+
+        // Decay is applied lazily, three minutes take off three.
+        clock.advance(Duration::from_secs(200));
+        assert_eq!(cache.counter(&"a"), Some(12));
+        cache.get(&"a");
+        assert_eq!(cache.counter(&"a"), Some(13));
+
+        // With the default factor, counters grow far more slowly.
+        cache.set_log_factor(10);
+        for _ in 0..1000 {
+            cache.get(&"a");
+        }
+        assert!((14..40).contains(&cache.counter(&"a").unwrap()));
+    }
+
+    #[test]
+    fn evicts_lowest_counter_of_the_sample() {
+        let clock     = ManualClock::new();
+        let mut cache = ApproxLfuCache::with_seed(10, 3);
+
+        cache.set_clock(clock);
+        cache.set_log_factor(0);
+        cache.set_samples(1000);
+
+        for key in 0..10 {
+            cache.insert(key, ());
+            for _ in 0..key {
+                cache.get(&key);
+            }
+        }
+        // Sampling far more than there are finds the minimum every time.
+        cache.insert(10, ());
+        assert!(!cache.contains_key(&0));
+        cache.insert(11, ());
+        assert!(!cache.contains_key(&10));
+        assert_eq!(cache.len(), 10);
+
+        assert_eq!(cache.remove(&5), Some(()));
+        assert_eq!(cache.len(), 9);
+        assert!(cache.iter().all(|(k, _)| cache.counter(k).is_some()));
+        assert!((1..10).all(|key| key == 5 || cache.contains_key(&key)));
+    }
+
+    /// Returns a trace of `len` accesses to keys in `0..keys`, where key `k`
+    /// is accessed with a probability proportional to `1 / (k + 1)`.
+    /// 
+    fn zipf_trace(keys: usize, len: usize, seed: u64) -> Vec<usize> {
+        let mut cdf = Vec::with_capacity(keys);
+        let mut sum = 0.0;
+
+        for k in 0..keys {
+            sum += 1.0 / (k + 1) as f64;
+            cdf.push(sum);
+        }
+        let mut rng = Rng::new(seed);
+
+        (0..len).map(|_| {
+            let x = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * sum;
+            cdf.partition_point(|&c| c < x).min(keys - 1)
+        }).collect()
+    }
+
+    #[test]
+    fn hit_ratio_close_to_exact_lfu() {
+        let trace = zipf_trace(10_000, 200_000, 11);
+        let clock = ManualClock::new();
+
+        let mut exact  = LfuCache::new(500);
+        let mut approx = ApproxLfuCache::with_seed(500, 11);
+        let mut hits   = (0, 0);
+
+        approx.set_clock(clock.clone());
+
+        for (i, &key) in trace.iter().enumerate() {
+            if i % 1000 == 0 {
+                clock.advance(Duration::from_secs(1));
+            }
+            match exact.get(&key) {
+                Some(_) => hits.0 += 1,
+                None    => exact.insert(key, ()),
+            }
+            match approx.get(&key) {
+                Some(_) => hits.1 += 1,
+                None    => approx.insert(key, ()),
+            }
+        }
+        let ratio = |hits| hits as f64 / trace.len() as f64;
+
+        // About 0.66 and 0.63.
+        assert!(ratio(hits.1) > 0.9 * ratio(hits.0));
+        assert_eq!(approx.len(), 500);
+    }
+}
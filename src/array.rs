@@ -0,0 +1,592 @@
+//! A fixed-capacity LFU cache that never allocates. The capacity is a const
+//! generic and all storage lives inline in the cache, so it works without
+//! `std` or `alloc`.
+//! 
+//! The layout mirrors `LfuCache`: entries sit in slots, each slot is linked
+//! into the queue of the frequency bucket it belongs to, and the buckets are
+//! linked together in ascending order of frequency. Links are plain array
+//! indices rather than `LinkedVector` handles.
+//! 
+//! Since entries stay in their slots, an item can also be reached by its slot
+//! through an `EntryId`, without scanning for its key.
+//! 
+
+use core::mem;
+
+/// Marks the end of a list, or the absence of a link.
+/// 
+const NIL: usize = usize::MAX;
+
+/// An occupied slot. `prev` and `next` link it into its bucket's queue.
+/// `stamp` tells the entry apart from the others that have held the slot.
+/// 
+struct Entry<K, V> {
+    key    : K,
+    value  : V,
+    stamp  : u64,
+    bucket : usize,
+    prev   : usize,
+    next   : usize,
+}
+
+/// A slot either holds an entry, or is on the free list and holds the index of
+/// the next free slot.
+/// 
+enum Slot<K, V> {
+    Free(usize),
+    Used(Entry<K, V>),
+}
+
+/// A frequency bucket. `head` and `tail` are the ends of its queue of slots.
+/// `prev` and `next` link it into the list of buckets, or, when it's unused,
+/// `next` links it into the free list.
+/// 
+#[derive(Clone, Copy)]
+struct Bucket {
+    freq : usize,
+    head : usize,
+    tail : usize,
+    prev : usize,
+    next : usize,
+}
+
+/// A handle to an item in an `ArrayLfuCache` or an `LfuCache`, see 
+/// `ArrayLfuCache::id_of()` and `LfuCache::id_of()`. It names the item's slot
+/// and the insertion that filled it, so once the item is evicted or removed 
+/// the id goes stale, and it never refers to an item inserted after it, even
+/// one with the same key in the same slot. Ids are only meaningful to the 
+/// cache that gave them out.
+/// 
+/// An `LfuCache` moves its items as its map grows, so its ids also hold the
+/// hash of the item's key, to find the item again by its stamp when it's no
+/// longer in its slot.
+/// 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntryId {
+    pub(crate) slot  : usize,
+    pub(crate) hash  : u64,
+    pub(crate) stamp : u64,
+}
+
+/// A Least Frequently Used cache holding at most `N` items in fixed-size
+/// arrays. It behaves the same as `LfuCache` with a capacity of `N`.
+/// 
+/// Updating frequencies and evicting are O(1), but without a hash map, finding
+/// a key is a linear scan, so this is meant for small `N`.
+/// 
+/// ```
+/// use lfu_cache::ArrayLfuCache;
+/// 
+/// let mut cache = ArrayLfuCache::<&str, u32, 2>::new();
+/// 
+/// cache.insert("a", 1);
+/// cache.insert("b", 2);
+/// cache.get(&"a");
+/// cache.insert("c", 3);
+/// 
+/// assert_eq!(cache.get(&"b"), None);
+/// assert_eq!(cache.get(&"a"), Some(&1));
+/// ```
+pub struct ArrayLfuCache<K, V, const N: usize> {
+    slots       : [Slot<K, V>; N],
+    buckets     : [Bucket; N],
+    free_slot   : usize,
+    free_bucket : usize,
+    head        : usize,
+    len         : usize,
+    stamps      : u64,
+}
+
+impl<K, V, const N: usize> ArrayLfuCache<K, V, N>
+where
+    K: Eq,
+{
+    /// Creates a new, empty cache with a capacity of `N`.
+    /// 
+    pub fn new() -> Self {
+        Self {
+            slots       : core::array::from_fn(|i| Slot::Free(Self::succ(i))),
+            buckets     : core::array::from_fn(|i| Bucket {
+                              freq : 0,
+                              head : NIL,
+                              tail : NIL,
+                              prev : NIL,
+                              next : Self::succ(i),
+                          }),
+            free_slot   : if N > 0 { 0 } else { NIL },
+            free_bucket : if N > 0 { 0 } else { NIL },
+            head        : NIL,
+            len         : 0,
+            stamps      : 0,
+        }
+    }
+
+    /// Inserts a key-value pair into the cache, and returns the item's id. An
+    /// item whose value is updated keeps its id. Returns `None` if `N` is 0.
+    /// 
+    pub fn insert(&mut self, key: K, value: V) -> Option<EntryId> {
+        if N == 0 { return None; }
+
+        if let Some(i) = self.find(&key) {
+            // The key already exists, update value and increment its frequency.
+            self.entry_mut(i).value = value;
+            self.incr_freq(i);
+            Some(self.id(i))
+        } else {
+            // This is a new key. Remove the LFU item if the cache is full.
+            if self.len == N {
+                self.pop_lfu();
+            }
+            // Get the frequency 1 bucket, or add it to the front.
+            let b = if self.head != NIL && self.buckets[self.head].freq == 1 {
+                self.head
+            } else {
+                self.alloc_bucket(1, NIL)
+            };
+            let i = self.free_slot;
+
+            self.free_slot = match self.slots[i] {
+                Slot::Free(next) => next,
+                Slot::Used(_)    => unreachable!("used slot on the free list"),
+            };
+            self.stamps  += 1;
+            self.slots[i] = Slot::Used(Entry {
+                key,
+                value,
+                stamp  : self.stamps,
+                bucket : b,
+                prev   : NIL,
+                next   : NIL,
+            });
+            self.push_back(b, i);
+            self.len += 1;
+            Some(self.id(i))
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    /// 
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let i = self.find(key)?;
+        self.incr_freq(i);
+        Some(&self.entry(i).value)
+    }
+
+    /// Returns the id of the item with the key, if it's present, with which
+    /// `get_by_id()` and `peek_by_id()` can reach it again without a scan.
+    /// 
+    pub fn id_of(&self, key: &K) -> Option<EntryId> {
+        self.find(key).map(|i| self.id(i))
+    }
+
+    /// Returns a reference to the value of the item with the id, and counts
+    /// an access like `get()` does. Returns `None` if the id is stale.
+    /// 
+    pub fn get_by_id(&mut self, id: EntryId) -> Option<&V> {
+        let i = self.resolve(id)?;
+        self.incr_freq(i);
+        Some(&self.entry(i).value)
+    }
+
+    /// Returns a reference to the value of the item with the id without
+    /// counting an access. Returns `None` if the id is stale.
+    /// 
+    pub fn peek_by_id(&self, id: EntryId) -> Option<&V> {
+        self.resolve(id).map(|i| &self.entry(i).value)
+    }
+
+    /// Removes the key from the cache and returns its value, if it was present.
+    /// 
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let i = self.find(key)?;
+        Some(self.remove_slot(i).1)
+    }
+
+    /// Removes the Least Frequently Used item from the cache and returns it.
+    /// 
+    pub fn pop_lfu(&mut self) -> Option<(K, V)> {
+        if self.head == NIL {
+            return None;
+        }
+        let i = self.buckets[self.head].head;
+        Some(self.remove_slot(i))
+    }
+
+    /// Returns the number of items in the cache.
+    /// 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the capacity of the cache, which is always `N`.
+    /// 
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Removes all items from the cache.
+    /// 
+    pub fn clear(&mut self) {
+        // Keep the count of insertions, so the old ids stay stale.
+        let stamps = self.stamps;
+
+        *self       = Self::new();
+        self.stamps = stamps;
+    }
+
+    /// Returns the frequencies of the buckets, lowest first. Empty buckets are
+    /// released, so each of them holds at least one item.
+    /// 
+    #[cfg(feature = "defmt")]
+    pub(crate) fn frequencies(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut b = self.head;
+
+        core::iter::from_fn(move || {
+            let bucket = self.buckets.get(b)?;
+
+            b = bucket.next;
+            Some(bucket.freq)
+        })
+    }
+
+    /// Returns the index that follows `i` on a freshly initialized free list.
+    /// 
+    fn succ(i: usize) -> usize {
+        if i + 1 < N { i + 1 } else { NIL }
+    }
+
+    /// Finds the slot holding `key`.
+    /// 
+    fn find(&self, key: &K) -> Option<usize> {
+        self.slots.iter().position(|slot| {
+            matches!(slot, Slot::Used(entry) if entry.key == *key)
+        })
+    }
+
+    fn id(&self, i: usize) -> EntryId {
+        EntryId { slot: i, hash: 0, stamp: self.entry(i).stamp }
+    }
+
+    /// Finds the slot the id refers to, if its entry is still there.
+    /// 
+    fn resolve(&self, id: EntryId) -> Option<usize> {
+        match self.slots.get(id.slot)? {
+            Slot::Used(entry) if entry.stamp == id.stamp => Some(id.slot),
+            _                                            => None,
+        }
+    }
+
+    fn entry(&self, i: usize) -> &Entry<K, V> {
+        match &self.slots[i] {
+            Slot::Used(entry) => entry,
+            Slot::Free(_)     => unreachable!("link to a free slot"),
+        }
+    }
+
+    fn entry_mut(&mut self, i: usize) -> &mut Entry<K, V> {
+        match &mut self.slots[i] {
+            Slot::Used(entry) => entry,
+            Slot::Free(_)     => unreachable!("link to a free slot"),
+        }
+    }
+
+    /// Takes the entry out of slot `i`, returning the slot to the free list
+    /// and the bucket too if the entry was its last occupant.
+    /// 
+    fn remove_slot(&mut self, i: usize) -> (K, V) {
+        let b = self.unlink(i);
+
+        if self.buckets[b].head == NIL {
+            self.release_bucket(b);
+        }
+        let slot = mem::replace(&mut self.slots[i], Slot::Free(self.free_slot));
+
+        self.free_slot = i;
+        self.len      -= 1;
+
+        match slot {
+            Slot::Used(entry) => (entry.key, entry.value),
+            Slot::Free(_)     => unreachable!("removed a free slot"),
+        }
+    }
+
+    /// Moves the entry in slot `i` to the bucket for the next higher frequency.
+    /// 
+    fn incr_freq(&mut self, i: usize) {
+        let b    = self.unlink(i);
+        let freq = self.buckets[b].freq;
+        let next = self.buckets[b].next;
+
+        if next != NIL && self.buckets[next].freq == freq + 1 {
+            // The next bucket is the one we want.
+            self.push_back(next, i);
+
+            if self.buckets[b].head == NIL {
+                self.release_bucket(b);
+            }
+        } else if self.buckets[b].head == NIL {
+            // The entry was the bucket's only occupant, so the bucket can
+            // simply be relabeled.
+            self.buckets[b].freq = freq + 1;
+            self.push_back(b, i);
+        } else {
+            let nb = self.alloc_bucket(freq + 1, b);
+            self.push_back(nb, i);
+        }
+    }
+
+    /// Detaches the entry in slot `i` from its bucket's queue and returns the
+    /// bucket's index.
+    /// 
+    fn unlink(&mut self, i: usize) -> usize {
+        let (b, prev, next) = {
+            let entry = self.entry(i);
+            (entry.bucket, entry.prev, entry.next)
+        };
+        if prev == NIL {
+            self.buckets[b].head = next;
+        } else {
+            self.entry_mut(prev).next = next;
+        }
+        if next == NIL {
+            self.buckets[b].tail = prev;
+        } else {
+            self.entry_mut(next).prev = prev;
+        }
+        b
+    }
+
+    /// Appends the entry in slot `i` to the queue of bucket `b`.
+    /// 
+    fn push_back(&mut self, b: usize, i: usize) {
+        let tail = self.buckets[b].tail;
+        {
+            let entry  = self.entry_mut(i);
+            entry.bucket = b;
+            entry.prev   = tail;
+            entry.next   = NIL;
+        }
+        if tail == NIL {
+            self.buckets[b].head = i;
+        } else {
+            self.entry_mut(tail).next = i;
+        }
+        self.buckets[b].tail = i;
+    }
+
+    /// Takes an empty bucket off the free list, sets its frequency, and links
+    /// it in after bucket `after`, or at the front if `after` is `NIL`. There
+    /// are never more non-empty buckets than entries, so one is always free
+    /// when an entry needs it.
+    /// 
+    fn alloc_bucket(&mut self, freq: usize, after: usize) -> usize {
+        let b    = self.free_bucket;
+        let next = if after == NIL { self.head } else {
+            self.buckets[after].next
+        };
+        self.free_bucket = self.buckets[b].next;
+        self.buckets[b]  = Bucket { freq, head: NIL, tail: NIL, prev: after, next };
+
+        if after == NIL {
+            self.head = b;
+        } else {
+            self.buckets[after].next = b;
+        }
+        if next != NIL {
+            self.buckets[next].prev = b;
+        }
+        b
+    }
+
+    /// Unlinks the empty bucket `b` from the bucket list and puts it on the
+    /// free list.
+    /// 
+    fn release_bucket(&mut self, b: usize) {
+        let Bucket { prev, next, .. } = self.buckets[b];
+
+        if prev == NIL {
+            self.head = next;
+        } else {
+            self.buckets[prev].next = next;
+        }
+        if next != NIL {
+            self.buckets[next].prev = prev;
+        }
+        self.buckets[b].next = self.free_bucket;
+        self.free_bucket     = b;
+    }
+}
+
+impl<K, V, const N: usize> Default for ArrayLfuCache<K, V, N>
+where
+    K: Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_eviction() {
+        let mut cache = ArrayLfuCache::<i32, i32, 2>::new();
+
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        assert_eq!(cache.get(&1), Some(&1));
+        cache.insert(3, 3);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&3), Some(&3));
+        cache.insert(4, 4);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&3), Some(&3));
+        assert_eq!(cache.get(&4), Some(&4));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut cache = ArrayLfuCache::<i32, i32, 0>::new();
+
+        cache.insert(0, 0);
+        assert_eq!(cache.get(&0), None);
+        assert_eq!(cache.pop_lfu(), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn remove_and_clear() {
+        let mut cache = ArrayLfuCache::<i32, i32, 3>::new();
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        cache.insert(3, 30);
+        cache.get(&1);
+        assert_eq!(cache.remove(&2), Some(20));
+        assert_eq!(cache.remove(&2), None);
+        assert_eq!(cache.pop_lfu(), Some((3, 30)));
+        assert_eq!(cache.pop_lfu(), Some((1, 10)));
+        assert_eq!(cache.pop_lfu(), None);
+
+        cache.insert(4, 40);
+        cache.insert(5, 50);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&4), None);
+
+        // Every slot and bucket is usable again after clearing.
+        for k in 0..3 { cache.insert(k, k); }
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn ids_go_stale() {
+        let mut cache = ArrayLfuCache::<i32, i32, 2>::new();
+
+        let one = cache.insert(1, 10).unwrap();
+        let two = cache.insert(2, 20).unwrap();
+
+        assert_eq!(cache.id_of(&1), Some(one));
+        assert_eq!(cache.insert(1, 11), Some(one));
+        assert_eq!(cache.peek_by_id(one), Some(&11));
+
+        // Reads by id count, so 2 is evicted for 3, which gets its slot.
+        assert_eq!(cache.get_by_id(one), Some(&11));
+        let three = cache.insert(3, 30).unwrap();
+
+        assert_eq!(cache.get_by_id(two), None);
+        assert_eq!(cache.peek_by_id(two), None);
+        assert_eq!(cache.peek_by_id(three), Some(&30));
+
+        // A key back in the slot it had is a different item.
+        assert_eq!(cache.remove(&3), Some(30));
+        let two_again = cache.insert(2, 21).unwrap();
+
+        assert_eq!(cache.id_of(&3), None);
+        assert_eq!(cache.peek_by_id(three), None);
+        assert_eq!(cache.peek_by_id(two), None);
+        assert_ne!(two_again, two);
+        assert_eq!(cache.peek_by_id(two_again), Some(&21));
+
+        // So is one inserted after clearing.
+        cache.clear();
+        let one_again = cache.insert(1, 12).unwrap();
+
+        assert_eq!(cache.peek_by_id(one), None);
+        assert_eq!(cache.get_by_id(one_again), Some(&12));
+
+        let mut empty = ArrayLfuCache::<i32, i32, 0>::new();
+
+        assert_eq!(empty.insert(1, 1), None);
+        assert_eq!(empty.get_by_id(one), None);
+    }
+
+    /// Runs the same pseudo-random sequence of operations against an
+    /// `ArrayLfuCache` and an `LfuCache` of the same capacity and checks that
+    /// every result matches.
+    /// 
+    #[cfg(feature = "std")]
+    fn differential<const N: usize>(seed: u64) {
+        use crate::LfuCache;
+
+        let mut array = ArrayLfuCache::<u64, u64, N>::new();
+        let mut heap  = LfuCache::new(N);
+        let mut state = seed;
+        let mut next  = || {
+            // xorshift64*
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        };
+        let keys = N as u64 * 2;
+
+        for step in 0..20_000 {
+            let r   = next();
+            let key = (r >> 8) % keys;
+
+            match r % 100 {
+                0..=39 => {
+                    array.insert(key, step);
+                    heap.insert(key, step);
+                },
+                40..=79 => {
+                    assert_eq!(array.get(&key), heap.get(&key), "step {step}");
+                },
+                80..=91 => {
+                    assert_eq!(array.remove(&key), heap.remove(&key));
+                },
+                92..=98 => {
+                    assert_eq!(array.pop_lfu(), heap.pop_lfu(), "step {step}");
+                },
+                _ => {
+                    array.clear();
+                    heap.clear();
+                },
+            }
+            assert_eq!(array.len(), heap.len());
+        }
+        // Draining both must give the same eviction order.
+        while let Some(entry) = heap.pop_lfu() {
+            assert_eq!(array.pop_lfu(), Some(entry));
+        }
+        assert!(array.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn matches_lfu_cache() {
+        differential::<4>(0x9e37_79b9_7f4a_7c15);
+        differential::<16>(0xdead_beef_cafe_f00d);
+        differential::<64>(0x0123_4567_89ab_cdef);
+    }
+}
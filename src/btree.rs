@@ -0,0 +1,210 @@
+//! An LFU cache for keys that are ordered rather than hashed. It keeps the
+//! same frequency queues as `LfuCache` and evicts in exactly the same order,
+//! but looks keys up in a `BTreeMap`, which also makes it possible to visit or
+//! remove a range of keys.
+//! 
+
+use std::collections::BTreeMap;
+use std::ops::RangeBounds;
+
+use linked_vector::*;
+
+use crate::cache::Value;
+use crate::queues::{self, Queued, Queues};
+use crate::EvictionOrder;
+
+/// A Least Frequently Used cache indexed by a `BTreeMap`. Lookups take
+/// O(log n) time instead of the O(1) of `LfuCache`, but keys only need to be
+/// `Ord`, and `range()` and `remove_range()` work on key ranges.
+/// 
+pub struct BTreeLfuCache<K, V> {
+    map         : BTreeMap<K, Value<V>>,
+    frequencies : Queues<K>,
+    capacity    : usize,
+}
+
+impl<K, V> BTreeLfuCache<K, V>
+where
+    K: Ord + Clone,
+{
+    /// Creates a new LFU cache with the given capacity.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            map         : BTreeMap::new(),
+            frequencies : LinkedVector::new(),
+            capacity,
+        }
+    }
+
+    /// Inserts a key-value pair into the cache.
+    /// 
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 { return; }
+
+        if let Some(vrec) = self.map.get_mut(&key) {
+            // The key already exists, update value and increment its frequency.
+            vrec.value = value;
+            queues::incr_freq(&mut self.frequencies, vrec);
+        } else {
+            // This is a new key. Remove the LFU item if the cache is full.
+            if self.map.len() >= self.capacity {
+                self.remove_lfu();
+            }
+            let mut vrec   = Value::new(value);
+            let     queued = Queued { hash: 0, key: key.clone() };
+
+            queues::push(&mut self.frequencies, queued, 1, &mut vrec);
+            self.map.insert(key, vrec);
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    /// 
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.map.get_mut(key).map(|vrec| {
+            // Move it to the next frequency queue.
+            queues::incr_freq(&mut self.frequencies, vrec);
+            &vrec.value
+        })
+    }
+
+    /// Removes the key from the cache and returns its value, if it was present.
+    /// 
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let vrec = self.map.remove(key)?;
+
+        queues::unlink(&mut self.frequencies, &vrec);
+        Some(vrec.value)
+    }
+
+    /// Removes the Least Frequently Used item from the cache and returns it.
+    /// Of the items with the lowest frequency count, the one that was accessed
+    /// least recently is chosen.
+    /// 
+    pub fn pop_lfu(&mut self) -> Option<(K, V)> {
+        let entry = self.remove_lfu();
+
+        // `remove_lfu()` keeps an emptied frequency 1 queue around for
+        // `insert()` to reuse, but nothing else will.
+        if self.frequencies.front().is_some_and(|q| q.1.is_empty()) {
+            self.frequencies.pop_front();
+        }
+        entry
+    }
+
+    /// Returns an iterator over the items with keys in the range, in key
+    /// order. Frequency counts aren't changed.
+    /// 
+    pub fn range<R>(&self, range: R) -> impl Iterator<Item = (&K, &V)>
+    where
+        R: RangeBounds<K>,
+    {
+        self.map.range(range).map(|(k, vrec)| (k, &vrec.value))
+    }
+
+    /// Removes every item with a key in the range, returning how many there
+    /// were.
+    /// 
+    pub fn remove_range<R>(&mut self, range: R) -> usize
+    where
+        R: RangeBounds<K>,
+    {
+        let keys = self.map.range(range).map(|(k, _)| k.clone())
+                                        .collect::<Vec<_>>();
+        for key in &keys {
+            self.remove(key);
+        }
+        keys.len()
+    }
+
+    /// Returns an iterator over the keys in eviction order: the key
+    /// `pop_lfu()` would remove first comes first. Frequency counts aren't
+    /// changed.
+    /// 
+    pub fn eviction_order(&self) -> EvictionOrder<'_, K> {
+        EvictionOrder { order: queues::lfu_order(&self.frequencies) }
+    }
+
+    /// Returns the number of items in the cache.
+    /// 
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the capacity of the cache.
+    /// 
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes all items from the cache. The capacity is unchanged.
+    /// 
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.frequencies.clear();
+    }
+
+    /// Removes the Least Frequently Used item from the cache.
+    /// 
+    fn remove_lfu(&mut self) -> Option<(K, V)> {
+        queues::pop_lfu(&mut self.frequencies).map(|Queued { key, .. }| {
+            let vrec = self.map.remove(&key).unwrap();
+            (key, vrec.value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LfuCache;
+
+    #[test]
+    fn range_queries() {
+        let mut cache = BTreeLfuCache::new(8);
+
+        for key in 0..8 { cache.insert(key, key * 10); }
+
+        assert_eq!(cache.range(2..5).collect::<Vec<_>>(),
+                   [(&2, &20), (&3, &30), (&4, &40)]);
+        assert_eq!(cache.remove_range(..3), 3);
+        assert_eq!(cache.remove_range(6..), 2);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(),
+                   [&3, &4, &5]);
+    }
+
+    #[test]
+    fn evicts_like_lfu_cache() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut rand  = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut btree = BTreeLfuCache::new(16);
+        let mut hash  = LfuCache::new(16);
+
+        for i in 0..20_000 {
+            let key = rand() % 40;
+
+            match rand() % 10 {
+                0     => assert_eq!(btree.remove(&key), hash.remove(&key)),
+                1     => assert_eq!(btree.pop_lfu(), hash.pop_lfu()),
+                2..=5 => assert_eq!(btree.get(&key), hash.get(&key)),
+                _     => {
+                    btree.insert(key, i);
+                    hash.insert(key, i);
+                },
+            }
+            assert!(btree.eviction_order().eq(hash.eviction_order()));
+        }
+    }
+}
@@ -0,0 +1,8163 @@
+//! `LfuCache`, the hash map backed cache, and its builder. The hash map owns
+//! the keys and value records, and the frequency queues hold a clone of each
+//! key, in the order the items will be evicted.
+//! 
+
+#[cfg(feature = "cached")]
+use std::borrow::Borrow;
+#[cfg(feature = "std")]
+use std::collections::{BinaryHeap, HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::convert::Infallible;
+#[cfg(feature = "debug-audit")]
+use std::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::ops::RangeBounds;
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+#[cfg(feature = "std")]
+use hashbrown::TryReserveError;
+#[cfg(feature = "std")]
+use linked_vector::*;
+
+#[cfg(feature = "std")]
+use crate::adapt::Adapter;
+#[cfg(feature = "std")]
+use crate::error::{CacheError, VersionMismatch};
+#[cfg(feature = "std")]
+use crate::clock::{Instant, Stopwatch};
+#[cfg(feature = "std")]
+use crate::hashed::HashedMap;
+#[cfg(feature = "std")]
+use crate::insertion::InsertionIndex;
+#[cfg(feature = "std")]
+use crate::prefix::PrefixIndex;
+#[cfg(feature = "std")]
+use crate::rng::Rng;
+#[cfg(feature = "std")]
+use crate::sketch::FrequencySketch;
+#[cfg(feature = "std")]
+use crate::tags::TagIndex;
+#[cfg(feature = "std")]
+use crate::wheel::TimerWheel;
+#[cfg(feature = "std")]
+use crate::queues::{self, Queued, Queues};
+#[cfg(feature = "std")]
+use crate::{Clock, Counter, DefaultState, LfuSnapshot, SeededState};
+#[cfg(feature = "std")]
+use crate::{AdaptiveCapacity, CapacityChange, EntryId};
+#[cfg(feature = "std")]
+use crate::SystemClock;
+#[cfg(feature = "std")]
+use crate::{EvictionReason, EvictionReceiver};
+#[cfg(feature = "std")]
+use crate::notify::EvictionSender;
+#[cfg(feature = "std")]
+use crate::entry::{AccessGuard, Bucket, EntryCursorMut, EntryGuard};
+#[cfg(feature = "std")]
+use crate::entry::OccupiedEntry;
+#[cfg(feature = "std")]
+use crate::iter::{Buckets, EvictionOrder, EvictionOrderMut, Iter};
+#[cfg(feature = "std")]
+use crate::iter::{Entries, IntoEntries, ValuesLfuFirst, ValuesLfuFirstMut};
+#[cfg(feature = "std")]
+use crate::iter::{EntriesUnordered, IterUnordered};
+#[cfg(feature = "std")]
+use crate::iter::KeysInInsertionOrder;
+#[cfg(feature = "debug-audit")]
+use crate::iter::AuditRecords;
+#[cfg(feature = "std")]
+use crate::stats::{BucketState, CacheState, CacheStats, LoadStats, WarmState};
+#[cfg(feature = "debug-verify")]
+use crate::verify::{self, Item, Shadow};
+
+/// A value record. It contains the value, the handle of the frequency queue
+/// it belongs to and the handle of its position in that queue, the stamp that
+/// tells it from every other entry the cache has made, and so the generation
+/// it was made in, and the number of times it was written.
+/// It also has the time of the last write and how long the item lives after 
+/// it, if it expires, and whether it's been marked for refresh since. The
+/// frequency count and the weight are kept too, for the policies that order
+/// the queues by a priority computed from them, along with a count of hits
+/// that can be reset without touching the frequency count, which is a `C`.
+/// 
+#[cfg(feature = "std")]
+pub(crate) struct Value<V, C = usize> {
+    pub(crate) value   : V,
+    pub(crate) hfreq   : HNode,
+    pub(crate) hpos    : HNode,
+    pub(crate) gen     : u64,
+    pub(crate) count   : C,
+    version : u64,
+    weight  : usize,
+    written : Stamp,
+    ttl     : Option<Stamp>,
+    marked  : bool,
+    hits    : u64,
+    boost   : u8,
+}
+
+/// A time on the cache's clock, or a span of it, as a value record keeps it.
+/// With the `compact` feature it's a count of nanoseconds, which takes half 
+/// the room of a `Duration`, still has a niche for `Option`, and lasts for 
+/// 584 years; longer spans are cut to that.
+/// 
+#[cfg(all(feature = "std", not(feature = "compact")))]
+#[derive(Clone, Copy)]
+struct Stamp(Duration);
+
+#[cfg(feature = "compact")]
+#[derive(Clone, Copy)]
+struct Stamp(core::num::NonZeroU64);
+
+#[cfg(all(feature = "std", not(feature = "compact")))]
+impl Stamp {
+    fn new(time: Duration) -> Self {
+        Self(time)
+    }
+
+    fn get(self) -> Duration {
+        self.0
+    }
+}
+
+#[cfg(feature = "compact")]
+impl Stamp {
+    fn new(time: Duration) -> Self {
+        // Off by one, so zero fits.
+        let nanos = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX - 1);
+
+        Self(core::num::NonZeroU64::MIN.saturating_add(nanos))
+    }
+
+    fn get(self) -> Duration {
+        Duration::from_nanos(self.0.get() - 1)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V, C: Counter> Value<V, C> {
+    pub(crate) fn new(value: V) -> Self {
+        Self {
+            value,
+            hfreq   : HNode::default(), // Which frequency queue.
+            hpos    : HNode::default(), // Position in the frequency queue.
+            gen     : 0,
+            version : 1,
+            count   : C::from_usize(1),
+            weight  : 1,
+            written : Stamp::new(Duration::ZERO),
+            ttl     : None,
+            marked  : false,
+            hits    : 0,
+            boost   : 0,
+        }
+    }
+
+    /// Returns the label of the item's queue: the bucket for its frequency 
+    /// count plus its priority boost, see `LfuCache::set_priority()`.
+    /// 
+    fn label(&self, log_base: usize) -> usize {
+        let count = self.count.to_usize().saturating_add(self.boost.into());
+
+        bucket(count, log_base)
+    }
+
+    /// Records a write of `value` at `now` that lives for `ttl`, if given,
+    /// and returns the value it replaced.
+    /// 
+    fn write(&mut self, value: V, now: Duration, ttl: Option<Duration>) -> V {
+        self.version += 1;
+        self.written  = Stamp::new(now);
+        self.ttl      = ttl.map(Stamp::new);
+        self.marked   = false;
+        std::mem::replace(&mut self.value, value)
+    }
+
+    /// Returns `true` if the item's time to live, plus `grace`, has run out
+    /// at `now`.
+    /// 
+    fn expired(&self, now: Duration, grace: Duration) -> bool {
+        self.ttl.is_some_and(|ttl| {
+            now.saturating_sub(self.written.get()) 
+                >= ttl.get().saturating_add(grace)
+        })
+    }
+
+    /// Returns the time the item expires at, if it has a TTL.
+    /// 
+    fn deadline(&self) -> Option<Duration> {
+        self.ttl.map(|ttl| self.written.get().saturating_add(ttl.get()))
+    }
+}
+
+/// A Least Frequently Used cache. A hash map implements the cache and queues 
+/// are maintained for frequency counts. The hash map uses `S` to hash keys,
+/// which is `DefaultState` unless another is given, and the items keep their
+/// frequency counts in `C`, which is `usize` unless another is given, see
+/// `with_counter()`.
+/// 
+#[cfg(feature = "std")]
+pub struct LfuCache<K, V, S = DefaultState, C = usize> {
+    map         : HashedMap<K, Value<V, C>, S>,
+    frequencies : Queues<K>,
+    capacity    : usize,
+    stats       : Option<CacheStats>,
+    loads       : Option<LoadStats>,
+    admission   : Option<FrequencySketch>,
+    increments  : Option<LogIncrements>,
+    tags        : Option<TagIndex<K>>,
+    prefixes    : Option<PrefixIndex<K>>,
+    insertion   : Option<InsertionIndex<K>>,
+
+    // Each entry is stamped with the count of entries made before it, and the
+    // current generation began with the stamp that was next at the last 
+    // `invalidate_all()`. Entries stamped before that are stale. They stay in
+    // the map, with their keys in the retired queues, until reclaimed.
+    stamps      : u64,
+    generation  : u64,
+    stale       : usize,
+    retired     : Vec<Queues<K>>,
+
+    // An item is pinned while any `EntryGuard` holds a clone of its `Arc`.
+    pins        : HashMap<K, Arc<()>>,
+
+    // Items whose values match are evicted before any others.
+    evict_first : Option<fn(&V) -> bool>,
+
+    // Item ages are measured with the clock, once there is one.
+    clock       : Option<Arc<dyn Clock>>,
+    refresh     : Option<Refresh<K>>,
+    expiries    : TimerWheel<K>,
+
+    // Expired items are kept this much longer and served stale, see
+    // `enable_stale_while_revalidate()`.
+    grace       : Duration,
+
+    // The TTL of items written without one, see `set_default_ttl()`.
+    default_ttl : Option<Duration>,
+
+    // Under LFU-DA and GDSF, the queues are ordered by priority rather than
+    // frequency count, and the age is the priority of the last item evicted.
+    // Under LRFU it's the number of accesses, which `lambda` scales. With
+    // plain LFU it stays 0.
+    policy      : Policy,
+    age         : usize,
+    lambda      : f64,
+
+    // With logarithmic bucketing, items are queued by the highest power of
+    // the base that's at most their frequency count. 0 means exact counts.
+    log_base    : usize,
+
+    // The total weight of the live items. Without a weigher each weighs 1.
+    // Items heavier than `item_share` of the weight limit are turned away.
+    weigher     : Option<Weigher<K, V>>,
+    weight      : usize,
+    item_share  : f64,
+
+    // A loaded value too heavy to cache, held here so that a reference to it
+    // can be handed back, until the next value is loaded or the cache is
+    // cleared.
+    uncached    : Option<V>,
+
+    // Evicted and expired items are sent here, once there's a receiver.
+    notify      : Option<EvictionSender<K, V>>,
+
+    // The hit ratio and the ghosts that adaptive capacity goes by.
+    adapter     : Option<Adapter>,
+
+    #[cfg(feature = "debug-audit")]
+    audit       : Option<AuditLog<K>>,
+
+    // Checks the cache's outcomes, see the `verify` module.
+    #[cfg(feature = "debug-verify")]
+    shadow      : Shadow<K>,
+}
+
+/// The state of probabilistic frequency increments, see 
+/// `LfuCacheBuilder::probabilistic_increments()`.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+struct LogIncrements {
+    rng    : Rng,
+    factor : u64,
+}
+
+/// The state of refresh-ahead, see `LfuCache::enable_refresh()`: how old an
+/// item has to be to be due for refresh, and the keys marked so far.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+struct Refresh<K> {
+    after : Duration,
+    due   : Vec<K>,
+    // Lookups mark keys without needing `K: Clone` themselves.
+    clone : fn(&K) -> K,
+}
+
+#[cfg(feature = "std")]
+impl<K: Clone> Refresh<K> {
+    fn new(after: Duration) -> Self {
+        Self { after, due: Vec::new(), clone: K::clone }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K> Refresh<K> {
+    /// Marks the key if its item is due for refresh, or is being served stale,
+    /// and isn't marked yet.
+    /// 
+    fn note<V, C>(&mut self, key: &K, vrec: &mut Value<V, C>, now: Duration)
+    where
+        C: Counter,
+    {
+        let since = now.saturating_sub(vrec.written.get());
+        let stale = vrec.expired(now, Duration::ZERO);
+
+        if !vrec.marked && (since >= self.after || stale) {
+            vrec.marked = true;
+            self.due.push((self.clone)(key));
+        }
+    }
+}
+
+/// Something that happened to a key, as recorded in the audit log.
+/// 
+#[cfg(feature = "debug-audit")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum AuditEvent<K> {
+    /// The key was added by `insert()`.
+    Inserted { key: K },
+    /// `insert()` replaced the key's value.
+    Updated { key: K },
+    /// `get()` found the key.
+    Hit { key: K },
+    /// The key was evicted, to make room or because it expired, as `reason`
+    /// says.
+    Evicted { victim: K, reason: EvictionReason },
+    /// The key was removed on request, by `remove()`, `pop_lfu()`, 
+    /// `retain_mut()` or the like.
+    Removed { key: K },
+    /// `clear()` removed every key.
+    Cleared,
+}
+
+#[cfg(feature = "debug-audit")]
+impl<K> AuditEvent<K> {
+    /// Returns the key the event is about, or `None` if it's about all of
+    /// them.
+    /// 
+    pub fn key(&self) -> Option<&K> {
+        match self {
+            Self::Inserted { key } | Self::Updated { key } | 
+            Self::Hit { key } | Self::Removed { key } => Some(key),
+            Self::Evicted { victim, .. } => Some(victim),
+            Self::Cleared => None,
+        }
+    }
+}
+
+/// An entry in the audit log. Sequence numbers increase by one with every
+/// event, so gaps show where older records were dropped.
+/// 
+#[cfg(feature = "debug-audit")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AuditRecord<K> {
+    pub seq   : u64,
+    pub event : AuditEvent<K>,
+}
+
+/// A ring buffer holding the most recent audit records.
+/// 
+#[cfg(feature = "debug-audit")]
+#[derive(Clone)]
+struct AuditLog<K> {
+    records  : VecDeque<AuditRecord<K>>,
+    capacity : usize,
+    next_seq : u64,
+    // Lookups and removals record keys without needing `K: Clone`.
+    clone    : fn(&K) -> K,
+}
+
+#[cfg(feature = "debug-audit")]
+impl<K> AuditLog<K> {
+    fn push(&mut self, event: AuditEvent<K>) {
+        if self.capacity == 0 { return; }
+
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(AuditRecord { seq: self.next_seq, event });
+        self.next_seq += 1;
+    }
+}
+
+/// How an `LfuCache` picks the item to evict, see `LfuCacheBuilder::policy()`.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum Policy {
+    /// Evicts the item with the lowest frequency count, the least recently 
+    /// used of those if there's a tie.
+    #[default]
+    Lfu,
+    /// LFU with dynamic aging. The cache keeps an age, which starts at 0, and
+    /// each item a priority: its frequency count plus the age as of its last
+    /// access. The item with the lowest priority is evicted, and the age 
+    /// becomes its priority. Items that were hot long ago thus fall behind
+    /// the ones used since, instead of staying cached for good.
+    /// 
+    /// The queues are ordered by priority, so the methods that report on them,
+    /// like `LfuCache::frequency_histogram()` and `LfuCache::buckets_in()`, 
+    /// report priorities. `LfuCache::frequency()` still returns frequency 
+    /// counts. An access that raises an item's priority by more than one
+    /// walks past the queues in between.
+    LfuDa,
+    /// Greedy-Dual Size Frequency: like `LfuDa`, but an item's priority is 
+    /// its frequency count times `scale`, divided by its weight, plus the 
+    /// age. Lighter items are kept over heavier ones that are used about as
+    /// often, so more of them fit, see `LfuCache::set_weigher()`. `scale`
+    /// sets how finely priorities are told apart: each one is rounded down 
+    /// to a whole number, and at least 1 is added to the age.
+    Gdsf { scale: usize },
+    /// Frequencies that decay over time. An item's score is its frequency 
+    /// count halved for every `half_life` since its last access, and the 
+    /// item with the lowest score is evicted. An item that was hot long ago
+    /// thus loses out to one that's been warm lately. Scores aren't updated
+    /// as time passes: since they all decay at the same rate, their order 
+    /// only changes when an item is accessed, and each item's priority is
+    /// worked out then, from its count and the time. Priorities are in 
+    /// sixteenths of a half-life, so scores within about 4% of each other
+    /// are told apart by recency instead.
+    /// 
+    /// The cache needs a clock, and the builder gives it the system's unless
+    /// it's given one, see `LfuCacheBuilder::clock()`. As with `LfuDa`, 
+    /// the methods that report on the queues report priorities.
+    Decay { half_life: Duration },
+    /// Least Recently/Frequently Used. Each item has a combined recency and
+    /// frequency, its CRF, which is 1 when it's inserted and becomes 
+    /// `1 + crf * 2^(-λ * elapsed)` on each access, where `elapsed` is the 
+    /// number of accesses to the cache since the item's last one. The item 
+    /// with the lowest CRF, as of now, is evicted. With a λ near 0, CRFs are
+    /// frequency counts and this is LFU; with a λ of 1 or more, the last 
+    /// access always counts for more than all the others put together, and
+    /// this is LRU. λ is set with `LfuCacheBuilder::lrfu()`, and is 0.01 
+    /// unless it's set.
+    /// 
+    /// As under `Decay`, the CRFs all decay at the same rate, so an item's
+    /// priority is only worked out when it's accessed, and the CRF is kept 
+    /// in the priority, finely enough that nearly every item has a queue of
+    /// its own. Logarithmic bucketing doesn't apply.
+    Lrfu,
+}
+
+#[cfg(feature = "std")]
+impl Policy {
+    /// Returns the priority of an item with the given frequency count and 
+    /// weight, while the cache's age is `age`.
+    /// 
+    fn priority(self, count: usize, weight: usize, age: usize) -> usize {
+        match self {
+            Self::Lfu            => count,
+            Self::LfuDa          => count + age,
+            Self::Gdsf { scale } => {
+                let share = count.saturating_mul(scale) / weight.max(1);
+                age.saturating_add(share.max(1))
+            },
+            // A score of `count` at `age`, in sixteenths of a half-life, is
+            // one of `count * 2^(age / 16)` at time 0. Sixteen times its log
+            // orders the items as their scores do at any other time.
+            Self::Decay { .. } => {
+                let log = (count.max(1) as f64).log2() * DECAY_STEPS;
+                age.saturating_add(log as usize).saturating_add(1)
+            },
+            Self::Lrfu => lrfu_priority(count.max(1) as f64, age),
+        }
+    }
+
+    /// Returns the new priority of an item accessed while the cache's age is
+    /// `age`: under `Lrfu` it's worked out from its `current` one, and under
+    /// the others from its new frequency count and its weight.
+    /// 
+    fn raised(self, current: usize, count: usize, weight: usize, age: usize) 
+        -> usize 
+    {
+        if self != Self::Lrfu { return self.priority(count, weight, age); }
+
+        let crf = lrfu_crf(current, age);
+
+        lrfu_priority(1.0 + crf, age).max(current)
+    }
+
+    /// Returns `true` if the cache's age is the priority of the last item 
+    /// evicted, as under `LfuDa` and `Gdsf`.
+    /// 
+    fn ages_by_eviction(self) -> bool {
+        matches!(self, Self::LfuDa | Self::Gdsf { .. })
+    }
+}
+
+/// The steps of priority in a half-life, under `Policy::Decay`.
+/// 
+#[cfg(feature = "std")]
+const DECAY_STEPS: f64 = 16.0;
+
+/// The steps of priority in a doubling of the CRF, under `Policy::Lrfu`.
+/// 
+#[cfg(feature = "std")]
+const LRFU_STEPS: f64 = 65536.0;
+
+/// The λ of `Policy::Lrfu`, unless the builder is given another.
+/// 
+#[cfg(feature = "std")]
+const LRFU_LAMBDA: f64 = 0.01;
+
+/// Returns the priority of an item with a CRF of `crf` while the cache's age
+/// is `age`, under `Policy::Lrfu`. The age is `λ * time` in steps, and a CRF
+/// of `crf` then is one of `crf * 2^(λ * time)` at time 0, whose log orders
+/// the items as their CRFs do at any other time.
+/// 
+#[cfg(feature = "std")]
+fn lrfu_priority(crf: f64, age: usize) -> usize {
+    let log = (crf.log2() * LRFU_STEPS).round() as usize;
+    age.saturating_add(log).saturating_add(1)
+}
+
+/// Returns the CRF an item with the given priority has while the cache's age
+/// is `age`, the inverse of `lrfu_priority()`.
+/// 
+#[cfg(feature = "std")]
+fn lrfu_crf(priority: usize, age: usize) -> f64 {
+    let log = priority as f64 - 1.0 - age as f64;
+    (log / LRFU_STEPS).exp2()
+}
+
+/// Returns the label of the queue for an item with the frequency count 
+/// `count`: the count itself, or with logarithmic bucketing, the highest power
+/// of `base` that's at most the count.
+/// 
+#[cfg(feature = "std")]
+fn bucket(count: usize, base: usize) -> usize {
+    if base == 0 { count } else { base.pow(count.max(1).ilog(base)) }
+}
+
+#[cfg(feature = "std")]
+type WeighFn<K, V> = Arc<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+/// Computes item weights and holds the weight limit, see 
+/// `LfuCache::set_weigher()`.
+/// 
+#[cfg(feature = "std")]
+struct Weigher<K, V> {
+    weigh : WeighFn<K, V>,
+    max   : usize,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> Clone for Weigher<K, V> {
+    fn clone(&self) -> Self {
+        Self { weigh: self.weigh.clone(), max: self.max }
+    }
+}
+
+/// Limits on how much work `LfuCache::run_maintenance()` does in one call.
+/// The default is no limit.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaintenanceBudget {
+    items : Option<usize>,
+    time  : Option<Duration>,
+}
+
+#[cfg(feature = "std")]
+impl MaintenanceBudget {
+    /// Returns a budget without limits.
+    /// 
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of items processed.
+    /// 
+    pub fn max_items(mut self, items: usize) -> Self {
+        self.items = Some(items);
+        self
+    }
+
+    /// Limits the time taken, roughly: the work is done in small batches, and
+    /// no new batch is started once `time` has passed.
+    /// 
+    pub fn max_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// What became of a key-value pair given to `LfuCache::insert_checked()`.
+/// 
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Eq)]
+#[must_use]
+#[non_exhaustive]
+pub enum InsertResult<V> {
+    /// The key was added.
+    Inserted,
+    /// The key was already cached, and this was its value.
+    Replaced(V),
+    /// The key was turned away by admission control, or because every item
+    /// is pinned or the capacity is 0.
+    Rejected,
+    /// The value's weight is over the limit for one item, see 
+    /// `LfuCache::set_max_item_fraction()`. Nothing was evicted for it, and 
+    /// the cache was left as it was, the key's old item included.
+    TooLarge { weight: usize },
+}
+
+/// What `LfuCache::lookup()` found for a key.
+/// 
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum GetResult<'a, V> {
+    /// The item is live.
+    Fresh(&'a V),
+    /// The item's TTL has run out, but it's within the stale-while-revalidate
+    /// window, so its value is served while it's refreshed.
+    Stale(&'a V),
+    /// The key isn't cached.
+    Miss,
+}
+
+#[cfg(feature = "std")]
+impl<V> InsertResult<V> {
+    /// Returns the value the pair replaced, if any, or the error for a pair
+    /// that wasn't cached.
+    /// 
+    pub fn into_result(self) -> Result<Option<V>, CacheError> {
+        match self {
+            Self::Inserted            => Ok(None),
+            Self::Replaced(old)       => Ok(Some(old)),
+            Self::Rejected            => Err(CacheError::Rejected),
+            Self::TooLarge { weight } => Err(CacheError::TooLarge { weight }),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, V> GetResult<'a, V> {
+    /// Returns the value, fresh or stale.
+    /// 
+    pub fn value(&self) -> Option<&'a V> {
+        match *self {
+            Self::Fresh(value) | Self::Stale(value) => Some(value),
+            Self::Miss                              => None,
+        }
+    }
+
+    /// Returns `true` if the value is being served stale.
+    /// 
+    pub fn is_stale(&self) -> bool {
+        matches!(self, Self::Stale(_))
+    }
+}
+
+/// How long an item has left to live, returned by `LfuCache::remaining_ttl()`.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemainingTtl {
+    /// The item expires after this long. It's zero for an item that has
+    /// expired but hasn't been dropped yet.
+    Expires(Duration),
+    /// The item has no TTL.
+    Never,
+    /// The key isn't cached.
+    Missing,
+}
+
+#[cfg(feature = "std")]
+impl RemainingTtl {
+    /// Returns the time left, if the item has a TTL.
+    /// 
+    pub fn duration(&self) -> Option<Duration> {
+        match *self {
+            Self::Expires(left)         => Some(left),
+            Self::Never | Self::Missing => None,
+        }
+    }
+}
+
+/// What a call of `LfuCache::run_maintenance()` or 
+/// `LfuCache::run_pending_tasks()` did.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Items left behind by `invalidate_all()` that were dropped.
+    pub reclaimed    : usize,
+    /// Expired items that were dropped.
+    pub expired      : usize,
+    /// The change adaptive capacity made, if any, see `LfuCache::adapt()`.
+    pub capacity     : Option<CapacityChange>,
+    /// Whether there's more work to do.
+    pub work_remains : bool,
+}
+
+/// Where a scan of an `LfuCache` is up to, see `LfuCache::scan()`. It's a 
+/// small `Copy` value, so it can be handed to a client between pages, and 
+/// with the `serde` feature it serializes to a token.
+/// 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanCursor {
+    after : Option<u64>,
+    done  : bool,
+}
+
+impl ScanCursor {
+    /// Returns a cursor for the start of a new scan.
+    /// 
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if the scan that returned this cursor is complete.
+    /// 
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+/// What the condition given to `LfuCache::evict_while()` sees before each
+/// eviction: the cache as it stands, and the item that would go next.
+/// 
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct EvictionContext<'a, K, V> {
+    /// The number of items in the cache.
+    pub len       : usize,
+    /// The total weight of the items, see `LfuCache::weight()`.
+    pub weight    : usize,
+    /// The key of the item up for eviction.
+    pub key       : &'a K,
+    /// Its value.
+    pub value     : &'a V,
+    /// Its frequency count.
+    pub frequency : usize,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> LfuCache<K, V> {
+    /// Creates a new LFU cache with the given capacity.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, DefaultState::default())
+    }
+
+    /// Like `new()`, but turns down a capacity of 0, with which the cache 
+    /// couldn't hold anything.
+    /// 
+    pub fn try_new(capacity: usize) -> Result<Self, CacheError> {
+        if capacity == 0 { return Err(CacheError::ZeroCapacity); }
+
+        Ok(Self::new(capacity))
+    }
+
+    /// Creates a cache holding the given `(key, value, frequency)` entries, 
+    /// such as a snapshot of another cache. For distinct keys that fit in
+    /// `capacity`, the result is the same as calling `insert_with_frequency()`
+    /// for each entry in turn, but the entries are sorted by frequency once 
+    /// and each queue is built in a single pass. Entries with equal 
+    /// frequencies are queued in the order given. If a key appears more than
+    /// once, the entry with the highest frequency wins, or the last of those
+    /// if they're tied.
+    /// 
+    /// If there are more entries than `capacity`, they're all loaded first 
+    /// and the cache is then truncated by popping LFU items until they fit, 
+    /// so the most frequently used entries are the ones kept. That isn't what
+    /// inserting them in turn does, since each insert into a full cache 
+    /// evicts the LFU item then, which can be an entry with a higher 
+    /// frequency than ones that come after it.
+    /// 
+    pub fn bulk_load(capacity : usize, 
+                     entries  : impl IntoIterator<Item = (K, V, usize)>) 
+        -> Self 
+    where
+        K: Eq + Hash + Clone,
+    {
+        let mut cache   = Self::new(capacity);
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+
+        if capacity == 0 { return cache; }
+
+        // The sort is stable, so queue order is kept within each frequency.
+        entries.sort_by_key(|(_, _, freq)| (*freq).max(1));
+
+        let mut hqueue = None;
+
+        for (key, value, freq) in entries {
+            let freq  = freq.max(1);
+            let hfreq = match hqueue {
+                Some(hfreq) if cache.frequencies.get(hfreq).0 == freq => hfreq,
+                _ => cache.frequencies.push_back((freq, LinkedVector::new())),
+            };
+            let mut vrec   = Value::new(value);
+            let     hash   = cache.map.hash(&key);
+            let     queued = Queued { hash, key: key.clone() };
+
+            vrec.hfreq = hfreq;
+            vrec.hpos  = cache.frequencies.get_mut(hfreq).1.push_back(queued);
+            vrec.count = freq;
+            vrec.gen   = cache.stamps;
+            hqueue     = Some(hfreq);
+
+            cache.stamps += 1;
+
+            if let Some(old) = cache.map.insert_hashed(hash, key, vrec) {
+                queues::unlink(&mut cache.frequencies, &old);
+            }
+        }
+        cache.weight = cache.map.len();
+
+        #[cfg(feature = "debug-verify")]
+        {
+            cache.shadow.learn_clone();
+            cache.shadow.lose();
+        }
+
+        while cache.len() > capacity {
+            cache.pop_lfu();
+        }
+        cache
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> LfuCache<K, V, SeededState> {
+    /// Creates a new LFU cache with the given capacity that hashes keys with a
+    /// fixed seed. Two caches created with the same seed and fed the same
+    /// operations behave identically, down to their iteration order, which
+    /// makes this useful for tests and simulations.
+    /// 
+    /// The hasher isn't DoS-resistant, so don't use it for keys an attacker
+    /// can choose.
+    /// 
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        Self::with_hasher(capacity, SeededState::new(seed))
+    }
+}
+
+/// Configures and creates an `LfuCache`, for when it needs more setting up
+/// than `LfuCache::new()` does. Each option corresponds to one of the cache's
+/// opt-in features, all of which are off unless set here.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct LfuCacheBuilder<S = DefaultState> {
+    capacity     : usize,
+    hash_builder : S,
+    stats        : bool,
+    admission    : bool,
+    increments   : Option<u64>,
+    rng_seed     : Option<u64>,
+    clock        : Option<Arc<dyn Clock>>,
+    refresh      : Option<Duration>,
+    grace        : Option<Duration>,
+    default_ttl  : Option<Duration>,
+    policy       : Policy,
+    lambda       : f64,
+    log_base     : usize,
+    adaptive     : Option<AdaptiveCapacity>,
+
+    #[cfg(feature = "debug-audit")]
+    audit_log    : Option<usize>,
+}
+
+#[cfg(feature = "std")]
+impl LfuCacheBuilder {
+    /// Creates a builder for a cache with the given capacity.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            hash_builder : DefaultState::default(),
+            stats        : false,
+            admission    : false,
+            increments   : None,
+            rng_seed     : None,
+            clock        : None,
+            refresh      : None,
+            grace        : None,
+            default_ttl  : None,
+            policy       : Policy::Lfu,
+            lambda       : LRFU_LAMBDA,
+            log_base     : 0,
+            adaptive     : None,
+
+            #[cfg(feature = "debug-audit")]
+            audit_log    : None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S> LfuCacheBuilder<S> {
+    /// Has the cache hash keys with `hash_builder`, see 
+    /// `LfuCache::with_hasher()`.
+    /// 
+    pub fn hasher<T>(self, hash_builder: T) -> LfuCacheBuilder<T> {
+        LfuCacheBuilder {
+            capacity     : self.capacity,
+            hash_builder,
+            stats        : self.stats,
+            admission    : self.admission,
+            increments   : self.increments,
+            rng_seed     : self.rng_seed,
+            clock        : self.clock,
+            refresh      : self.refresh,
+            grace        : self.grace,
+            default_ttl  : self.default_ttl,
+            policy       : self.policy,
+            lambda       : self.lambda,
+            log_base     : self.log_base,
+            adaptive     : self.adaptive,
+
+            #[cfg(feature = "debug-audit")]
+            audit_log    : self.audit_log,
+        }
+    }
+
+    /// Has the cache keep `CacheStats`, see `LfuCache::enable_stats()`.
+    /// 
+    pub fn stats(mut self) -> Self {
+        self.stats = true;
+        self
+    }
+
+    /// Enables admission control, see `LfuCache::enable_admission()`.
+    /// 
+    pub fn admission(mut self) -> Self {
+        self.admission = true;
+        self
+    }
+
+    /// Enables the audit log, see `LfuCache::enable_audit_log()`.
+    /// 
+    #[cfg(feature = "debug-audit")]
+    pub fn audit_log(mut self, len: usize) -> Self {
+        self.audit_log = Some(len);
+        self
+    }
+
+    /// Makes frequency increments probabilistic, like Redis's LFU counters.
+    /// An access to an item with frequency `f` only increments it with a 
+    /// probability of `1 / ((f - 1) * factor + 1)`, so the first access
+    /// always counts and each one after that is less likely to. Frequency
+    /// counts then grow roughly logarithmically with the number of accesses,
+    /// and popular items stop being moved to a new queue on every access. 
+    /// The relative order of hot and cold items is kept, though items with
+    /// similar access counts can swap places.
+    /// 
+    /// A `factor` of 0 makes every access count, as usual.
+    /// 
+    pub fn probabilistic_increments(mut self, factor: u64) -> Self {
+        self.increments = Some(factor);
+        self
+    }
+
+    /// Seeds the random number generator used for probabilistic increments,
+    /// making the cache's choices repeatable. Without a seed it's seeded 
+    /// randomly.
+    /// 
+    pub fn rng_seed(mut self, seed: u64) -> Self {
+        self.rng_seed = Some(seed);
+        self
+    }
+
+    /// Has the cache tell time with `clock`, see `LfuCache::set_clock()`.
+    /// 
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Enables refresh-ahead, see `LfuCache::enable_refresh()`.
+    /// 
+    pub fn refresh_after(mut self, after: Duration) -> Self {
+        self.refresh = Some(after);
+        self
+    }
+
+    /// Has the cache serve expired items stale for up to `window`, see 
+    /// `LfuCache::enable_stale_while_revalidate()`.
+    /// 
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.grace = Some(window);
+        self
+    }
+
+    /// Has every item written without a TTL of its own expire once `ttl` has
+    /// passed, see `LfuCache::set_default_ttl()`.
+    /// 
+    pub fn expire_after_write(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the eviction policy. The default is plain LFU.
+    /// 
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Has the cache evict by `Policy::Lrfu`, with `lambda` as λ: the nearer
+    /// it is to 0 the more the cache behaves like LFU, and from 1 up it 
+    /// behaves like LRU.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCacheBuilder;
+    /// 
+    /// let mut cache = LfuCacheBuilder::new(2).lrfu(1.0).build();
+    /// 
+    /// cache.insert(1, "a");
+    /// for _ in 0..10 { cache.get(&1); }
+    /// cache.insert(2, "b");
+    /// cache.insert(3, "c");
+    /// 
+    /// // Like LRU, the cache kept the two keys used last.
+    /// assert!(!cache.contains_key(&1));
+    /// ```
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `lambda` is negative or not finite.
+    /// 
+    pub fn lrfu(mut self, lambda: f64) -> Self {
+        assert!(lambda >= 0.0 && lambda.is_finite(),
+                "lambda must be finite and at least 0");
+        self.policy = Policy::Lrfu;
+        self.lambda = lambda;
+        self
+    }
+
+    /// Has the cache queue items by frequency buckets at powers of `base` 
+    /// (1, 2, 4, 8, ... for a base of 2) instead of by exact frequency count.
+    /// An access only moves an item to another queue when its count crosses
+    /// into the next bucket, so there are only about `log(max_frequency)` 
+    /// queues however skewed the workload is. Exact counts are still kept, 
+    /// and returned by `LfuCache::frequency()`, but items in the same bucket
+    /// are evicted least recently used first, whatever their counts. Methods
+    /// that report on the queues, like `LfuCache::frequency_histogram()`, 
+    /// report bucket labels. Under the other policies the bucket label takes 
+    /// the place of the count in the priority.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `base` is less than 2.
+    /// 
+    pub fn log_buckets(mut self, base: usize) -> Self {
+        assert!(base >= 2, "the bucket base must be at least 2");
+        self.log_base = base;
+        self
+    }
+
+    /// Has the cache tune its capacity to a hit ratio, see 
+    /// `LfuCache::enable_adaptive_capacity()`.
+    /// 
+    pub fn adaptive_capacity(mut self, adaptive: AdaptiveCapacity) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    /// Creates the cache.
+    /// 
+    pub fn build<K, V>(self) -> LfuCache<K, V, S> 
+    where
+        K: Eq + Hash + Clone,
+        S: BuildHasher,
+    {
+        let mut cache = LfuCache::with_hasher(self.capacity, 
+                                              self.hash_builder);
+        if self.stats     { cache.enable_stats(); }
+        if self.admission { cache.enable_admission(); }
+
+        cache.clock    = self.clock;
+        cache.policy   = self.policy;
+        cache.lambda   = self.lambda;
+        cache.log_base = self.log_base;
+
+        if let Policy::Decay { .. } = self.policy {
+            cache.clock.get_or_insert_with(|| Arc::new(SystemClock::new()));
+        }
+
+        if let Some(after) = self.refresh { cache.enable_refresh(after); }
+
+        if let Some(adaptive) = self.adaptive {
+            cache.enable_adaptive_capacity(adaptive);
+        }
+
+        if let Some(window) = self.grace {
+            cache.enable_stale_while_revalidate(window);
+        }
+        if let Some(ttl) = self.default_ttl {
+            cache.set_default_ttl(Some(ttl));
+        }
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(len) = self.audit_log {
+            cache.enable_audit_log(len);
+        }
+        if let Some(factor) = self.increments {
+            let seed = self.rng_seed.unwrap_or_else(|| {
+                RandomState::new().hash_one(0)
+            });
+            cache.increments = Some(LogIncrements { 
+                rng: Rng::new(seed), 
+                factor,
+            });
+        }
+        cache
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> LfuCache<K, V, S> {
+    /// Creates a new LFU cache with the given capacity that uses
+    /// `hash_builder` to hash keys.
+    /// 
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::with_counter(capacity, hash_builder)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, C: Counter> LfuCache<K, V, S, C> {
+    /// Like `with_hasher()`, for a cache that keeps its frequency counts in
+    /// `C` rather than `usize`, see the `Counter` trait. `C` comes with the
+    /// type of the cache:
+    /// 
+    /// ```
+    /// use lfu_cache::{DefaultState, LfuCache};
+    /// 
+    /// let mut cache = LfuCache::<_, _, DefaultState, u8>::with_counter(
+    ///                     10, DefaultState::default());
+    /// 
+    /// cache.insert("a", 1);
+    /// for _ in 0..300 { cache.get(&"a"); }
+    /// assert_eq!(cache.frequency(&"a"), Some(255));
+    /// ```
+    pub fn with_counter(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            map         : HashedMap::with_capacity_and_hasher(capacity, 
+                                                              hash_builder),
+            frequencies : LinkedVector::new(),
+            capacity,
+            stats       : None,
+            loads       : None,
+            admission   : None,
+            increments  : None,
+            tags        : None,
+            prefixes    : None,
+            insertion   : None,
+            stamps      : 0,
+            generation  : 0,
+            stale       : 0,
+            retired     : Vec::new(),
+            pins        : HashMap::new(),
+            evict_first : None,
+            clock       : None,
+            refresh     : None,
+            grace       : Duration::ZERO,
+            default_ttl : None,
+            expiries    : TimerWheel::new(),
+            policy      : Policy::Lfu,
+            age         : 0,
+            lambda      : LRFU_LAMBDA,
+            log_base    : 0,
+            weigher     : None,
+            weight      : 0,
+            item_share  : 1.0,
+            uncached    : None,
+            notify      : None,
+            adapter     : None,
+
+            #[cfg(feature = "debug-audit")]
+            audit       : None,
+
+            #[cfg(feature = "debug-verify")]
+            shadow      : Shadow::new(),
+        }
+    }
+
+    /// Returns the number of items in the cache.
+    /// 
+    pub fn len(&self) -> usize {
+        self.map.len() - self.stale
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of items the cache holds.
+    /// 
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the total weight of the items, see `set_weigher()`. Without a
+    /// weigher every item weighs 1.
+    /// 
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Returns the number of items invalidated by `invalidate_all()` that 
+    /// haven't been reclaimed yet, and are still taking up memory.
+    /// 
+    pub fn pending_reclaim(&self) -> usize {
+        self.stale
+    }
+
+    /// Returns an estimate of the memory the cache takes up, in bytes: the 
+    /// cache itself, its map and its queues, at their allocated capacities.
+    /// Memory the keys and values own, and the optional indexes and logs,
+    /// aren't counted. The `compact` feature makes the value records smaller,
+    /// and so does a smaller counter, see `with_counter()`.
+    /// 
+    pub fn memory_footprint(&self) -> usize {
+        use std::mem::size_of;
+
+        // Each queue node holds an optional value and two links.
+        fn nodes<T>(queue: &LinkedVector<T>) -> usize {
+            queue.capacity() * size_of::<(Option<T>, HNode, HNode)>()
+        }
+        let queues = |queues: &Queues<K>| {
+            nodes(queues) + queues.iter().map(|(_, q)| nodes(q)).sum::<usize>()
+        };
+        // A map slot holds a hash, a key and a value record, plus a control
+        // byte.
+        let map = self.map.capacity() 
+                  * (size_of::<(u64, K, Value<V, C>)>() + 1);
+
+        size_of::<Self>() 
+            + map 
+            + queues(&self.frequencies) 
+            + self.retired.iter().map(queues).sum::<usize>()
+    }
+
+    /// Returns the cache's stats, or `None` if they aren't enabled.
+    /// 
+    pub fn stats(&self) -> Option<&CacheStats> {
+        self.stats.as_ref()
+    }
+
+    /// Enables the stats, starting them from `stats` rather than from zero.
+    /// 
+    #[cfg(feature = "serde")]
+    pub(crate) fn restore_stats(&mut self, stats: CacheStats) {
+        self.stats = Some(stats);
+        self.loads = Some(LoadStats::default());
+    }
+
+    /// Returns how long the loaders given to `get_or_insert_with()` and the
+    /// like have taken on misses, timed on the cache's clock if it has one, 
+    /// or `None` if stats aren't enabled. Hits aren't timed.
+    /// 
+    pub fn load_stats(&self) -> Option<&LoadStats> {
+        self.loads.as_ref()
+    }
+
+    /// Sets the load stats back to zero, if they're enabled, leaving the rest
+    /// of the stats as they are.
+    /// 
+    pub fn reset_load_stats(&mut self) {
+        if let Some(loads) = &mut self.loads { *loads = LoadStats::default(); }
+    }
+
+    /// Returns an iterator over the audit log, oldest record first. It's empty
+    /// if the log isn't enabled.
+    /// 
+    #[cfg(feature = "debug-audit")]
+    pub fn audit_log(&self) -> AuditRecords<'_, K> {
+        let records = self.audit.as_ref().map(|log| log.records.iter());
+
+        AuditRecords { records, key: None }
+    }
+
+    /// Returns the number of items at each frequency count, as 
+    /// `(frequency, count)` pairs in ascending order of frequency. Empty 
+    /// frequencies are skipped, as `buckets_in()` skips them.
+    /// 
+    pub fn frequency_histogram(&self) -> Vec<(usize, usize)> {
+        self.frequencies.iter().filter(|(_, q)| !q.is_empty())
+                               .map(|(freq, q)| (*freq, q.len()))
+                               .collect()
+    }
+
+    /// Returns a structured snapshot of the cache's shape: its capacity, 
+    /// size, the keys in each frequency queue that has any, and its stats. 
+    /// Keys are cloned; values are left out, so the snapshot is safe to log 
+    /// or ship to tooling without exposing payloads. With the `serde` 
+    /// feature enabled, the snapshot can be serialized.
+    /// 
+    pub fn dump_state(&self) -> CacheState<K> 
+    where
+        K: Clone,
+    {
+        CacheState {
+            capacity : self.capacity,
+            len      : self.len(),
+            buckets  : self.frequencies.iter()
+                                       .filter(|(_, queue)| !queue.is_empty())
+                                       .map(|(freq, queue)| {
+                           BucketState {
+                               frequency : *freq,
+                               keys      : queue.iter()
+                                                .map(|q| q.key.clone())
+                                                .collect(),
+                           }
+                       }).collect(),
+            stats    : self.stats,
+        }
+    }
+
+    /// Returns an iterator over the key-value pairs of the cache in arbitrary
+    /// order, the map's, which depends on the hasher. It's quicker than 
+    /// `iter()`, which looks up each item in the map. Looking at items this 
+    /// way doesn't change their frequency counts.
+    /// 
+    pub fn iter_unordered(&self) -> IterUnordered<'_, K, V, C> {
+        IterUnordered { items: self.map.iter(), generation: self.generation }
+    }
+
+    /// Like `iter_unordered()`, with each item's hash, as the map keeps it.
+    /// 
+    fn iter_hashed(&self) -> impl Iterator<Item = (u64, &K, &V)> {
+        self.map.iter_hashed()
+                .filter(|(_, _, vrec)| vrec.gen >= self.generation)
+                .map(|(hash, key, vrec)| (hash, key, &vrec.value))
+    }
+
+    /// Like `iter_unordered()`, but with each item's frequency count too, 
+    /// see `entries()`.
+    /// 
+    pub fn entries_unordered(&self) -> EntriesUnordered<'_, K, V, C> {
+        EntriesUnordered { items      : self.map.iter(), 
+                           generation : self.generation }
+    }
+
+    /// Consumes the cache, returning an iterator that moves its items out in
+    /// eviction order, the item `pop_lfu()` would remove first coming first,
+    /// with their frequency counts, see `entries()`. Nothing is cloned, and
+    /// the items that aren't iterated over are dropped with the iterator.
+    /// 
+    pub fn into_entries(self) -> IntoEntries<K, V, S, C> {
+        IntoEntries {
+            left   : self.len(),
+            queues : self.frequencies,
+            map    : self.map,
+        }
+    }
+
+    /// Returns an iterator over the keys in eviction order: the key 
+    /// `pop_lfu()` would remove first comes first. Frequency counts aren't
+    /// changed.
+    /// 
+    pub fn eviction_order(&self) -> EvictionOrder<'_, K> {
+        EvictionOrder { order: queues::lfu_order(&self.frequencies) }
+    }
+
+    /// Returns the time on the cache's clock, or zero if it doesn't have one.
+    /// 
+    fn now(&self) -> Duration {
+        self.clock.as_ref().map_or(Duration::ZERO, |clock| clock.now())
+    }
+
+    /// Returns the age to work out the priorities of items accessed now 
+    /// with, see `Policy`. Under `Policy::Decay` it's the time in sixteenths
+    /// of a half-life. Under `Policy::Lrfu` it's `λ` times the number of 
+    /// accesses so far, in steps, and this counts one more. An operation 
+    /// takes one step, however many items it touches.
+    /// 
+    fn next_age(&mut self) -> usize {
+        if self.policy == Policy::Lrfu { self.age += 1; }
+
+        self.current_age()
+    }
+
+    /// Returns the age `next_age()` last did, without counting an access, 
+    /// or the age evictions have raised it to since, under `Policy::LfuDa`.
+    /// 
+    fn current_age(&self) -> usize {
+        match self.policy {
+            Policy::Decay { half_life } => {
+                let time = self.now().as_secs_f64() / half_life.as_secs_f64();
+                (time * DECAY_STEPS) as usize
+            },
+            Policy::Lrfu => {
+                (self.age as f64 * self.lambda * LRFU_STEPS) as usize
+            },
+            _ => self.age,
+        }
+    }
+
+    /// Starts timing a load on the cache's clock, see `load_stats()`.
+    /// 
+    pub(crate) fn stopwatch(&self) -> Stopwatch {
+        Stopwatch::start(self.clock.as_ref())
+    }
+
+    /// Starts timing a load if load stats are kept.
+    /// 
+    pub(crate) fn start_load(&self) -> Option<Stopwatch> {
+        self.loads.is_some().then(|| self.stopwatch())
+    }
+
+    /// Records a load that was started with `start_load()`.
+    /// 
+    pub(crate) fn finish_load(&mut self, watch: Option<Stopwatch>, ok: bool) {
+        if let (Some(loads), Some(watch)) = (&mut self.loads, watch) {
+            loads.record(watch.elapsed(), ok);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, C: Counter> LfuCache<K, V, S, C> 
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns an iterator over the key-value pairs of the cache in eviction
+    /// order, the item `pop_lfu()` would remove first coming first. The order
+    /// only depends on what's been done to the cache, not on its hasher, so
+    /// two caches given the same operations iterate alike, see 
+    /// `iter_unordered()` for a quicker iterator in arbitrary order. Looking
+    /// at items this way doesn't change their frequency counts.
+    /// 
+    pub fn iter(&self) -> Iter<'_, K, V, S, C> {
+        Iter { order: queues::lfu_order(&self.frequencies), map: &self.map }
+    }
+
+    /// Like `iter()`, but with each item's frequency count too, as 
+    /// `frequency()` would return it. Under `Policy::Lfu` the items in each
+    /// bucket of `frequency_histogram()` are the ones with its frequency.
+    /// 
+    pub fn entries(&self) -> Entries<'_, K, V, S, C> {
+        Entries { order: queues::lfu_order(&self.frequencies), map: &self.map }
+    }
+
+    /// Returns `true` if the key is cached, without counting an access.
+    /// 
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.contains_hashed(self.map.hash(key), key)
+    }
+
+    /// Returns the hash of `key` under the cache's hasher, for the methods
+    /// that take it rather than hash the key again.
+    /// 
+    pub(crate) fn hash_key(&self, key: &K) -> u64 {
+        self.map.hash(key)
+    }
+
+    /// Does the work of `contains_key()` for a key whose hash is `hash`.
+    /// 
+    pub(crate) fn contains_hashed(&self, hash: u64, key: &K) -> bool {
+        self.map.get_hashed(hash, key).is_some_and(|vrec| {
+            vrec.gen >= self.generation 
+                && !vrec.expired(self.now(), self.grace)
+        })
+    }
+
+    /// Returns the key's place in the order keys entered the cache: the 
+    /// number it was given from a sequence that counts up with each new key,
+    /// see `enable_insertion_order()`. Overwriting a cached key keeps its 
+    /// number, while a key that's evicted and inserted again gets a new one.
+    /// Returns `None` if the key isn't cached or insertion order isn't kept.
+    /// 
+    pub fn insertion_index(&self, key: &K) -> Option<u64> {
+        self.insertion.as_ref()?.get(key)
+    }
+
+    /// Returns an iterator over the cached keys in the order they entered the
+    /// cache, oldest first, see `insertion_index()`. It's empty if insertion
+    /// order isn't kept.
+    /// 
+    pub fn keys_in_insertion_order(&self) -> KeysInInsertionOrder<'_, K> {
+        KeysInInsertionOrder { keys: self.insertion.as_ref().map(|i| i.keys()) }
+    }
+
+    /// Returns the frequency count of the key, or `None` if it isn't cached.
+    /// It's the count of accesses, without the key's priority boost, see
+    /// `set_priority()`.
+    /// 
+    pub fn frequency(&self, key: &K) -> Option<usize> {
+        self.map.get(key).filter(|vrec| vrec.gen >= self.generation)
+                         .map(|vrec| vrec.count.to_usize())
+    }
+
+    /// Returns the key's priority boost, or `None` if it isn't cached, see
+    /// `set_priority()`.
+    /// 
+    pub fn priority(&self, key: &K) -> Option<u8> {
+        self.map.get(key).filter(|vrec| vrec.gen >= self.generation)
+                         .map(|vrec| vrec.boost)
+    }
+
+    /// Returns how long the key's item has left before its TTL runs out, or
+    /// whether it has no TTL or isn't cached. The stale-while-revalidate
+    /// window isn't counted, see `enable_stale_while_revalidate()`.
+    /// 
+    pub fn remaining_ttl(&self, key: &K) -> RemainingTtl {
+        let vrec = self.map.get(key).filter(|vrec| vrec.gen >= self.generation);
+        let now  = self.now();
+
+        match vrec.map(|vrec| vrec.deadline()) {
+            Some(Some(at)) => RemainingTtl::Expires(at.saturating_sub(now)),
+            Some(None)     => RemainingTtl::Never,
+            None           => RemainingTtl::Missing,
+        }
+    }
+
+    /// Returns the items that expire within `window` from now, soonest first,
+    /// each with the time it has left, for refreshing them before they go.
+    /// Items that have already expired aren't included, and an item that 
+    /// expires right at the end of the window is. The items are found in the
+    /// slots of the expiry index that the window covers, so this takes time
+    /// in proportion to the number of items that expire within about the 
+    /// window, plus sorting them, rather than to the size of the cache. 
+    /// Frequency counts aren't changed.
+    /// 
+    pub fn iter_expiring_within(&self, window: Duration) 
+        -> impl Iterator<Item = (&K, &V, Duration)> 
+    {
+        let now = self.now();
+
+        let mut items = self.expiries
+                            .due_by(now.saturating_add(window))
+                            .filter_map(|(key, at)| {
+            let (key, vrec) = self.map.get_key_value(key)?;
+            let left        = at.checked_sub(now)?;
+
+            (vrec.gen >= self.generation 
+                && !left.is_zero() && left <= window)
+            .then_some((key, &vrec.value, left))
+        }).collect::<Vec<_>>();
+
+        items.sort_by_key(|(_, _, left)| *left);
+        items.into_iter()
+    }
+
+    /// Returns the number of times the key has been looked up with `get()`, 
+    /// or the likes of `get_or_insert_with()`, since it was inserted or since
+    /// `reset_hit_counters()` was last called, or `None` if it isn't cached. 
+    /// Peeks and accesses through `observe()` aren't counted. Unlike the 
+    /// frequency count, the hit count has no part in eviction. It stops at 
+    /// `u64::MAX`.
+    /// 
+    pub fn hits(&self, key: &K) -> Option<u64> {
+        self.map.get(key).filter(|vrec| vrec.gen >= self.generation)
+                         .map(|vrec| vrec.hits)
+    }
+
+    /// Sets the hit count of every item back to 0, see `hits()`. Frequency 
+    /// counts and eviction order aren't changed.
+    /// 
+    pub fn reset_hit_counters(&mut self) {
+        self.map.values_mut().for_each(|vrec| vrec.hits = 0);
+    }
+
+    /// Returns an iterator over the audit records that concern `key`, oldest
+    /// first. Events that affect every key, like `AuditEvent::Cleared`, are
+    /// included.
+    /// 
+    #[cfg(feature = "debug-audit")]
+    pub fn audit_for<'a>(&'a self, key: &'a K) -> AuditRecords<'a, K> {
+        AuditRecords { key: Some(key), ..self.audit_log() }
+    }
+
+    /// Returns a page of up to `limit` items, cloned, starting where `cursor`
+    /// left off, and the cursor for the next page. A scan starts with 
+    /// `ScanCursor::new()` and is complete once the cursor returned 
+    /// `is_done()`. The cache can change between pages: every item that's 
+    /// cached for the whole scan is returned at least once, while items that
+    /// come or go during it may or may not be. Frequency counts aren't 
+    /// changed.
+    /// 
+    /// Items are visited in the order of their keys' hashes, which don't 
+    /// change as the cache does. Each page visits every item to find the next
+    /// hashes in line, so a full scan takes O(n² / limit) time. A page can 
+    /// hold more than `limit` items if their hashes collide, and always holds
+    /// at least one item unless the scan is done.
+    /// 
+    pub fn scan(&self, cursor: ScanCursor, limit: usize) 
+        -> (Vec<(K, V)>, ScanCursor) 
+    where
+        K: Clone,
+        V: Clone,
+    {
+        if cursor.done { return (Vec::new(), cursor); }
+
+        let after  = |&hash: &u64| cursor.after.is_none_or(|a| hash > a);
+        let hashes = self.iter_hashed().map(|(hash, _, _)| hash).filter(after);
+
+        // Find the hash the page ends at, keeping the `limit` lowest in a 
+        // max-heap as the rest go by.
+        let limit    = limit.max(1);
+        let mut heap = BinaryHeap::with_capacity(limit + 1);
+        let mut left = 0;
+
+        for hash in hashes {
+            left += 1;
+            heap.push(hash);
+
+            if heap.len() > limit { heap.pop(); }
+        }
+        let Some(&last) = heap.peek() else {
+            return (Vec::new(), ScanCursor { after: cursor.after, done: true });
+        };
+        let page = self.iter_hashed()
+                       .filter(|&(hash, _, _)| after(&hash) && hash <= last)
+                       .map(|(_, key, value)| (key.clone(), value.clone()))
+                       .collect::<Vec<_>>();
+
+        let done = page.len() == left;
+
+        (page, ScanCursor { after: Some(last), done })
+    }
+
+    /// Returns a snapshot of the cache's items, in eviction order, that later
+    /// changes to the cache don't affect. The keys and values are cloned into
+    /// it, which for `Arc`s only means bumping their reference counts, so 
+    /// with `Arc` values the snapshot is cheap; otherwise it's a full copy.
+    /// 
+    pub fn snapshot_shared(&self) -> LfuSnapshot<K, V> 
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let entries = queues::lfu_order(&self.frequencies).map(|(freq, key)| {
+            (key.clone(), self.map[key].value.clone(), freq)
+        });
+        LfuSnapshot::new(entries.collect())
+    }
+
+    /// Returns the keys of the cache with their frequency counts, in eviction
+    /// order, to be saved and given to `apply_warm_state()` later, such as 
+    /// after a restart. The values are left out, so the state stays small.
+    /// Frequency counts aren't changed.
+    /// 
+    pub fn export_warm_state(&self) -> WarmState<K> 
+    where
+        K: Clone,
+    {
+        let entries = queues::lfu_order(&self.frequencies).map(|(_, key)| {
+            (key.clone(), self.map[key].count.to_usize())
+        });
+        WarmState { entries: entries.collect() }
+    }
+
+    /// Returns clones of up to `n` items from the hot end of the eviction 
+    /// order, the reverse of it, with their frequency counts: the item 
+    /// `pop_lfu()` would remove last comes first. They're for seeding another
+    /// cache through `warm_from()`, such as a new replica's. Frequency counts
+    /// aren't changed.
+    /// 
+    pub fn hot_entries(&self, n: usize) -> Vec<(K, V, usize)> 
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let hottest = self.frequencies.iter().rev().flat_map(|(_, queue)| {
+            queue.iter().rev()
+        });
+        hottest.take(n).map(|Queued { hash, key }| {
+            let vrec = self.map.get_hashed(*hash, key).unwrap();
+            (key.clone(), vrec.value.clone(), vrec.count.to_usize())
+        })
+        .collect()
+    }
+
+    /// Returns the key of the item that `pop_lfu()`, or an insert into the
+    /// full cache, would remove next. Pinned items are passed over, and 
+    /// unreferenced ones go first if `enable_unreferenced_eviction()` is on.
+    /// 
+    pub fn eviction_candidate(&self) -> Option<&K> {
+        self.candidate_at().map(|at| self.key_at(at))
+    }
+
+    /// Returns the handles of the eviction candidate's key in the queues, see
+    /// `key_at()`.
+    /// 
+    fn candidate_at(&self) -> Option<(HNode, HNode)> {
+        let freq_qs   = &self.frequencies;
+        let queued    = |(hfreq, hpos): (HNode, HNode)| {
+            freq_qs.get(hfreq).1.get(hpos)
+        };
+        let mut order = std::iter::successors(queues::first(freq_qs), |&at| {
+            queues::after(freq_qs, at)
+        });
+        if let Some(dead) = self.evict_first {
+            let at = order.clone().find(|&at| {
+                let Queued { hash, key } = queued(at);
+                let vrec = self.map.get_hashed(*hash, key).unwrap();
+
+                !self.is_pinned(key) && dead(&vrec.value)
+            });
+            if at.is_some() { return at; }
+        }
+        if self.pins.is_empty() {
+            order.next()
+        } else {
+            order.find(|&at| !self.is_pinned(&queued(at).key))
+        }
+    }
+
+    /// Returns `true` if `key` is the next item to be evicted, see 
+    /// `eviction_candidate()`.
+    /// 
+    pub fn would_evict_next(&self, key: &K) -> bool {
+        self.eviction_candidate() == Some(key)
+    }
+
+    /// Returns the bucket of items with the frequency count `freq`, or `None`
+    /// if no item has that count. Finding it takes a walk past the buckets 
+    /// for lower frequencies.
+    /// 
+    pub fn peek_bucket(&self, freq: usize) -> Option<Bucket<'_, K, V, S, C>> {
+        self.buckets_in(freq..=freq).next()
+    }
+
+    /// Returns an iterator over the buckets with frequency counts in the 
+    /// range, from lowest frequency to highest. Empty frequencies are skipped.
+    /// 
+    pub fn buckets_in<R>(&self, range: R) 
+        -> Buckets<'_, K, V, S, C> 
+    where
+        R: RangeBounds<usize>,
+    {
+        Buckets {
+            queues  : self.frequencies.iter(),
+            range   : (range.start_bound().cloned(), 
+                       range.end_bound().cloned()),
+            map     : &self.map,
+            started : false,
+        }
+    }
+
+    /// Returns an iterator over the values in eviction order, coldest first.
+    /// Frequency counts aren't changed.
+    /// 
+    pub fn values_lfu_first(&self) -> ValuesLfuFirst<'_, K, V, S, C> {
+        ValuesLfuFirst { order: queues::lfu_order(&self.frequencies), 
+                         map  : &self.map }
+    }
+
+    /// Returns an iterator over mutable references to the values in eviction
+    /// order, coldest first. Frequency counts aren't changed.
+    /// 
+    pub fn values_lfu_first_mut(&mut self) -> ValuesLfuFirstMut<'_, K, V> {
+        ValuesLfuFirstMut { items: self.iter_mut_by_eviction_order() }
+    }
+
+    /// Returns an iterator over the items in eviction order, coldest first,
+    /// yielding each key, a mutable reference to its value and its frequency
+    /// count. Frequency counts aren't changed.
+    /// 
+    pub fn iter_mut_by_eviction_order(&mut self) 
+        -> EvictionOrderMut<'_, K, V> 
+    {
+        let map = &mut self.map;
+
+        // The order comes from the queues but the values live in the map, so
+        // pointers to them are gathered first. Each key is in exactly one 
+        // queue, so the pointers are all to different values, and the map 
+        // isn't accessed again while they're handed out.
+        let items = queues::lfu_order(&self.frequencies)
+                        .map(|(freq, key)| {
+                            let value = &mut map.get_mut(key).unwrap().value;
+                            (key, value as *mut V, freq)
+                        })
+                        .collect::<Vec<_>>();
+
+        EvictionOrderMut { items: items.into_iter(), _map: PhantomData }
+    }
+
+    /// Calls `f` with each item's key, value and frequency count, as 
+    /// `frequency()` would return it, in eviction order, coldest first. 
+    /// Frequency counts aren't changed. It's `entries()` as a visitor.
+    /// 
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V, usize)) {
+        for (key, value, freq) in self.entries() { f(key, value, freq); }
+    }
+
+    /// Like `for_each()`, with a mutable reference to each value. Values 
+    /// changed this way keep the weight they were written with.
+    /// 
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(&K, &mut V, usize)) {
+        for (_, key) in queues::lfu_order(&self.frequencies) {
+            let vrec = self.map.get_mut(key).unwrap();
+
+            f(key, &mut vrec.value, vrec.count.to_usize());
+        }
+    }
+
+    /// Returns a cursor on the LFU item, which walks the items in eviction
+    /// order and can change, bump or remove each one it comes to, see 
+    /// `EntryCursorMut`. It's for walks `retain_mut()` can't do, ones that
+    /// keep state, stop early, or count accesses along the way.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCache;
+    /// 
+    /// let mut cache = LfuCache::new(10);
+    /// 
+    /// for key in 0..6 { cache.insert(key, key * 10); }
+    /// 
+    /// // Drop the coldest items until 25 has been freed up.
+    /// let mut freed  = 0;
+    /// let mut cursor = cache.cursor_entries_mut();
+    /// 
+    /// while freed < 25 {
+    ///     let Some((_, value)) = cursor.remove_current() else { break };
+    ///     freed += value;
+    /// }
+    /// assert_eq!(cursor.current(), Some((&3, &mut 30, 1)));
+    /// assert_eq!(cache.len(), 3);
+    /// ```
+    pub fn cursor_entries_mut(&mut self) -> EntryCursorMut<'_, K, V, S, C> {
+        let at = queues::first(&self.frequencies);
+
+        EntryCursorMut { cache: self, at }
+    }
+
+    /// Returns the key whose handles, of its queue and of its node in the 
+    /// queue, are `at`.
+    /// 
+    pub(crate) fn key_at(&self, at: (HNode, HNode)) -> &K {
+        &self.frequencies.get(at.0).1.get(at.1).key
+    }
+
+    /// Like `key_at()`, returning the item with its frequency count.
+    /// 
+    pub(crate) fn entry_at(&mut self, at: (HNode, HNode)) 
+        -> (&K, &mut V, usize) 
+    {
+        let Queued { hash, key } = self.frequencies.get(at.0).1.get(at.1);
+        let vrec = self.map.get_mut_hashed(*hash, key).unwrap();
+
+        (key, &mut vrec.value, vrec.count.to_usize())
+    }
+
+    /// Returns the handles of the key after the one at `at` in eviction
+    /// order, see `key_at()`.
+    /// 
+    pub(crate) fn position_after(&self, at: (HNode, HNode)) 
+        -> Option<(HNode, HNode)> 
+    {
+        queues::after(&self.frequencies, at)
+    }
+
+    /// Returns a clone of the cached key equal to `key`, stale or not, for 
+    /// callers that only have a borrowed form of it.
+    /// 
+    #[cfg(feature = "cached")]
+    pub(crate) fn stored_key<Q>(&self, key: &Q) -> Option<K>
+    where
+        K: Borrow<Q> + Clone,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get_key_value(key).map(|(key, _)| key.clone())
+    }
+
+    /// Counts a lookup of a key that isn't cached, as `get()` would.
+    /// 
+    #[cfg(feature = "cached")]
+    pub(crate) fn count_miss<Q: Hash + ?Sized>(&mut self, key: &Q) {
+        let hash = self.map.hash(key);
+
+        if let Some(sketch) = &mut self.admission { sketch.increment(hash); }
+
+        if let Some(adapter) = &mut self.adapter {
+            adapter.lookup(false, hash);
+        }
+        if let Some(stats) = &mut self.stats { stats.misses += 1; }
+    }
+
+    /// Returns the key's value, stale or not, without counting an access.
+    /// 
+    pub(crate) fn peek_value(&self, key: &K) -> Option<&V> {
+        self.map.get(key).map(|vrec| &vrec.value)
+    }
+
+    /// Returns the key's value, mutably, without counting an access.
+    /// 
+    pub(crate) fn peek_value_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.map.get_mut(key).map(|vrec| &mut vrec.value)
+    }
+
+    /// Returns `true` if an `EntryGuard` is protecting the key's item.
+    /// 
+    fn is_pinned(&self, key: &K) -> bool {
+        self.pins.get(key).is_some_and(|pin| Arc::strong_count(pin) > 1)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    /// 
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_hashed(self.map.hash(key), key).map(|vrec| &vrec.value)
+    }
+
+    /// Does the work of `get()` for a key whose hash is `hash`, returning the
+    /// item's value record.
+    /// 
+    fn get_hashed(&mut self, hash: u64, key: &K) -> Option<&Value<V, C>> {
+        #[cfg(feature = "debug-verify")]
+        if let Some(expect) = self.predict(|shadow, _| shadow.get(key)) {
+            let _ = self.hit(hash, key);
+
+            self.verify("get()", expect, key);
+            return self.map.get_hashed(hash, key);
+        }
+        self.hit(hash, key)
+    }
+
+    /// Does the work of `get_hashed()`.
+    /// 
+    fn hit(&mut self, hash: u64, key: &K) -> Option<&Value<V, C>> {
+        self.drop_stale(hash, key);
+
+        let index = self.map.find_index_hashed(hash, key);
+        self.hit_at(hash, index)
+    }
+
+    /// Does the work of `hit()` once the key's been checked for staleness, 
+    /// for the item in the bucket at `index`, or for a miss.
+    /// 
+    fn hit_at(&mut self, hash: u64, index: Option<usize>) 
+        -> Option<&Value<V, C>> 
+    {
+        if let Some(sketch) = &mut self.admission { sketch.increment(hash); }
+
+        let now  = self.now();
+        let age  = self.next_age();
+        let item = index.and_then(|index| self.map.get_at_mut(index));
+
+        if let Some(stats) = &mut self.stats {
+            if item.is_some() { stats.hits += 1; } else { stats.misses += 1; }
+        }
+        if let Some(adapter) = &mut self.adapter {
+            adapter.lookup(item.is_some(), hash);
+        }
+        #[cfg(feature = "debug-audit")]
+        if let (Some(log), Some((key, _))) = (&mut self.audit, &item) {
+            log.push(AuditEvent::Hit { key: (log.clone)(key) });
+        }
+        item.map(|(key, vrec)| {
+            if let Some(refresh) = &mut self.refresh { 
+                refresh.note(key, vrec, now); 
+            }
+            vrec.hits = vrec.hits.saturating_add(1);
+
+            // Move it to the next frequency queue.
+            Self::access(&mut self.frequencies, &mut self.increments, 
+                         self.policy, age, self.log_base, vrec);
+            &*vrec
+        })
+    }
+
+    /// Removes the key from the cache and returns its value, if it was present.
+    /// 
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        #[cfg(feature = "debug-verify")]
+        if let Some(expect) = self.predict(|shadow, _| shadow.remove(key)) {
+            let before = self.state(key);
+            let value  = self.remove_entry(key).map(|(_, value)| value);
+
+            verify::check("remove()", "the removed item", 
+                          expect, before.filter(|_| value.is_some()));
+            self.verify("remove()", None, key);
+            return value;
+        }
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes the key from the cache and returns the stored key along with
+    /// its value, if it was present. The key returned is the one the item was
+    /// inserted with.
+    /// 
+    pub fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let hash = self.map.hash(key);
+
+        self.drop_stale(hash, key);
+        self.remove_hashed(hash, key)
+    }
+
+    /// Does the work of `remove_entry()` once the key's been checked for
+    /// staleness.
+    /// 
+    fn remove_hashed(&mut self, hash: u64, key: &K) -> Option<(K, V)> {
+        let (key, vrec) = self.map.remove_entry_hashed(hash, key)?;
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        // The queue holds a clone of the key, which is dropped.
+        queues::unlink(&mut self.frequencies, &vrec);
+        self.weight -= vrec.weight;
+        self.unindex(&key);
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(log) = &mut self.audit {
+            log.push(AuditEvent::Removed { key: (log.clone)(&key) });
+        }
+        Some((key, vrec.value))
+    }
+
+    /// Drops the key's item if `invalidate_all()` left it behind, or if it 
+    /// has expired, so that lookups only find live items.
+    /// 
+    fn drop_stale(&mut self, hash: u64, key: &K) {
+        if self.stale > 0 && self.map.get_hashed(hash, key)
+                                     .is_some_and(|v| v.gen < self.generation)
+        {
+            self.map.remove_entry_hashed(hash, key);
+            self.stale -= 1;
+            return;
+        }
+        let Some(clock) = &self.clock else { return };
+
+        let now = clock.now();
+
+        if self.map.get_hashed(hash, key)
+                   .is_some_and(|v| v.expired(now, self.grace)) 
+        {
+            self.expire(hash, key);
+        }
+    }
+
+    /// Drops an expired item.
+    /// 
+    fn expire(&mut self, hash: u64, key: &K) {
+        let (key, vrec) = self.map.remove_entry_hashed(hash, key).unwrap();
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        queues::unlink(&mut self.frequencies, &vrec);
+        self.weight -= vrec.weight;
+        self.unindex(&key);
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(log) = &mut self.audit {
+            log.push(AuditEvent::Evicted { victim : (log.clone)(&key), 
+                                           reason : EvictionReason::Expired });
+        }
+        self.send_evicted(key, vrec.value, EvictionReason::Expired);
+    }
+
+    /// Counts an access to the given key. Normally this increments its 
+    /// frequency, but with probabilistic increments enabled that only happens
+    /// with a probability of `1 / ((freq - 1) * factor + 1)`. The item is 
+    /// then queued by its new priority, see `Policy`. With logarithmic 
+    /// bucketing that's often the queue it's already in, and it only moves
+    /// to the back.
+    /// 
+    fn access(freq_qs    : &mut Queues<K>,
+              increments : &mut Option<LogIncrements>,
+              policy     : Policy,
+              age        : usize,
+              log_base   : usize,
+              vrec       : &mut Value<V, C>)
+    {
+        #[cfg(all(test, feature = "debug-verify"))]
+        if verify::fault() { return; }
+
+        if let Some(LogIncrements { rng, factor }) = increments {
+            let freq = vrec.count.to_usize() as u64;
+            let odds = (freq - 1).saturating_mul(*factor).saturating_add(1);
+
+            if !rng.one_in(odds) { return; }
+        }
+        // A saturated count stays put, and the item goes to the back of its
+        // queue.
+        vrec.count = vrec.count.saturating_incr();
+
+        let label    = vrec.label(log_base);
+        let current  = freq_qs.get(vrec.hfreq).0;
+        let priority = policy.raised(current, label, vrec.weight, age);
+
+        Self::requeue(freq_qs, vrec, priority);
+    }
+
+    /// Moves the key to the queue for `priority`.
+    /// 
+    fn requeue(freq_qs  : &mut Queues<K>,
+               vrec     : &mut Value<V, C>,
+               priority : usize)
+    {
+        let current = freq_qs.get(vrec.hfreq).0;
+
+        if priority == current + 1 {
+            queues::incr_freq(freq_qs, vrec);
+        } else if priority >= current {
+            queues::raise_freq(freq_qs, vrec, priority);
+        } else {
+            let key = queues::unlink(freq_qs, vrec);
+            queues::push(freq_qs, key, priority, vrec);
+        }
+    }
+
+    /// Drops a key that has left the cache from the secondary indexes.
+    /// 
+    fn unindex(&mut self, key: &K) {
+        if let Some(tags)      = &mut self.tags      { tags.remove(key); }
+        if let Some(prefixes)  = &mut self.prefixes  { prefixes.remove(key); }
+        if let Some(insertion) = &mut self.insertion { insertion.remove(key); }
+
+        if !self.pins.is_empty() { self.pins.remove(key); }
+
+        self.unschedule(key);
+    }
+
+    /// Takes the key out of the expiry index, for an item that's left the
+    /// cache or no longer has a TTL.
+    /// 
+    fn unschedule(&mut self, key: &K) {
+        if !self.expiries.is_empty() { self.expiries.cancel(key); }
+    }
+
+    /// Sends an item the cache has let go of to the eviction receiver, if
+    /// there is one.
+    /// 
+    fn send_evicted(&self, key: K, value: V, reason: EvictionReason) {
+        if let Some(notify) = &self.notify { notify.send(key, value, reason); }
+    }
+
+    /// Applies an operation to the shadow model, rebuilding the model first
+    /// if it's lost, and returns the model's prediction. Returns `None`, and
+    /// loses the model, if the cache isn't plain.
+    /// 
+    #[cfg(feature = "debug-verify")]
+    fn predict<T>(&mut self, op: impl FnOnce(&mut Shadow<K>, usize) -> T) 
+        -> Option<T> 
+    {
+        if !self.plain() {
+            self.shadow.lose();
+            return None;
+        }
+        if !self.shadow.is_synced() {
+            let items = self.eviction_order().map(|key| {
+                let vrec = &self.map[key];
+                (self.shadow.dup(key), vrec.version, vrec.count.to_usize())
+            }).collect::<Vec<_>>();
+
+            self.shadow.sync(items.into_iter());
+        }
+        Some(op(&mut self.shadow, self.capacity))
+    }
+
+    /// Returns the version and frequency count of the item with the key.
+    /// 
+    #[cfg(feature = "debug-verify")]
+    fn state(&self, key: &K) -> verify::State {
+        self.map.get(key).filter(|vrec| vrec.gen >= self.generation)
+                         .map(|vrec| (vrec.version, vrec.count.to_usize()))
+    }
+
+    /// Checks the key's state and `len()` against the model after `op`, and
+    /// marks the model as in step.
+    /// 
+    #[cfg(feature = "debug-verify")]
+    fn verify(&mut self, op: &str, expect: verify::State, key: &K) {
+        verify::check(op, "the item (version, frequency)", 
+                      expect, self.state(key));
+        verify::check(op, "len()", self.shadow.len(), self.len());
+        self.shadow.confirm();
+    }
+
+    /// Returns `true` if the shadow model can follow the cache as it's set
+    /// up, see the `verify` module. The model's counts don't saturate, so it
+    /// can't follow a cache with a counter smaller than `usize`, nor does it
+    /// know about priority boosts.
+    /// 
+    #[cfg(feature = "debug-verify")]
+    fn plain(&self) -> bool {
+        C::MAX.to_usize() == usize::MAX
+            && self.policy == Policy::Lfu 
+            && self.log_base    == 0
+            && self.admission.is_none() 
+            && self.increments.is_none()
+            && self.weigher.is_none() 
+            && self.pins.is_empty() 
+            && self.evict_first.is_none()
+            && self.default_ttl.is_none() 
+            && self.expiries.is_empty()
+            && self.map.values().all(|vrec| vrec.boost == 0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, C: Counter> LfuCache<K, V, S, C> 
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the cache. With admission control 
+    /// enabled, a new key may be turned away instead of evicting an item, see
+    /// `enable_admission()`.
+    /// 
+    pub fn insert(&mut self, key: K, value: V) {
+        #[cfg(feature = "debug-verify")]
+        if let Some((expect, victim)) = self.predict(|shadow, capacity| {
+            shadow.insert(&key, capacity)
+        }) {
+            let probe = key.clone();
+
+            self.insert_versioned(key, value);
+            self.verify_victim("insert()", victim);
+            self.verify("insert()", expect, &probe);
+            return;
+        }
+        self.insert_versioned(key, value);
+    }
+
+    /// Inserts a key-value pair like `insert()` does, and returns the item's
+    /// new version number. Every item starts at version 1 when it's inserted,
+    /// and each write to it, through any of the insert methods, adds one. The
+    /// version is kept as the item's frequency changes and goes with it when 
+    /// it's evicted, so a key that comes back starts over at 1. If nothing 
+    /// was stored, because the capacity is 0 or admission control turned the
+    /// key away, this returns 0.
+    /// 
+    pub fn insert_versioned(&mut self, key: K, value: V) -> u64 {
+        self.write_over(key, value, None)
+    }
+
+    /// Inserts a key-value pair like `insert()` does, and says what became of
+    /// it, see `InsertResult`.
+    /// 
+    pub fn insert_checked(&mut self, key: K, value: V) -> InsertResult<V> {
+        self.write(key, value, None).1
+    }
+
+    /// Inserts a key-value pair like `insert_checked()` does, but first makes
+    /// room in the map for a new key, and if the allocator refuses, returns
+    /// its error instead, leaving the cache as it was. The map is the only 
+    /// part whose growth can fail this way: the frequency queues, the timer
+    /// wheel of items with a TTL and the indexes grow as they do for 
+    /// `insert()`, though once the cache is full the queues reuse the nodes
+    /// of the items evicted.
+    /// 
+    pub fn try_insert(&mut self, key: K, value: V) 
+        -> Result<InsertResult<V>, TryReserveError> 
+    {
+        // The map only has to grow for a new key, once it's out of room, so
+        // only then is the key looked up first.
+        let hash = self.map.hash(&key);
+
+        if self.map.len() >= self.map.capacity() 
+           && self.map.get_hashed(hash, &key).is_none() 
+        {
+            self.map.try_reserve(1)?;
+        }
+        Ok(self.write_hashed(hash, key, value, None).1)
+    }
+
+    /// Inserts a key-value pair like `insert()` does, and returns a mutable
+    /// reference to the value stored, so it can be read or changed without
+    /// looking it up again. A new key has a frequency count of 1 afterwards;
+    /// one already cached counts the access of the write, as with `insert()`.
+    /// 
+    /// The value is stored even if admission control would turn it away, 
+    /// since the cache has to hold it to return a reference to it, as with 
+    /// `get_or_insert_with()`. A value too large to cache, see 
+    /// `set_max_item_fraction()`, is turned away as `insert()` turns it away:
+    /// it's counted in the stats as too large, nothing is evicted for it, and
+    /// the key's item, if any, is left as it was. The reference is then to 
+    /// the value itself, which the cache holds on to, uncached, until the 
+    /// next value is loaded or the cache is cleared.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCache;
+    /// 
+    /// let mut cache = LfuCache::new(10);
+    /// 
+    /// cache.insert_and_get_mut("log", Vec::new()).push("started");
+    /// 
+    /// assert_eq!(cache.frequency(&"log"), Some(1));
+    /// assert_eq!(cache.get(&"log").unwrap(), &["started"]);
+    /// ```
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn insert_and_get_mut(&mut self, key: K, value: V) -> &mut V {
+        assert!(self.capacity > 0, "a cache with a capacity of 0 can't hold \
+                                    a value");
+
+        let hash = self.map.hash(&key);
+
+        self.drop_stale(hash, &key);
+
+        if self.too_large(Self::weigh(&self.weigher, &key, &value)) {
+            return self.uncached.insert(value);
+        }
+        if self.map.get_hashed(hash, &key).is_some() {
+            let probe = key.clone();
+
+            self.write_over(key, value, None);
+            return self.peek_value_mut(&probe).unwrap();
+        }
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        if let Some(sketch) = &mut self.admission { sketch.increment(hash); }
+
+        // The write's step of age. `insert_loaded()` reads it once it's 
+        // made room.
+        self.next_age();
+
+        self.insert_loaded(hash, key, value)
+    }
+
+    /// Inserts a key-value pair like `insert()` does, and has the item expire
+    /// once `ttl` has passed. An expired item is dropped when its key is next
+    /// used, or by `purge_expired()`; until then it still counts toward 
+    /// `len()`. A plain `insert()` over the key gives it the default TTL, if
+    /// there is one, or clears its TTL, see `set_default_ttl()`.
+    /// 
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) {
+        self.clock.get_or_insert_with(|| Arc::new(SystemClock::new()));
+        self.write_over(key, value, Some(ttl));
+    }
+
+    /// Pushes the expiry of the key's item out by `extra`, without changing
+    /// its value, version or frequency count. An item that has expired, but
+    /// hasn't been dropped yet, is brought back to life for `extra` from now.
+    /// Returns `false`, and does nothing, if the key isn't cached or its item
+    /// has no TTL.
+    /// 
+    pub fn extend_ttl(&mut self, key: &K, extra: Duration) -> bool {
+        let now = self.now();
+
+        let Some(vrec) = self.map.get_mut(key) else { return false };
+        let Some(at)   = vrec.deadline() else { return false };
+
+        if vrec.gen < self.generation { return false; }
+
+        let at = at.max(now).saturating_add(extra);
+
+        vrec.ttl = Some(Stamp::new(at.saturating_sub(vrec.written.get())));
+
+        if let Some(at) = vrec.deadline() {
+            self.schedule(key.clone(), at);
+        }
+        true
+    }
+
+    /// Replaces the value of a cached item without counting an access, and
+    /// returns the old one. It counts as a write otherwise: the version goes
+    /// up, and the item's age, and its TTL if it has one, start over. That 
+    /// makes it the way to store refreshed values, see `enable_refresh()`. If
+    /// the key isn't cached, this does nothing and returns `None`. The old 
+    /// value is handed back rather than sent to the eviction receiver as 
+    /// `EvictionReason::Replaced`, as with `insert_checked()`; the writes 
+    /// that return nothing, like `insert_with_frequency()`, send it.
+    /// 
+    pub fn replace_value(&mut self, key: &K, value: V) -> Option<V> {
+        let hash = self.map.hash(key);
+
+        self.drop_stale(hash, key);
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        let now  = self.now();
+        let vrec = self.map.get_mut_hashed(hash, key)?;
+
+        // The weigher runs before anything changes, in case it panics.
+        let weight = Self::weigh(&self.weigher, key, &value);
+
+        vrec.version += 1;
+        vrec.written  = Stamp::new(now);
+        vrec.marked   = false;
+
+        let old = std::mem::replace(&mut vrec.value, value);
+
+        Self::reweigh(&mut self.weight, vrec, weight);
+
+        if let Some(at) = vrec.deadline() { self.schedule(key.clone(), at); }
+        if let Some(stats) = &mut self.stats { stats.updates += 1; }
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(log) = &mut self.audit {
+            log.push(AuditEvent::Updated { key: key.clone() });
+        }
+        self.shed_weight();
+        Some(old)
+    }
+
+    /// Inserts a key-value pair, but only if the item's current version is 
+    /// `expected`, returning its new version. An `expected` version of 0 
+    /// stands for a key that isn't cached. Otherwise the cache is left as it
+    /// is and the versions are returned in the error, so a stale 
+    /// read-modify-write can't overwrite a newer value.
+    /// 
+    pub fn insert_if_version(&mut self, key: K, value: V, expected: u64) 
+        -> Result<u64, VersionMismatch> 
+    {
+        let hash = self.map.hash(&key);
+
+        self.drop_stale(hash, &key);
+
+        let actual = self.map.get_hashed(hash, &key)
+                             .map_or(0, |vrec| vrec.version);
+
+        if actual != expected {
+            return Err(VersionMismatch { expected, actual });
+        }
+        Ok(self.insert_versioned(key, value))
+    }
+
+    /// Inserts a key-value pair like `insert()` does, but gives the item a 
+    /// frequency count of `freq`, or 1 if `freq` is 0, instead of counting an
+    /// access. This is for restoring a cache's state from elsewhere. Each call
+    /// walks the queues up to `freq`; `bulk_load()` restores a whole cache
+    /// much faster.
+    /// 
+    pub fn insert_with_frequency(&mut self, key: K, value: V, freq: usize) {
+        if self.capacity == 0 { return; }
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        let freq = C::from_usize(freq.max(1)).to_usize();
+        let hash = self.map.hash(&key);
+
+        self.drop_stale(hash, &key);
+        self.reclaim(2);
+
+        let now = self.now();
+        let ttl = self.schedule_default(&key, now);
+        let age = self.next_age();
+
+        if let Some(vrec) = self.map.get_mut_hashed(hash, &key) {
+            let weight = Self::weigh(&self.weigher, &key, &value);
+
+            let old = vrec.write(value, now, ttl);
+
+            Self::reweigh(&mut self.weight, vrec, weight);
+
+            vrec.count   = C::from_usize(freq);
+            let priority = self.policy.priority(vrec.label(self.log_base),
+                                                vrec.weight, age);
+            let stored   = queues::unlink(&mut self.frequencies, vrec);
+
+            queues::push(&mut self.frequencies, stored, priority, vrec);
+
+            if let Some(stats) = &mut self.stats { stats.updates += 1; }
+
+            #[cfg(feature = "debug-audit")]
+            if let Some(log) = &mut self.audit {
+                log.push(AuditEvent::Updated { key: key.clone() });
+            }
+            self.send_evicted(key, old, EvictionReason::Replaced);
+            self.shed_weight();
+        } else {
+            let weight = Self::weigh(&self.weigher, &key, &value);
+
+            if self.too_large(weight) {
+                self.unschedule(&key);
+                return;
+            }
+            if !self.make_room(hash, weight) {
+                if let Some(stats) = &mut self.stats { stats.rejections += 1; }
+
+                self.unschedule(&key);
+                return;
+            }
+            let age = self.current_age();
+
+            self.insert_new(hash, key, value, freq, weight, age).ttl = 
+                ttl.map(Stamp::new);
+        }
+    }
+
+    /// Restores the keys in `state`, from `export_warm_state()`, with their
+    /// frequency counts, loading each key's value with `loader`. Keys the 
+    /// loader returns `None` for are skipped. The keys are inserted coldest
+    /// first, as by `insert_with_frequency()`, so the queues come out in the
+    /// order they were exported in, and if they don't all fit it's the 
+    /// coldest that are evicted. Returns how many keys were loaded.
+    /// 
+    pub fn apply_warm_state(&mut self, 
+                            state      : WarmState<K>, 
+                            mut loader : impl FnMut(&K) -> Option<V>) 
+        -> usize
+    {
+        let mut loaded = 0;
+
+        for (key, freq) in state.entries {
+            if let Some(value) = loader(&key) {
+                self.insert_with_frequency(key, value, freq);
+                loaded += 1;
+            }
+        }
+        loaded
+    }
+
+    /// Inserts the items from another cache's `hot_entries()`, hottest 
+    /// first, at the frequency counts they come with, as by 
+    /// `insert_with_frequency()`. They're inserted coldest first, so their 
+    /// order in the other cache is kept, and if they don't all fit it's the
+    /// coldest that are evicted.
+    /// 
+    pub fn warm_from<I>(&mut self, entries: I) 
+    where
+        I: IntoIterator<Item = (K, V, usize)>,
+    {
+        let entries = entries.into_iter().collect::<Vec<_>>();
+
+        for (key, value, freq) in entries.into_iter().rev() {
+            self.insert_with_frequency(key, value, freq);
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key, first 
+    /// inserting the value returned by `f` if the key isn't cached. Hits and
+    /// misses are counted just like `get()` followed by `insert()` would, and
+    /// the LFU item is evicted to make room if the cache is full.
+    /// 
+    /// A value loaded this way is always inserted, even with admission control
+    /// enabled, since the cache has to hold it to return a reference to it.
+    /// The exception is a value too large to cache, see 
+    /// `set_max_item_fraction()`: it's counted in the stats as too large and
+    /// handed back without being cached or evicting anything. The cache holds
+    /// on to it until the next value is loaded or the cache is cleared.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &V {
+        self.get_or_insert_with_key(key, |_| f())
+    }
+
+    /// Like `get_or_insert_with()`, but `f` is handed a reference to the key.
+    /// It's only called on a miss, so keys don't have to be cloned into the
+    /// closure just in case.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn get_or_insert_with_key(&mut self, key: K, f: impl FnOnce(&K) -> V) 
+        -> &V 
+    {
+        let load = |key: &K| Ok::<_, Infallible>(f(key));
+
+        match self.get_or_try_insert_mut(key, load) {
+            Ok(value) => value,
+            Err(e)    => match e {},
+        }
+    }
+
+    /// Like `get_or_insert_with()`, but returns a mutable reference. A new 
+    /// value is inserted with a frequency of 1, and the reference points 
+    /// straight at it, without looking it up again.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn get_mut_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) 
+        -> &mut V 
+    {
+        let load = |_: &K| Ok::<_, Infallible>(f());
+
+        match self.get_or_try_insert_mut(key, load) {
+            Ok(value) => value,
+            Err(e)    => match e {},
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key, 
+    /// inserting `V::default()` first if it isn't cached. This is
+    /// `get_mut_or_insert_with(key, V::default)`, so that 
+    /// `*cache.get_or_default(key) += 1` counts occurrences of the keys that
+    /// are used often enough to stay cached.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn get_or_default(&mut self, key: K) -> &mut V 
+    where
+        V: Default,
+    {
+        self.get_mut_or_insert_with(key, V::default)
+    }
+
+    /// Like `get_or_insert_with()`, but for loaders that can fail. If `f` 
+    /// returns an error, it's passed on and nothing is inserted or evicted;
+    /// the only trace of the call is the miss counted in the stats.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn get_or_try_insert_with<E>(&mut self, 
+                                     key : K, 
+                                     f   : impl FnOnce() -> Result<V, E>) 
+        -> Result<&V, E> 
+    {
+        Ok(self.get_or_try_insert_mut(key, |_| f())?)
+    }
+
+    /// Looks up all the keys, and loads the ones that aren't cached with a 
+    /// single call to `loader`, for origins that fetch in batches. A key is 
+    /// looked up once however often it appears, and a hit counts an access 
+    /// like `get()` does. `loader` is handed every missing key once, in the 
+    /// order they first appear, and the pairs it returns are inserted like 
+    /// `get_or_insert_with()` inserts, evicting to make room, without 
+    /// counting another access. A pair for a key that's cached by then only 
+    /// replaces its value, see `replace_value()`. It isn't called if every 
+    /// key hit.
+    /// 
+    /// Returns the values in the order of `keys`, duplicates included. A key
+    /// `loader` didn't return a value for stays a miss, as does one whose 
+    /// value was evicted to make room for the rest of the batch.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCache;
+    /// 
+    /// let mut cache = LfuCache::new(10);
+    /// 
+    /// cache.insert(1, "one".to_string());
+    /// 
+    /// let values = cache.get_or_insert_with_many(vec![1, 2, 3, 2], |keys| {
+    ///     assert_eq!(keys, [2, 3]);
+    ///     vec![(2, "two".to_string())]
+    /// });
+    /// assert!(values.iter().map(|v| v.map(String::as_str))
+    ///               .eq([Some("one"), Some("two"), None, Some("two")]));
+    /// ```
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn get_or_insert_with_many<I>(&mut self, 
+                                      keys   : Vec<K>, 
+                                      loader : impl FnOnce(&[K]) -> I)
+        -> Vec<Option<&V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        assert!(self.capacity > 0, "a cache with a capacity of 0 can't hold \
+                                    a value");
+        let mut missing = Vec::new();
+        let mut seen    = HashSet::new();
+
+        for key in &keys {
+            if seen.insert(key) && self.get(key).is_none() {
+                missing.push(key.clone());
+            }
+        }
+        if !missing.is_empty() {
+            #[cfg(feature = "debug-verify")]
+            self.shadow.lose();
+
+            let watch = self.start_load();
+            let pairs = loader(&missing);
+
+            self.finish_load(watch, true);
+
+            // The lookups above took the batch's accesses.
+            for (key, value) in pairs {
+                let hash = self.map.hash(&key);
+
+                self.drop_stale(hash, &key);
+
+                if self.map.get_hashed(hash, &key).is_none() {
+                    self.insert_loaded(hash, key, value);
+                } else if let Some(old) = self.replace_value(&key, value) {
+                    self.send_evicted(key, old, EvictionReason::Replaced);
+                }
+            }
+        }
+        keys.iter().map(|key| self.peek_value(key)).collect()
+    }
+
+    /// Inserts a key-value pair like `insert()` does, and gives the key the
+    /// tags, replacing any it had. `invalidate_tag()` then removes every item
+    /// with a given tag. If the pair isn't inserted because admission control
+    /// turned it away, the tags are dropped too.
+    /// 
+    pub fn insert_with_tags<T>(&mut self, 
+                               key   : K, 
+                               value : V, 
+                               tags  : impl IntoIterator<Item = T>)
+    where
+        T: Into<Box<str>>,
+    {
+        self.insert(key.clone(), value);
+
+        if self.map.contains_key(&key) {
+            let tags = tags.into_iter().map(Into::into).collect();
+            self.tags.get_or_insert_with(TagIndex::new).set(&key, tags);
+        }
+    }
+
+    /// Inserts a key-value pair like `insert()` does, and gives the key a
+    /// priority boost, see `set_priority()`. If the pair isn't inserted 
+    /// because admission control turned it away, the priority is dropped 
+    /// too.
+    /// 
+    pub fn insert_with_priority(&mut self, key: K, value: V, priority: u8) {
+        self.insert(key.clone(), value);
+        self.set_priority(&key, priority);
+    }
+
+    /// Gives the key a priority boost, for items that cost more to recreate
+    /// than their popularity shows. The boost is added to the item's 
+    /// frequency count wherever it's queued, so a fresh item with a boost of
+    /// 3 sits in the queue for 4 and outlives the items used up to three 
+    /// times as often. It's 0 unless it's set, and is kept until the key
+    /// leaves the cache, across updates of its value. Changing it moves the
+    /// item to the back of its new queue, as an access would.
+    /// 
+    /// `frequency()` still returns the count of accesses, but the methods 
+    /// that report on the queues, like `frequency_histogram()` and 
+    /// `peek_bucket()`, report boosted counts. With logarithmic bucketing the
+    /// boosted count is bucketed, and under the other policies it takes the
+    /// place of the count in the priority. Returns `false` if the key isn't
+    /// cached.
+    /// 
+    pub fn set_priority(&mut self, key: &K, priority: u8) -> bool {
+        let Some(vrec) = self.map.get_mut(key)
+                                 .filter(|vrec| vrec.gen >= self.generation)
+        else {
+            return false;
+        };
+        if vrec.boost == priority { return true; }
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        // The item's queue is moved by as much as the boost changes its 
+        // priority, so it keeps the age it was last accessed at.
+        let weight  = vrec.weight;
+        let old     = self.policy.priority(vrec.label(self.log_base), 
+                                           weight, 0);
+        vrec.boost  = priority;
+        let new     = self.policy.priority(vrec.label(self.log_base), 
+                                           weight, 0);
+        let current = self.frequencies.get(vrec.hfreq).0;
+
+        Self::requeue(&mut self.frequencies, vrec, 
+                      current.saturating_add(new).saturating_sub(old));
+        true
+    }
+
+    /// Removes every item with the tag, returning how many there were.
+    /// 
+    pub fn invalidate_tag(&mut self, tag: &str) -> usize {
+        let Some(tags) = &self.tags else { return 0 };
+
+        tags.keys(tag).iter().filter(|key| self.discard(key)).count()
+    }
+
+    /// Starts keeping an ordered index of the keys, which `invalidate_prefix()`
+    /// needs. It costs a copy of each key's string, and an extra ordered
+    /// insert and removal each time a key enters or leaves the cache.
+    /// 
+    pub fn enable_prefix_index(&mut self) 
+    where
+        K: AsRef<str>,
+    {
+        let keys = self.iter_unordered().map(|(key, _)| key);
+
+        self.prefixes = Some(PrefixIndex::new(K::as_ref, keys));
+    }
+
+    /// Starts numbering keys in the order they enter the cache, see 
+    /// `insertion_index()`. The items already cached are numbered in 
+    /// eviction order. It costs a map entry and a tree entry per key, each 
+    /// with a copy of it.
+    /// 
+    pub fn enable_insertion_order(&mut self) {
+        let keys = queues::lfu_order(&self.frequencies).map(|(_, key)| key);
+
+        self.insertion = Some(InsertionIndex::new(keys));
+    }
+
+    /// Removes every item whose key starts with `prefix`, returning how many
+    /// there were. The keys are found through the prefix index, so this takes
+    /// time in proportion to the number removed.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the prefix index isn't enabled, see `enable_prefix_index()`.
+    /// 
+    pub fn invalidate_prefix(&mut self, prefix: &str) -> usize {
+        let prefixes = self.prefixes.as_ref()
+                                    .expect("prefix index not enabled");
+
+        prefixes.with_prefix(prefix).iter()
+                .filter(|key| self.discard(key))
+                .count()
+    }
+
+    /// Removes every item whose key isn't in `keys`, returning how many were
+    /// removed. The items that stay keep their frequency counts. This visits
+    /// each item once, so it takes time in proportion to the size of the
+    /// cache, however big `keys` is.
+    /// 
+    pub fn retain_keys_in<T>(&mut self, keys: &HashSet<K, T>) -> usize 
+    where
+        T: BuildHasher,
+    {
+        self.retain_by(|key, _| keys.contains(key))
+    }
+
+    /// Keeps only the items for which `f` returns `true`, and returns how
+    /// many were removed. `f` is given each item's key, a mutable reference to
+    /// its value, and its frequency count, so items can be fixed up rather 
+    /// than dropped. Frequency counts aren't changed, nor are the weights of
+    /// changed values, see `set_weigher()`. Each item is visited once, in 
+    /// eviction order.
+    /// 
+    pub fn retain_mut(&mut self, mut f: impl FnMut(&K, &mut V, usize) -> bool)
+        -> usize
+    {
+        self.retain_by(|key, vrec| {
+            f(key, &mut vrec.value, vrec.count.to_usize())
+        })
+    }
+
+    /// Looks the key up like `get()` does, and says whether the value is 
+    /// fresh or is being served stale, see `enable_stale_while_revalidate()`.
+    /// 
+    pub fn lookup(&mut self, key: &K) -> GetResult<'_, V> {
+        let now   = self.now();
+        let hash  = self.map.hash(key);
+        let stale = self.map.get_hashed(hash, key).is_some_and(|vrec| {
+            vrec.gen >= self.generation && vrec.expired(now, Duration::ZERO)
+        });
+        match self.get_hashed(hash, key).map(|vrec| &vrec.value) {
+            Some(value) if stale => GetResult::Stale(value),
+            Some(value)          => GetResult::Fresh(value),
+            None                 => GetResult::Miss,
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key, like `get()`
+    /// does, along with the item's version number, see `insert_versioned()`.
+    /// 
+    pub fn get_versioned(&mut self, key: &K) -> Option<(&V, u64)> {
+        self.get_hashed(self.map.hash(key), key)
+            .map(|vrec| (&vrec.value, vrec.version))
+    }
+
+    /// Returns the id of the key's item, if it's cached, with which 
+    /// `get_by_id()` and `peek_by_id()` can reach the item again without 
+    /// hashing the key. Overwriting the item's value keeps its id. The id goes
+    /// stale once the item is evicted or removed, or the cache is cleared, 
+    /// and it never refers to an item inserted after that, even one with the
+    /// same key.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCache;
+    /// 
+    /// let mut cache = LfuCache::new(2);
+    /// 
+    /// cache.insert("a", 1);
+    /// let id = cache.id_of(&"a").unwrap();
+    /// 
+    /// assert_eq!(cache.get_by_id(id), Some(&1));
+    /// assert_eq!(cache.frequency(&"a"), Some(2));
+    /// 
+    /// cache.remove(&"a");
+    /// cache.insert("a", 2);
+    /// assert_eq!(cache.peek_by_id(id), None);
+    /// ```
+    pub fn id_of(&self, key: &K) -> Option<EntryId> {
+        let hash    = self.map.hash(key);
+        let slot    = self.map.find_index_hashed(hash, key)?;
+        let (.., v) = self.map.get_at(slot)?;
+
+        (v.gen >= self.generation && !v.expired(self.now(), self.grace))
+        .then_some(EntryId { slot, hash, stamp: v.gen })
+    }
+
+    /// Returns a reference to the value of the item with the id, and counts
+    /// an access like `get()` does, see `id_of()`. Returns `None` if the id 
+    /// is stale.
+    /// 
+    pub fn get_by_id(&mut self, id: EntryId) -> Option<&V> {
+        let index     = self.resolve(id)?;
+        let (_, k, v) = self.map.get_at(index)?;
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        if self.clock.is_some() && v.expired(self.now(), self.grace) {
+            let key = k.clone();
+
+            self.expire(id.hash, &key);
+            return self.hit_at(id.hash, None).map(|vrec| &vrec.value);
+        }
+        self.hit_at(id.hash, Some(index)).map(|vrec| &vrec.value)
+    }
+
+    /// Returns a reference to the value of the item with the id without 
+    /// counting an access, see `id_of()`. Returns `None` if the id is stale.
+    /// 
+    pub fn peek_by_id(&self, id: EntryId) -> Option<&V> {
+        let (.., vrec) = self.map.get_at(self.resolve(id)?)?;
+
+        (!vrec.expired(self.now(), self.grace)).then_some(&vrec.value)
+    }
+
+    /// Finds the bucket of the item the id was given out for, if it's still 
+    /// cached: in the bucket the id names, or, if the map has grown since,
+    /// among the buckets of the key's hash. No key is compared, since no 
+    /// other item has the same stamp.
+    /// 
+    fn resolve(&self, id: EntryId) -> Option<usize> {
+        let issued = |vrec: &Value<V, C>| {
+            vrec.gen == id.stamp && vrec.gen >= self.generation
+        };
+        match self.map.get_at(id.slot) {
+            Some((hash, _, vrec)) if hash == id.hash && issued(vrec) => {
+                Some(id.slot)
+            },
+            _ => self.map.find_index_by(id.hash, issued),
+        }
+    }
+
+    /// Looks the key up without counting the access yet, for values that may
+    /// turn out not to be used, such as ones that fail validation. The access
+    /// is counted when the returned guard is committed, see `AccessGuard`. A 
+    /// miss is counted right away, as `get()` would count it.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCache;
+    /// 
+    /// let mut cache = LfuCache::new(2);
+    /// 
+    /// cache.insert("stale", 1);
+    /// cache.insert("fresh", 2);
+    /// 
+    /// if let Some(value) = cache.get_deferred(&"stale") {
+    ///     if *value == 1 { value.cancel(); }
+    /// }
+    /// cache.get_deferred(&"fresh").unwrap().commit();
+    /// 
+    /// assert_eq!(cache.frequency(&"stale"), Some(1));
+    /// assert_eq!(cache.frequency(&"fresh"), Some(2));
+    /// ```
+    pub fn get_deferred(&mut self, key: &K) 
+        -> Option<AccessGuard<'_, K, V, S, C>> 
+    {
+        let hash = self.map.hash(key);
+
+        self.drop_stale(hash, key);
+
+        if self.map.get_hashed(hash, key).is_none() {
+            self.get(key);
+            return None;
+        }
+        Some(AccessGuard { cache: self, key: key.clone(), on_drop: false })
+    }
+
+    /// Records an access to `key` that didn't go through the cache, such as a
+    /// popularity hint from elsewhere. If the key is cached, its frequency 
+    /// count is incremented just as `get()` would. If it isn't, and admission
+    /// control is enabled, the access is counted toward the key's admission
+    /// the next time it's inserted. Otherwise this does nothing.
+    /// 
+    pub fn observe(&mut self, key: &K) {
+        let hash = self.map.hash(key);
+
+        self.drop_stale(hash, key);
+        self.observe_hashed(hash, key);
+    }
+
+    /// Like `observe()`, for a key that's known to be live, as the item of an
+    /// `OccupiedEntry` is, so it isn't expired on the way.
+    /// 
+    pub(crate) fn observe_live(&mut self, key: &K) {
+        self.observe_hashed(self.map.hash(key), key);
+    }
+
+    /// Does the work of `observe()` once the key's been checked for
+    /// staleness.
+    /// 
+    fn observe_hashed(&mut self, hash: u64, key: &K) {
+        if let Some(sketch) = &mut self.admission { sketch.increment(hash); }
+
+        let age = self.next_age();
+
+        if let Some(vrec) = self.map.get_mut_hashed(hash, key) {
+            Self::access(&mut self.frequencies, &mut self.increments, 
+                         self.policy, age, self.log_base, vrec);
+        }
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+    }
+
+    /// Like `remove_entry()`, for a key that's known to be live, see
+    /// `observe_live()`.
+    /// 
+    pub(crate) fn remove_live(&mut self, key: &K) -> Option<(K, V)> {
+        self.remove_hashed(self.map.hash(key), key)
+    }
+
+    /// Removes the Least Frequently Used item from the cache and returns it.
+    /// Of the items with the lowest frequency count, the one that was accessed
+    /// least recently is chosen. Pinned items are skipped, see `guard()`.
+    /// 
+    pub fn pop_lfu(&mut self) -> Option<(K, V)> {
+        #[cfg(feature = "debug-verify")]
+        let victim = self.predict(|shadow, _| shadow.pop_lfu());
+
+        let entry = self.take_lfu().map(|(_, key, value)| (key, value));
+
+        // `remove_lfu()` keeps an emptied frequency 1 queue around for
+        // `insert()` to reuse, but nothing else will.
+        if self.frequencies.front().is_some_and(|q| q.1.is_empty()) {
+            self.frequencies.pop_front();
+        }
+        if let Some((key, _)) = &entry { self.unindex(key); }
+
+        #[cfg(feature = "debug-audit")]
+        if let (Some(log), Some((key, _))) = (&mut self.audit, &entry) {
+            log.push(AuditEvent::Removed { key: key.clone() });
+        }
+        #[cfg(feature = "debug-verify")]
+        if let Some(victim) = victim {
+            self.verify_victim("pop_lfu()", victim);
+            verify::check("pop_lfu()", "len()", self.shadow.len(), self.len());
+            self.shadow.confirm();
+        }
+        entry
+    }
+
+    /// Removes the LFU item like `pop_lfu()` does, but only if `f` returns
+    /// `true` for it.
+    /// 
+    pub fn pop_lfu_if(&mut self, f: impl FnOnce(&K, &V) -> bool) 
+        -> Option<(K, V)> 
+    {
+        let (hfreq, hpos)        = self.candidate_at()?;
+        let Queued { hash, key } = self.frequencies.get(hfreq).1.get(hpos);
+        let vrec                 = self.map.get_hashed(*hash, key).unwrap();
+
+        if f(key, &vrec.value) { self.pop_lfu() } else { None }
+    }
+
+    /// Sets the maximum number of items the cache holds. If it holds more than
+    /// that, items are evicted, in eviction order, until it doesn't, or until
+    /// only pinned items are left.
+    /// 
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.len() > capacity && self.evict() {}
+    }
+
+    /// Removes items in the order `pop_lfu()` would until there are at most
+    /// `n` left, and returns them, first removed first. The capacity isn't 
+    /// changed, so the cache can fill up again afterwards. Pinned items are 
+    /// kept, so if there are more than `n` of them, more than `n` items are 
+    /// left.
+    /// 
+    pub fn truncate(&mut self, n: usize) -> Vec<(K, V)> {
+        let mut victims = Vec::with_capacity(self.len().saturating_sub(n));
+
+        while self.len() > n {
+            match self.pop_lfu() {
+                Some(victim) => victims.push(victim),
+                None         => break,
+            }
+        }
+        victims
+    }
+
+    /// Removes every item in the bucket for the frequency count `freq`, see
+    /// `peek_bucket()`, and returns them in the bucket's order, the one they
+    /// would be evicted in. The other buckets are left as they are. Pinned 
+    /// items are removed too, which unpins them. Returns an empty `Vec` if no
+    /// item has that count.
+    /// 
+    pub fn drain_frequency(&mut self, freq: usize) -> Vec<(K, V)> {
+        let mut hnext = self.frequencies.front_node();
+
+        // The queues are in order of frequency, so the walk stops at the 
+        // first one past it.
+        let hfreq = loop {
+            let Some(hfreq) = hnext else { return Vec::new() };
+            let label       = self.frequencies.get(hfreq).0;
+
+            if label == freq { break hfreq; }
+            if label >  freq { return Vec::new(); }
+
+            hnext = self.frequencies.next_node(hfreq);
+        };
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        let (_, queue) = self.frequencies.remove(hfreq);
+
+        queue.into_iter().map(|Queued { hash, key }| {
+            let vrec = self.map.remove_entry_hashed(hash, &key).unwrap().1;
+
+            self.weight -= vrec.weight;
+            self.unindex(&key);
+
+            #[cfg(feature = "debug-audit")]
+            if let Some(log) = &mut self.audit {
+                log.push(AuditEvent::Removed { key: key.clone() });
+            }
+            (key, vrec.value)
+        })
+        .collect()
+    }
+
+    /// Removes items in the order `pop_lfu()` would for as long as `cond`
+    /// returns `true` for the next one, and returns them, first removed 
+    /// first. Before each removal `cond` is shown the cache's length and 
+    /// weight and the item up next, see `EvictionContext`, so it can shed
+    /// items down to a weight or a length, or stop at the first warm one.
+    /// Eviction stops when the cache runs out of items, or of items that
+    /// aren't pinned, whatever `cond` says.
+    /// 
+    pub fn evict_while<F>(&mut self, mut cond: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&EvictionContext<'_, K, V>) -> bool,
+    {
+        let mut victims = Vec::new();
+
+        while let Some(key) = self.eviction_candidate() {
+            let vrec    = &self.map[key];
+            let context = EvictionContext { len       : self.len(), 
+                                            weight    : self.weight, 
+                                            key, 
+                                            value     : &vrec.value, 
+                                            frequency : vrec.count.to_usize() };
+            if !cond(&context) { break; }
+
+            match self.pop_lfu() {
+                Some(victim) => victims.push(victim),
+                None         => break,
+            }
+        }
+        victims
+    }
+
+    /// Returns the key of the LFU item, the one `pop_lfu()` or an insert into
+    /// the full cache would remove next, and a mutable reference to its value.
+    /// Its frequency count and its place in the queue aren't changed. The 
+    /// item is the one `eviction_candidate()` names.
+    /// 
+    pub fn peek_lfu_mut(&mut self) -> Option<(&K, &mut V)> {
+        // The key is borrowed from its queue, which leaves the map free.
+        let (hfreq, hpos)        = self.candidate_at()?;
+        let Queued { hash, key } = self.frequencies.get(hfreq).1.get(hpos);
+
+        Some((key, &mut self.map.get_mut_hashed(*hash, key).unwrap().value))
+    }
+
+    /// Returns a view of the LFU item, the one `pop_lfu()` would remove, 
+    /// through which it can be read, changed, bumped or removed. Pinned items
+    /// are passed over, see `eviction_candidate()`.
+    /// 
+    pub fn lfu_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, S, C>> {
+        self.live_entry(Self::eviction_candidate)
+    }
+
+    /// Returns a view of the Most Frequently Used item, the one at the far 
+    /// end of the eviction order: of the items with the highest frequency 
+    /// count, the one accessed most recently. See `lfu_entry()`.
+    /// 
+    pub fn mfu_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, S, C>> {
+        self.live_entry(|cache| queues::mfu(&cache.frequencies))
+    }
+
+    /// Returns a view of the item `pick` picks, dropping the expired items it
+    /// picks on the way, so the view's item is live. The view keeps it live:
+    /// nothing it does expires the item, whatever the clock says by then.
+    /// 
+    fn live_entry(&mut self, pick: fn(&Self) -> Option<&K>)
+        -> Option<OccupiedEntry<'_, K, V, S, C>>
+    {
+        loop {
+            let key  = pick(self)?.clone();
+            let hash = self.map.hash(&key);
+
+            self.drop_stale(hash, &key);
+
+            if self.map.get_hashed(hash, &key).is_some() {
+                return Some(OccupiedEntry { cache: self, key });
+            }
+        }
+    }
+
+    /// Pins the key's item, protecting it from eviction for as long as the
+    /// returned guard, or any clone of it, is alive. Pinned items are skipped 
+    /// by `pop_lfu()` and by inserts into a full cache, which evict the next 
+    /// item in line instead. If every item is pinned, inserts of new keys are
+    /// dropped, except those of `get_or_insert_with()` and friends, which 
+    /// take the cache over capacity. Items can still be removed explicitly, 
+    /// which unpins them. Returns `None` if the key isn't cached.
+    /// 
+    pub fn guard(&mut self, key: &K) -> Option<EntryGuard<K>> {
+        let hash = self.map.hash(key);
+
+        self.drop_stale(hash, key);
+
+        self.map.get_hashed(hash, key)?;
+
+        let pin = self.pins.entry(key.clone()).or_default();
+
+        Some(EntryGuard { key: key.clone(), _pin: pin.clone() })
+    }
+
+    /// Removes all items from the cache. The capacity is unchanged.
+    /// 
+    pub fn clear(&mut self) {
+        if let Some(notify) = &self.notify {
+            for (key, vrec) in self.map.drain() {
+                if vrec.gen >= self.generation {
+                    notify.send(key, vrec.value, EvictionReason::Cleared);
+                }
+            }
+        }
+        self.map.clear();
+        self.frequencies.clear();
+        self.retired.clear();
+        self.pins.clear();
+        self.expiries.clear();
+        self.uncached = None;
+        self.stale    = 0;
+        self.age      = 0;
+        self.weight   = 0;
+
+        if let Some(tags)      = &mut self.tags      { tags.clear(); }
+        if let Some(prefixes)  = &mut self.prefixes  { prefixes.clear(); }
+        if let Some(insertion) = &mut self.insertion { insertion.clear(); }
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(log) = &mut self.audit {
+            log.push(AuditEvent::Cleared);
+        }
+        #[cfg(feature = "debug-verify")]
+        self.shadow.clear();
+    }
+
+    /// Removes all items from the cache in constant time. The items become
+    /// unreachable right away, but their memory is only released as they're
+    /// reclaimed: an item is dropped when its key is next used, a couple are
+    /// dropped with each insert, and `reclaim()` drops them in bulk. Until 
+    /// then they're counted by `pending_reclaim()`, not by `len()`. The tag,
+    /// prefix and insertion order indexes, if enabled, are emptied right away.
+    /// 
+    pub fn invalidate_all(&mut self) {
+        let queues = std::mem::take(&mut self.frequencies);
+
+        if !queues.is_empty() {
+            self.retired.push(queues);
+        }
+        self.stale       = self.map.len();
+        self.generation  = self.stamps;
+        self.age         = 0;
+        self.weight      = 0;
+        self.pins.clear();
+        self.expiries.clear();
+
+        if let Some(tags)      = &mut self.tags      { tags.clear(); }
+        if let Some(prefixes)  = &mut self.prefixes  { prefixes.clear(); }
+        if let Some(insertion) = &mut self.insertion { insertion.clear(); }
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(log) = &mut self.audit {
+            log.push(AuditEvent::Cleared);
+        }
+        #[cfg(feature = "debug-verify")]
+        self.shadow.clear();
+    }
+
+    /// Drops up to `max_items` of the items left behind by `invalidate_all()`,
+    /// returning how many were dropped.
+    /// 
+    pub fn reclaim(&mut self, max_items: usize) -> usize {
+        self.reclaim_visiting(max_items).1
+    }
+
+    /// Does the work of `reclaim()`, returning how many keys of the retired
+    /// queues it visited as well as how many items it dropped. Keys that were
+    /// dropped already are visited for nothing, so work can be done while
+    /// nothing is dropped.
+    /// 
+    fn reclaim_visiting(&mut self, max_items: usize) -> (usize, usize) {
+        let mut dropped = 0;
+        let mut visited = 0;
+
+        // Each key in the retired queues is visited once. Some were already 
+        // dropped or reinserted since then, which leaves nothing to do.
+        while visited < max_items.saturating_mul(2) && dropped < max_items {
+            let Some(queues) = self.retired.last_mut() else { break };
+            let Some(queue)  = queues.front_mut()      else {
+                self.retired.pop();
+                continue;
+            };
+            let Some(Queued { hash, key }) = queue.1.pop_front() else {
+                queues.pop_front();
+                continue;
+            };
+            visited += 1;
+
+            if self.map.get_hashed(hash, &key)
+                       .is_some_and(|v| v.gen < self.generation) 
+            {
+                self.map.remove_entry_hashed(hash, &key);
+                self.stale -= 1;
+                dropped    += 1;
+            }
+        }
+        (visited, dropped)
+    }
+
+    /// Drops every item whose TTL has run out, returning how many there were.
+    /// The items with TTLs are kept in a timer wheel by expiry time, so this
+    /// takes time in proportion to the number dropped.
+    /// 
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.now();
+
+        self.expire_due(now, usize::MAX).1
+    }
+
+    /// Does some of the cache's deferred work, within `budget`: dropping the
+    /// items left behind by `invalidate_all()`, and the items that have 
+    /// expired. The work left over is picked up by the next call, so calling
+    /// this regularly with a small budget gets the cache to the same state as
+    /// `reclaim(usize::MAX)` and `purge_expired()` would at once, without any
+    /// one call taking long. The report says whether work remains. With 
+    /// adaptive capacity, the capacity is tuned too, see `adapt()`.
+    /// 
+    pub fn run_maintenance(&mut self, budget: MaintenanceBudget) 
+        -> MaintenanceReport 
+    {
+        const BATCH: usize = 32;
+
+        let start      = Instant::now();
+        let now        = self.now();
+        let mut left   = budget.items.unwrap_or(usize::MAX);
+        let mut report = MaintenanceReport::default();
+
+        while left > 0 && budget.time.is_none_or(|t| start.elapsed() < t) {
+            let batch = left.min(BATCH);
+
+            let (retired, reclaimed) = self.reclaim_visiting(batch);
+            let rest                 = batch.saturating_sub(retired);
+            let (visited, expired)   = self.expire_due(now, rest);
+
+            // Only once the retired queues and the due expiries are empty.
+            if retired + visited == 0 { break; }
+
+            report.reclaimed += reclaimed;
+            report.expired   += expired;
+            left              = left.saturating_sub(retired + visited);
+        }
+        report.capacity     = self.adapt();
+        report.work_remains = self.stale > 0 
+            || now.checked_sub(self.grace).is_some_and(|cutoff| {
+                self.expiries.has_due(cutoff)
+            });
+        report
+    }
+
+    /// Does all of the cache's deferred work at once, like 
+    /// `run_maintenance()` without a budget: drops the items left behind by
+    /// `invalidate_all()`, and the items that have expired, and tunes the
+    /// capacity if adaptive capacity is enabled. It's the one call to make 
+    /// from a timer for a cache that uses any of these. When nothing is 
+    /// pending it does nothing, and returns an empty report.
+    /// 
+    /// ```
+    /// use lfu_cache::{LfuCache, MaintenanceReport};
+    /// 
+    /// let mut cache = LfuCache::new(10);
+    /// 
+    /// for key in 0..10 { cache.insert(key, key); }
+    /// cache.invalidate_all();
+    /// 
+    /// assert_eq!(cache.run_pending_tasks().reclaimed, 10);
+    /// assert_eq!(cache.run_pending_tasks(), MaintenanceReport::default());
+    /// ```
+    pub fn run_pending_tasks(&mut self) -> MaintenanceReport {
+        self.run_maintenance(MaintenanceBudget::unlimited())
+    }
+
+    /// Has the cache tell time with `clock` instead of the system's clock, 
+    /// such as a `ManualClock` in tests. The items already cached keep the 
+    /// times they were written at, so this is best done while it's empty.
+    /// 
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Some(Arc::new(clock));
+    }
+
+    /// Limits the cache by the total weight of its items, as well as by their
+    /// number. `weigh` gives an item's weight when its value is written, and
+    /// items are evicted, in eviction order, until the total is at most 
+    /// `max_weight`. An item heavier than that on its own is turned away by
+    /// the insert methods, see `set_max_item_fraction()`. The items already
+    /// cached are weighed now. Under `Policy::Gdsf`, heavier items also get
+    /// lower priorities.
+    /// 
+    /// Values changed in place, through `get_mut()` and the like, keep the 
+    /// weight they were written with.
+    /// 
+    pub fn set_weigher<F>(&mut self, max_weight: usize, weigh: F)
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        let weigher = Weigher { weigh: Arc::new(weigh), max: max_weight };
+        let gen     = self.generation;
+
+        // Everything's weighed before any weight is changed, so a panicking
+        // weigher leaves the old weights in place. The map isn't changed in
+        // between, so it's iterated in the same order both times.
+        let weights = self.map.iter().map(|(key, vrec)| {
+            (weigher.weigh)(key, &vrec.value)
+        }).collect::<Vec<_>>();
+
+        self.weight = 0;
+
+        for (vrec, weight) in self.map.values_mut().zip(weights) {
+            vrec.weight = weight;
+
+            if vrec.gen >= gen { self.weight += vrec.weight; }
+        }
+        self.weigher = Some(weigher);
+        self.shed_weight();
+    }
+
+    /// Has the insert methods turn away items heavier than `fraction` of the
+    /// weight limit, rather than evict most of the cache to fit them, see
+    /// `InsertResult::TooLarge`. By default only items heavier than the limit
+    /// itself are turned away. It applies once a weigher is set, and isn't 
+    /// applied to the items already cached.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `fraction` isn't more than 0 and at most 1.
+    /// 
+    pub fn set_max_item_fraction(&mut self, fraction: f64) {
+        assert!(fraction > 0.0 && fraction <= 1.0, 
+                "the fraction must be in (0, 1]");
+        self.item_share = fraction;
+    }
+
+    /// Enables refresh-ahead. Once it's been `after` since an item was 
+    /// written, it's due for refresh: the next lookup still returns its value,
+    /// but also marks the key, and `take_refresh_candidates()` hands marked
+    /// keys out so they can be reloaded and stored with `replace_value()` 
+    /// before they expire. A key is marked at most once until its item is 
+    /// written again.
+    /// 
+    pub fn enable_refresh(&mut self, after: Duration) {
+        self.clock.get_or_insert_with(|| Arc::new(SystemClock::new()));
+        self.refresh = Some(Refresh::new(after));
+    }
+
+    /// Returns the keys marked for refresh since the last call, in the order
+    /// they were marked, see `enable_refresh()`. Keys that have since been 
+    /// written or have left the cache are left out.
+    /// 
+    pub fn take_refresh_candidates(&mut self) -> Vec<K> {
+        let Some(refresh) = &mut self.refresh else { return Vec::new() };
+
+        let mut seen = HashSet::new();
+
+        refresh.due.drain(..).filter(|key| {
+            self.map.get(key).is_some_and(|vrec| {
+                vrec.marked && vrec.gen >= self.generation
+            }) && seen.insert(key.clone())
+        }).collect()
+    }
+
+    /// Enables stale-while-revalidate. An item whose TTL has run out is kept
+    /// for up to `window` longer, during which lookups still return its value
+    /// and `lookup()` says it's `GetResult::Stale`. The first lookup of a 
+    /// stale item marks its key for refresh, as `enable_refresh()` does, so
+    /// that `take_refresh_candidates()` hands it out to be reloaded and 
+    /// stored, with a new TTL, by `insert_with_ttl()` or `replace_value()`.
+    /// Once the window has passed too, the item is dropped like any expired
+    /// one.
+    /// 
+    pub fn enable_stale_while_revalidate(&mut self, window: Duration) {
+        self.clock.get_or_insert_with(|| Arc::new(SystemClock::new()));
+        self.refresh.get_or_insert_with(|| Refresh::new(Duration::MAX));
+        self.grace = window;
+    }
+
+    /// Sets the TTL of the items written from now on without one of their 
+    /// own, by `insert()`, `get_or_insert_with()` and the like, or clears it
+    /// with `None`. `insert_with_ttl()` still gives an item the TTL it's 
+    /// given. Items already cached keep the TTLs they were written with.
+    /// 
+    pub fn set_default_ttl(&mut self, ttl: Option<Duration>) {
+        if ttl.is_some() {
+            self.clock.get_or_insert_with(|| Arc::new(SystemClock::new()));
+        }
+        self.default_ttl = ttl;
+    }
+
+    /// Returns the TTL given to items written without one, see 
+    /// `set_default_ttl()`.
+    /// 
+    pub fn default_ttl(&self) -> Option<Duration> {
+        self.default_ttl
+    }
+
+    /// Starts keeping `CacheStats`, and `LoadStats`, with all counters at 
+    /// zero. If stats were already enabled, this resets them.
+    /// 
+    pub fn enable_stats(&mut self) {
+        self.stats = Some(CacheStats::default());
+        self.loads = Some(LoadStats::default());
+    }
+
+    /// Enables TinyLFU-style admission control. The cache starts estimating
+    /// how often every key is accessed, cached or not, in a compact frequency
+    /// sketch that forgets old accesses over time. When the cache is full, a
+    /// new key is only inserted if its estimate is higher than that of the
+    /// item that would be evicted for it; otherwise the insert is dropped.
+    /// This keeps one-off keys from pushing out items that are used often.
+    /// 
+    pub fn enable_admission(&mut self) {
+        self.admission = Some(FrequencySketch::new(self.capacity));
+    }
+
+    /// Enables adaptive capacity: the cache tunes its capacity to the hit 
+    /// ratio set by `adaptive`, within its bounds, see the `adapt` module.
+    /// `adapt()` makes the changes, and `run_maintenance()` calls it. A 
+    /// capacity outside the bounds is brought within them by the first call.
+    /// 
+    /// ```
+    /// use lfu_cache::{AdaptiveCapacity, LfuCache};
+    /// 
+    /// let mut cache = LfuCache::new(100);
+    /// 
+    /// cache.enable_adaptive_capacity(AdaptiveCapacity::target_hit_ratio(0.9)
+    ///                                                 .bounds(10, 1000)
+    ///                                                 .min_lookups(100));
+    /// 
+    /// // Every lookup of a small set of keys hits, so a smaller cache will do.
+    /// for key in 0..10 { cache.insert(key, key); }
+    /// for i in 0..100 { cache.get(&(i % 10)); }
+    /// 
+    /// let change = cache.adapt().unwrap();
+    /// 
+    /// assert_eq!((change.from, change.to), (100, 90));
+    /// assert_eq!(cache.capacity(), 90);
+    /// ```
+    pub fn enable_adaptive_capacity(&mut self, adaptive: AdaptiveCapacity) {
+        self.adapter = Some(Adapter::new(adaptive));
+    }
+
+    /// Tunes the capacity to the target hit ratio, if adaptive capacity is 
+    /// enabled and there have been enough lookups since the last call, see
+    /// `enable_adaptive_capacity()`. The capacity is raised if the hit ratio
+    /// since then is short of the target and the misses were often enough on
+    /// keys evicted lately, and lowered if the ratio is well above it, by
+    /// `set_capacity()`. With stats enabled, the changes are counted in the
+    /// `grows` and `shrinks` stats. Returns the change made, if any.
+    /// 
+    pub fn adapt(&mut self) -> Option<CapacityChange> {
+        let change = self.adapter.as_mut()?.decide(self.capacity)?;
+
+        if let Some(stats) = &mut self.stats {
+            if change.grew() { stats.grows += 1; } else { stats.shrinks += 1; }
+        }
+        self.set_capacity(change.to);
+        Some(change)
+    }
+
+    /// Starts sending the items the cache lets go of to a channel that holds
+    /// up to `bound` messages, and returns its receiving end. Each message
+    /// holds the item's key and value, and its `EvictionReason`: whether it
+    /// was evicted, expired, removed by `invalidate_tag()`, `retain_mut()` and
+    /// the like, replaced by a write, or cleared by `clear()`. Values that are
+    /// handed back to the caller, by `remove()`, `pop_lfu()`, 
+    /// `insert_checked()`, `replace_value()` and the like, aren't sent, nor
+    /// are the items `invalidate_all()` leaves behind.
+    /// 
+    /// The cache never waits on the receiver: when the channel is full, the
+    /// message is dropped and counted, see `EvictionReceiver::dropped()`. A
+    /// `bound` of 0 only delivers to a receiver already waiting in `recv()`.
+    /// A second call replaces the first receiver, which then sees the end of
+    /// its channel, and clones of the cache start without one.
+    /// 
+    pub fn eviction_receiver(&mut self, bound: usize) 
+        -> EvictionReceiver<K, V> 
+    {
+        let (tx, rx) = EvictionSender::channel(bound);
+
+        self.notify = Some(tx);
+        rx
+    }
+
+    /// Starts recording the last `len` operations on the cache in an audit
+    /// log. Each record holds a clone of the key involved, but never the 
+    /// value. If the log was already enabled, it's started afresh.
+    /// 
+    #[cfg(feature = "debug-audit")]
+    pub fn enable_audit_log(&mut self, len: usize) {
+        self.audit = Some(AuditLog {
+            records  : VecDeque::with_capacity(len),
+            capacity : len,
+            next_seq : 0,
+            clone    : K::clone,
+        });
+    }
+
+    /// Panics if the map and the frequency queues disagree in any way.
+    /// 
+    #[cfg(test)]
+    fn check_invariants(&self) {
+        let mut hnext = self.frequencies.front_node();
+        let mut prev  = 0;
+        let mut count = 0;
+
+        while let Some(hfreq) = hnext {
+            let (freq, queue) = self.frequencies.get(hfreq);
+
+            assert!(*freq > prev, "queues out of order");
+            assert!(!queue.is_empty() || (*freq == 1 && prev == 0), 
+                    "empty queue with frequency {freq}");
+
+            let mut hnext_pos = queue.front_node();
+
+            while let Some(hpos) = hnext_pos {
+                let Queued { hash, key } = queue.get(hpos);
+
+                assert_eq!(*hash, self.map.hash(key), "queued hash is wrong");
+
+                let vrec = &self.map[key];
+
+                assert!(vrec.hfreq == hfreq && vrec.hpos == hpos, 
+                        "stale handles in value record");
+                assert!(self.policy != Policy::Lfu 
+                        || vrec.label(self.log_base) == *freq,
+                        "frequency count out of step");
+                hnext_pos = queue.next_node(hpos);
+                count    += 1;
+            }
+            hnext = self.frequencies.next_node(hfreq);
+            prev  = *freq;
+        }
+        assert_eq!(count, self.len(), "keys missing from queues");
+        assert_eq!(self.map.values().filter(|v| v.gen < self.generation)
+                                    .count(), 
+                   self.stale, "stale entries miscounted");
+        assert_eq!(self.map.values().filter(|v| v.gen >= self.generation)
+                                    .map(|v| v.weight).sum::<usize>(),
+                   self.weight, "weight miscounted");
+
+        let mut stamps = self.map.values().map(|v| v.gen).collect::<Vec<_>>();
+
+        stamps.sort_unstable();
+        stamps.dedup();
+        assert!(stamps.len() == self.map.len()
+                    && stamps.last().is_none_or(|last| *last < self.stamps),
+                "entry stamp reused");
+        assert!(self.map.iter_hashed()
+                        .all(|(hash, key, _)| hash == self.map.hash(key)),
+                "key filed under the wrong hash");
+
+        assert!(self.pins.keys().all(|key| self.map.contains_key(key)),
+                "pin left on a removed key");
+
+        if let Some(tags) = &self.tags {
+            assert!(tags.tagged().all(|key| self.map.contains_key(key)), 
+                    "tags left on a removed key");
+        }
+        if let Some(prefixes) = &self.prefixes {
+            assert_eq!(prefixes.keys().count(), self.len(), 
+                       "prefix index out of step");
+            assert!(prefixes.keys().all(|key| self.map.contains_key(key)), 
+                    "removed key left in prefix index");
+        }
+        if let Some(insertion) = &self.insertion {
+            assert_eq!(insertion.keys().count(), self.len(), 
+                       "insertion index out of step");
+            assert!(insertion.keys().all(|key| self.map.contains_key(key)), 
+                    "removed key left in insertion index");
+        }
+        for (key, vrec) in self.map.iter() {
+            let Some(at) = vrec.deadline() else { continue };
+
+            assert!(self.expiries.get(key) == Some(at) 
+                        || vrec.gen < self.generation, 
+                    "item with a TTL missing from the expiry index");
+        }
+        assert!(self.expiries.iter().all(|(key, at)| {
+                    self.map.get(key).is_some_and(|vrec| {
+                        vrec.deadline() == Some(at)
+                    })
+                }),
+                "expiry index out of step");
+        assert!(self.len() <= self.capacity, "over capacity");
+    }
+
+    /// Checks that the item the model evicted, if any, is the one that's gone
+    /// from the cache after `op`.
+    /// 
+    #[cfg(feature = "debug-verify")]
+    fn verify_victim(&self, op: &str, victim: Option<(K, Item)>) {
+        let model = victim.as_ref().map(|(_, item)| *item);
+        let cache = match &victim {
+            Some((key, item)) if !self.contains_key(key) => Some(*item),
+            _ => self.shadow.missing(|key| self.contains_key(key)),
+        };
+        verify::check(op, "the victim", model, cache);
+    }
+
+    /// Decides whether the key whose hash is `hash` is allowed to evict the
+    /// LFU item. Without admission control, it always is.
+    /// 
+    fn admit(&self, hash: u64) -> bool {
+        let Some(sketch) = &self.admission else { return true };
+        let Some((hfreq, hpos)) = self.candidate_at() else { return true };
+        let victim = self.frequencies.get(hfreq).1.get(hpos);
+
+        sketch.estimate(hash) > sketch.estimate(victim.hash)
+    }
+
+    /// Returns `true` if inserting the key, which isn't cached, would store
+    /// nothing: the cache is full, and either admission control turns the key
+    /// away or every item is pinned.
+    /// 
+    pub(crate) fn would_reject(&self, key: &K) -> bool {
+        self.capacity == 0 
+            || self.len() >= self.capacity 
+               && (!self.admit(self.map.hash(key)) 
+                   || self.eviction_candidate().is_none())
+    }
+
+    /// Looks up the key, counting the access, and inserts the value returned 
+    /// by `f` if it isn't there. Returns the key's value either way, unless 
+    /// `f` fails, see `insert_loaded()`.
+    /// 
+    fn get_or_try_insert_mut<E>(&mut self, 
+                                key : K, 
+                                f   : impl FnOnce(&K) -> Result<V, E>)
+        -> Result<&mut V, E>
+    {
+        assert!(self.capacity > 0, "a cache with a capacity of 0 can't hold \
+                                    a value");
+
+        let hash = self.map.hash(&key);
+
+        self.drop_stale(hash, &key);
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        if let Some(sketch) = &mut self.admission { sketch.increment(hash); }
+
+        let now = self.now();
+
+        let age = self.next_age();
+
+        // Returning the reference from `get_mut()` directly would keep the map
+        // borrowed for the rest of the function, insertion path included.
+        let vrec = self.map.get_mut_hashed(hash, &key)
+                           .map(|vrec| vrec as *mut Value<V, C>);
+
+        if let Some(adapter) = &mut self.adapter {
+            adapter.lookup(vrec.is_some(), hash);
+        }
+        if let Some(vrec) = vrec {
+            // SAFETY: The pointer came from an exclusive borrow of the map,
+            //         which isn't touched again before it's returned.
+            let vrec = unsafe { &mut *vrec };
+
+            if let Some(stats) = &mut self.stats { stats.hits += 1; }
+
+            vrec.hits = vrec.hits.saturating_add(1);
+
+            if let Some(refresh) = &mut self.refresh { 
+                refresh.note(&key, vrec, now); 
+            }
+            #[cfg(feature = "debug-audit")]
+            if let Some(log) = &mut self.audit {
+                log.push(AuditEvent::Hit { key });
+            }
+            Self::access(&mut self.frequencies, &mut self.increments, 
+                         self.policy, age, self.log_base, vrec);
+            return Ok(&mut vrec.value);
+        }
+        if let Some(stats) = &mut self.stats { stats.misses += 1; }
+
+        let watch = self.start_load();
+        let value = f(&key);
+
+        self.finish_load(watch, value.is_ok());
+
+        let value = value?;
+
+        Ok(self.insert_loaded(hash, key, value))
+    }
+
+    /// Inserts a loaded value for a key that isn't cached, evicting to make 
+    /// room without consulting admission control, and returns it. A value 
+    /// too large to cache is counted as such and handed back without being
+    /// cached or evicting anything, see `uncached`. The caller has taken the
+    /// operation's step of age with `next_age()`.
+    /// 
+    fn insert_loaded(&mut self, hash: u64, key: K, value: V) -> &mut V {
+        let weight = Self::weigh(&self.weigher, &key, &value);
+
+        if self.too_large(weight) {
+            return self.uncached.insert(value);
+        }
+        self.uncached = None;
+        self.reclaim(2);
+
+        // If every item is pinned, the cache has to go over its limits.
+        while (self.len() >= self.capacity || self.overweight(weight)) 
+            && self.evict() 
+        {}
+        let ttl  = self.schedule_default(&key, self.now());
+        let age  = self.current_age();
+        let vrec = self.insert_new(hash, key, value, 1, weight, age);
+
+        vrec.ttl = ttl.map(Stamp::new);
+        &mut vrec.value
+    }
+
+    /// Writes the pair like `write()` does, for the insert methods that don't
+    /// return the value they replace, which goes to the eviction receiver as
+    /// `EvictionReason::Replaced` instead. Returns the item's new version.
+    /// 
+    fn write_over(&mut self, key: K, value: V, ttl: Option<Duration>) -> u64 {
+        let copy = self.notify.as_ref().map(|_| key.clone());
+
+        match (self.write(key, value, ttl), copy) {
+            ((version, InsertResult::Replaced(old)), Some(key)) => {
+                self.send_evicted(key, old, EvictionReason::Replaced);
+                version
+            },
+            ((version, _), _) => version,
+        }
+    }
+
+    /// Inserts a key-value pair that lives for `ttl`, if given, returning its
+    /// new version, see `insert_versioned()`, and what became of it. The 
+    /// version is 0 if it wasn't stored.
+    /// 
+    pub(crate) fn write(&mut self, key: K, value: V, ttl: Option<Duration>) 
+        -> (u64, InsertResult<V>) 
+    {
+        self.write_hashed(self.map.hash(&key), key, value, ttl)
+    }
+
+    /// Does the work of `write()` for a key whose hash is `hash`.
+    /// 
+    pub(crate) fn write_hashed(&mut self, 
+                               hash  : u64, 
+                               key   : K, 
+                               value : V, 
+                               ttl   : Option<Duration>) 
+        -> (u64, InsertResult<V>) 
+    {
+        if self.capacity == 0 { return (0, InsertResult::Rejected); }
+
+        let ttl = ttl.or(self.default_ttl);
+
+        self.drop_stale(hash, &key);
+        self.reclaim(2);
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        // Nothing's changed for an item that's too large.
+        let weight = Self::weigh(&self.weigher, &key, &value);
+
+        if self.too_large(weight) {
+            return (0, InsertResult::TooLarge { weight });
+        }
+        if let Some(sketch) = &mut self.admission { sketch.increment(hash); }
+
+        let now = self.now();
+
+        match ttl.map(|ttl| now.saturating_add(ttl)) {
+            Some(at) => self.schedule(key.clone(), at),
+            None     => self.unschedule(&key),
+        }
+        let age = self.next_age();
+
+        if let Some(vrec) = self.map.get_mut_hashed(hash, &key) {
+            // The key already exists, update value and increment its frequency.
+            let old = vrec.write(value, now, ttl);
+
+            Self::reweigh(&mut self.weight, vrec, weight);
+            Self::access(&mut self.frequencies, &mut self.increments, 
+                         self.policy, age, self.log_base, vrec);
+
+            let version = vrec.version;
+
+            if let Some(stats) = &mut self.stats { stats.updates += 1; }
+
+            #[cfg(feature = "debug-audit")]
+            if let Some(log) = &mut self.audit {
+                log.push(AuditEvent::Updated { key });
+            }
+            self.shed_weight();
+            (version, InsertResult::Replaced(old))
+        } else {
+            // This is a new key. Remove the LFU item if the cache is full. 
+            // If admission control turns the key away, or every item is 
+            // pinned, the insert is dropped. When the LFU item is sure to be
+            // the only one to go, the new key takes its place in one step.
+            let swap = self.len() >= self.capacity 
+                       && self.weigher.is_none()
+                       && self.pins.is_empty() 
+                       && self.evict_first.is_none();
+
+            let room = if swap { 
+                self.admit(hash) 
+            } else { 
+                self.make_room(hash, weight) 
+            };
+            if !room {
+                if let Some(stats) = &mut self.stats { stats.rejections += 1; }
+
+                self.unschedule(&key);
+                return (0, InsertResult::Rejected);
+            }
+            let vrec = if swap {
+                self.replace_lfu(hash, key, value, age)
+            } else {
+                // Evicting may have raised the age, under `Policy::LfuDa`,
+                // so it's read again, without another step.
+                let age = self.current_age();
+
+                self.insert_new(hash, key, value, 1, weight, age)
+            };
+
+            vrec.ttl = ttl.map(Stamp::new);
+            (vrec.version, InsertResult::Inserted)
+        }
+    }
+
+    /// Evicts the LFU item to make room for a new one.
+    /// 
+    fn evict(&mut self) -> bool {
+        let Some((hash, victim, value)) = self.take_lfu() else { 
+            return false 
+        };
+        self.evicted(hash, victim, value);
+        true
+    }
+
+    /// Evicts the LFU item and adds a new key with a frequency of 1 in its
+    /// place, like `evict()` followed by `insert_new()`, but reusing the LFU
+    /// item's queue where it can, and its node and key in the queue, see
+    /// `queues::replace_lfu()`. The cache must be full, without pins, a 
+    /// weigher or `evict_first`, so that the LFU item is the one to go.
+    /// `age` is the write's age, from `next_age()`.
+    /// 
+    fn replace_lfu(&mut self, hash: u64, key: K, value: V, age: usize) 
+        -> &mut Value<V, C> 
+    {
+        let policy   = self.policy;
+        let mut vrec = Value::new(value);
+
+        // The victim comes out of the map first, since its key in the queue
+        // is overwritten with the new one. It's found by the hash the queue
+        // keeps with it.
+        let (hfreq, hpos) = queues::first(&self.frequencies).unwrap();
+        let lfu           = self.frequencies.get(hfreq).1.get(hpos);
+        let lfu_hash      = lfu.hash;
+        let (victim, old) = self.map.remove_entry_hashed(lfu_hash, &lfu.key)
+                                    .unwrap();
+
+        let popped = queues::replace_lfu(&mut self.frequencies, 
+                                         hash,
+                                         &key, 
+                                         &mut vrec, 
+                                         |popped| {
+            let age = if policy.ages_by_eviction() { popped } else { age };
+            policy.priority(1, 1, age)
+        }).unwrap();
+
+        if policy.ages_by_eviction() { self.age = popped; }
+
+        self.weight -= old.weight;
+        self.evicted(lfu_hash, victim, old.value);
+        self.init_record(&key, &mut vrec, 1, 1);
+        self.map.insert_unique_hashed(hash, key, vrec)
+    }
+
+    /// Does the bookkeeping for an evicted item, whose key's hash is `hash`.
+    /// 
+    fn evicted(&mut self, hash: u64, victim: K, value: V) {
+        if let Some(stats) = &mut self.stats { stats.evictions += 1; }
+
+        if let Some(adapter) = &mut self.adapter {
+            adapter.evicted(hash, self.capacity);
+        }
+
+        self.unindex(&victim);
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(log) = &mut self.audit {
+            log.push(AuditEvent::Evicted { victim : victim.clone(), 
+                                           reason : EvictionReason::Capacity });
+        }
+        self.send_evicted(victim, value, EvictionReason::Capacity);
+    }
+
+    /// Removes the LFU item that isn't pinned, and returns it with its key's
+    /// hash.
+    /// 
+    fn take_lfu(&mut self) -> Option<(u64, K, V)> {
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        if self.pins.is_empty() && self.evict_first.is_none() {
+            let priority = self.frequencies.front().map(|q| q.0);
+            let (hash, key, vrec) = Self::remove_lfu(&mut self.frequencies, 
+                                                     &mut self.map)?;
+            self.weight -= vrec.weight;
+
+            if self.policy != Policy::Lfu {
+                if self.policy.ages_by_eviction() {
+                    self.age = priority.unwrap();
+                }
+                // New items go in above the age, or the time, so an emptied 
+                // queue at the front won't be filled again.
+                if self.frequencies.front().is_some_and(|q| q.1.is_empty()) {
+                    self.frequencies.pop_front();
+                }
+            }
+            return Some((hash, key, vrec.value));
+        }
+        // Forget the pins whose guards have all been dropped.
+        self.pins.retain(|_, pin| Arc::strong_count(pin) > 1);
+
+        let (hfreq, hpos)        = self.candidate_at()?;
+        let Queued { hash, key } = self.frequencies.get(hfreq).1.get(hpos);
+        let hash                 = *hash;
+        let (key, vrec) = self.map.remove_entry_hashed(hash, key).unwrap();
+
+        if self.policy.ages_by_eviction() {
+            self.age = self.frequencies.get(vrec.hfreq).0;
+        }
+        queues::unlink(&mut self.frequencies, &vrec);
+        self.weight -= vrec.weight;
+        Some((hash, key, vrec.value))
+    }
+
+    /// Returns the weight of an item, 1 without a weigher.
+    /// 
+    fn weigh(weigher: &Option<Weigher<K, V>>, key: &K, value: &V) -> usize {
+        weigher.as_ref().map_or(1, |weigher| (weigher.weigh)(key, value))
+    }
+
+    /// Gives an item whose value was replaced its new weight, and updates the
+    /// total. The new value is weighed before it's written, so that a 
+    /// panicking weigher leaves the item as it was.
+    /// 
+    fn reweigh(total: &mut usize, vrec: &mut Value<V, C>, weight: usize) {
+        *total      = *total - vrec.weight + weight;
+        vrec.weight = weight;
+    }
+
+    /// Returns `true` if an item of `weight` is too heavy to be cached at all,
+    /// see `set_max_item_fraction()`, counting it in the stats if it is.
+    /// 
+    fn too_large(&mut self, weight: usize) -> bool {
+        let too_large = self.over_share(weight);
+
+        if too_large {
+            if let Some(stats) = &mut self.stats { stats.too_large += 1; }
+        }
+        too_large
+    }
+
+    /// Returns `true` if an item of `weight` is over the share of the weight
+    /// limit one item may take.
+    /// 
+    fn over_share(&self, weight: usize) -> bool {
+        self.weigher.as_ref().is_some_and(|weigher| {
+            weight as f64 > weigher.max as f64 * self.item_share
+        })
+    }
+
+    /// Returns `true` if the pair is too heavy to be cached at all, so that
+    /// inserting it would only remove the key's item, without counting it in
+    /// the stats.
+    /// 
+    pub(crate) fn would_be_too_large(&self, key: &K, value: &V) -> bool {
+        self.over_share(Self::weigh(&self.weigher, key, value))
+    }
+
+    /// Returns `true` if adding an item of `weight` would go over the weight
+    /// limit.
+    /// 
+    fn overweight(&self, weight: usize) -> bool {
+        self.weigher.as_ref().is_some_and(|weigher| {
+            self.weight.saturating_add(weight) > weigher.max
+        })
+    }
+
+    /// Evicts items until a new key of `weight` fits, returning `false` if it
+    /// doesn't: admission control turned it away, or every item is pinned. 
+    /// An item that's too large must have been turned away already.
+    /// 
+    fn make_room(&mut self, hash: u64, weight: usize) -> bool {
+        if self.len() >= self.capacity && (!self.admit(hash) || !self.evict()) {
+            return false;
+        }
+        while self.overweight(weight) {
+            if !self.evict() { return false; }
+        }
+        true
+    }
+
+    /// Evicts items until the total weight is within the limit again, after
+    /// an item got heavier.
+    /// 
+    fn shed_weight(&mut self) {
+        while self.overweight(0) && self.evict() {}
+    }
+
+    /// Removes every item whose key fails the predicate, walking the queues 
+    /// so that emptied ones can be dropped as they're found. Returns how many
+    /// items were removed.
+    /// 
+    fn retain_by(&mut self, mut f: impl FnMut(&K, &mut Value<V, C>) -> bool) 
+        -> usize 
+    {
+        let mut removed = 0;
+        let mut hnext   = self.frequencies.front_node();
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.lose();
+
+        while let Some(hfreq) = hnext {
+            hnext = self.frequencies.next_node(hfreq);
+
+            let queue = &mut self.frequencies.get_mut(hfreq).1;
+            let mut hnext_pos = queue.front_node();
+
+            while let Some(hpos) = hnext_pos {
+                hnext_pos = queue.next_node(hpos);
+
+                let Queued { hash, key } = queue.get(hpos);
+
+                if f(key, self.map.get_mut_hashed(*hash, key).unwrap()) { 
+                    continue; 
+                }
+                // The record's value is dropped last, once the item is gone
+                // from every index.
+                let Queued { hash, key } = queue.remove(hpos);
+                let (_, vrec) = self.map.remove_entry_hashed(hash, &key)
+                                        .unwrap();
+
+                self.weight -= vrec.weight;
+
+                if let Some(tags) = &mut self.tags { tags.remove(&key); }
+
+                if let Some(prefixes) = &mut self.prefixes {
+                    prefixes.remove(&key);
+                }
+                if let Some(insertion) = &mut self.insertion {
+                    insertion.remove(&key);
+                }
+                self.pins.remove(&key);
+                self.expiries.cancel(&key);
+                #[cfg(feature = "debug-audit")]
+                if let Some(log) = &mut self.audit {
+                    log.push(AuditEvent::Removed { key: key.clone() });
+                }
+                if let Some(notify) = &self.notify {
+                    notify.send(key, vrec.value, EvictionReason::Removed);
+                }
+                removed += 1;
+            }
+            if queue.is_empty() {
+                self.frequencies.remove(hfreq);
+            }
+        }
+        removed
+    }
+
+    /// Removes the key's item for good, sending it to the eviction receiver
+    /// as `EvictionReason::Removed`, and returns `true` if it was cached.
+    /// 
+    fn discard(&mut self, key: &K) -> bool {
+        let Some((key, value)) = self.remove_entry(key) else { return false };
+
+        self.send_evicted(key, value, EvictionReason::Removed);
+        true
+    }
+
+    /// Files the key in the expiry index as due at `at`, in place of the 
+    /// entry it has, if any.
+    /// 
+    fn schedule(&mut self, key: K, at: Duration) {
+        self.expiries.schedule(key, at);
+    }
+
+    /// Schedules the expiry of a key that's written at `now` without a TTL of
+    /// its own, if the cache has a default TTL, and returns that TTL.
+    /// 
+    fn schedule_default(&mut self, key: &K, now: Duration) 
+        -> Option<Duration> 
+    {
+        let Some(ttl) = self.default_ttl else {
+            self.unschedule(key);
+            return None;
+        };
+
+        self.schedule(key.clone(), now.saturating_add(ttl));
+        Some(ttl)
+    }
+
+    /// Takes up to `max_items` entries that are due at `now` out of the 
+    /// expiry index, dropping their items. Returns how many entries were 
+    /// taken, and how many items dropped.
+    /// 
+    fn expire_due(&mut self, now: Duration, max_items: usize) 
+        -> (usize, usize) 
+    {
+        let Some(cutoff) = now.checked_sub(self.grace) else { return (0, 0) };
+
+        let due     = self.expiries.take_due(cutoff, max_items);
+        let visited = due.len();
+        let mut expired = 0;
+
+        for (key, at) in due {
+            let hash = self.map.hash(&key);
+            let live = self.map.get_hashed(hash, &key).is_some_and(|vrec| {
+                vrec.gen >= self.generation && vrec.deadline() == Some(at)
+            });
+            if live {
+                self.expire(hash, &key);
+                expired += 1;
+            }
+        }
+        (visited, expired)
+    }
+
+    /// Adds a key that isn't in the cache with the given frequency, returning
+    /// its value record. There must be room for it. `age` is the caller's 
+    /// age for the operation, so a write steps it once.
+    /// 
+    fn insert_new(&mut self, 
+                  hash   : u64, 
+                  key    : K, 
+                  value  : V, 
+                  freq   : usize, 
+                  weight : usize,
+                  age    : usize) 
+        -> &mut Value<V, C> 
+    {
+        // Create a new value record and push its key to the frequency queue.
+        let mut vrec = Value::new(value);
+
+        self.init_record(&key, &mut vrec, freq, weight);
+
+        // A count too high for `C` was cut down to its highest.
+        let label    = vrec.label(self.log_base);
+        let priority = self.policy.priority(label, weight, age);
+
+        let queued = Queued { hash, key: key.clone() };
+
+        queues::push(&mut self.frequencies, queued, priority, &mut vrec);
+
+        #[cfg(feature = "debug-verify")]
+        self.shadow.learn_clone();
+
+        // Insert the key-value pair into the map.
+        self.map.insert_unique_hashed(hash, key, vrec)
+    }
+
+    /// Does the bookkeeping for a new key, and fills in the rest of its value
+    /// record, all but its queue handles.
+    /// 
+    fn init_record(&mut self, 
+                   key    : &K, 
+                   vrec   : &mut Value<V, C>, 
+                   freq   : usize, 
+                   weight : usize) 
+    {
+        if let Some(stats) = &mut self.stats { stats.inserts += 1; }
+
+        if let Some(prefixes)  = &mut self.prefixes  { prefixes.insert(key); }
+        if let Some(insertion) = &mut self.insertion { insertion.insert(key); }
+
+        #[cfg(feature = "debug-audit")]
+        if let Some(log) = &mut self.audit {
+            log.push(AuditEvent::Inserted { key: key.clone() });
+        }
+        vrec.gen     = self.stamps;
+        vrec.written = Stamp::new(self.now());
+        vrec.count   = C::from_usize(freq);
+        vrec.weight  = weight;
+        self.weight += weight;
+        self.stamps += 1;
+    }
+
+    /// Removes the Least Frequently Used item from the cache.
+    /// 
+    fn remove_lfu(freq_qs : &mut Queues<K>,
+                  map     : &mut HashedMap<K, Value<V, C>, S>)
+        -> Option<(u64, K, Value<V, C>)>
+    {
+        // Pop the first entry and remove it from the map.
+        queues::pop_lfu(freq_qs).map(|Queued { hash, key }| {
+            let (key, vrec) = map.remove_entry_hashed(hash, &key).unwrap();
+            (hash, key, vrec)
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, C: Counter> LfuCache<K, Arc<V>, S, C> 
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Removes every item whose `Arc` isn't shared with anything outside the
+    /// cache, returning how many were removed. This visits every item.
+    /// 
+    pub fn purge_unreferenced(&mut self) -> usize {
+        self.retain_by(|_, vrec| Arc::strong_count(&vrec.value) > 1)
+    }
+
+    /// Makes evictions prefer items whose `Arc` isn't shared with anything 
+    /// outside the cache, taking the least frequently used of those before 
+    /// falling back on LFU order. Finding such an item is a scan in eviction
+    /// order, so evictions take longer the more referenced items come first.
+    /// 
+    pub fn enable_unreferenced_eviction(&mut self) {
+        self.evict_first = Some(|value| Arc::strong_count(value) == 1);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, C: Counter> Clone for LfuCache<K, V, S, C> 
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        let mut cache = Self::with_counter(self.capacity, 
+                                           self.map.hasher().clone());
+        cache.clone_from(self);
+        cache
+    }
+
+    /// Makes `self` a copy of `source`, reusing the allocations `self` already
+    /// has: its hash table, its frequency queues and their nodes, and the keys
+    /// and values of entries present in both caches, which are updated with
+    /// `clone_from()`. The hash map keeps its own hasher, so iteration order
+    /// can differ from `source`, but the caches otherwise behave the same.
+    /// 
+    fn clone_from(&mut self, source: &Self) {
+        // Keys and values are cloned all through, and a clone that panics 
+        // would leave the map, the queues and the indexes out of step, so the
+        // cache is cleared before the panic carries on.
+        let copy = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.copy_from(source)
+        }));
+        if let Err(payload) = copy {
+            self.clear();
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, C: Counter> LfuCache<K, V, S, C> 
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    /// Does the work of `clone_from()`.
+    /// 
+    fn copy_from(&mut self, source: &Self) {
+        // Drop the entries the source doesn't have. The rest are updated in
+        // place below.
+        let gen = self.generation;
+
+        self.map.retain(|key, vrec| {
+            vrec.gen >= gen && source.map.get(key).is_some_and(|src| {
+                src.gen >= source.generation
+            })
+        });
+        self.pins.retain(|key, _| self.map.contains_key(key));
+        self.uncached = None;
+        self.stamps     = self.stamps.max(source.stamps);
+        self.generation = self.stamps;
+        self.stale      = 0;
+        self.retired.clear();
+
+        self.capacity = source.capacity;
+        self.stats    = source.stats;
+        self.loads    = source.loads;
+        self.admission.clone_from(&source.admission);
+        self.increments.clone_from(&source.increments);
+        self.tags.clone_from(&source.tags);
+        self.prefixes.clone_from(&source.prefixes);
+        self.insertion.clone_from(&source.insertion);
+        self.evict_first = source.evict_first;
+        self.clock.clone_from(&source.clock);
+        self.refresh.clone_from(&source.refresh);
+        self.grace       = source.grace;
+        self.default_ttl = source.default_ttl;
+        self.expiries.clone_from(&source.expiries);
+        self.policy      = source.policy;
+        self.log_base    = source.log_base;
+        self.age         = source.age;
+        self.lambda      = source.lambda;
+        self.weight      = source.weight;
+        self.weigher.clone_from(&source.weigher);
+        self.item_share = source.item_share;
+        self.adapter.clone_from(&source.adapter);
+
+        #[cfg(feature = "debug-audit")]
+        self.audit.clone_from(&source.audit);
+
+        #[cfg(feature = "debug-verify")]
+        {
+            self.shadow.learn_clone();
+            self.shadow.lose();
+        }
+
+        // Walk the destination's queues alongside the source's, overwriting
+        // them and appending more as needed. The handles of each value record 
+        // are re-derived from wherever its key lands.
+        let mut hnext = self.frequencies.front_node();
+
+        for (freq, src_queue) in source.frequencies.iter() {
+            let hfreq = match hnext {
+                Some(hfreq) => {
+                    hnext = self.frequencies.next_node(hfreq);
+                    hfreq
+                },
+                None => self.frequencies.push_back((0, LinkedVector::new())),
+            };
+            let queue = self.frequencies.get_mut(hfreq);
+            let mut hnext_pos = queue.1.front_node();
+
+            queue.0 = *freq;
+
+            for Queued { hash: src_hash, key } in src_queue.iter() {
+                // The destination's hasher may not be the source's.
+                let hash = self.map.hash(key);
+                let hpos = match hnext_pos {
+                    Some(hpos) => {
+                        hnext_pos = queue.1.next_node(hpos);
+
+                        let slot = queue.1.get_mut(hpos);
+
+                        slot.hash = hash;
+                        slot.key.clone_from(key);
+                        hpos
+                    },
+                    None => {
+                        queue.1.push_back(Queued { hash, key: key.clone() })
+                    },
+                };
+                let src = source.map.get_hashed(*src_hash, key).unwrap();
+
+                let vrec = match self.map.get_mut_hashed(hash, key) {
+                    Some(vrec) => {
+                        vrec.value.clone_from(&src.value);
+                        vrec
+                    },
+                    None => {
+                        let vrec = Value::new(src.value.clone());
+                        self.map.insert_unique_hashed(hash, key.clone(), vrec)
+                    },
+                };
+                vrec.hfreq   = hfreq;
+                vrec.hpos    = hpos;
+                vrec.gen     = self.stamps;
+                vrec.version = src.version;
+                vrec.count   = src.count;
+                vrec.weight  = src.weight;
+                vrec.written = src.written;
+                vrec.ttl     = src.ttl;
+                vrec.marked  = src.marked;
+                vrec.hits    = src.hits;
+                vrec.boost   = src.boost;
+                self.stamps += 1;
+            }
+            // Trim what's left of the destination's queue.
+            while let Some(hpos) = hnext_pos {
+                hnext_pos = queue.1.next_node(hpos);
+                queue.1.remove(hpos);
+            }
+        }
+        // And any queues left over.
+        while let Some(hfreq) = hnext {
+            hnext = self.frequencies.next_node(hfreq);
+            self.frequencies.remove(hfreq);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+
+    macro_rules! vec2d {
+        ($( [$($x:expr),*] ),*) => (vec![$(vec![$($x),*]),*]);
+    }
+
+    #[test]
+    fn seeded_iteration_order() {
+        let run = || {
+            let mut cache = LfuCache::with_seed(50, 0x5eed);
+
+            for i in 0..1000u64 {
+                let key = i * 7919 % 131;
+
+                if i % 3 == 0 {
+                    cache.get(&key);
+                } else {
+                    cache.insert(key, i);
+                }
+            }
+            cache.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>()
+        };
+        let first = run();
+
+        assert_eq!(first.len(), 50);
+        assert_eq!(first, run());
+    }
+
+    /// Runs the same operations on each cache and checks they all agree.
+    /// 
+    fn assert_same_behavior(caches: &mut [&mut LfuCache<u64, String>]) {
+        caches.iter().for_each(|cache| cache.check_invariants());
+
+        for i in 0..2000u64 {
+            let key = i * 31 % 37;
+            let results = caches.iter_mut().map(|cache| {
+                match i % 4 {
+                    0 => cache.get(&key).cloned(),
+                    1 => cache.remove(&(key / 2)),
+                    _ => { cache.insert(key, i.to_string()); None },
+                }
+            }).collect::<Vec<_>>();
+
+            assert!(results.windows(2).all(|w| w[0] == w[1]), "op {i}");
+        }
+        caches.iter().for_each(|cache| cache.check_invariants());
+
+        let drained = caches.iter_mut().map(|cache| {
+            std::iter::from_fn(|| cache.pop_lfu()).collect::<Vec<_>>()
+        }).collect::<Vec<_>>();
+
+        assert!(drained.windows(2).all(|w| w[0] == w[1]));
+    }
+
+    #[test]
+    fn clone_from_matches_clone() {
+        let mut source = LfuCache::new(20);
+
+        for i in 0..300u64 {
+            let key = i * 13 % 29;
+            if i % 3 == 0 {
+                source.get(&key);
+            } else {
+                source.insert(key, format!("v{i}"));
+            }
+        }
+        for key in (0..29).step_by(4) { source.set_priority(&key, 3); }
+
+        // A destination with more queues and entries than the source, some of 
+        // them sharing keys with it, and boosted differently.
+        let mut dest = LfuCache::new(40);
+
+        for i in 0..40u64 {
+            for _ in 0..i % 9 {
+                dest.insert(i + 10, "old".to_string());
+            }
+        }
+        for key in (10..50).step_by(3) { dest.set_priority(&key, 6); }
+
+        let mut cloned = source.clone();
+
+        dest.clone_from(&source);
+        assert_eq!(dest.len(), source.len());
+
+        for other in [&cloned, &dest] {
+            assert!(other.eviction_order().eq(source.eviction_order()));
+            assert!(source.iter().all(|(key, _)| {
+                other.priority(key) == source.priority(key)
+            }));
+        }
+        assert_same_behavior(&mut [&mut source, &mut cloned, &mut dest]);
+    }
+
+    #[test]
+    fn clone_from_grows_destination() {
+        let mut source = LfuCache::new(10);
+
+        for i in 0..10u64 {
+            for _ in 0..=i {
+                source.insert(i, i.to_string());
+            }
+        }
+        let mut dest   = LfuCache::new(1);
+        let mut cloned = source.clone();
+
+        dest.insert(3, "three".to_string());
+        dest.clone_from(&source);
+        assert_same_behavior(&mut [&mut source, &mut cloned, &mut dest]);
+    }
+
+    #[test]
+    fn values_in_eviction_order() {
+        let mut cache = LfuCache::new(8);
+
+        for i in 0..40u32 {
+            let key = i * 5 % 11;
+            if i % 3 == 0 {
+                cache.get(&key);
+            } else {
+                cache.insert(key, key * 100);
+            }
+        }
+        let keys   = cache.eviction_order().copied().collect::<Vec<_>>();
+        let values = cache.values_lfu_first().copied().collect::<Vec<_>>();
+
+        assert_eq!(values, keys.iter().map(|k| k * 100).collect::<Vec<_>>());
+
+        let mut clone  = cache.clone();
+        let     popped = std::iter::from_fn(|| clone.pop_lfu())
+                             .map(|(_, v)| v)
+                             .collect::<Vec<_>>();
+
+        assert_eq!(values, popped);
+
+        for value in cache.values_lfu_first_mut() {
+            *value += 1;
+        }
+        assert_eq!(cache.values_lfu_first().copied().collect::<Vec<_>>(), 
+                   values.iter().map(|v| v + 1).collect::<Vec<_>>());
+        assert_eq!(cache.eviction_order().copied().collect::<Vec<_>>(), keys);
+    }
+
+    #[test]
+    fn reports_skip_emptied_queues() {
+        let mut cache = LfuCache::new(4);
+
+        for key in 0..4 { cache.insert(key, ()); }
+        cache.get(&3);
+
+        // Shrinking empties the queue of frequency 1, which is kept.
+        cache.set_capacity(1);
+
+        assert!(cache.peek_bucket(1).is_none());
+        assert_eq!(cache.frequency_histogram(), [(2, 1)]);
+        assert!(cache.dump_state().buckets.iter().map(|b| b.frequency)
+                                                 .eq([2]));
+    }
+
+    #[test]
+    fn dump_state_matches_cache() {
+        let mut cache = LfuCache::new(6);
+
+        cache.enable_stats();
+
+        for i in 0..30u32 {
+            let key = i * 7 % 10;
+            if i % 2 == 0 {
+                cache.get(&key);
+            } else {
+                cache.insert(key, i);
+            }
+        }
+        let state = cache.dump_state();
+
+        assert_eq!(state.capacity, 6);
+        assert_eq!(state.len, cache.len());
+        assert_eq!(state.buckets.iter()
+                                .map(|b| (b.frequency, b.keys.len()))
+                                .collect::<Vec<_>>(),
+                   cache.frequency_histogram());
+        assert_eq!(state.buckets.iter()
+                                .flat_map(|b| b.keys.iter())
+                                .collect::<Vec<_>>(),
+                   cache.eviction_order().collect::<Vec<_>>());
+
+        let stats = state.stats.unwrap();
+
+        assert_eq!(stats.hits + stats.misses, 15);
+        assert_eq!(stats.inserts + stats.updates, 15);
+        assert_eq!(stats.inserts - stats.evictions, cache.len() as u64);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&state).unwrap();
+
+            assert_eq!(json["len"], cache.len());
+            assert_eq!(json["buckets"][0]["frequency"], 
+                       state.buckets[0].frequency);
+            assert_eq!(serde_json::from_value::<CacheState<u32>>(json).unwrap(), 
+                       state);
+        }
+    }
+
+    #[cfg(feature = "debug-audit")]
+    #[test]
+    fn audit_history_of_key() {
+        let mut cache = LfuCache::new(2);
+
+        cache.enable_audit_log(64);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.get(&"a");
+        cache.insert("a", 3);
+        cache.get(&"b");
+        cache.get(&"b");
+        cache.get(&"b");
+        cache.insert("c", 4);
+        cache.get(&"a");
+        cache.insert("a", 5);
+        cache.remove(&"a");
+
+        let history = cache.audit_for(&"a").map(|rec| rec.event.clone())
+                           .collect::<Vec<_>>();
+        assert_eq!(history, [
+            AuditEvent::Inserted { key: "a" },
+            AuditEvent::Hit      { key: "a" },
+            AuditEvent::Updated  { key: "a" },
+            AuditEvent::Evicted  { victim: "a", 
+                                   reason: EvictionReason::Capacity },
+            AuditEvent::Inserted { key: "a" },
+            AuditEvent::Removed  { key: "a" },
+        ]);
+        // Sequence numbers count every event, so a's records have gaps where
+        // other keys' events went.
+        let seqs = cache.audit_for(&"a").map(|rec| rec.seq).collect::<Vec<_>>();
+        assert_eq!(seqs, [0, 2, 3, 7, 10, 11]);
+    }
+
+    #[cfg(feature = "debug-audit")]
+    #[test]
+    fn audit_log_is_bounded() {
+        let mut cache = LfuCache::new(4);
+
+        cache.enable_audit_log(3);
+
+        for i in 0..10 {
+            cache.insert(i, i);
+        }
+        cache.clear();
+
+        let log = cache.audit_log().cloned().collect::<Vec<_>>();
+        assert_eq!(log, [
+            AuditRecord { seq: 14, event: AuditEvent::Evicted { 
+                victim : 5, 
+                reason : EvictionReason::Capacity,
+            } },
+            AuditRecord { seq: 15, event: AuditEvent::Inserted { key: 9 } },
+            AuditRecord { seq: 16, event: AuditEvent::Cleared },
+        ]);
+        assert_eq!(cache.audit_for(&3).count(), 1);
+    }
+
+    #[test]
+    fn observed_key_wins_admission() {
+        // The hasher is seeded, so that no run's sketch has 11 share its 
+        // counters with the keys seen before it.
+        let mut cache = LfuCache::with_seed(4, 7);
+
+        cache.enable_admission();
+        cache.enable_stats();
+
+        for key in 0..4 {
+            cache.insert(key, key);
+        }
+        // 10 isn't cached, but it's been seen a few times, so it's allowed to
+        // evict the coldest item. 11 hasn't, so it's turned away.
+        for _ in 0..3 {
+            cache.observe(&10);
+        }
+        cache.insert(10, 10);
+        cache.insert(11, 11);
+
+        assert_eq!(cache.get(&10), Some(&10));
+        assert_eq!(cache.get(&11), None);
+        assert_eq!(cache.get(&0), None);
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.stats().unwrap().rejections, 1);
+
+        // Observing a cached key bumps its frequency.
+        cache.observe(&1);
+        assert_eq!(cache.eviction_order().last(), Some(&1));
+    }
+
+    #[test]
+    fn observe_without_admission() {
+        let mut cache = LfuCache::new(2);
+
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        cache.observe(&3);
+        cache.observe(&1);
+        cache.insert(3, 3);
+
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), [&3, &1]);
+    }
+
+    #[test]
+    fn probabilistic_increments_keep_order() {
+        // Keys 0..10 are hot, 10..20 warm and 20..30 cold.
+        let run = || {
+            let mut cache = LfuCacheBuilder::new(30)
+                .hasher(SeededState::new(1))
+                .probabilistic_increments(10)
+                .rng_seed(42)
+                .build();
+
+            for key in 0..30 { cache.insert(key, key); }
+
+            for round in 0..1000 {
+                for key in 0..30 {
+                    let accesses = match key { 
+                        0..10  => 1000, 
+                        10..20 => 100, 
+                        _      => 10,
+                    };
+                    if round < accesses { cache.get(&key); }
+                }
+            }
+            cache
+        };
+        let cache = run();
+        let freqs = (0..30).map(|key| cache.frequency(&key).unwrap())
+                           .collect::<Vec<_>>();
+
+        let (hot, rest)  = freqs.split_at(10);
+        let (warm, cold) = rest.split_at(10);
+
+        assert!(hot.iter().min() > warm.iter().max());
+        assert!(warm.iter().min() > cold.iter().max());
+
+        // Exact counting would have made 11,100 promotions.
+        assert!(freqs.iter().map(|f| f - 1).sum::<usize>() < 500);
+
+        // The same seed makes the same choices.
+        assert_eq!(run().frequency_histogram(), cache.frequency_histogram());
+    }
+
+    #[test]
+    fn loader_gets_key_only_on_miss() {
+        use std::cell::Cell;
+
+        thread_local! { static CLONES: Cell<usize> = const { Cell::new(0) }; }
+
+        #[derive(PartialEq, Eq, Hash, Debug)]
+        struct Key(String);
+
+        impl Clone for Key {
+            fn clone(&self) -> Self {
+                CLONES.with(|n| n.set(n.get() + 1));
+                Key(self.0.clone())
+            }
+        }
+        let key   = |s: &str| Key(s.to_string());
+        let mut cache = LfuCache::new(2);
+        let mut loads = 0;
+
+        assert_eq!(cache.get_or_insert_with_key(key("a"), |k| {
+            loads += 1;
+            k.0.len()
+        }), &1);
+        let clones = CLONES.with(Cell::get);
+
+        assert_eq!(cache.get_or_insert_with_key(key("a"), |_| unreachable!()), 
+                   &1);
+        assert_eq!(CLONES.with(Cell::get), clones);
+
+        cache.insert(key("bb"), 2);
+        assert_eq!(cache.get_or_insert_with(key("ccc"), || 3), &3);
+        assert_eq!(loads, 1);
+
+        // "bb" had the lowest frequency, so it made room for "ccc".
+        assert_eq!(cache.get(&key("bb")), None);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), 
+                   [&key("ccc"), &key("a")]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn loader_needs_capacity() {
+        LfuCache::new(0).get_or_insert_with(1, || 1);
+    }
+
+    #[test]
+    fn failed_load_leaves_no_trace() {
+        let mut cache = LfuCache::with_seed(2, 1);
+        let mut fresh = LfuCache::with_seed(2, 1);
+
+        for cache in [&mut cache, &mut fresh] {
+            cache.insert(1, 1);
+            cache.insert(2, 2);
+            cache.get(&2);
+        }
+        // A full cache, so a successful load would evict key 1.
+        assert_eq!(cache.get_or_try_insert_with(3, || Err("offline")), 
+                   Err("offline"));
+        assert_eq!(cache.dump_state(), fresh.dump_state());
+
+        let loaded = cache.get_or_try_insert_with(3, || Ok::<_, ()>(3));
+
+        assert_eq!(loaded, Ok(&3));
+        fresh.insert(3, 3);
+        assert_eq!(cache.dump_state(), fresh.dump_state());
+
+        // Hits don't call the loader.
+        assert_eq!(cache.get_or_try_insert_with(3, || Err(())), Ok(&3));
+        assert_eq!(cache.frequency(&3), Some(2));
+    }
+
+    #[test]
+    fn batch_loader_is_called_once_with_the_misses() {
+        let mut cache = LfuCacheBuilder::new(3).stats().build();
+        let mut calls = Vec::new();
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+
+        let values = cache.get_or_insert_with_many(vec![3, 1, 4, 3, 5, 1], 
+                                                   |keys| {
+            calls.push(keys.to_vec());
+            keys.iter().filter(|&&key| key != 5).map(|&key| (key, key * 10))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(values, [Some(&30), Some(&10), Some(&40), Some(&30), None,
+                            Some(&10)]);
+        assert_eq!(calls, [vec![3, 4, 5]]);
+
+        // Repeated keys were looked up once, and loading counted nothing.
+        let stats = cache.stats().unwrap();
+
+        assert_eq!((stats.hits, stats.misses), (1, 3));
+        assert_eq!(cache.frequency(&1), Some(2));
+        assert_eq!(cache.frequency(&3), Some(1));
+
+        // Key 5 made no entry, and 2, the LFU item, made room for the rest.
+        assert!(!cache.contains_key(&5) && !cache.contains_key(&2));
+        assert_eq!(cache.len(), 3);
+        cache.check_invariants();
+
+        // With nothing missing, the loader isn't called.
+        let values = cache.get_or_insert_with_many(vec![4, 1], |_| {
+            unreachable!() as Vec<_>
+        });
+        assert_eq!(values, [Some(&40), Some(&10)]);
+
+        // A pair repeated, or for a key that's cached, replaces the value
+        // and leaves the frequency be.
+        let values = cache.get_or_insert_with_many(vec![6], |_| {
+            [(6, 60), (6, 61), (4, 41)]
+        });
+        assert_eq!(values, [Some(&61)]);
+        assert_eq!(cache.frequency(&6), Some(1));
+        assert_eq!(cache.peek_value(&4), Some(&41));
+        assert_eq!(cache.frequency(&4), Some(2));
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn counters_through_mut_reference() {
+        let mut cache = LfuCache::new(2);
+
+        for key in ["a", "b", "a", "a", "c", "b"] {
+            *cache.get_mut_or_insert_with(key, || 0) += 1;
+        }
+        // "b" was evicted for "c", then "c" for "b", so it restarted at 1.
+        assert_eq!(cache.get(&"a"), Some(&3));
+        assert_eq!(cache.get(&"b"), Some(&1));
+        assert_eq!(cache.get(&"c"), None);
+        assert_eq!(cache.frequency(&"a"), Some(4));
+    }
+
+    #[test]
+    fn word_count() {
+        let text  = "the cat and the dog and the bird saw the fox and the owl";
+        let mut cache = LfuCache::with_seed(3, 1);
+
+        cache.enable_stats();
+
+        for word in text.split(' ') {
+            *cache.get_or_default(word) += 1;
+        }
+        // The rare words kept evicting each other from the one free slot.
+        let mut counts = cache.iter().map(|(k, v)| (*k, *v))
+                                     .collect::<Vec<_>>();
+        counts.sort();
+
+        assert_eq!(counts, [("and", 3), ("owl", 1), ("the", 5)]);
+        assert_eq!(cache.stats().unwrap().evictions, 5);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn mutate_in_eviction_order() {
+        let mut cache = LfuCache::new(8);
+
+        for key in 0..8 { cache.insert(key, Vec::new()); }
+        for key in 0..8 {
+            for _ in 0..key % 3 { cache.get(&key); }
+        }
+        let mut visited = Vec::new();
+
+        for (key, value, freq) in cache.iter_mut_by_eviction_order() {
+            assert_eq!(freq, key % 3 + 1);
+            value.push(visited.len());
+            visited.push(*key);
+        }
+        assert_eq!(visited, [0, 3, 6, 1, 4, 7, 2, 5]);
+        assert_eq!(cache.eviction_order().copied().collect::<Vec<_>>(), 
+                   visited);
+
+        for (i, key) in visited.iter().enumerate() {
+            assert_eq!(cache.frequency(key), Some(key % 3 + 1));
+            assert_eq!(cache.map[key].value, [i]);
+        }
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn invalidate_by_tag() {
+        let mut cache = LfuCache::new(4);
+
+        cache.insert_with_tags(1, "a", ["tenant:1", "dataset:v3"]);
+        cache.insert_with_tags(2, "b", ["tenant:2", "dataset:v3"]);
+        cache.insert_with_tags(3, "c", ["tenant:1"]);
+        cache.insert(4, "d");
+
+        assert_eq!(cache.invalidate_tag("dataset:v3"), 2);
+        assert_eq!(cache.invalidate_tag("dataset:v3"), 0);
+        assert_eq!(cache.iter().count(), 2);
+        assert_eq!(cache.tags.as_ref().unwrap().len(), 1);
+
+        // Retagging replaces the old tags.
+        cache.insert_with_tags(3, "c", ["tenant:3"]);
+        assert_eq!(cache.invalidate_tag("tenant:1"), 0);
+        assert_eq!(cache.invalidate_tag("tenant:3"), 1);
+        assert_eq!(cache.get(&4), Some(&"d"));
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn boosted_item_outlives_its_peers() {
+        let mut cache = LfuCache::new(4);
+
+        // Key 0 costs more to recreate, but is used no more than the rest.
+        cache.insert_with_priority(0, "costly", 3);
+        for key in 1..4 { cache.insert(key, "cheap"); }
+
+        for key in 0..4 { cache.get(&key); }
+        assert_eq!(cache.frequency(&0), Some(2));
+        assert_eq!(cache.priority(&0), Some(3));
+        assert_eq!(cache.frequency_histogram(), [(2, 3), (5, 1)]);
+        cache.check_invariants();
+
+        // A stream of newcomers takes the cheap keys' places, but not its.
+        for key in 4..10 {
+            cache.insert(key, "new");
+            cache.get(&key);
+        }
+        assert!(cache.contains_key(&0));
+        assert_eq!(cache.eviction_order().last(), Some(&0));
+
+        // Without the boost it's one of the pack again, queued as if it had
+        // just been used.
+        assert!(cache.set_priority(&0, 0));
+        cache.check_invariants();
+        assert_eq!(cache.frequency_histogram(), [(2, 4)]);
+        assert!(cache.eviction_order().eq([&7, &8, &9, &0]));
+        assert!(!cache.set_priority(&1, 1));
+        assert_eq!(cache.priority(&1), None);
+
+        // The boost is added to the age under LFU-DA too.
+        let mut cache = LfuCacheBuilder::new(2).policy(Policy::LfuDa).build();
+
+        cache.insert(1, ());
+        cache.insert(2, ());
+        cache.set_priority(&1, 2);
+        cache.insert(3, ());
+        assert!(cache.contains_key(&1) && !cache.contains_key(&2));
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn eviction_drops_tags() {
+        let mut cache = LfuCache::new(2);
+
+        for key in 0..100 {
+            cache.insert_with_tags(key, key, [format!("key:{key}"), 
+                                              "all".to_string()]);
+            cache.check_invariants();
+        }
+        cache.pop_lfu();
+
+        // Only the last item is left.
+        assert_eq!(cache.tags.as_ref().unwrap().len(), 2);
+        assert_eq!(cache.invalidate_tag("key:98"), 0);
+        assert_eq!(cache.invalidate_tag("all"), 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn invalidate_by_prefix() {
+        let mut cache = LfuCache::new(8);
+
+        cache.insert("user/1/name".to_string(), 1);
+        cache.enable_prefix_index();
+
+        for key in ["user/1/profile", "user/12/profile", "user/2/profile", 
+                    "user/2/avatar", "user", "group/1"] {
+            cache.insert(key.to_string(), 0);
+        }
+        cache.check_invariants();
+
+        assert_eq!(cache.invalidate_prefix("user/1"), 3);
+        assert_eq!(cache.invalidate_prefix("user/12/"), 0);
+        assert_eq!(cache.invalidate_prefix("user/2/"), 2);
+        assert_eq!(cache.len(), 2);
+        cache.check_invariants();
+
+        let mut keys = cache.iter().map(|(k, _)| k.as_str())
+                                   .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, ["group/1", "user"]);
+
+        // Evicted keys leave the index.
+        for i in 0..20 {
+            cache.insert(format!("user/{i}"), i);
+            cache.check_invariants();
+        }
+        let in_cache = cache.iter().filter(|(k, _)| k.starts_with("user/"))
+                                   .count();
+        assert_eq!(cache.invalidate_prefix("user/"), in_cache);
+        assert_eq!(cache.invalidate_prefix(""), cache.len());
+        assert!(cache.is_empty());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn retain_whitelisted_keys() {
+        let fill = || {
+            let mut cache = LfuCache::new(10);
+
+            for key in 0..10 {
+                cache.insert(key, key);
+                for _ in 0..key % 4 { cache.get(&key); }
+            }
+            cache
+        };
+        let mut cache = fill();
+        assert_eq!(cache.retain_keys_in(&(0..10).collect::<HashSet<_>>()), 0);
+        assert_eq!(cache.dump_state(), fill().dump_state());
+
+        assert_eq!(cache.retain_keys_in(&HashSet::from([1, 2, 5, 8, 42])), 6);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), 
+                   [&8, &1, &5, &2]);
+        assert_eq!(cache.frequency(&2), Some(3));
+        cache.check_invariants();
+
+        assert_eq!(cache.retain_keys_in(&HashSet::new()), 4);
+        assert!(cache.is_empty());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn bulk_load_matches_inserts() {
+        let entries = (0..200u64).map(|i| (i * 7 % 200, i, i as usize * 13 % 9))
+                                 .collect::<Vec<_>>();
+        let mut loaded   = LfuCache::bulk_load(300, entries.clone());
+        let mut inserted = LfuCache::new(300);
+
+        for (key, value, freq) in entries {
+            inserted.insert_with_frequency(key, value, freq);
+        }
+        loaded.check_invariants();
+        assert_eq!(loaded.dump_state(), inserted.dump_state());
+
+        for i in 0..2000u64 {
+            let key = i * 31 % 250;
+            if i % 3 == 0 {
+                assert_eq!(loaded.get(&key), inserted.get(&key));
+            } else {
+                loaded.insert(key, i);
+                inserted.insert(key, i);
+            }
+        }
+        assert!(loaded.eviction_order().eq(inserted.eviction_order()));
+
+        // Over capacity, it's the same as loading everything and then popping
+        // down to the capacity, which inserting in turn isn't.
+        let entries = (0..200u64).map(|i| (i * 7 % 200, i, i as usize * 13 % 9))
+                                 .collect::<Vec<_>>();
+        let loaded       = LfuCache::bulk_load(50, entries.clone());
+        let mut inserted = LfuCache::new(200);
+
+        for (key, value, freq) in entries {
+            inserted.insert_with_frequency(key, value, freq);
+        }
+        while inserted.len() > 50 { inserted.pop_lfu(); }
+        inserted.set_capacity(50);
+        loaded.check_invariants();
+        assert_eq!(loaded.dump_state(), inserted.dump_state());
+
+        let entries        = [("a", 1, 5), ("b", 2, 5), ("c", 3, 1)];
+        let loaded         = LfuCache::bulk_load(2, entries);
+        let mut one_by_one = LfuCache::new(2);
+
+        for (key, value, freq) in entries {
+            one_by_one.insert_with_frequency(key, value, freq);
+        }
+        assert_eq!(loaded.eviction_order().collect::<Vec<_>>(), [&"a", &"b"]);
+        assert_eq!(one_by_one.eviction_order().collect::<Vec<_>>(), 
+                   [&"c", &"b"]);
+    }
+
+    #[test]
+    fn bulk_load_truncates_lfu_first() {
+        let entries  = [("a", 1, 3), ("b", 2, 1), ("a", 3, 2), ("c", 4, 1), 
+                        ("d", 5, 5)];
+        let mut full = LfuCache::bulk_load(4, entries);
+        let cache    = LfuCache::bulk_load(2, entries);
+
+        assert_eq!(full.get(&"a"), Some(&1));
+        assert_eq!(full.eviction_order().collect::<Vec<_>>(), 
+                   [&"b", &"c", &"a", &"d"]);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), [&"a", &"d"]);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn eviction_candidate_is_popped() {
+        let mut cache = LfuCache::new(6);
+
+        assert_eq!(cache.eviction_candidate(), None);
+
+        for i in 0..300u64 {
+            let key = i * 17 % 11;
+
+            // Few keys and frequent removals make for lots of ties.
+            match i % 5 {
+                0 => { cache.remove(&(key / 2)); },
+                1 => { cache.get(&key); },
+                _ => cache.insert(key, i),
+            }
+            let candidate = cache.eviction_candidate().copied();
+            let popped    = cache.clone().pop_lfu().map(|(k, _)| k);
+
+            assert_eq!(candidate, popped);
+
+            if let Some(key) = candidate {
+                assert!(cache.would_evict_next(&key));
+                assert!(!cache.would_evict_next(&(key + 1)));
+            }
+        }
+    }
+
+    #[test]
+    fn flush_victim_before_eviction() {
+        // (value, dirty)
+        let mut cache = LfuCache::new(3);
+
+        cache.insert("a", (1, true));
+        cache.insert("b", (2, true));
+        cache.insert("c", (3, true));
+        cache.get(&"a");
+        cache.get(&"c");
+
+        let (key, value) = cache.peek_lfu_mut().unwrap();
+        assert_eq!(key, &"b");
+        value.1 = false;
+
+        assert_eq!(cache.pop_lfu_if(|_, value| value.1), None);
+        assert_eq!(cache.frequency(&"b"), Some(1));
+        assert!(cache.would_evict_next(&"b"));
+
+        let (key, value) = cache.peek_lfu_mut().unwrap();
+        assert_eq!(key, &"b");
+        value.0 = 20;
+
+        cache.insert("d", (4, true));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.len(), 3);
+
+        cache.get(&"d");
+        assert_eq!(cache.pop_lfu_if(|key, _| key == &"a"), 
+                   Some(("a", (1, true))));
+    }
+
+    #[test]
+    fn inspect_buckets() {
+        let mut cache = LfuCache::new(8);
+
+        for key in 0..6 {
+            cache.insert(key, key * 10);
+            for _ in 0..key / 2 * 2 { cache.get(&key); }
+        }
+        // Frequencies 1, 3 and 5, two keys each.
+        assert!(cache.peek_bucket(2).is_none());
+        assert!(cache.peek_bucket(9).is_none());
+
+        let min = cache.peek_bucket(1).unwrap();
+        assert_eq!((min.frequency(), min.len()), (1, 2));
+        assert_eq!(min.iter().collect::<Vec<_>>(), [(&0, &0), (&1, &10)]);
+
+        let max = cache.peek_bucket(5).unwrap();
+        assert_eq!(max.iter().collect::<Vec<_>>(), [(&4, &40), (&5, &50)]);
+
+        let freqs = |range: std::ops::Range<_>| {
+            cache.buckets_in(range).map(|b| b.frequency()).collect::<Vec<_>>()
+        };
+        assert_eq!(freqs(2..5), [3]);
+        assert_eq!(freqs(1..6), [1, 3, 5]);
+        assert!(freqs(6..9).is_empty());
+
+        // Removing the last items with a frequency drops its bucket.
+        cache.pop_lfu();
+        cache.insert(6, 60);
+        cache.remove(&1);
+        cache.remove(&6);
+        assert!(cache.peek_bucket(1).is_none());
+        assert_eq!(cache.buckets_in(..).count(), 2);
+    }
+
+    #[test]
+    fn remove_entry_returns_stored_key() {
+        let mut cache = LfuCache::new(4);
+        let mut key   = String::with_capacity(100);
+
+        key.push_str("buffer");
+        let ptr = key.as_ptr();
+
+        cache.insert(key, 1);
+        cache.insert("other".to_string(), 2);
+        cache.get(&"buffer".to_string());
+
+        let (key, value) = cache.remove_entry(&"buffer".to_string()).unwrap();
+
+        assert_eq!((key.as_str(), value), ("buffer", 1));
+        assert_eq!((key.as_ptr(), key.capacity()), (ptr, 100));
+        assert_eq!(cache.remove_entry(&key), None);
+        assert_eq!(cache.len(), 1);
+        cache.check_invariants();
+        assert_eq!(cache.buckets_in(..).count(), 1);
+    }
+
+    #[test]
+    fn invalidate_all_is_lazy() {
+        let mut cache = LfuCache::new(50);
+
+        for key in 0..50 { cache.insert(key, (0, key)); }
+        for key in 0..10 { cache.get(&key); }
+
+        // The values are (generation, key), so stale ones are easy to spot.
+        for gen in 1..6 {
+            cache.invalidate_all();
+            assert_eq!(cache.len(), 0);
+            assert!(cache.pending_reclaim() > 0);
+            assert_eq!(cache.iter().count(), 0);
+            cache.check_invariants();
+
+            for key in (0..60).step_by(gen) {
+                assert_eq!(cache.get(&key), None);
+                assert_eq!(cache.frequency(&key), None);
+                cache.insert(key, (gen, key));
+            }
+            for key in (0..60).step_by(3) {
+                if let Some(value) = cache.get(&key) {
+                    assert_eq!(value, &(gen, key));
+                }
+                let cached = cache.frequency(&(key + 1)).is_some();
+
+                assert_eq!(cache.remove(&(key + 1)).map(|v| v.0), 
+                           cached.then_some(gen));
+            }
+            assert!(cache.iter().all(|(_, value)| value.0 == gen));
+            cache.check_invariants();
+        }
+        let pending = cache.pending_reclaim();
+
+        assert_eq!(cache.reclaim(3), 3.min(pending));
+        assert_eq!(cache.reclaim(usize::MAX), pending.saturating_sub(3));
+        assert_eq!(cache.pending_reclaim(), 0);
+        assert!(cache.retired.is_empty() || cache.reclaim(1) == 0);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn stale_writer_is_rejected() {
+        let mut cache = LfuCache::new(2);
+
+        assert_eq!(cache.insert_if_version("n", 10, 0), Ok(1));
+        assert_eq!(cache.insert_if_version("n", 10, 0), 
+                   Err(VersionMismatch { expected: 0, actual: 1 }));
+
+        // Two writers read the same version, then both try to write back.
+        let (a, va) = cache.get_versioned(&"n").map(|(v, n)| (*v, n)).unwrap();
+        let (b, vb) = cache.get_versioned(&"n").map(|(v, n)| (*v, n)).unwrap();
+
+        assert_eq!(cache.insert_if_version("n", a + 1, va), Ok(2));
+        assert_eq!(cache.insert_if_version("n", b + 2, vb), 
+                   Err(VersionMismatch { expected: 1, actual: 2 }));
+        assert_eq!(cache.get_versioned(&"n"), Some((&11, 2)));
+
+        // The version survives frequency changes but not eviction.
+        assert_eq!(cache.frequency(&"n"), Some(5));
+        assert_eq!(cache.insert_versioned("m", 0), 1);
+        assert_eq!(cache.insert_versioned("m", 1), 2);
+        cache.insert("m", 2);
+        cache.get(&"m");
+        cache.get(&"m");
+        assert_eq!(cache.insert_versioned("o", 0), 1);
+        assert_eq!(cache.get(&"n"), None);
+        assert_eq!(cache.insert_versioned("n", 0), 1);
+        assert_eq!(cache.get_versioned(&"m"), Some((&2, 3)));
+    }
+
+    #[test]
+    fn guarded_entry_survives_eviction() {
+        let mut cache = LfuCache::new(3);
+
+        for key in 0..3 { cache.insert(key, key); }
+        cache.get(&1);
+        cache.get(&2);
+
+        let guard = cache.guard(&0).unwrap();
+        assert_eq!(guard.key(), &0);
+        assert_eq!(cache.eviction_candidate(), Some(&1));
+
+        // Key 0 would have been the victim every time.
+        for key in 3..10 {
+            cache.insert(key, key);
+            assert_eq!(cache.get(&0), Some(&0));
+            cache.check_invariants();
+        }
+        let other = cache.guard(&9).unwrap().clone();
+        assert_eq!(cache.pop_lfu(), Some((2, 2)));
+        cache.insert(10, 10);
+        assert!(!cache.would_evict_next(&0));
+
+        // Everything is pinned, so new keys are turned away.
+        let _last = cache.guard(&10).unwrap();
+        cache.insert(11, 11);
+        assert_eq!(cache.get(&11), None);
+        assert_eq!(cache.pop_lfu(), None);
+
+        // The guards' keys are unpinned once they're dropped.
+        drop(guard);
+        drop(other);
+        cache.insert(11, 11);
+        assert_eq!(cache.get(&9), None);
+        assert_eq!(cache.len(), 3);
+
+        // Explicit removal unpins, and so does a loader that runs over.
+        assert_eq!(cache.remove(&10), Some(10));
+        assert!(cache.guard(&42).is_none());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn guard_on_dropped_key_unpins_on_clone_from() {
+        let mut cache  = LfuCache::new(2);
+        let mut source = LfuCache::new(2);
+
+        cache.insert(1, 1);
+        let guard = cache.guard(&1).unwrap();
+
+        source.insert(2, 2);
+        source.get(&2);
+        source.get(&2);
+        cache.clone_from(&source);
+        assert!(!cache.is_pinned(&1));
+        cache.check_invariants();
+
+        // The re-inserted key 1 isn't pinned by the old guard, so it's the 
+        // one to go rather than the frequency-3 item.
+        cache.insert(1, 1);
+        cache.insert(3, 3);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some(&2));
+        drop(guard);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn purge_unreferenced_arcs() {
+        let mut cache = LfuCache::new(8);
+        let held      = (0..6).map(Arc::new).collect::<Vec<_>>();
+
+        for (key, value) in held.iter().enumerate() {
+            cache.insert(key, value.clone());
+        }
+        cache.insert(6, Arc::new(6));
+
+        let (even, _odd): (Vec<_>, Vec<_>) = held.into_iter()
+                                                 .partition(|v| **v % 2 == 0);
+        drop(even);
+
+        assert_eq!(cache.purge_unreferenced(), 4);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), [&1, &3, &5]);
+        assert_eq!(cache.purge_unreferenced(), 0);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn evict_unreferenced_first() {
+        let mut cache = LfuCache::new(4);
+        let held      = [Arc::new(0), Arc::new(1)];
+
+        cache.enable_unreferenced_eviction();
+        cache.insert(0, held[0].clone());
+        cache.insert(1, held[1].clone());
+        cache.insert(2, Arc::new(2));
+        cache.insert(3, Arc::new(3));
+        cache.get(&2);
+
+        // Key 3 is the least used of the unreferenced items.
+        assert_eq!(cache.eviction_candidate(), Some(&3));
+        cache.insert(4, held[0].clone());
+        cache.insert(5, held[0].clone());
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), 
+                   [&0, &1, &4, &5]);
+
+        // With every item referenced, plain LFU order is back.
+        cache.insert(6, Arc::new(6));
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), 
+                   [&1, &4, &5, &6]);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn entry_ids_go_stale() {
+        let mut cache = LfuCache::new(2);
+
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        let one = cache.id_of(&1).unwrap();
+        let two = cache.id_of(&2).unwrap();
+
+        // Overwriting keeps the id, and get_by_id() counts like get().
+        cache.insert(1, "c");
+        assert_eq!(cache.get_by_id(one), Some(&"c"));
+        assert_eq!(cache.peek_by_id(one), Some(&"c"));
+        assert_eq!(cache.frequency(&1), Some(3));
+
+        // Key 2 is evicted, and comes back as a different item, likely in 
+        // the same bucket.
+        cache.insert(3, "d");
+        assert_eq!(cache.get_by_id(two), None);
+        cache.remove(&3);
+        cache.insert(2, "e");
+        assert_eq!(cache.peek_by_id(two), None);
+        assert_eq!(cache.get_by_id(two), None);
+        assert_eq!(cache.frequency(&2), Some(1));
+
+        cache.invalidate_all();
+        cache.insert(1, "f");
+        assert_eq!(cache.peek_by_id(one), None);
+        assert_ne!(cache.id_of(&1), Some(one));
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn entry_ids_outlive_growth() {
+        let mut cache = LfuCache::new(2);
+
+        cache.insert(0, 0);
+        cache.get(&0);
+        let id       = cache.id_of(&0).unwrap();
+        let capacity = cache.map.capacity();
+
+        cache.set_capacity(1000);
+        for key in 1..1000 { cache.insert(key, key); }
+
+        assert!(cache.map.capacity() > capacity);
+        assert_eq!(cache.get_by_id(id), Some(&0));
+        assert_eq!(cache.frequency(&0), Some(3));
+        assert_eq!(cache.id_of(&0).map(|moved| moved.stamp), Some(id.stamp));
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn peek_lfu_mut_skips_referenced_items() {
+        let mut cache = LfuCache::new(4);
+        let held      = Arc::new(0);
+
+        cache.enable_unreferenced_eviction();
+        cache.insert(0, held.clone());
+        cache.insert(1, Arc::new(1));
+        cache.get(&1);
+
+        let candidate = cache.eviction_candidate().copied();
+        let (key, _)  = cache.peek_lfu_mut().unwrap();
+        assert_eq!(Some(*key), candidate);
+        assert_eq!(*key, 1);
+        assert_eq!(cache.pop_lfu().map(|(key, _)| key), candidate);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn refresh_ahead_marks_once() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCacheBuilder::new(4)
+                            .clock(clock.clone())
+                            .refresh_after(Duration::from_secs(10))
+                            .build();
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        clock.advance(Duration::from_secs(5));
+        cache.get(&"a");
+        assert!(cache.take_refresh_candidates().is_empty());
+
+        // Due keys are still served, and marked on their first lookup only.
+        clock.advance(Duration::from_secs(5));
+        for _ in 0..3 { assert_eq!(cache.get(&"a"), Some(&1)); }
+        assert_eq!(cache.take_refresh_candidates(), ["a"]);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert!(cache.take_refresh_candidates().is_empty());
+
+        // A key refreshed before its candidates are taken isn't handed out.
+        cache.get(&"b");
+        assert_eq!(cache.replace_value(&"b", 20), Some(2));
+        assert!(cache.take_refresh_candidates().is_empty());
+
+        // Refreshing starts the staleness period over, without an access.
+        assert_eq!(cache.replace_value(&"a", 10), Some(1));
+        assert_eq!(cache.frequency(&"a"), Some(6));
+        clock.advance(Duration::from_secs(9));
+        cache.get(&"a");
+        assert!(cache.take_refresh_candidates().is_empty());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"a"), Some(&10));
+        assert_eq!(cache.take_refresh_candidates(), ["a"]);
+        assert_eq!(cache.replace_value(&"c", 3), None);
+    }
+
+    #[test]
+    fn expired_items_are_dropped() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(4);
+
+        cache.set_clock(clock.clone());
+        cache.insert_with_ttl(1, 1, Duration::from_secs(10));
+        cache.insert_with_ttl(2, 2, Duration::from_secs(20));
+        cache.insert_with_ttl(3, 3, Duration::from_secs(20));
+        cache.insert(4, 4);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.len(), 3);
+
+        // A plain insert clears the TTL; a refresh restarts it.
+        cache.insert(2, 2);
+        clock.advance(Duration::from_secs(5));
+        cache.replace_value(&3, 30);
+        clock.advance(Duration::from_secs(15));
+        assert_eq!(cache.get(&3), Some(&30));
+        assert_eq!(cache.purge_expired(), 0);
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), [&4, &2]);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn replaced_values_are_counted_with_or_without_a_ttl() {
+        let mut cache = LfuCache::new(4);
+        let rx        = cache.eviction_receiver(8);
+
+        cache.enable_stats();
+        cache.insert(1, 1);
+        cache.insert_with_ttl(2, 2, Duration::from_secs(10));
+
+        assert_eq!(cache.replace_value(&1, 10), Some(1));
+        assert_eq!(cache.replace_value(&2, 20), Some(2));
+        assert_eq!(cache.stats().map(|stats| stats.updates), Some(2));
+
+        // The old values were handed back, so they aren't sent as well.
+        assert!(rx.try_recv().is_none());
+
+        cache.insert_with_frequency(2, 21, 3);
+        assert_eq!(rx.try_recv().map(|msg| (msg.value, msg.reason)),
+                   Some((20, EvictionReason::Replaced)));
+    }
+
+    #[cfg(feature = "debug-audit")]
+    #[test]
+    fn replaced_values_are_audited() {
+        let mut cache = LfuCache::new(4);
+
+        cache.enable_audit_log(8);
+        cache.insert_with_ttl("a", 1, Duration::from_secs(10));
+        cache.replace_value(&"a", 2);
+
+        let history = cache.audit_for(&"a").map(|rec| rec.event.clone())
+                           .collect::<Vec<_>>();
+        assert_eq!(history, [AuditEvent::Inserted { key: "a" },
+                             AuditEvent::Updated  { key: "a" }]);
+    }
+
+    #[test]
+    fn scan_covers_items_kept_throughout() {
+        let mut cache = LfuCache::with_seed(100, 7);
+
+        for key in 0..100 { cache.insert(key, key * 2); }
+
+        let mut kept   = (0..100).collect::<HashSet<_>>();
+        let mut seen   = HashSet::new();
+        let mut cursor = ScanCursor::new();
+        let mut pages  = 0;
+
+        while !cursor.is_done() {
+            let (page, next) = cache.scan(cursor, 7);
+
+            assert!(page.iter().all(|&(key, value)| value == key * 2));
+            seen.extend(page.into_iter().map(|(key, _)| key));
+            cursor = next;
+            pages += 1;
+
+            // Keep the cache busy between pages: new keys evict old ones.
+            for key in (0..100).step_by(3) { cache.get(&key); }
+            cache.insert(1000 + pages * 2, (1000 + pages * 2) * 2);
+            cache.insert(1001 + pages * 2, (1001 + pages * 2) * 2);
+            cache.remove(&(pages * 5));
+            kept.retain(|key| cache.contains_key(key));
+        }
+        assert!(pages <= 100 / 7 + 10);
+        assert!(kept.len() > 30);
+        assert!(kept.is_subset(&seen));
+        assert_eq!(cache.scan(cursor, 7).0, []);
+
+        let empty = LfuCache::<u32, u32>::new(4);
+        let (page, cursor) = empty.scan(ScanCursor::new(), 10);
+        assert!(page.is_empty() && cursor.is_done());
+    }
+
+    #[test]
+    fn maintenance_passes_over_keys_dropped_already() {
+        let mut cache = LfuCache::new(100);
+
+        for key in 0..100 { cache.insert(key, key); }
+        cache.invalidate_all();
+
+        // The lookups drop the first 64 before they're reclaimed.
+        for key in 0..64 { cache.get(&key); }
+
+        let report = cache.run_pending_tasks();
+
+        assert_eq!(report.reclaimed, 36);
+        assert!(!report.work_remains);
+        assert_eq!(cache.pending_reclaim(), 0);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn maintenance_converges_in_small_steps() {
+        let clock = ManualClock::new();
+        let secs  = Duration::from_secs;
+        let setup = || {
+            let mut cache = LfuCacheBuilder::new(300)
+                                .hasher(SeededState::new(1))
+                                .clock(clock.clone())
+                                .build();
+            for key in 0..150 { cache.insert(key, key); }
+            cache.invalidate_all();
+
+            // Each insert reclaims a couple of items, which leaves some.
+            for key in 150..200 {
+                match key % 3 {
+                    0 => cache.insert_with_ttl(key, key, secs(5)),
+                    1 => cache.insert_with_ttl(key, key, secs(50)),
+                    _ => cache.insert(key, key),
+                }
+            }
+            // Rewrites move their entries in the expiry index.
+            for key in (150..200).step_by(9) {
+                cache.insert_with_ttl(key, key, secs(1));
+            }
+            cache
+        };
+        let mut whole = setup();
+        let mut cache = setup();
+        clock.advance(Duration::from_secs(10));
+
+        let report    = whole.run_maintenance(MaintenanceBudget::unlimited());
+        assert!(!report.work_remains);
+        assert_eq!(whole.pending_reclaim(), 0);
+
+        let mut steps = 0;
+        let mut total = MaintenanceReport::default();
+        let budget    = MaintenanceBudget::unlimited().max_items(5);
+
+        loop {
+            let report = cache.run_maintenance(budget);
+            total.reclaimed += report.reclaimed;
+            total.expired   += report.expired;
+            steps += 1;
+            cache.check_invariants();
+            if !report.work_remains { break; }
+        }
+        assert!(steps > 10);
+        assert_eq!((total.reclaimed, total.expired), 
+                   (report.reclaimed, report.expired));
+        assert_eq!(cache.pending_reclaim(), 0);
+        assert!(cache.eviction_order().eq(whole.eviction_order()));
+        assert_eq!(cache.purge_expired(), 0);
+
+        clock.advance(Duration::from_secs(50));
+        assert_eq!(cache.purge_expired(), 17);
+        assert_eq!(cache.len(), 16);
+    }
+
+    #[test]
+    fn buckets_drain_whole() {
+        let mut cache = LfuCache::new(12);
+
+        for key in 0..12 {
+            cache.insert(key, key * 10);
+            for _ in 0..key % 3 { cache.get(&key); }
+        }
+        let _pin = cache.guard(&4).unwrap();
+
+        // The never-accessed items, in the order they'd be evicted in.
+        assert_eq!(cache.drain_frequency(1), 
+                   [(0, 0), (3, 30), (6, 60), (9, 90)]);
+        assert_eq!(cache.frequency_histogram(), [(2, 4), (3, 4)]);
+        cache.check_invariants();
+
+        // A bucket between others, pinned item and all.
+        cache.insert(20, 200);
+        for _ in 0..2 { cache.get(&20); }
+        cache.insert(21, 210);
+
+        assert_eq!(cache.drain_frequency(2), 
+                   [(1, 10), (4, 40), (7, 70), (10, 100)]);
+        assert_eq!(cache.frequency_histogram(), [(1, 1), (3, 5)]);
+        assert!(!cache.is_pinned(&4));
+        assert!(cache.eviction_order().eq(&[21, 2, 5, 8, 11, 20]));
+        cache.check_invariants();
+
+        // Counts no item has leave the cache alone.
+        assert!(cache.drain_frequency(2).is_empty());
+        assert!(cache.drain_frequency(0).is_empty());
+        assert!(cache.drain_frequency(7).is_empty());
+        assert_eq!(cache.len(), 6);
+
+        // The cache fills up again from where it was.
+        for key in 30..36 { cache.insert(key, key); }
+        assert_eq!(cache.len(), 12);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn visitors_see_every_item_in_eviction_order() {
+        let mut cache = LfuCache::new(10);
+
+        for key in 0..10 {
+            cache.insert(key, key);
+            for _ in 0..key % 4 { cache.get(&key); }
+        }
+        let mut total = 0;
+        let mut keys  = Vec::new();
+
+        cache.for_each(|&key, &value, freq| {
+            assert_eq!(key, value);
+            total += freq;
+            keys.push(key);
+        });
+        let histogram = cache.frequency_histogram();
+
+        assert_eq!(total, histogram.iter().map(|(freq, n)| freq * n)
+                                          .sum::<usize>());
+        assert!(keys.iter().eq(cache.eviction_order()));
+
+        // Changing the values leaves the counts and the order alone.
+        let before = cache.entries().map(|(&key, _, freq)| (key, freq))
+                                    .collect::<Vec<_>>();
+        cache.for_each_mut(|&key, value, freq| *value = key * 100 + freq);
+
+        assert!(cache.entries().map(|(&key, _, freq)| (key, freq))
+                               .eq(before.iter().copied()));
+        assert_eq!(cache.peek_value(&3), Some(&304));
+        assert_eq!(cache.frequency_histogram(), histogram);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn cursor_edits_keep_the_queues_whole() {
+        let mut rng = crate::rng::Rng::new(733);
+
+        for round in 0..300 {
+            let policy = [Policy::Lfu, Policy::LfuDa][round % 2];
+            let mut cache = LfuCacheBuilder::new(40).policy(policy).build();
+
+            for _ in 0..120 {
+                let key = rng.next_u64() % 50;
+
+                if cache.get(&key).is_none() { cache.insert(key, key); }
+            }
+            // Walks that don't bump come to the items in eviction order.
+            let bumps       = round % 3 != 0;
+            let order       = cache.eviction_order().copied()
+                                                   .collect::<Vec<_>>();
+            let mut seen    = Vec::new();
+            let mut removed = Vec::new();
+            let mut cursor  = cache.cursor_entries_mut();
+
+            for _ in 0..100 {
+                let Some((&key, _, freq)) = cursor.current() else { break };
+
+                assert_eq!(cursor.cache.frequency(&key), Some(freq));
+                seen.push(key);
+
+                match rng.next_u64() % if bumps { 4 } else { 3 } {
+                    0 => cursor.move_next(),
+                    1 => {
+                        if let Some((_, value, _)) = cursor.current() {
+                            *value += 1000;
+                        }
+                        cursor.move_next();
+                    },
+                    2 => removed.push(cursor.remove_current().unwrap().0),
+                    _ => {
+                        cursor.bump_current();
+                        assert_eq!(cursor.cache.frequency(&key), 
+                                   Some(freq + 1));
+                    },
+                }
+                cursor.cache.check_invariants();
+
+                if rng.one_in(30) { break; }
+            }
+            cache.check_invariants();
+
+            assert_eq!(cache.len() + removed.len(), order.len());
+            assert!(removed.iter().all(|key| !cache.contains_key(key)));
+            assert!(cache.entries().all(|(key, value, _)| {
+                value % 1000 == *key
+            }));
+
+            if !bumps {
+                let left = order.iter().filter(|key| !removed.contains(key));
+
+                assert_eq!(seen, order[..seen.len()]);
+                assert!(cache.eviction_order().eq(left));
+            }
+        }
+        // A cursor on an empty cache has nothing to do.
+        let mut cache  = LfuCache::<u32, u32>::new(4);
+        let mut cursor = cache.cursor_entries_mut();
+
+        cursor.move_next();
+        cursor.bump_current();
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(cursor.current(), None);
+    }
+
+    #[test]
+    fn refused_growth_leaves_the_cache_as_it_was() {
+        let mut cache = LfuCache::new(3);
+
+        // The map is sized for 3 items, so raising the capacity lets it fill
+        // up before the cache does.
+        cache.set_capacity(100);
+        cache.enable_stats();
+
+        let mut key = 0;
+
+        while cache.map.len() < cache.map.capacity() {
+            assert_eq!(cache.try_insert(key, key), Ok(InsertResult::Inserted));
+            for _ in 0..key { cache.get(&key); }
+            key += 1;
+        }
+        let state = |cache: &LfuCache<u32, u32>| {
+            let entries = cache.entries().map(|(&key, &value, freq)| {
+                (key, value, freq)
+            });
+            (entries.collect::<Vec<_>>(), cache.stats().copied())
+        };
+        let before = state(&cache);
+
+        crate::hashed::refuse_growth(true);
+
+        assert!(matches!(cache.try_insert(key, key),
+                         Err(TryReserveError::AllocError { .. })));
+        assert_eq!(state(&cache), before);
+        cache.check_invariants();
+
+        // A key already cached takes no more room.
+        assert_eq!(cache.try_insert(0, 10), Ok(InsertResult::Replaced(0)));
+
+        crate::hashed::refuse_growth(false);
+
+        assert_eq!(cache.try_insert(key, key), Ok(InsertResult::Inserted));
+        assert_eq!(cache.len(), before.0.len() + 1);
+        assert!(cache.map.capacity() > before.0.len());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn pending_tasks_are_settled_in_one_call() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(100);
+
+        cache.set_clock(clock.clone());
+        cache.enable_adaptive_capacity(AdaptiveCapacity::target_hit_ratio(0.9)
+                                                        .bounds(10, 1000)
+                                                        .min_lookups(100));
+        for key in 0..100 { cache.insert(key, key); }
+        cache.invalidate_all();
+
+        // Items to expire, and items whose lookups all hit.
+        for key in 100..110 { 
+            cache.insert_with_ttl(key, key, Duration::from_secs(1));
+        }
+        for key in 200..210 { cache.insert(key, key); }
+        for i in 0..100 { cache.get(&(200 + i % 10)); }
+
+        let pending = cache.pending_reclaim();
+
+        assert!(pending > 0);
+        clock.advance(Duration::from_secs(2));
+
+        let report = cache.run_pending_tasks();
+
+        assert_eq!((report.reclaimed, report.expired), (pending, 10));
+        assert_eq!(report.capacity.map(|change| change.to), Some(90));
+        assert!(!report.work_remains);
+
+        assert_eq!(cache.pending_reclaim(), 0);
+        assert_eq!(cache.purge_expired(), 0);
+        assert_eq!(cache.len(), 10);
+        assert_eq!(cache.capacity(), 90);
+        assert_eq!(cache.run_pending_tasks(), MaintenanceReport::default());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn snapshot_ignores_later_writes() {
+        let mut cache = LfuCache::new(8);
+
+        for key in 0..8 { cache.insert(key, Arc::new(key.to_string())); }
+        for key in 0..4 { cache.get(&key); }
+
+        let snapshot = cache.snapshot_shared();
+        let reader   = snapshot.clone();
+        let order    = cache.eviction_order().copied().collect::<Vec<_>>();
+
+        for key in 0..100 {
+            cache.insert(key % 12, Arc::new(format!("new {key}")));
+            cache.get(&(key % 5));
+        }
+        cache.remove(&1);
+        assert!(!cache.eviction_order().eq(order.iter()));
+
+        assert_eq!(reader.len(), 8);
+        assert!(reader.iter().map(|(key, _)| key).eq(order.iter()));
+        assert!(reader.iter().all(|(key, value)| **value == key.to_string()));
+        let freqs = reader.iter_with_frequency().map(|(_, _, freq)| freq);
+        assert_eq!(freqs.sum::<usize>(), 12);
+        drop(snapshot);
+        assert_eq!(reader.len(), 8);
+    }
+
+    #[test]
+    fn read_only_methods_need_no_clone() {
+        #[derive(PartialEq, Eq, Hash, Debug)]
+        struct Id(u32);
+
+        fn describe<K, V, S>(cache: &LfuCache<K, V, S>, key: &K) 
+            -> (usize, bool, Option<usize>) 
+        where
+            K: Eq + Hash,
+            S: BuildHasher,
+        {
+            (cache.len(), cache.contains_key(key), cache.frequency(key))
+        }
+        fn take<K, V, S>(cache: &mut LfuCache<K, V, S>, key: &K) -> Option<V>
+        where
+            K: Eq + Hash,
+            S: BuildHasher,
+        {
+            cache.get(key);
+            cache.remove(key)
+        }
+        let mut cache = LfuCache::<Id, u32>::new(4);
+
+        assert_eq!(describe(&cache, &Id(1)), (0, false, None));
+        assert_eq!(cache.get(&Id(1)), None);
+        assert_eq!(cache.remove(&Id(1)), None);
+        assert_eq!(cache.remove_entry(&Id(1)), None);
+        assert_eq!(take(&mut cache, &Id(1)), None);
+        assert!(cache.is_empty());
+        assert_eq!(cache.capacity(), 4);
+        assert_eq!(cache.iter().count(), 0);
+        assert_eq!(cache.eviction_order().next(), None);
+        assert_eq!(cache.eviction_candidate(), None);
+        assert!(!cache.would_evict_next(&Id(1)));
+        assert!(cache.peek_bucket(1).is_none());
+        assert_eq!(cache.values_lfu_first().count(), 0);
+        assert!(cache.frequency_histogram().is_empty());
+        assert!(cache.stats().is_none());
+        assert_eq!(cache.pending_reclaim(), 0);
+    }
+
+    #[test]
+    fn stale_hot_items_age_out_under_lfu_da() {
+        let run = |policy| {
+            let mut cache = LfuCacheBuilder::new(5).policy(policy).build();
+
+            // Keys 0 and 1 are hot for a while, then never used again.
+            for _ in 0..50 {
+                cache.insert(0, 0);
+                cache.insert(1, 1);
+            }
+            // From then on, keys 2 and 3 are used a few times each, and a 
+            // stream of one-off keys goes by.
+            for i in 0..500 {
+                cache.insert(2 + i % 2, 0);
+                cache.insert(100 + i, 0);
+                cache.check_invariants();
+            }
+            cache
+        };
+        let lfu = run(Policy::Lfu);
+
+        assert!(lfu.contains_key(&0) && lfu.contains_key(&1));
+        assert_eq!(lfu.frequency(&0), Some(50));
+
+        let da = run(Policy::LfuDa);
+
+        assert!(!da.contains_key(&0) && !da.contains_key(&1));
+        assert!(da.contains_key(&2) && da.contains_key(&3));
+        assert!(da.contains_key(&599));
+    }
+
+    #[test]
+    fn decayed_hot_key_loses_to_a_warm_one() {
+        let half_life = Duration::from_secs(60);
+        let run = |policy| {
+            let clock     = ManualClock::new();
+            let mut cache = LfuCacheBuilder::new(3).clock(clock.clone())
+                                                   .policy(policy)
+                                                   .build();
+            // Key 0 is used 100 times, then never again. Key 1 is used twice
+            // every half-life from then on.
+            for _ in 0..100 { cache.insert(0, ()); }
+
+            for round in 0..8 {
+                clock.advance(half_life);
+                cache.get(&1).is_none().then(|| cache.insert(1, ()));
+                cache.get(&1);
+                cache.check_invariants();
+
+                // After a round, key 1 has a score of 2 to key 0's 50.
+                if round == 0 {
+                    assert!(cache.eviction_order().eq([&1, &0]));
+                }
+            }
+            assert_eq!(cache.frequency(&0), Some(100));
+
+            // A newcomer makes room by evicting the lowest score.
+            cache.insert(2, ());
+            cache.insert(3, ());
+            cache
+        };
+        let lfu = run(Policy::Lfu);
+
+        assert!(lfu.eviction_order().eq([&3, &1, &0]));
+
+        // 100 halved eight times is under 1, and key 1 is at 16.
+        let decay = run(Policy::Decay { half_life });
+
+        assert!(decay.eviction_order().eq([&2, &3, &1]));
+    }
+
+    #[test]
+    fn lrfu_spans_lfu_and_lru() {
+        let mut rng   = crate::rng::Rng::new(716);
+        let     trace = (0..3000).map(|_| {
+                            let range = 1 + rng.next_u64() % 40;
+                            rng.next_u64() % range
+                        }).collect::<Vec<_>>();
+        let run = |mut cache: LfuCache<u64, ()>| {
+            let hits = trace.iter().map(|&key| {
+                let hit = cache.get(&key).is_some();
+
+                if !hit { cache.insert(key, ()); }
+                cache.check_invariants();
+                hit
+            }).collect::<Vec<_>>();
+
+            (hits, cache.eviction_order().copied().collect::<Vec<_>>())
+        };
+        let mut lru  = std::collections::VecDeque::new();
+        let lru_hits = trace.iter().map(|&key| {
+            let hit = lru.iter().position(|&k| k == key)
+                         .map(|i| lru.remove(i)).is_some();
+
+            lru.push_back(key);
+            if lru.len() > 8 { lru.pop_front(); }
+            hit
+        }).collect::<Vec<_>>();
+
+        // A λ of 1 is LRU, down to the eviction order.
+        let (hits, order) = run(LfuCacheBuilder::new(8).lrfu(1.0).build());
+
+        assert_eq!(hits, lru_hits);
+        assert!(order.iter().eq(&lru));
+
+        // A λ near 0 is LFU.
+        let lfu = run(LfuCacheBuilder::new(8).build());
+
+        assert_eq!(run(LfuCacheBuilder::new(8).lrfu(1e-7).build()), lfu);
+        assert_ne!(lfu.0, lru_hits);
+
+        // And the default is in between.
+        let (hits, _) = run(LfuCacheBuilder::new(8).policy(Policy::Lrfu)
+                                                   .build());
+        assert!(hits != lfu.0 && hits != lru_hits);
+    }
+
+    #[test]
+    fn every_operation_is_one_step_of_age() {
+        let mut cache = LfuCacheBuilder::new(3).policy(Policy::Lrfu).build();
+        let mut age   = cache.age;
+        let mut step  = |cache: &LfuCache<u32, u32>| {
+            assert_eq!(cache.age, age + 1);
+            age = cache.age;
+        };
+        // New keys into room, a hit, an update, a new key in place of the 
+        // LFU item, and ones loaded on a miss.
+        for key in 0..3 { cache.insert(key, key); step(&cache); }
+
+        cache.get(&0);
+        step(&cache);
+        cache.insert(0, 1);
+        step(&cache);
+        cache.insert(3, 3);
+        step(&cache);
+        cache.get_or_insert_with(4, || 4);
+        step(&cache);
+        cache.insert_and_get_mut(5, 5);
+        step(&cache);
+        cache.get_or_insert_with_many(vec![6], |keys| {
+            keys.iter().map(|&key| (key, key)).collect::<Vec<_>>()
+        });
+        step(&cache);
+
+        assert_eq!(cache.len(), 3);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn gdsf_keeps_small_items_over_a_large_one() {
+        // Key 0 weighs 60 and is used five times a round; keys 1 to 80 weigh
+        // 1 and are used three times a round each. They can't all fit in 100.
+        let run = |policy| {
+            let mut cache = LfuCacheBuilder::new(100).policy(policy).build();
+            let mut small = 0;
+
+            cache.set_weigher(100, |key: &u32, _: &()| {
+                if *key == 0 { 60 } else { 1 }
+            });
+            for _ in 0..20 {
+                for _ in 0..5 {
+                    if cache.get(&0).is_none() { cache.insert(0, ()); }
+                }
+                for _ in 0..3 {
+                    for key in 1..=80 {
+                        match cache.get(&key) {
+                            Some(_) => small += 1,
+                            None    => { cache.insert(key, ()); }
+                        }
+                    }
+                }
+                cache.check_invariants();
+                assert!(cache.weight() <= 100);
+            }
+            (cache, small)
+        };
+        let (lfu, lfu_hits) = run(Policy::Lfu);
+
+        // The small items push each other out before they're used again.
+        assert!(lfu.contains_key(&0));
+        assert_eq!(lfu.len(), 41);
+        assert_eq!(lfu_hits, 0);
+
+        // GDSF still lets key 0 back in on a miss, but it's the first to go
+        // once the small items come back.
+        let (gdsf, gdsf_hits) = run(Policy::Gdsf { scale: 100 });
+
+        assert!(!gdsf.contains_key(&0));
+        assert_eq!(gdsf.len(), 80);
+        assert_eq!(gdsf.weight(), 80);
+        assert!(gdsf_hits >= 3000);
+    }
+
+    #[test]
+    fn entries_agree_with_histogram() {
+        let mut cache = LfuCache::with_seed(8, 5);
+
+        // Leave some stale items behind, which neither should count.
+        for i in 0..4 { cache.insert(i, i); }
+        cache.invalidate_all();
+
+        for i in 0..12u32 {
+            cache.insert(i % 10, i);
+            for _ in 0..i % 4 { cache.get(&(i % 10)); }
+        }
+        cache.remove(&3);
+
+        let mut hist = HashMap::new();
+
+        for (key, _, freq) in cache.entries() {
+            assert_eq!(cache.frequency(key), Some(freq));
+            *hist.entry(freq).or_insert(0) += 1;
+        }
+        let mut hist = hist.into_iter().collect::<Vec<_>>();
+        hist.sort();
+
+        // The emptied frequency 1 queue can be kept around for reuse.
+        let mut buckets = cache.frequency_histogram();
+        buckets.retain(|&(_, n)| n > 0);
+        assert_eq!(hist, buckets);
+        assert_eq!(cache.entries().count(), cache.len());
+
+        let mut entries = cache.entries().map(|(k, v, f)| (*k, *v, f))
+                                         .collect::<Vec<_>>();
+        let mut owned   = cache.into_entries().collect::<Vec<_>>();
+        entries.sort();
+        owned.sort();
+        assert_eq!(entries, owned);
+    }
+
+    #[test]
+    fn into_entries_moves_items_in_eviction_order() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        thread_local! { static CLONES: Cell<usize> = const { Cell::new(0) }; }
+
+        #[derive(PartialEq, Eq, Hash, Debug)]
+        struct Key(u32);
+
+        impl Clone for Key {
+            fn clone(&self) -> Self {
+                CLONES.with(|n| n.set(n.get() + 1));
+                Key(self.0)
+            }
+        }
+        // The values can't be cloned at all.
+        let live      = Rc::new(());
+        let mut cache = LfuCache::new(6);
+        let mut rng   = crate::rng::Rng::new(3);
+
+        for _ in 0..200 {
+            let key = Key(rng.next_u64() as u32 % 10);
+
+            if cache.get(&key).is_none() { cache.insert(key, live.clone()); }
+        }
+        cache.invalidate_all();
+
+        for key in 0..8 { cache.insert(Key(key), live.clone()); }
+        for _ in 0..40 { cache.get(&Key(rng.next_u64() as u32 % 8)); }
+
+        let exp = cache.eviction_order().map(|key| {
+            (key.0, cache.frequency(key).unwrap())
+        }).collect::<Vec<_>>();
+        let clones = CLONES.with(Cell::get);
+
+        let mut items = cache.into_entries();
+
+        assert_eq!(items.len(), 6);
+
+        let got = items.by_ref().take(4).map(|(key, _, freq)| (key.0, freq))
+                                        .collect::<Vec<_>>();
+
+        assert_eq!(got, exp[..4]);
+        assert_eq!(items.len(), 2);
+        assert_eq!(Rc::strong_count(&live), 1 + 2);
+        assert_eq!(CLONES.with(Cell::get), clones);
+
+        // What's left goes with the iterator.
+        drop(items);
+        assert_eq!(Rc::strong_count(&live), 1);
+    }
+
+    #[test]
+    fn lone_key_takes_its_queue_along() {
+        let mut cache = LfuCache::new(3);
+
+        cache.insert(1, ());
+        cache.insert(2, ());
+        for _ in 0..3 { cache.get(&1); }
+        cache.check_invariants();
+        assert_eq!(cache.frequency_histogram(), [(1, 1), (4, 1)]);
+
+        // Key 2 is relabeled up to 3, then joins key 1's queue behind it.
+        for _ in 0..2 { cache.get(&2); }
+        cache.check_invariants();
+        assert_eq!(cache.frequency_histogram(), [(3, 1), (4, 1)]);
+        cache.get(&2);
+        cache.check_invariants();
+        assert_eq!(cache.frequency_histogram(), [(4, 2)]);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), [&1, &2]);
+
+        cache.insert(3, ());
+        cache.get(&1);
+        cache.check_invariants();
+        assert_eq!(cache.frequency_histogram(), [(1, 1), (4, 1), (5, 1)]);
+        assert_eq!(cache.pop_lfu(), Some((3, ())));
+    }
+
+    #[test]
+    fn receiver_gets_every_eviction() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(8);
+        let rx        = cache.eviction_receiver(10_000);
+        let mut rng   = crate::rng::Rng::new(7);
+        let mut exp   = Vec::new();
+
+        cache.set_clock(clock.clone());
+        cache.enable_stats();
+
+        for i in 0..5_000 {
+            let key = rng.next_u64() % 20;
+
+            if rng.next_u64() % 3 < 2 {
+                cache.get(&key);
+                continue;
+            }
+            if cache.contains_key(&key) {
+                exp.push((key, cache.map[&key].value, 
+                          EvictionReason::Replaced));
+            } else if cache.len() == 8 {
+                let lfu = *cache.eviction_order().next().unwrap();
+                exp.push((lfu, cache.map[&lfu].value, 
+                          EvictionReason::Capacity));
+            }
+            cache.insert(key, i);
+        }
+        // Values handed back by `remove()` aren't sent, expired ones are.
+        let key = *cache.eviction_order().next().unwrap();
+        cache.remove(&key);
+        cache.insert_with_ttl(100, 1, Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.purge_expired(), 1);
+        exp.push((100, 1, EvictionReason::Expired));
+
+        let got = rx.try_iter().map(|msg| (msg.key, msg.value, msg.reason))
+                               .collect::<Vec<_>>();
+
+        let evictions = exp.iter().filter(|msg| {
+            msg.2 == EvictionReason::Capacity
+        });
+        assert_eq!(got, exp);
+        assert_eq!(cache.stats().unwrap().evictions as usize, 
+                   evictions.count());
+        assert_eq!(rx.dropped(), 0);
+
+        drop(cache);
+        assert_eq!(rx.recv(), None);
+    }
+
+    #[test]
+    fn receiver_is_told_why() {
+        use EvictionReason::*;
+
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(3);
+        let rx        = cache.eviction_receiver(64);
+        let got       = || {
+            rx.try_iter().map(|msg| (msg.key, msg.value, msg.reason))
+                         .collect::<Vec<_>>()
+        };
+        cache.set_clock(clock.clone());
+        cache.insert_with_tags("a", 1, ["t"]);
+        cache.insert("b", 2);
+        cache.insert("b", 3);
+        cache.insert_with_frequency("b", 4, 5);
+        cache.insert_with_ttl("c", 5, Duration::from_secs(1));
+        cache.insert("d", 6);
+        assert_eq!(got(), [("b", 2, Replaced), ("b", 3, Replaced), 
+                           ("a", 1, Capacity)]);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(got(), [("c", 5, Expired)]);
+
+        cache.insert_with_tags("e", 7, ["t"]);
+        assert_eq!(cache.invalidate_tag("t"), 1);
+        cache.insert("f", 8);
+        assert_eq!(cache.retain_mut(|key, _, _| *key != "f"), 1);
+        assert_eq!(got(), [("e", 7, Removed), ("f", 8, Removed)]);
+
+        // Values handed back to the caller aren't sent.
+        cache.insert("g", 9);
+        assert_eq!(cache.remove(&"g"), Some(9));
+        assert_eq!(cache.insert_checked("d", 10), InsertResult::Replaced(6));
+        assert_eq!(cache.replace_value(&"d", 11), Some(10));
+        assert_eq!(cache.pop_lfu(), Some(("d", 11)));
+        cache.insert("k", 12);
+        cache.insert("l", 13);
+        assert_eq!(cache.truncate(2), [("k", 12)]);
+        assert_eq!(cache.evict_while(|ctx| ctx.len > 1), [("l", 13)]);
+        cache.insert("m", 14);
+        assert_eq!(cache.drain_frequency(1), [("m", 14)]);
+        assert_eq!(got(), []);
+
+        cache.set_weigher(4, |_, value| *value);
+        assert_eq!(cache.insert_checked("b", 5), 
+                   InsertResult::TooLarge { weight: 5 });
+        assert_eq!(got(), []);
+        assert_eq!(cache.remove(&"b"), Some(4));
+
+        cache.insert("h", 1);
+        cache.insert("i", 2);
+        cache.invalidate_all();
+        cache.insert("j", 3);
+        cache.clear();
+        assert_eq!(got(), [("j", 3, Cleared)]);
+    }
+
+    #[cfg(feature = "debug-audit")]
+    #[test]
+    fn audit_log_says_why() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(1);
+
+        cache.set_clock(clock.clone());
+        cache.enable_audit_log(16);
+        cache.insert_with_ttl(1, 1, Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+        cache.purge_expired();
+        cache.insert(2, 2);
+        cache.insert(3, 3);
+        cache.pop_lfu();
+
+        let reasons = cache.audit_log().filter_map(|rec| match rec.event {
+            AuditEvent::Evicted { victim, reason } => Some((victim, reason)),
+            _                                      => None,
+        });
+        assert_eq!(reasons.collect::<Vec<_>>(), [
+            (1, EvictionReason::Expired),
+            (2, EvictionReason::Capacity),
+        ]);
+        // Taking an item, as `pop_lfu()` does, is a removal like `remove()`.
+        assert_eq!(cache.audit_log().last().map(|rec| rec.event.clone()),
+                   Some(AuditEvent::Removed { key: 3 }));
+    }
+
+    #[test]
+    fn full_channel_drops_and_counts() {
+        let mut cache = LfuCache::new(1);
+        let first     = cache.eviction_receiver(4);
+
+        for i in 0..4 { cache.insert(i, i); }
+
+        let rx = cache.eviction_receiver(2);
+
+        assert_eq!(first.try_iter().map(|msg| msg.key).collect::<Vec<_>>(),
+                   [0, 1, 2]);
+        assert_eq!(first.recv(), None);
+
+        for i in 4..10 { cache.insert(i, i); }
+
+        assert_eq!(rx.try_iter().map(|msg| msg.key).collect::<Vec<_>>(), 
+                   [3, 4]);
+        assert_eq!(rx.dropped(), 4);
+        assert!(cache.clone().notify.is_none());
+    }
+
+    #[test]
+    fn sync_cache_sends_to_another_thread() {
+        let cache = Arc::new(crate::SyncLfuCache::new(4));
+        let rx    = cache.eviction_receiver(1);
+
+        let writer = std::thread::spawn({
+            let cache = cache.clone();
+            move || for i in 0..100 { cache.insert(i, i * 2); }
+        });
+        // With a bound of 1, a few messages may be dropped, but none lost
+        // track of.
+        let mut got = 0;
+
+        while let Some(msg) = rx.recv_timeout(Duration::from_secs(5)) {
+            assert_eq!(msg.value, msg.key * 2);
+            got += 1;
+            if got as u64 + rx.dropped() == 96 { break; }
+        }
+        writer.join().unwrap();
+        assert_eq!(got as u64 + rx.dropped(), 96);
+    }
+
+    #[test]
+    fn entry_views_of_either_end() {
+        let mut cache = LfuCache::new(4);
+
+        assert!(cache.lfu_entry().is_none());
+        assert!(cache.mfu_entry().is_none());
+
+        for i in 1..=4 { cache.insert(i, i * 10); }
+        cache.get(&3);
+        cache.get(&3);
+        cache.get(&4);
+
+        let mut lfu = cache.lfu_entry().unwrap();
+
+        assert_eq!((lfu.key(), lfu.get(), lfu.frequency()), (&1, &10, 1));
+        *lfu.get_mut() += 1;
+        lfu.bump();
+        assert_eq!(lfu.frequency(), 2);
+        assert_eq!(cache.eviction_candidate(), Some(&2));
+        assert_eq!(cache.frequency(&1), Some(2));
+
+        let mfu = cache.mfu_entry().unwrap();
+
+        assert_eq!((mfu.key(), mfu.frequency()), (&3, 3));
+        *mfu.into_mut() = 33;
+        assert_eq!(cache.peek_value(&3), Some(&33));
+
+        // Removing 2 leaves the frequency 1 queue empty, and it goes.
+        assert_eq!(cache.lfu_entry().unwrap().remove(), (2, 20));
+        assert_eq!(cache.frequency_histogram(), [(2, 2), (3, 1)]);
+        assert_eq!(cache.lfu_entry().unwrap().remove(), (4, 40));
+        assert_eq!(cache.mfu_entry().unwrap().remove(), (3, 33));
+        assert_eq!(cache.mfu_entry().unwrap().remove(), (1, 11));
+        assert!(cache.is_empty());
+        assert_eq!(cache.frequencies.len(), 0);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn entry_views_pass_over_expired_items() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(8);
+        let ttl       = Duration::from_secs(1);
+
+        cache.set_clock(clock.clone());
+
+        // The items with a TTL are at either end of the eviction order.
+        cache.insert_with_ttl(0, 0, ttl);
+        cache.insert(1, 1);
+        cache.insert(3, 3);
+        cache.insert_with_ttl(2, 2, ttl);
+        cache.get(&1);
+        for _ in 0..2 { cache.get(&3); }
+        for _ in 0..3 { cache.get(&2); }
+        clock.advance(Duration::from_secs(5));
+
+        assert_eq!(cache.lfu_entry().unwrap().key(), &1);
+        assert_eq!(cache.mfu_entry().unwrap().remove(), (3, 3));
+        assert_eq!(cache.len(), 1);
+
+        // An item whose TTL runs out while it's viewed stays until removed.
+        cache.insert_with_ttl(4, 4, ttl);
+
+        let mut lfu = cache.lfu_entry().unwrap();
+
+        clock.advance(Duration::from_secs(5));
+        lfu.bump();
+        assert_eq!(lfu.frequency(), 2);
+        assert_eq!(lfu.remove(), (4, 4));
+        assert_eq!(cache.len(), 1);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn insertion_order_survives_frequency_changes() {
+        let mut cache = LfuCache::new(3);
+
+        cache.insert("a", 1);
+        cache.enable_insertion_order();
+        assert_eq!(cache.insertion_index(&"a"), Some(0));
+
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        cache.get(&"c");
+        cache.get(&"c");
+        cache.insert("a", 10);
+
+        // Overwriting "a" kept its number, and "b" goes to make room.
+        cache.insert("d", 4);
+        assert_eq!(cache.keys_in_insertion_order().collect::<Vec<_>>(), 
+                   [&"a", &"c", &"d"]);
+        assert_eq!(cache.insertion_index(&"b"), None);
+
+        // Back after an eviction, "b" is the newest.
+        cache.insert("b", 20);
+        assert_eq!(cache.insertion_index(&"b"), Some(4));
+        assert_eq!(cache.keys_in_insertion_order().collect::<Vec<_>>(), 
+                   [&"a", &"c", &"b"]);
+
+        cache.remove(&"c");
+        cache.retain_keys_in(&HashSet::from(["b"]));
+        cache.check_invariants();
+        assert_eq!(cache.keys_in_insertion_order().len(), 1);
+
+        cache.clear();
+        cache.insert("e", 5);
+        assert_eq!(cache.insertion_index(&"e"), Some(5));
+        assert_eq!(LfuCache::<u8, u8>::new(1).keys_in_insertion_order().len(),
+                   0);
+    }
+
+    #[test]
+    fn insertion_order_matches_a_model() {
+        let mut cache = LfuCache::new(6);
+        let mut rng   = crate::rng::Rng::new(11);
+        let mut seq   = std::collections::HashMap::new();
+        let mut next  = 0;
+
+        cache.enable_insertion_order();
+
+        for i in 0..5_000 {
+            let key = rng.next_u64() % 15;
+
+            if rng.next_u64().is_multiple_of(4) {
+                cache.get(&key);
+                continue;
+            }
+            if !cache.contains_key(&key) {
+                if cache.len() == 6 {
+                    seq.remove(cache.eviction_order().next().unwrap());
+                }
+                seq.insert(key, next);
+                next += 1;
+            }
+            cache.insert(key, i);
+
+            let mut exp = seq.iter().map(|(k, s)| (*s, *k)).collect::<Vec<_>>();
+            exp.sort();
+
+            assert!(cache.keys_in_insertion_order()
+                         .eq(exp.iter().map(|(_, key)| key)));
+            assert_eq!(cache.insertion_index(&key), seq.get(&key).copied());
+        }
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn retain_mut_fixes_up_and_drops() {
+        let mut cache = LfuCache::new(10);
+
+        for i in 0..10 {
+            cache.insert(i, "x".repeat(i * 10));
+            for _ in 0..i % 4 { cache.get(&i); }
+        }
+        let before = cache.frequency_histogram();
+        let mut seen = Vec::new();
+
+        // Drop the items used only once, and shorten the long values.
+        let removed = cache.retain_mut(|key, value, freq| {
+            seen.push((*key, freq));
+            value.truncate(40);
+            freq > 1
+        });
+        assert_eq!(removed, 3);
+        assert_eq!(seen.len(), 10);
+        assert!(seen.iter().all(|&(key, freq)| freq == key % 4 + 1));
+        assert!(seen.windows(2).all(|w| w[0].1 <= w[1].1));
+
+        assert_eq!(cache.len(), 7);
+        assert!(cache.iter().all(|(_, value)| value.len() <= 40));
+        assert_eq!(cache.peek_value(&9).map(String::len), Some(40));
+        assert_eq!(cache.peek_value(&3).map(String::len), Some(30));
+        assert_eq!(cache.frequency(&9), Some(2));
+        assert_eq!(cache.frequency_histogram(), &before[1..]);
+        cache.check_invariants();
+
+        assert_eq!(cache.retain_mut(|_, _, _| false), 7);
+        assert!(cache.is_empty());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn log_buckets_keep_few_queues() {
+        let mut exact  = LfuCache::<usize, ()>::new(500);
+        let mut logged = LfuCacheBuilder::new(500).log_buckets(2).build();
+        let mut rng    = crate::rng::Rng::new(685);
+        let mut hits   = (0, 0);
+
+        // Key `k` of 10,000 is accessed with a probability proportional to
+        // `1 / (k + 1)`.
+        let mut cdf = Vec::new();
+        let mut sum = 0.0;
+
+        for k in 0..10_000 {
+            sum += 1.0 / (k + 1) as f64;
+            cdf.push(sum);
+        }
+        for _ in 0..200_000 {
+            let x   = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * sum;
+            let key = cdf.partition_point(|&c| c < x).min(9_999);
+
+            match exact.get(&key) {
+                Some(_) => hits.0 += 1,
+                None    => exact.insert(key, ()),
+            }
+            match logged.get(&key) {
+                Some(_) => hits.1 += 1,
+                None    => logged.insert(key, ()),
+            }
+        }
+        logged.check_invariants();
+
+        let max = logged.frequency(&0).unwrap();
+
+        assert!(max > 1000);
+        assert_eq!(logged.frequency_histogram().len(), 
+                   max.ilog2() as usize + 1);
+        assert!(logged.frequency_histogram().iter()
+                      .all(|(label, _)| label.is_power_of_two()));
+        assert!(exact.frequency_histogram().len() > 50);
+
+        // Both are about 0.66.
+        let ratio = |hits| hits as f64 / 200_000.0;
+
+        assert!((ratio(hits.0) - ratio(hits.1)).abs() < 0.02);
+    }
+
+    #[test]
+    fn log_buckets_move_only_across_boundaries() {
+        let mut cache = LfuCacheBuilder::new(3).log_buckets(3).build();
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+
+        // "a" and "b" are both in the bucket for 3, while "c" is in the one
+        // for 1.
+        for _ in 0..4 { cache.get(&"a"); }
+        for _ in 0..2 { cache.get(&"b"); }
+
+        assert_eq!(cache.frequency(&"a"), Some(5));
+        assert_eq!(cache.frequency(&"b"), Some(3));
+        assert_eq!(cache.frequency_histogram(), [(1, 1), (3, 2)]);
+
+        // "c" goes first, and then whichever of the two in the same bucket
+        // was used least recently, despite its higher count.
+        cache.get(&"b");
+        cache.insert("d", 4);
+        assert!(!cache.contains_key(&"c"));
+        cache.get(&"d");
+        cache.get(&"d");
+        assert_eq!(cache.frequency_histogram(), [(3, 3)]);
+        assert_eq!(cache.pop_lfu(), Some(("a", 1)));
+
+        cache.insert_with_frequency("e", 5, 10);
+        assert_eq!(cache.frequency_histogram(), [(3, 2), (9, 1)]);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn hit_counters_reset_apart_from_frequencies() {
+        let mut cache = LfuCache::new(3);
+        let mut other = LfuCache::new(3);
+
+        for cache in [&mut cache, &mut other] {
+            for key in 0..3 { cache.insert(key, key); }
+            for _ in 0..3 { cache.get(&0); }
+            cache.get(&1);
+            cache.observe(&2);
+            cache.peek_value_mut(&2);
+        }
+        assert_eq!(cache.hits(&0), Some(3));
+        assert_eq!(cache.hits(&1), Some(1));
+        assert_eq!(cache.hits(&2), Some(0));
+        assert_eq!(cache.hits(&3), None);
+
+        cache.reset_hit_counters();
+        assert!((0..3).all(|key| cache.hits(&key) == Some(0)));
+
+        // The cache that was reset goes on just like the one that wasn't.
+        for cache in [&mut cache, &mut other] {
+            cache.get(&1);
+            cache.insert(3, 3);
+            cache.get(&3);
+        }
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(),
+                   other.eviction_order().collect::<Vec<_>>());
+        assert_eq!(cache.frequency_histogram(), other.frequency_histogram());
+        assert_eq!(cache.frequency(&0), Some(4));
+        assert_eq!(cache.hits(&0), Some(0));
+        assert_eq!(cache.hits(&1), Some(1));
+        assert_eq!(cache.hits(&3), Some(1));
+        assert_eq!(other.hits(&1), Some(2));
+        assert_eq!(cache.clone().hits(&1), Some(1));
+    }
+
+    /// Runs `f` on the cache, expecting it to panic, and checks the cache is
+    /// still whole afterwards.
+    /// 
+    fn assert_survives<K, V>(cache : &mut LfuCache<K, V>, 
+                             f     : impl FnOnce(&mut LfuCache<K, V>))
+    where
+        K: Eq + Hash + Clone,
+    {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(cache)));
+
+        assert!(result.is_err(), "the hook didn't panic");
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn panicking_hooks_leave_the_cache_whole() {
+        let mut cache = LfuCache::new(4);
+
+        // Values of 13 can't be weighed.
+        cache.set_weigher(100, |_, value: &u32| {
+            assert!(*value != 13, "unlucky");
+            *value as usize
+        });
+        for key in 1..4 { cache.insert(key, key); }
+        cache.enable_insertion_order();
+
+        assert_survives(&mut cache, |c| {
+            c.get_or_insert_with(9, || panic!());
+        });
+        assert_survives(&mut cache, |c| {
+            let load = || -> Result<_, ()> { panic!() };
+            let _    = c.get_or_try_insert_with(9, load);
+        });
+        assert_survives(&mut cache, |c| c.insert(9, 13));
+        assert_survives(&mut cache, |c| c.insert(1, 13));
+        assert_survives(&mut cache, |c| { c.replace_value(&2, 13); });
+        assert_survives(&mut cache, |c| c.insert_with_frequency(3, 13, 5));
+        assert_survives(&mut cache, |c| {
+            c.set_weigher(100, |key, _| if *key == 2 { panic!() } else { 1 });
+        });
+        assert!(!cache.contains_key(&9));
+
+        let items = cache.iter().map(|(k, v)| (*k, *v));
+
+        assert_eq!(items.collect::<HashMap<_, _>>(), 
+                   HashMap::from([(1, 1), (2, 2), (3, 3)]));
+        assert_eq!(cache.frequency(&1), Some(1));
+        assert_eq!(cache.weight(), 6);
+
+        assert_survives(&mut cache, |c| { c.pop_lfu_if(|_, _| panic!()); });
+        assert_survives(&mut cache, |c| {
+            c.retain_mut(|key, _, _| if *key == 2 { panic!() } else { false });
+        });
+        assert!(cache.contains_key(&2));
+
+        // And the cache carries on.
+        for key in 10..20 { cache.insert(key, 1); }
+        cache.get(&19);
+        cache.check_invariants();
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.frequency(&19), Some(2));
+    }
+
+    #[test]
+    fn panicking_clone_leaves_the_cache_whole() {
+        // A value that can't be cloned once it's been marked.
+        #[derive(Debug)]
+        struct Brittle(bool);
+
+        impl Clone for Brittle {
+            fn clone(&self) -> Self {
+                assert!(!self.0, "brittle");
+                Brittle(false)
+            }
+        }
+        let mut source = LfuCache::new(4);
+        let mut target = LfuCache::new(4);
+
+        for key in 0..4 { 
+            source.insert(key, Brittle(key == 2)); 
+            target.insert(key + 2, Brittle(false));
+        }
+        target.enable_insertion_order();
+        source.get(&3);
+
+        assert_survives(&mut target, |t| t.clone_from(&source));
+        assert!(target.is_empty());
+
+        // It can still be cloned into once the source can be cloned.
+        source.remove(&2);
+        target.clone_from(&source);
+        target.check_invariants();
+        assert_eq!(target.eviction_order().collect::<Vec<_>>(), [&0, &1, &3]);
+        target.insert(5, Brittle(false));
+        target.check_invariants();
+    }
+
+    #[test]
+    fn truncate_keeps_the_capacity() {
+        let mut cache = LfuCache::new(6);
+
+        for key in 0..6 { cache.insert(key, key * 10); }
+        for key in [0, 3, 3, 5] { cache.get(&key); }
+
+        let order = cache.eviction_order().copied().collect::<Vec<_>>();
+
+        assert!(cache.truncate(6).is_empty());
+        assert!(cache.truncate(10).is_empty());
+
+        let victims = cache.truncate(2);
+
+        assert_eq!(victims.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+                   order[..4]);
+        assert!(victims.iter().all(|(k, v)| *v == k * 10));
+        assert_eq!(cache.eviction_order().copied().collect::<Vec<_>>(),
+                   order[4..]);
+        assert_eq!(cache.capacity(), 6);
+        cache.check_invariants();
+
+        // It fills up to its capacity again.
+        for key in 10..20 { cache.insert(key, key); }
+        assert_eq!(cache.len(), 6);
+
+        let guard = cache.guard(&19).unwrap();
+
+        assert_eq!(cache.truncate(0).len(), 5);
+        assert_eq!(cache.len(), 1);
+        drop(guard);
+        assert_eq!(cache.truncate(0).len(), 1);
+        assert!(cache.is_empty());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn evict_while_sheds_to_a_target() {
+        let mut cache = LfuCache::new(8);
+
+        cache.set_weigher(100, |_, value: &usize| *value);
+
+        for key in 0..8 { cache.insert(key, key + 1); }
+        for key in [0, 1, 1, 6] { cache.get(&key); }
+
+        let order  = cache.eviction_order().copied().collect::<Vec<_>>();
+        let keys   = |victims: Vec<(usize, usize)>| {
+            victims.into_iter().map(|(key, _)| key).collect::<Vec<_>>()
+        };
+
+        // Down to a weight: 36 to at most 20.
+        assert_eq!(cache.weight(), 36);
+
+        let light = cache.evict_while(|ctx| ctx.weight > 20);
+
+        assert!(cache.weight() <= 20);
+        assert!(cache.weight() + light.last().unwrap().1 > 20);
+        assert_eq!(keys(light), order[..order.len() - cache.len()]);
+
+        // Down to a length.
+        let short = cache.evict_while(|ctx| ctx.len > 2);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.eviction_order().eq(order[order.len() - 2..].iter()));
+
+        // Or up to the first warm item, which is left in place.
+        assert!(!short.is_empty());
+        assert!(cache.evict_while(|ctx| ctx.frequency < 2).is_empty());
+        assert!(cache.evict_while(|_| false).is_empty());
+
+        // It stops once there's nothing left to evict.
+        let guard = cache.guard(&1).unwrap();
+
+        assert_eq!(cache.evict_while(|_| true).len(), 1);
+        assert_eq!(cache.len(), 1);
+        drop(guard);
+        assert_eq!(keys(cache.evict_while(|ctx| *ctx.key == 1)), [1]);
+        assert!(cache.evict_while(|_| true).is_empty());
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn stale_while_revalidate() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCacheBuilder::new(4)
+                            .clock(clock.clone())
+                            .stale_while_revalidate(Duration::from_secs(5))
+                            .build();
+        let ttl = Duration::from_secs(10);
+
+        cache.insert_with_ttl("a", 1, ttl);
+        cache.insert_with_ttl("b", 2, ttl);
+        cache.insert("c", 3);
+
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(cache.lookup(&"a"), GetResult::Fresh(&1));
+        assert!(cache.take_refresh_candidates().is_empty());
+
+        // Expired, but within the window: served stale, and queued for 
+        // refresh once.
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.lookup(&"a"), GetResult::Stale(&1));
+        assert!(cache.lookup(&"a").is_stale());
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.lookup(&"c"), GetResult::Fresh(&3));
+        assert_eq!(cache.purge_expired(), 0);
+        assert!(cache.contains_key(&"b"));
+        assert_eq!(cache.take_refresh_candidates(), ["a"]);
+        assert!(cache.take_refresh_candidates().is_empty());
+
+        // The refreshed value is fresh again, with a new TTL.
+        cache.insert_with_ttl("a", 10, ttl);
+        assert_eq!(cache.lookup(&"a"), GetResult::Fresh(&10));
+
+        // Past the window, it's a miss.
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.lookup(&"b"), GetResult::Miss);
+        assert_eq!(cache.lookup(&"b").value(), None);
+        assert_eq!(cache.lookup(&"a").value(), Some(&10));
+        assert_eq!(cache.len(), 2);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn stale_items_are_purged_after_the_window() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(4);
+
+        cache.set_clock(clock.clone());
+        cache.enable_stale_while_revalidate(Duration::from_secs(5));
+        cache.insert_with_ttl(1, 1, Duration::from_secs(10));
+        cache.insert_with_ttl(2, 2, Duration::from_secs(12));
+
+        clock.advance(Duration::from_secs(14));
+        assert_eq!(cache.purge_expired(), 0);
+
+        let report = cache.run_maintenance(MaintenanceBudget::default());
+        assert!(!report.work_remains);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.purge_expired(), 1);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.lookup(&2), GetResult::Stale(&2));
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn oversized_loads_are_handed_back() {
+        let mut cache = LfuCache::new(10);
+
+        cache.enable_stats();
+        cache.set_weigher(100, |_, value: &usize| *value);
+
+        for key in 0..10 { cache.insert(key, 10); }
+
+        // Loads too heavy to cache come back without evicting anything.
+        assert_eq!(cache.get_or_insert_with(10, || 1000), &1000);
+        assert_eq!(*cache.get_mut_or_insert_with(11, || 101), 101);
+        assert_eq!(cache.get_or_try_insert_with(12, || Ok::<_, ()>(500)), 
+                   Ok(&500));
+        assert_eq!(cache.get_or_insert_with_many(vec![0, 13], |_| {
+            vec![(13, 200)]
+        }), [Some(&10), None]);
+
+        assert_eq!(cache.len(), 10);
+        assert_eq!(cache.weight(), 100);
+        assert!((0..10).all(|key| cache.contains_key(&key)));
+
+        let stats = cache.stats().unwrap();
+
+        assert_eq!(stats.too_large, 4);
+        assert_eq!(stats.evictions, 0);
+
+        // A load that fits is cached as before.
+        assert_eq!(cache.get_or_insert_with(14, || 10), &10);
+        assert!(cache.contains_key(&14));
+        assert_eq!(cache.stats().unwrap().evictions, 1);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn oversized_insert_and_get_mut_evicts_nothing() {
+        let mut cache = LfuCache::new(4);
+
+        cache.enable_stats();
+        cache.set_weigher(40, |_, value: &Vec<u8>| value.len());
+
+        for key in 0..4 { cache.insert(key, vec![0; 10]); }
+
+        // Neither a new key nor a cached one is stored, or counted twice.
+        cache.insert_and_get_mut(4, vec![1; 41]).push(1);
+        assert_eq!(cache.insert_and_get_mut(0, vec![2; 50]).len(), 50);
+
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.weight(), 40);
+        assert!(!cache.contains_key(&4));
+        assert_eq!(cache.peek_value(&0), Some(&vec![0; 10]));
+
+        let stats = cache.stats().unwrap();
+
+        assert_eq!(stats.too_large, 2);
+        assert_eq!(stats.evictions, 0);
+        assert_eq!(stats.inserts, 4);
+
+        cache.insert_and_get_mut(0, vec![3; 5]).push(3);
+        assert_eq!(cache.peek_value(&0), Some(&vec![3; 6]));
+        assert_eq!(cache.stats().unwrap().too_large, 2);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn oversized_items_are_turned_away() {
+        let mut cache = LfuCache::new(10);
+
+        cache.enable_stats();
+        cache.set_weigher(100, |_, value: &usize| *value);
+
+        for key in 0..10 { cache.insert(key, 10); }
+
+        let order = cache.eviction_order().copied().collect::<Vec<_>>();
+
+        assert_eq!(cache.insert_checked(10, 101), 
+                   InsertResult::TooLarge { weight: 101 });
+        assert_eq!(cache.eviction_order().copied().collect::<Vec<_>>(), order);
+        assert_eq!(cache.weight(), 100);
+
+        // A quarter of the limit is as heavy as an item can be.
+        cache.set_max_item_fraction(0.25);
+        assert_eq!(cache.insert_checked(10, 26), 
+                   InsertResult::TooLarge { weight: 26 });
+        assert_eq!(cache.len(), 10);
+        assert_eq!(cache.insert_checked(10, 25), InsertResult::Inserted);
+        assert_eq!(cache.len(), 8);
+
+        // A cached key whose new value is too large keeps its old one.
+        assert_eq!(cache.insert_checked(10, 5), InsertResult::Replaced(25));
+        cache.insert(10, 30);
+        assert_eq!(cache.peek_value(&10), Some(&5));
+
+        let stats = cache.stats().unwrap();
+
+        assert_eq!(stats.too_large, 3);
+        assert_eq!(stats.evictions, 3);
+        assert_eq!(stats.rejections, 0);
+        cache.check_invariants();
+
+        let mut cache = LfuCache::new(1);
+
+        cache.enable_admission();
+        cache.insert(1, ());
+        assert_eq!(cache.insert_checked(2, ()), InsertResult::Rejected);
+        assert_eq!(LfuCache::new(0).insert_checked(1, ()), 
+                   InsertResult::Rejected);
+    }
+
+    #[test]
+    fn loads_are_timed_on_the_clock() {
+        let clock     = ManualClock::new();
+        let mut cache = LfuCache::new(4);
+        let ms        = Duration::from_millis;
+
+        cache.set_clock(clock.clone());
+        assert_eq!(cache.load_stats(), None);
+        cache.enable_stats();
+
+        for (key, took) in [(1, 5), (2, 50), (3, 0), (4, 2000)] {
+            cache.get_or_insert_with(key, || { clock.advance(ms(took)); key });
+        }
+        let failed = cache.get_or_try_insert_with(5, || {
+            clock.advance(ms(20));
+            Err(())
+        });
+        assert_eq!(failed, Err(()));
+
+        // Hits don't call the loader, and aren't timed.
+        for key in 1..5 { cache.get_or_insert_with(key, || unreachable!()); }
+
+        let loads = *cache.load_stats().unwrap();
+
+        assert_eq!((loads.loads, loads.failures, loads.calls()), (4, 1, 5));
+        assert_eq!(loads.load_time, ms(2075));
+        assert_eq!(loads.max_load_time, ms(2000));
+        assert_eq!(loads.histogram, [1, 0, 1, 2, 0, 1, 0]);
+
+        cache.reset_load_stats();
+        assert_eq!(cache.load_stats(), Some(&LoadStats::default()));
+        assert_eq!(cache.stats().unwrap().hits, 4);
+
+        let shared = crate::SyncLfuCache::from_cache(cache);
+
+        shared.get_or_insert_with(9, || { clock.advance(ms(30)); 9 });
+        assert_eq!(shared.lock().load_stats().unwrap().load_time, ms(30));
+    }
+
+    #[test]
+    fn default_ttl_is_for_writes_without_one() {
+        let clock     = ManualClock::new();
+        let secs      = Duration::from_secs;
+        let mut cache = LfuCacheBuilder::new(8)
+                            .clock(clock.clone())
+                            .expire_after_write(secs(10))
+                            .build();
+
+        assert_eq!(cache.default_ttl(), Some(secs(10)));
+
+        cache.insert("a", 1);
+        cache.insert_with_ttl("b", 2, secs(30));
+        cache.get_or_insert_with("c", || 3);
+        cache.insert_with_frequency("d", 4, 3);
+
+        // A new default only applies to what's written after it's set.
+        clock.advance(secs(5));
+        cache.set_default_ttl(Some(secs(20)));
+        cache.insert("a", 10);
+        cache.insert("e", 5);
+
+        clock.advance(secs(5));
+        assert_eq!(cache.purge_expired(), 2);
+        assert!(!cache.contains_key(&"c") && !cache.contains_key(&"d"));
+        assert_eq!(cache.len(), 3);
+
+        clock.advance(secs(15));
+        assert_eq!(cache.purge_expired(), 2);
+        assert!(!cache.contains_key(&"a") && !cache.contains_key(&"e"));
+        assert!(cache.contains_key(&"b"));
+
+        // Without a default, a plain insert clears the TTL again.
+        cache.set_default_ttl(None);
+        cache.insert("b", 20);
+        cache.insert("f", 6);
+
+        clock.advance(secs(100));
+        assert_eq!(cache.purge_expired(), 0);
+        assert_eq!(cache.len(), 2);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn ttls_can_be_read_and_extended() {
+        let clock     = ManualClock::new();
+        let secs      = Duration::from_secs;
+        let mut cache = LfuCache::new(4);
+
+        cache.set_clock(clock.clone());
+        cache.insert_with_ttl("a", 1, secs(10));
+        cache.insert_with_ttl("b", 2, secs(10));
+        cache.insert("c", 3);
+        cache.get(&"a");
+
+        clock.advance(secs(4));
+        assert_eq!(cache.remaining_ttl(&"a"), RemainingTtl::Expires(secs(6)));
+        assert_eq!(cache.remaining_ttl(&"c"), RemainingTtl::Never);
+        assert_eq!(cache.remaining_ttl(&"z"), RemainingTtl::Missing);
+        assert_eq!(cache.remaining_ttl(&"c").duration(), None);
+
+        assert!(cache.extend_ttl(&"a", secs(5)));
+        assert!(!cache.extend_ttl(&"c", secs(5)));
+        assert!(!cache.extend_ttl(&"z", secs(5)));
+        assert_eq!(cache.remaining_ttl(&"a").duration(), Some(secs(11)));
+        assert_eq!(cache.remaining_ttl(&"c"), RemainingTtl::Never);
+
+        // "b" has expired, but it's still held until it's purged, and it can
+        // be brought back.
+        clock.advance(secs(8));
+        assert_eq!(cache.remaining_ttl(&"b"), RemainingTtl::Expires(secs(0)));
+        assert!(!cache.contains_key(&"b"));
+        assert!(cache.extend_ttl(&"b", secs(2)));
+        assert_eq!(cache.remaining_ttl(&"b"), RemainingTtl::Expires(secs(2)));
+        assert_eq!(cache.purge_expired(), 0);
+        assert_eq!(cache.get(&"b"), Some(&2));
+
+        // The old deadlines are passed over, and the new ones kept.
+        clock.advance(secs(2));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.remaining_ttl(&"b"), RemainingTtl::Missing);
+        assert!(!cache.extend_ttl(&"b", secs(3)));
+        assert_eq!(cache.peek_value(&"a"), Some(&1));
+        assert_eq!(cache.frequency(&"a"), Some(2));
+
+        clock.advance(secs(1));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.len(), 1);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn expiring_items_come_soonest_first() {
+        let clock     = ManualClock::new();
+        let secs      = Duration::from_secs;
+        let mut cache = LfuCache::new(8);
+        let soon      = |cache: &LfuCache<_, _>, window| {
+            cache.iter_expiring_within(window)
+                 .map(|(key, value, left)| (*key, *value, left.as_secs()))
+                 .collect::<Vec<_>>()
+        };
+        cache.set_clock(clock.clone());
+        cache.insert_with_ttl("a", 1, secs(40));
+        cache.insert_with_ttl("b", 2, secs(10));
+        cache.insert_with_ttl("c", 3, secs(30));
+        cache.insert_with_ttl("d", 4, secs(5));
+        cache.insert("e", 5);
+
+        // Written twice at once, "c" still has one entry in the expiry index.
+        cache.insert_with_ttl("c", 3, secs(30));
+
+        assert_eq!(soon(&cache, secs(30)), [("d", 4, 5), ("b", 2, 10),
+                                            ("c", 3, 30)]);
+        assert_eq!(soon(&cache, secs(29)), [("d", 4, 5), ("b", 2, 10)]);
+        assert_eq!(soon(&cache, secs(0)), []);
+
+        // Expired items are left out, and new deadlines replace old ones.
+        clock.advance(secs(5));
+        cache.extend_ttl(&"b", secs(20));
+        cache.insert("c", 30);
+
+        assert_eq!(soon(&cache, secs(35)), [("b", 2, 25), ("a", 1, 35)]);
+        assert_eq!(cache.frequency(&"a"), Some(1));
+    }
+
+    #[test]
+    fn inserted_values_can_be_changed_in_place() {
+        let mut cache = LfuCache::new(2);
+
+        cache.insert_and_get_mut(1, vec![1]).push(2);
+        assert_eq!(cache.frequency(&1), Some(1));
+        assert_eq!(cache.peek_value(&1), Some(&vec![1, 2]));
+
+        // A write over a cached key counts, but the reference doesn't.
+        let value = cache.insert_and_get_mut(1, vec![3]);
+
+        assert_eq!(value, &[3]);
+        value.push(4);
+        assert_eq!(cache.frequency(&1), Some(2));
+        assert_eq!(cache.peek_value(&1), Some(&vec![3, 4]));
+
+        // A full cache evicts to make room.
+        cache.insert(2, vec![]);
+        *cache.insert_and_get_mut(3, vec![]) = vec![5];
+
+        assert!(!cache.contains_key(&2));
+        assert_eq!(cache.peek_value(&3), Some(&vec![5]));
+        assert_eq!(cache.frequency(&3), Some(1));
+
+        // Admission control doesn't get a say.
+        let mut cache = LfuCacheBuilder::new(1).admission().build();
+
+        cache.insert(1, 1);
+        for _ in 0..5 { cache.get(&1); }
+
+        assert_eq!(cache.insert_and_get_mut(2, 2), &2);
+        assert_eq!(cache.len(), 1);
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn expiry_index_follows_rescheduling() {
+        let clock     = ManualClock::new();
+        let ms        = Duration::from_millis;
+        let mut cache = LfuCache::new(6);
+        let soon      = |cache: &LfuCache<_, _>, window| {
+            cache.iter_expiring_within(window).map(|(key, _, _)| *key)
+                                              .collect::<Vec<_>>()
+        };
+        cache.set_clock(clock.clone());
+
+        // Around the ends of the expiry wheel's first two levels, of 64 and
+        // 4096 ticks of a millisecond.
+        for (key, ttl) in [(1, 63), (2, 64), (3, 65), (4, 4095), (5, 4096)] {
+            cache.insert_with_ttl(key, key, ms(ttl));
+        }
+        cache.insert_with_ttl(6, 6, ms(10));
+        cache.remove(&6);
+        cache.check_invariants();
+
+        clock.advance(ms(63));
+        assert_eq!(cache.purge_expired(), 1);
+        clock.advance(ms(1));
+        assert_eq!(cache.purge_expired(), 1);
+
+        // An extended TTL moves the item past the end of the first level, 
+        // and a rewrite without a TTL takes it out of the index.
+        assert!(cache.extend_ttl(&3, ms(4000)));
+        cache.insert(4, 40);
+        clock.advance(ms(1));
+
+        assert_eq!(cache.purge_expired(), 0);
+        assert_eq!(soon(&cache, ms(4001)), [3]);
+        cache.check_invariants();
+
+        clock.advance(ms(4000));
+        assert_eq!(cache.purge_expired(), 1);
+        clock.advance(ms(30));
+        assert_eq!(cache.purge_expired(), 0);
+        clock.advance(ms(1));
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.get(&4), Some(&40));
+
+        // Evicted items leave the index along with the cache.
+        for key in 10..20 { cache.insert_with_ttl(key, key, ms(100)); }
+
+        assert_eq!(cache.len(), 6);
+        cache.check_invariants();
+        clock.advance(ms(100));
+        assert_eq!(cache.purge_expired(), 5);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn deferred_accesses_count_once_committed() {
+        let mut cache = LfuCache::new(2);
+
+        cache.enable_stats();
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+
+        // Committed, the access counts; cancelled or just dropped, it doesn't.
+        let guard = cache.get_deferred(&1).unwrap();
+
+        assert_eq!((*guard, guard.key()), ("one", &1));
+        guard.commit();
+        assert_eq!(cache.frequency(&1), Some(2));
+
+        cache.get_deferred(&1).unwrap().cancel();
+        drop(cache.get_deferred(&2));
+
+        assert_eq!(cache.frequency(&1), Some(2));
+        assert_eq!(cache.frequency(&2), Some(1));
+        assert_eq!(cache.stats().map(|stats| stats.hits), Some(1));
+
+        // Unless the guard was told to commit when it's dropped.
+        {
+            let guard = cache.get_deferred(&2).unwrap().commit_on_drop();
+
+            assert_eq!(guard.len(), 3);
+        }
+        assert_eq!(cache.frequency(&2), Some(2));
+
+        cache.get_deferred(&2).unwrap().commit_on_drop().cancel();
+        assert_eq!(cache.frequency(&2), Some(2));
+
+        // Nor does a guard count an access that a panic cut short.
+        let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = cache.get_deferred(&2).unwrap().commit_on_drop();
+            panic!("validation failed");
+        }));
+        assert!(unwound.is_err());
+        assert_eq!(cache.frequency(&2), Some(2));
+
+        // A miss counts right away.
+        assert!(cache.get_deferred(&3).is_none());
+        assert_eq!(cache.stats().map(|stats| (stats.hits, stats.misses)), 
+                   Some((2, 1)));
+        cache.check_invariants();
+    }
+
+    #[test]
+    fn hot_entries_warm_a_peer() {
+        let mut cache = LfuCache::new(8);
+
+        for key in 0..8 {
+            cache.insert(key, key * 10);
+            for _ in 0..key % 3 { cache.get(&key); }
+        }
+        let mut order = cache.eviction_order().copied().collect::<Vec<_>>();
+
+        order.reverse();
+
+        // The hottest items, in the reverse of the eviction order.
+        let hot = cache.hot_entries(5);
+
+        assert_eq!(hot.iter().map(|(key, _, _)| *key).collect::<Vec<_>>(), 
+                   order[..5]);
+        assert!(hot.iter().all(|&(key, value, freq)| {
+            value == key * 10 && cache.frequency(&key) == Some(freq)
+        }));
+        assert!(hot.windows(2).all(|pair| pair[0].2 >= pair[1].2));
+        assert_eq!(cache.hot_entries(20).len(), 8);
+        assert_eq!(cache.frequency(&2), Some(3));
+
+        // A fresh cache warmed with all of them orders them the same way.
+        let mut peer = LfuCache::new(8);
+
+        peer.warm_from(cache.hot_entries(8));
+        assert!(peer.eviction_order().eq(cache.eviction_order()));
+        assert!(peer.entries().eq(cache.entries()));
+        peer.check_invariants();
+
+        // One with less room keeps the hottest.
+        let mut small = LfuCache::new(3);
+
+        small.warm_from(cache.hot_entries(8));
+        assert_eq!(small.hot_entries(3), cache.hot_entries(3));
+        small.check_invariants();
+    }
+
+    // The shadow model of `debug-verify` hashes keys of its own.
+    #[cfg(not(feature = "debug-verify"))]
+    #[test]
+    fn operations_hash_their_key_once() {
+        use std::cell::Cell;
+        use std::hash::Hasher;
+
+        thread_local! {
+            static HASHED: Cell<usize> = const { Cell::new(0) };
+        }
+        #[derive(Clone, PartialEq, Eq)]
+        struct Counted(u32);
+
+        impl Hash for Counted {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                HASHED.with(|hashed| hashed.set(hashed.get() + 1));
+                self.0.hash(state);
+            }
+        }
+        let hashes = |f: &mut dyn FnMut()| {
+            let before = HASHED.with(Cell::get);
+            f();
+            HASHED.with(Cell::get) - before
+        };
+        let mut cache = LfuCache::new(1000);
+
+        assert_eq!(hashes(&mut || {
+            for key in 0..1000 { cache.insert(Counted(key), key); }
+        }), 1000);
+        assert_eq!(hashes(&mut || { cache.get(&Counted(7)); }), 1);
+        assert_eq!(hashes(&mut || { cache.insert(Counted(7), 70); }), 1);
+        assert_eq!(hashes(&mut || { cache.observe(&Counted(7)); }), 1);
+        assert_eq!(hashes(&mut || { cache.remove(&Counted(7)); }), 1);
+        assert_eq!(hashes(&mut || { cache.get(&Counted(7)); }), 1);
+        assert_eq!(hashes(&mut || { cache.lookup(&Counted(8)); }), 1);
+        assert_eq!(hashes(&mut || { cache.get_versioned(&Counted(8)); }), 1);
+        assert_eq!(hashes(&mut || { 
+            assert!(cache.try_insert(Counted(7), 7).is_ok()); 
+        }), 1);
+
+        // The victim of an eviction is found by the hash its queue keeps, and
+        // so are the LFU item's value and the item `pop_lfu_if()` removes.
+        assert_eq!(hashes(&mut || { cache.insert(Counted(1000), 0); }), 1);
+        assert_eq!(hashes(&mut || { cache.peek_lfu_mut(); }), 0);
+        assert_eq!(hashes(&mut || { cache.pop_lfu_if(|_, _| true); }), 0);
+        assert_eq!(hashes(&mut || { cache.pop_lfu(); }), 0);
+    }
+
+    #[cfg(feature = "debug-verify")]
+    #[test]
+    fn shadow_model_follows_other_changes() {
+        let mut cache = LfuCache::new(3);
+
+        for key in 0..3 { cache.insert(key, key); }
+
+        // Changes the model can't follow lose it, and it's rebuilt before the
+        // next operation it checks.
+        cache.observe(&0);
+        cache.insert_with_frequency(1, 1, 5);
+        assert!(!cache.shadow.is_synced());
+
+        cache.insert(3, 3);
+        assert!(cache.shadow.is_synced());
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.pop_lfu(), Some((3, 3)));
+
+        cache.retain_mut(|key, _, _| *key != 0);
+        cache.get(&1);
+        assert!(cache.shadow.is_synced());
+        assert_eq!(cache.shadow.len(), 1);
+
+        cache.invalidate_all();
+        cache.insert(4, 4);
+        assert!(cache.shadow.is_synced());
+
+        // Nor does it follow admission control.
+        cache.enable_admission();
+        cache.insert(5, 5);
+        assert!(!cache.shadow.is_synced());
+    }
+
+    #[cfg(feature = "debug-verify")]
+    #[test]
+    #[should_panic(expected = "get() diverged from the model")]
+    fn shadow_model_catches_a_fault() {
+        let mut cache = LfuCache::new(2);
+
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+
+        // The cache stops counting accesses.
+        crate::verify::inject_fault(true);
+        cache.get(&1);
+    }
+
+    #[test]
+    fn iteration_doesnt_depend_on_the_hasher() {
+        // With the default hasher, each cache is seeded randomly.
+        let run = || {
+            let mut cache = LfuCache::new(64);
+
+            for i in 0..500_u32 {
+                cache.insert(i % 100, i);
+                cache.get(&(i * 7 % 100));
+
+                if i % 11 == 0 { cache.remove(&(i % 13)); }
+            }
+            cache
+        };
+        let (a, b) = (run(), run());
+
+        assert!(a.iter().eq(b.iter()));
+        assert!(a.entries().eq(b.entries()));
+        assert!(a.iter().map(|(key, _)| key).eq(a.eviction_order()));
+        assert!(a.entries().all(|(key, _, n)| a.frequency(key) == Some(n)));
+
+        let mut unordered = a.iter_unordered().collect::<Vec<_>>();
+        let mut ordered   = a.iter().collect::<Vec<_>>();
+
+        unordered.sort();
+        ordered.sort();
+        assert_eq!(unordered, ordered);
+        assert_eq!(a.entries_unordered().count(), a.len());
+    }
+
+    #[test]
+    fn small_counters_match_until_they_saturate() {
+        fn cache<C: Counter>() -> LfuCache<String, usize, SeededState, C> {
+            LfuCache::with_counter(16, SeededState::new(9))
+        }
+        let mut wide  = cache::<usize>();
+        let mut short = cache::<u16>();
+        let mut tiny  = cache::<u8>();
+        let mut rng   = crate::rng::Rng::new(5);
+
+        for i in 0..20_000 {
+            let key = format!("k{}", rng.next_u64() % 40);
+
+            match rng.next_u64() % 4 {
+                0 => {
+                    wide.insert(key.clone(), i);
+                    short.insert(key.clone(), i);
+                    tiny.insert(key, i);
+                },
+                1 => {
+                    let value = wide.remove(&key);
+
+                    assert_eq!(short.remove(&key), value);
+                    assert_eq!(tiny.remove(&key), value);
+                },
+                _ => {
+                    let value = wide.get(&key).copied();
+
+                    assert_eq!(short.get(&key).copied(), value);
+                    assert_eq!(tiny.get(&key).copied(), value);
+                },
+            }
+            assert!(wide.entries().eq(short.entries()));
+            assert!(wide.entries().eq(tiny.entries()));
+        }
+        // Past 255 accesses, only the `u8` counts fall behind.
+        let hot = wide.eviction_order().last().unwrap().clone();
+
+        for _ in 0..300 {
+            wide.get(&hot);
+            short.get(&hot);
+            tiny.get(&hot);
+        }
+        assert!(wide.frequency(&hot).unwrap() > 300);
+        assert_eq!(short.frequency(&hot), wide.frequency(&hot));
+        assert_eq!(tiny.frequency(&hot), Some(255));
+        assert!(wide.eviction_order().eq(tiny.eviction_order()));
+        tiny.check_invariants();
+    }
+
+    #[test]
+    fn saturated_counts_stay_in_their_queue() {
+        let mut cache = LfuCache::<_, _, SeededState, u8>::with_counter(
+                            4, SeededState::new(1));
+
+        cache.insert(1, ());
+        cache.insert(2, ());
+        for _ in 0..300 { cache.get(&1); }
+        for _ in 0..254 { cache.get(&2); }
+        cache.check_invariants();
+        assert_eq!(cache.frequency_histogram(), [(255, 2)]);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), [&1, &2]);
+
+        // No queue past 255: an access moves the item to the back of its own.
+        cache.get(&1);
+        cache.check_invariants();
+        assert_eq!(cache.frequency_histogram(), [(255, 2)]);
+        assert_eq!(cache.eviction_order().collect::<Vec<_>>(), [&2, &1]);
+        assert_eq!(cache.entries().map(|(_, _, n)| n).collect::<Vec<_>>(),
+                   [255, 255]);
+
+        // Counts given outright saturate too.
+        cache.insert_with_frequency(3, (), 1000);
+        assert_eq!(cache.frequency(&3), Some(255));
+        cache.check_invariants();
+
+        // Smaller counts make smaller value records.
+        assert!(size_of::<Value<(), u16>>() < size_of::<Value<(), usize>>());
+        assert!(size_of::<Value<(), u8>>() < size_of::<Value<(), usize>>());
+    }
+
+    #[test]
+    fn hasher_doesnt_change_behavior() {
+        // Only the order of `iter_unordered()` may depend on the hasher.
+        let mut random = LfuCache::with_hasher(16, RandomState::new());
+        let mut seeded = LfuCache::with_hasher(16, SeededState::new(9));
+        let mut rng    = crate::rng::Rng::new(5);
+
+        for i in 0..20_000 {
+            let key = format!("k{}", rng.next_u64() % 40);
+
+            match rng.next_u64() % 4 {
+                0 => {
+                    random.insert(key.clone(), i);
+                    seeded.insert(key, i);
+                },
+                1 => assert_eq!(random.remove(&key), seeded.remove(&key)),
+                _ => assert_eq!(random.get(&key), seeded.get(&key)),
+            }
+            assert!(random.eviction_order().eq(seeded.eviction_order()));
+        }
+        assert_eq!(random.pop_lfu(), seeded.pop_lfu());
+    }
+
+    #[test]
+    fn matches_a_naive_model() {
+        // The model keeps `(key, value, frequency, last use)` and evicts the
+        // lowest frequency, least recently used first.
+        let mut model = Vec::<(u32, usize, usize, usize)>::new();
+        let mut cache = LfuCache::new(8);
+        let mut rng   = crate::rng::Rng::new(3);
+
+        for tick in 0..20_000 {
+            let key = (rng.next_u64() % 24) as u32;
+            let op  = rng.next_u64() % 8;
+            let pos = model.iter().position(|e| e.0 == key);
+
+            match op {
+                0..=3 => {
+                    let hit = pos.map(|i| {
+                        model[i].2 += 1;
+                        model[i].3  = tick;
+                        &model[i].1
+                    });
+                    assert_eq!(cache.get(&key), hit);
+                },
+                4..=6 => {
+                    if let Some(i) = pos {
+                        model[i] = (key, tick, model[i].2 + 1, tick);
+                    } else {
+                        if model.len() == 8 {
+                            let lfu = (0..8).min_by_key(|&i| {
+                                (model[i].2, model[i].3)
+                            });
+                            model.swap_remove(lfu.unwrap());
+                        }
+                        model.push((key, tick, 1, tick));
+                    }
+                    cache.insert(key, tick);
+                },
+                _ => {
+                    let lfu = (0..model.len()).min_by_key(|&i| {
+                        (model[i].2, model[i].3)
+                    });
+                    let lfu = lfu.map(|i| model.swap_remove(i));
+                    assert_eq!(cache.pop_lfu(), lfu.map(|e| (e.0, e.1)));
+                },
+            }
+            cache.check_invariants();
+            assert_eq!(cache.len(), model.len());
+        }
+    }
+
+    #[test]
+    fn recycled_slots_change_nothing() {
+        // A weigher keeps inserts into a full cache off the path that 
+        // recycles the victim's node, so the two caches should stay alike.
+        for policy in [Policy::Lfu, Policy::LfuDa] {
+            let mut fast = LfuCacheBuilder::new(16).policy(policy).build();
+            let mut slow = LfuCacheBuilder::new(16).policy(policy).build();
+            let mut rng  = crate::rng::Rng::new(735);
+
+            slow.set_weigher(usize::MAX, |_, _| 1);
+            fast.enable_stats();
+            slow.enable_stats();
+
+            for tick in 0..20_000 {
+                let key = format!("key {}", rng.next_u64() % 64);
+
+                match rng.next_u64() % 8 {
+                    0..=2 => assert_eq!(fast.get(&key), slow.get(&key)),
+                    3     => assert_eq!(fast.pop_lfu(), slow.pop_lfu()),
+                    _     => assert_eq!(fast.insert_checked(key.clone(), tick),
+                                        slow.insert_checked(key, tick)),
+                }
+                assert!(fast.entries().eq(slow.entries()));
+                fast.check_invariants();
+            }
+            let evictions = |cache: &LfuCache<String, i32>| {
+                cache.stats().map(|stats| stats.evictions)
+            };
+            assert!(evictions(&fast) > Some(1000));
+            assert_eq!(evictions(&fast), evictions(&slow));
+        }
+    }
+
+    #[test]
+    fn heavy_insert_evicts_several() {
+        let mut cache = LfuCache::new(10);
+
+        cache.set_weigher(3, |key: &u32, _: &()| if *key == 4 { 3 } else { 1 });
+        cache.insert(1, ());
+        cache.insert(2, ());
+        cache.insert(3, ());
+        cache.get(&2);
+        cache.get(&3);
+
+        // Key 1's emptied queue doesn't stop key 2 from being evicted next.
+        cache.insert(4, ());
+        cache.check_invariants();
+        assert_eq!(cache.iter().map(|(k, _)| *k).collect::<Vec<_>>(), [4]);
+        assert_eq!(cache.weight(), 3);
+    }
+
+    #[test]
+    fn value_records_fit_the_budget() {
+        use std::mem::size_of;
+
+        // Two queue handles, which are bigger in debug builds, and the rest.
+        let handles = 2 * size_of::<HNode>();
+        let budget  = if cfg!(feature = "compact") { 64 } else { 80 };
+
+        assert!(size_of::<Value<()>>() <= handles + budget);
+
+        // The map is allocated up front, the queues as items come in.
+        let mut cache = LfuCache::new(100);
+        let empty     = cache.memory_footprint();
+
+        assert!(empty >= 100 * size_of::<(u32, Value<()>)>());
+
+        for i in 0..100 { cache.insert(i, ()); }
+        assert!(cache.memory_footprint() > empty);
+    }
+
+    #[test]
+    fn warm_state_restores_frequencies() {
+        let mut cache = LfuCache::new(10);
+
+        for i in 0..10u32 {
+            cache.insert(i, i * 10);
+            for _ in 0..i % 4 { cache.get(&i); }
+        }
+        let state = cache.export_warm_state();
+
+        assert_eq!(state.entries.len(), 10);
+        assert_eq!(state.entries[0], (0, 1));
+
+        // The loader declines the multiples of 3.
+        let load = |key: &u32| (!key.is_multiple_of(3)).then_some(key * 10);
+        let mut warm = LfuCache::new(10);
+
+        assert_eq!(warm.apply_warm_state(state.clone(), load), 6);
+        warm.check_invariants();
+        for (key, freq) in &state.entries {
+            if key.is_multiple_of(3) {
+                assert!(!warm.contains_key(key));
+            } else {
+                assert_eq!(warm.frequency(key), Some(*freq));
+                assert_eq!(warm.peek_value(key), Some(&(key * 10)));
+            }
+        }
+        let order = cache.eviction_order().filter(|key| !key.is_multiple_of(3));
+        assert!(warm.eviction_order().eq(order));
+
+        // Into a smaller cache, the hottest keys are the ones left.
+        let mut small = LfuCache::new(3);
+
+        assert_eq!(small.apply_warm_state(state, |key| Some(*key)), 10);
+        small.check_invariants();
+        assert!(small.eviction_order().eq(cache.eviction_order().skip(7)));
+    }
+
+    #[test]
+    fn test_1() {
+        let null = i32::MIN;
+        let cmd  = ["LfuCache","put","put","get","put","get",
+                    "get","put","get","get","get"];
+        let data = vec2d![[2],[1,1],[2,2],[1],[3,3],[2],[3],[4,4],[1],[3],[4]];
+        let exp  = [null,null,null,1,null,-1,3,null,-1,3,4];
+
+        let mut cache = None;
+
+        for ((cmd, data), exp) in cmd.into_iter().zip(data).zip(exp) {
+            match cmd {
+                "LfuCache" => {
+                    cache = Some(LfuCache::new(data[0] as usize));
+                },
+                "put" => {
+                    if let Some(cache) = &mut cache {
+                        cache.insert(data[0], data[1]);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                "get" => {
+                    if let Some(cache) = &mut cache {
+                        assert_eq!(cache.get(&data[0]).map_or(-1, |v| *v), exp);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                _ => panic!("Bad command!"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_2() {
+        let null = i32::MIN;
+        let cmd  = ["LfuCache","put","get"];
+        let data = vec2d![[0],[0,0],[0]];
+        let exp  = [null,null,-1];
+
+        let mut cache = None;
+
+        for ((cmd, data), exp) in cmd.into_iter().zip(data).zip(exp) {
+            match cmd {
+                "LfuCache" => {
+                    cache = Some(LfuCache::new(data[0] as usize));
+                },
+                "put" => {
+                    if let Some(cache) = &mut cache {
+                        cache.insert(data[0], data[1]);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                "get" => {
+                    if let Some(cache) = &mut cache {
+                        assert_eq!(cache.get(&data[0]).map_or(-1, |v| *v), exp);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                _ => panic!("Bad command!"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_3() {
+        let null = i32::MIN;
+        let cmd  = ["LfuCache","put","get","put","get","get","get","put","get","put","put","put","put","put","put","get","put","put","get","put","put","put","put","put","put","put","get","put","put","put","put","put","put","get","put","put","get","put","get","put","get","put","put","get","put","put","put","get","get","put","put","get","put","get","get","get","get","put","get","put","put","put","get","put","put","get","get","get","put","put","put","put","get","get","get","get","put","get","put","get","put","put","get","get","get","get","get","get","put","put","get","get","put","get","put","put","get","get","put","put","put","put","put","get","put","get","get","put","get","put","put","put","put","get","put","put","put","put","get","get","get","get","put","put","get","get","put","put","put","put","put","put","put","get","put","put","get","get","put","put","put","put","put","put","put","put","put","put","put","get","put","get","get","get","put","put","put","put","put","get","put","put","get","put","put","get","put","get","put","get","put","get","get","get","get","put","get","get","get","put","put","put","get","get","get","put","put","put","put","get","get","get","put","get","get","get","get","put","get","put","put","get","get","put","put","get","get","put","put","put","get","put","get","get","put","put","put","get","put","get","put","get","put","get","put","get","put","put","get","put","put","get","put","get","get","put","get","get","get","put","get","put","put","put","put","get","get","put","get","put","get","put","get","put","get","get","get","get","get","put","put","get","put","get","put","put","get","get","put","get","get","put","get","put","put","get","put","put","get","get","put","put","get","get","put","get","put","put","get","put","put","put","get","put","put","get","get","get","get","put","put","get","get","put","put","get","put","put","put","put","get","put","get","put","get","get","put","get","put","put","get","get","put","get","put","put","get","put","get","put","put","put","put","get","get","get","put","put","put","put","get","get","put","put","get","put","put","get","put","put","get","put","put","put","put","put","get","get","put","put","put","get","put","get","get","put","put","get","put","put","put","put","put","get","put","get","get","put","get","put","put","get","get","put","put","put","put","put","put","get","put","get","get","get","get","put","get","put","put","get","put","get","put","put","get","put","put","put","put","put","put","put","put","put","put","put","get","put","get","put","get","put","put","put","get","put","get","get","put","get","get","put","get","put","put","get","get","get","get","get","put","put","put","get","put","get","put","get","get","get","put","get","put","put","put","get","get","get","get","put","put","put","put","put","put","get","get","put","put","get","put","put","get","put","get","get","get","get","put","get","get","get","put","get","get","put","put","get","put","put","put","put","get","put","put","get","put","put","put","put","put","get","put","put","put","put","put","put","put","get","put","put","put","put","put","put","get","get","put","get","get","get","get","get","put","put","put","put","put","put","put","get","put","put","get","put","get","put","put","get","put","put","get","put","put","get","get","get","get","put","put","put","get","get","put","get","put","put","put","put","get","put","put","get","get","put","put","put","put","put","put","put","put","put","get","get","put","put","get","put","put","put","put","get","put","get","get","put","get","put","get","put","put","put","put","get","put","put","get","put","put","get","put","put","get","get","put","put","get","put","put","put","put","get","get","get","put","put","get","put","get","put","put","put","put","get","put","put","put","put","get","get","put","put","get","get","put","put","put","put","get","put","put","put","get","get","get","put","put","put","put","get","put","get","get","put","put","put","put","put","get","get","put","put","put","get","put","put","put","put","get","put","put","get","get","put","put","get","get","put","put","get","put","put","put","put","get","get","get","get","put","get","get","put","put","put","get","get","put","put","get","get","get","put","get","get","get","put","get","get","get","put","put","put","put","get","put","put","put","get","get","put","get","put","put","get","get","put","get","get","get","get","put","put","put","put","put","put","put","put","get","get","put","put","put","get","put","get","get","get","put","put","get","put","put","put","put","put","put","get","put","put","put","put","get","put","put","get","put","get","put","get","put","get","put","get","put","put","put","get","put","get","get","get","get","get","put","put","put","get","put","put","put","get","get","put","put","put","put","get","put","put","put","put","put","put","get","get","put","put","put","put","put","put","get","put","put","put","get","get","put","put","put","put","put","put","put","put","put","get","put","put","get","get","get","get","put","put","get","put","get","get","get","put","put","put","put","put","put","put","get","get","put","put","put","put","put","get","get","put","put","get","put","get","put","put","put","get","put","get","put","get","get","put","get","get","get","put","put","put","put","get","put","get","put","put","put","put","put","put","get","put","put","get","put","put","get","put","put","put","put","get","put","put","get","get","put","put","put","put","get","put","get","put","put","put","put","put","put","put","put","put","get","put","put","put","put","get","get","get","put","get","get","put","put","put","put","get","put","get","get","get","put","get","get","put","get","put","get","put","put","get","put","get","get","get","get","put","put","get","put","get","get","get","get","put","put","put","get","put","get","put","get","put","get","get","put","get","get","put","put","put","put","get","get","put","put","put","get","put","get","put","put","get","put","get","put","get","put","get","get","put","get","get","put","put","put","get","put","put","get","get","put","get","put","get","put","put","get","get"];
+        let data = vec2d![[105],[33,219],[39],[96,56],[129],[115],[112],[3,280],[40],[85,193],[10,10],[100,136],[12,66],[81,261],[33,58],[3],[121,308],[129,263],[105],[104,38],[65,85],[3,141],[29,30],[80,191],[52,191],[8,300],[136],[48,261],[3,193],[133,193],[60,183],[128,148],[52,176],[48],[48,119],[10,241],[124],[130,127],[61],[124,27],[94],[29,304],[102,314],[110],[23,49],[134,12],[55,90],[14],[104],[77,165],[60,160],[117],[58,30],[54],[136],[128],[131],[48,114],[136],[46,51],[129,291],[96,207],[131],[89,153],[120,154],[111],[47],[5],[114,157],[57,82],[113,106],[74,208],[56],[59],[100],[132],[127,202],[75],[102,147],[37],[53,79],[119,220],[47],[101],[89],[20],[93],[7],[48,109],[71,146],[43],[122],[3,160],[17],[80,22],[80,272],[75],[117],[76,204],[74,141],[107,93],[34,280],[31,94],[132],[71,258],[61],[60],[69,272],[46],[42,264],[87,126],[107,236],[131,218],[79],[41,71],[94,111],[19,124],[52,70],[131],[103],[81],[126],[61,279],[37,100],[95],[54],[59,136],[101,219],[15,248],[37,91],[11,174],[99,65],[105,249],[85],[108,287],[96,4],[70],[24],[52,206],[59,306],[18,296],[79,95],[50,131],[3,161],[2,229],[39,183],[90,225],[75,23],[136,280],[119],[81,272],[106],[106],[70],[73,60],[19,250],[82,291],[117,53],[16,176],[40],[7,70],[135,212],[59],[81,201],[75,305],[101],[8,250],[38],[28,220],[21],[105,266],[105],[85],[55],[6],[78,83],[126],[102],[66],[61,42],[127,35],[117,105],[128],[102],[50],[24,133],[40,178],[78,157],[71,22],[25],[82],[129],[126,12],[45],[40],[86],[100],[30,110],[49],[47,185],[123,101],[102],[5],[40,267],[48,155],[108],[45],[14,182],[20,117],[43,124],[38],[77,158],[111],[39],[69,126],[113,199],[21,216],[11],[117,207],[30],[97,84],[109],[99,218],[109],[113,1],[62],[49,89],[53,311],[126],[32,153],[14,296],[22],[14,225],[49],[75],[61,241],[7],[6],[31],[75,15],[115],[84,181],[125,111],[105,94],[48,294],[106],[61],[53,190],[16],[12,252],[28],[111,122],[122],[10,21],[59],[72],[39],[6],[126],[131,177],[105,253],[26],[43,311],[79],[91,32],[7,141],[38],[13],[79,135],[43],[94],[80,182],[53],[120,309],[3,109],[97],[9,128],[114,121],[56],[56],[124,86],[34,145],[131],[78],[86,21],[98],[115,164],[47,225],[95],[89,55],[26,134],[8,15],[11],[84,276],[81,67],[46],[39],[92],[96],[89,51],[136,240],[45],[27],[24,209],[82,145],[10],[104,225],[120,203],[121,108],[11,47],[89],[80,66],[16],[95,101],[49],[1],[77,184],[27],[74,313],[14,118],[16],[74],[88,251],[124],[58,101],[42,81],[2],[133,101],[16],[1,254],[25,167],[53,56],[73,198],[48],[30],[95],[90,102],[92,56],[2,130],[52,11],[9],[23],[53,275],[23,258],[57],[136,183],[75,265],[85],[68,274],[15,255],[85],[33,314],[101,223],[39,248],[18,261],[37,160],[112],[65],[31,240],[40,295],[99,231],[123],[34,43],[87],[80],[47,279],[89,299],[72],[26,277],[92,13],[46,92],[67,163],[85,184],[38],[35,65],[70],[81],[40,65],[80],[80,23],[76,258],[69],[133],[123,196],[119,212],[13,150],[22,52],[20,105],[61,233],[97],[128,307],[85],[80],[73],[30],[46,44],[95],[121,211],[48,307],[2],[27,166],[50],[75,41],[101,105],[2],[110,121],[32,88],[75,84],[30,165],[41,142],[128,102],[105,90],[86,68],[13,292],[83,63],[5,239],[5],[68,204],[127],[42,137],[93],[90,258],[40,275],[7,96],[108],[104,91],[63],[31],[31,89],[74],[81],[126,148],[107],[13,28],[21,139],[114],[5],[89],[133],[20],[96,135],[86,100],[83,75],[14],[26,195],[37],[1,287],[79],[15],[6],[68,11],[52],[124,80],[123,277],[99,281],[133],[90],[45],[127],[9,68],[123,6],[124,251],[130,191],[23,174],[69,295],[32],[37],[1,64],[48,116],[68],[117,173],[16,89],[84],[28,234],[129],[89],[55],[83],[99,264],[129],[84],[14],[26,274],[109],[110],[96,120],[128,207],[12],[99,233],[20,305],[26,24],[102,32],[82],[16,30],[5,244],[130],[109,36],[134,162],[13,165],[45,235],[112,80],[6],[34,98],[64,250],[18,237],[72,21],[42,105],[57,108],[28,229],[83],[1,34],[93,151],[132,94],[18,24],[57,68],[42,137],[35],[80],[10,288],[21],[115],[131],[30],[43],[97,262],[55,146],[81,112],[2,212],[5,312],[82,107],[14,151],[77],[60,42],[90,309],[90],[131,220],[86],[106,85],[85,254],[14],[66,262],[88,243],[3],[50,301],[118,91],[25],[105],[100],[89],[111,152],[65,24],[41,264],[117],[117],[80,45],[38],[11,151],[126,203],[128,59],[6,129],[91],[118,2],[50,164],[74],[80],[48,308],[109,82],[3,48],[123,10],[59,249],[128,64],[41,287],[52,278],[98,151],[12],[25],[18,254],[24,40],[119],[66,44],[61,19],[80,132],[62,111],[80],[57,188],[132],[42],[18,314],[48],[86,138],[8],[27,88],[96,178],[17,104],[112,86],[25],[129,119],[93,44],[115],[33,36],[85,190],[10],[52,182],[76,182],[109],[118],[82,301],[26,158],[71],[108,309],[58,132],[13,299],[117,183],[115],[89],[42],[11,285],[30,144],[69],[31,53],[21],[96,162],[4,227],[77,120],[128,136],[92],[119,208],[87,61],[9,40],[48,273],[95],[35],[62,267],[88,161],[59],[85],[131,53],[114,98],[90,257],[108,46],[54],[128,223],[114,168],[89,203],[100],[116],[14],[61,104],[44,161],[60,132],[21,310],[89],[109,237],[105],[32],[78,101],[14,71],[100,47],[102,33],[44,29],[85],[37],[68,175],[116,182],[42,47],[9],[64,37],[23,32],[11,124],[130,189],[65],[33,219],[79,253],[80],[16],[38,18],[35,67],[107],[88],[37,13],[71,188],[35],[58,268],[18,260],[73,23],[28,102],[129],[88],[65],[80],[119,146],[113],[62],[123,138],[18,1],[26,208],[107],[107],[76,132],[121,191],[4],[8],[117],[11,118],[43],[69],[136],[66,298],[25],[71],[100],[26,141],[53,256],[111,205],[126,106],[43],[14,39],[44,41],[23,230],[131],[53],[104,268],[30],[108,48],[72,45],[58],[46],[128,301],[71],[99],[113],[121],[130,122],[102,5],[111,51],[85,229],[86,157],[82,283],[88,52],[136,105],[40],[63],[114,244],[29,82],[83,278],[131],[56,33],[123],[11],[119],[119,1],[48,52],[47],[127,136],[78,38],[117,64],[130,134],[93,69],[70,98],[68],[4,3],[92,173],[114,65],[7,309],[31],[107,271],[110,69],[45],[35,288],[20],[38,79],[46],[6,123],[19],[84,95],[76],[71,31],[72,171],[35,123],[32],[73,85],[94],[128],[28],[38],[109],[85,197],[10,41],[71,50],[128],[3,55],[15,9],[127,215],[17],[37],[111,272],[79,169],[86,206],[40,264],[134],[16,207],[27,127],[29,48],[32,122],[15,35],[117,36],[127],[36],[72,70],[49,201],[89,215],[134,290],[77,64],[26,101],[99],[36,96],[84,129],[125,264],[43],[38],[24,76],[45,2],[32,24],[84,235],[16,240],[17,289],[49,94],[90,54],[88,199],[23],[87,19],[11,19],[24],[57],[4],[40],[133,286],[127,231],[51],[52,196],[27],[10],[93],[115,143],[62,64],[59,200],[75,85],[7,93],[117,270],[116,6],[32],[135],[2,140],[23,1],[11,69],[89,30],[27,14],[100],[61],[99,41],[88,12],[41],[52,203],[65],[62,78],[104,276],[105,307],[7],[23,123],[22],[35,299],[69],[11],[14,112],[115],[112],[108],[110,165],[83,165],[36,260],[54,73],[36],[93,69],[134],[125,96],[74,127],[110,305],[92,309],[87,45],[31,266],[10],[114,206],[49,141],[82],[92,3],[91,160],[41],[60,147],[36,239],[23,296],[134,120],[6],[5,283],[117,68],[35],[120],[44,191],[121,14],[118,113],[84,106],[23],[15,240],[37],[52,256],[119,116],[101,7],[14,157],[29,225],[4,247],[8,112],[8,189],[96,220],[104],[72,106],[23,170],[67,209],[70,39],[18],[6],[34],[121,157],[16],[19],[83,283],[13,22],[33,143],[88,133],[88],[5,49],[38],[110],[67],[23,227],[68],[3],[27,265],[31],[13,103],[116],[111,282],[43,71],[134],[70,141],[14],[119],[43],[122],[38,187],[8,9],[63],[42,140],[83],[92],[106],[28],[57,139],[36,257],[30,204],[72],[105,243],[16],[74,25],[22],[118,144],[133],[71],[99,21],[26],[35],[89,209],[106,158],[76,63],[112,216],[128],[54],[16,165],[76,206],[69,253],[23],[54,111],[80],[111,72],[95,217],[118],[4,146],[47],[108,290],[43],[70,8],[117],[121],[42,220],[48],[32],[68,213],[30,157],[62,68],[58],[125,283],[132,45],[85],[92],[23,257],[74],[18,256],[90],[10,158],[57,34],[27],[107]];
+        let exp  = [null,null,-1,null,-1,-1,-1,null,-1,null,null,null,null,null,null,280,null,null,-1,null,null,null,null,null,null,null,-1,null,null,null,null,null,null,261,null,null,-1,null,-1,null,-1,null,null,-1,null,null,null,-1,38,null,null,-1,null,-1,-1,148,-1,null,-1,null,null,null,-1,null,null,-1,-1,-1,null,null,null,null,-1,-1,136,-1,null,-1,null,-1,null,null,-1,-1,153,-1,-1,-1,null,null,-1,-1,null,-1,null,null,-1,-1,null,null,null,null,null,-1,null,-1,160,null,51,null,null,null,null,-1,null,null,null,null,218,-1,261,-1,null,null,-1,-1,null,null,null,null,null,null,null,193,null,null,-1,-1,null,null,null,null,null,null,null,null,null,null,null,220,null,-1,-1,-1,null,null,null,null,null,-1,null,null,306,null,null,219,null,-1,null,-1,null,266,193,90,-1,null,-1,147,-1,null,null,null,148,147,131,null,null,null,null,-1,291,291,null,-1,178,-1,136,null,-1,null,null,147,-1,null,null,287,-1,null,null,null,-1,null,-1,183,null,null,null,174,null,110,null,-1,null,-1,null,-1,null,null,12,null,null,-1,null,89,305,null,70,-1,94,null,-1,null,null,null,null,-1,241,null,176,null,220,null,-1,null,306,-1,183,-1,12,null,null,-1,null,95,null,null,-1,-1,null,311,111,null,190,null,null,84,null,null,-1,-1,null,null,177,157,null,-1,null,null,-1,null,null,null,174,null,null,51,183,-1,4,null,null,-1,-1,null,null,21,null,null,null,null,51,null,176,null,89,-1,null,-1,null,null,176,313,null,86,null,null,229,null,176,null,null,null,null,294,110,101,null,null,null,null,128,49,null,null,82,null,null,193,null,null,193,null,null,null,null,null,-1,85,null,null,null,101,null,126,66,null,null,-1,null,null,null,null,null,-1,null,-1,67,null,66,null,null,126,101,null,null,null,null,null,null,84,null,184,23,198,110,null,101,null,null,130,null,131,null,null,130,null,null,null,null,null,null,null,null,null,null,null,239,null,35,null,-1,null,null,null,287,null,-1,240,null,313,67,null,236,null,null,121,239,299,101,105,null,null,null,118,null,160,null,135,255,-1,null,11,null,null,null,101,258,-1,35,null,null,null,null,null,null,88,160,null,null,11,null,null,276,null,291,299,90,75,null,291,276,118,null,-1,121,null,null,252,null,null,null,null,145,null,null,191,null,null,null,null,null,-1,null,null,null,null,null,null,null,75,null,null,null,null,null,null,-1,23,null,139,-1,177,165,311,null,null,null,null,null,null,null,184,null,null,309,null,100,null,null,151,null,null,109,null,null,-1,90,136,299,null,null,null,173,173,null,-1,null,null,null,null,-1,null,null,313,45,null,null,null,null,null,null,null,null,null,252,-1,null,null,212,null,null,null,null,132,null,-1,137,null,308,null,15,null,null,null,null,-1,null,null,-1,null,null,288,null,null,-1,2,null,null,22,null,null,null,null,-1,299,137,null,null,295,null,139,null,null,null,null,13,null,null,null,null,101,-1,null,null,249,190,null,null,null,null,-1,null,null,null,136,-1,151,null,null,null,null,203,null,90,88,null,null,null,null,null,190,160,null,null,null,40,null,null,null,null,24,null,null,132,30,null,null,236,-1,null,null,67,null,null,null,null,119,-1,24,132,null,1,-1,null,null,null,236,236,null,null,-1,15,183,null,311,295,183,null,-1,188,47,null,null,null,null,311,null,null,null,53,256,null,144,null,null,268,44,null,188,233,1,191,null,null,null,null,null,null,null,null,275,-1,null,null,null,53,null,138,118,146,null,null,279,null,null,null,null,null,null,175,null,null,null,null,53,null,null,-1,null,305,null,44,null,250,null,132,null,null,null,88,null,111,301,102,-1,-1,null,null,null,301,null,null,null,-1,13,null,null,null,null,-1,null,null,null,null,null,null,215,-1,null,null,null,null,null,null,233,null,null,null,311,-1,null,null,null,null,null,null,null,null,null,230,null,null,76,188,-1,264,null,null,-1,null,-1,41,-1,null,null,null,null,null,null,null,24,-1,null,null,null,null,null,47,104,null,null,287,null,24,null,null,null,93,null,-1,null,295,69,null,-1,-1,48,null,null,null,null,-1,null,-1,null,null,null,null,null,null,41,null,null,283,null,null,287,null,null,null,null,-1,null,null,299,203,null,null,null,null,296,null,13,null,null,null,null,null,null,null,null,null,276,null,null,null,null,1,-1,98,null,240,250,null,null,null,null,133,null,-1,305,-1,null,175,55,null,266,null,-1,null,null,-1,null,157,116,71,-1,null,null,-1,null,283,3,-1,102,null,null,null,106,null,240,null,-1,null,286,50,null,101,299,null,null,null,null,301,-1,null,null,null,227,null,132,null,null,144,null,279,null,71,null,68,157,null,52,24,null,null,null,268,null,null,197,3,null,25,null,54,null,null,-1,271];
+
+        let mut cache = None;
+
+        for ((cmd, data), exp) in cmd.into_iter().zip(data).zip(exp) {
+            match cmd {
+                "LfuCache" => {
+                    cache = Some(LfuCache::new(data[0] as usize));
+                },
+                "put" => {
+                    if let Some(cache) = &mut cache {
+                        cache.insert(data[0], data[1]);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                "get" => {
+                    if let Some(cache) = &mut cache {
+                        assert_eq!(cache.get(&data[0]).map_or(-1, |v| *v), exp);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                _ => panic!("Bad command!"),
+            }
+            // To see output run tests with: 
+            // cargo test -- --test-threads=1 --nocapture
+            println!("cache: {:?}", cache.as_ref().unwrap().frequencies);
+        }
+    }
+
+    #[test]
+    fn test_4() {
+        let null = i32::MIN;
+        let cmd  = ["LfuCache","put","put","put","put","put","get","put","get","get","put","get","put","put","put","get","put","get","get","get","get","put","put","get","get","get","put","put","get","put","get","put","get","get","get","put","put","put","get","put","get","get","put","put","get","put","put","put","put","get","put","put","get","put","put","get","put","put","put","put","put","get","put","put","get","put","get","get","get","put","get","get","put","put","put","put","get","put","put","put","put","get","get","get","put","put","put","get","put","put","put","get","put","put","put","get","get","get","put","put","put","put","get","put","put","put","put","put","put","put"];
+        let data = vec2d![[10],[10,13],[3,17],[6,11],[10,5],[9,10],[13],[2,19],[2],[3],[5,25],[8],[9,22],[5,5],[1,30],[11],[9,12],[7],[5],[8],[9],[4,30],[9,3],[9],[10],[10],[6,14],[3,1],[3],[10,11],[8],[2,14],[1],[5],[4],[11,4],[12,24],[5,18],[13],[7,23],[8],[12],[3,27],[2,12],[5],[2,9],[13,4],[8,18],[1,7],[6],[9,29],[8,21],[5],[6,30],[1,12],[10],[4,15],[7,22],[11,26],[8,17],[9,29],[5],[3,4],[11,30],[12],[4,29],[3],[9],[6],[3,4],[1],[10],[3,29],[10,28],[1,20],[11,13],[3],[3,12],[3,8],[10,9],[3,26],[8],[7],[5],[13,17],[2,27],[11,15],[12],[9,19],[2,15],[3,16],[1],[12,17],[9,1],[6,19],[4],[5],[5],[8,1],[11,7],[5,2],[9,28],[1],[2,2],[7,4],[4,22],[7,24],[9,26],[13,28],[11,26]];
+        let exp  = [null,null,null,null,null,null,-1,null,19,17,null,-1,null,null,null,-1,null,-1,5,-1,12,null,null,3,5,5,null,null,1,null,-1,null,30,5,30,null,null,null,-1,null,-1,24,null,null,18,null,null,null,null,14,null,null,18,null,null,11,null,null,null,null,null,18,null,null,-1,null,4,29,30,null,12,11,null,null,null,null,29,null,null,null,null,17,-1,18,null,null,null,-1,null,null,null,20,null,null,null,29,18,18,null,null,null,null,20,null,null,null,null,null,null,null];
+
+        let mut cache = None;
+
+        for ((cmd, data), exp) in cmd.into_iter().zip(data).zip(exp) {
+            match cmd {
+                "LfuCache" => {
+                    cache = Some(LfuCache::new(data[0] as usize));
+                },
+                "put" => {
+                    if let Some(cache) = &mut cache {
+                        cache.insert(data[0], data[1]);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                "get" => {
+                    if let Some(cache) = &mut cache {
+                        assert_eq!(cache.get(&data[0]).map_or(-1, |v| *v), exp);
+                    } else {
+                        panic!("cache is None");
+                    }
+                },
+                _ => panic!("Bad command!"),
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! `LfuCache` as a store for the `cached` crate. With the `cached` feature it
+//! implements `cached::Cached`, so it can back a `#[cached]` function, given
+//! as its `ty` and built by its `create`, or be used wherever the crate's own
+//! stores are.
+//! 
+//! The trait looks keys up by any borrowed form of `K`, while the cache's own
+//! methods take `&K`, so each lookup first finds the stored key and clones it.
+//! The hit, miss and eviction counters are those of `CacheStats`, and are only
+//! there once `enable_stats()` has been called.
+//! 
+
+use std::borrow::Borrow;
+use std::convert::Infallible;
+use std::hash::{BuildHasher, Hash};
+
+use crate::{InsertResult, LfuCache};
+
+impl<K, V, S> ::cached::Cached<K, V> for LfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    type Error = Infallible;
+
+    fn cache_get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.stored_key(k) {
+            Some(key) => self.get(&key),
+            None      => { self.count_miss(k); None },
+        }
+    }
+
+    fn cache_get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(key) = self.stored_key(k) else {
+            self.count_miss(k);
+            return None;
+        };
+        self.get(&key)?;
+        self.peek_value_mut(&key)
+    }
+
+    /// Inserts the pair like `insert()` does, and returns the value it
+    /// replaced.
+    /// 
+    fn cache_set(&mut self, k: K, v: V) -> Option<V> {
+        match self.write(k, v, None).1 {
+            InsertResult::Replaced(old) => Some(old),
+            _                           => None,
+        }
+    }
+
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0, see `get_mut_or_insert_with()`.
+    /// 
+    fn cache_get_or_set_with_mut<F: FnOnce() -> V>(&mut self, key: K, f: F)
+        -> &mut V
+    {
+        self.get_mut_or_insert_with(key, f)
+    }
+
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0, see `get_or_try_insert_with()`.
+    /// 
+    fn cache_try_get_or_set_with_mut<F, E>(&mut self, key: K, f: F)
+        -> Result<&mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        self.get_or_try_insert_with(key.clone(), f)?;
+
+        Ok(self.peek_value_mut(&key).unwrap())
+    }
+
+    fn cache_remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache_remove_entry(k).map(|(_, v)| v)
+    }
+
+    fn cache_remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(&self.stored_key(k)?)
+    }
+
+    fn cache_clear(&mut self) {
+        self.clear();
+    }
+
+    fn cache_reset(&mut self) {
+        self.clear();
+        self.cache_reset_metrics();
+    }
+
+    fn cache_size(&self) -> usize {
+        self.len()
+    }
+
+    fn cache_reset_metrics(&mut self) {
+        if self.stats().is_some() { self.enable_stats(); }
+    }
+
+    /// Checks for the key without counting an access.
+    /// 
+    fn cache_contains<Q>(&mut self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.stored_key(k).is_some_and(|key| self.contains_key(&key))
+    }
+
+    fn cache_hits(&self) -> Option<u64> {
+        self.stats().map(|stats| stats.hits)
+    }
+
+    fn cache_misses(&self) -> Option<u64> {
+        self.stats().map(|stats| stats.misses)
+    }
+
+    fn cache_capacity(&self) -> Option<usize> {
+        Some(self.capacity())
+    }
+
+    fn cache_evictions(&self) -> Option<u64> {
+        self.stats().map(|stats| stats.evictions)
+    }
+}
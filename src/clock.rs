@@ -0,0 +1,105 @@
+//! Time sources for the cache's time-based policies, such as expiring items
+//! and refreshing them ahead of time. The cache only ever asks a clock how
+//! much time has passed since some fixed point, so a manual clock can stand in
+//! for the system's to make tests repeatable.
+//! 
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// The standard library's `Instant` panics on `wasm32-unknown-unknown`, where
+// `web_time`'s reads `performance.now()`. Elsewhere they're the same type.
+#[cfg(not(feature = "wasm"))]
+pub(crate) use std::time::Instant;
+#[cfg(feature = "wasm")]
+pub(crate) use web_time::Instant;
+
+/// A source of time for the cache.
+/// 
+pub trait Clock: core::fmt::Debug + Send + Sync {
+    /// Returns the time that has passed since the clock's fixed starting
+    /// point. It must never go backwards.
+    /// 
+    fn now(&self) -> Duration;
+}
+
+/// A clock that follows the system's monotonic clock, starting from when it
+/// was created. This is the clock a cache uses unless it's given another.
+/// 
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Creates a clock that starts now.
+    /// 
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only moves when it's told to. Clones share the same time, so
+/// a test can keep one and hand another to the cache.
+/// 
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Creates a clock that reads zero.
+    /// 
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by `by`.
+    /// 
+    pub fn advance(&self, by: Duration) {
+        self.nanos.fetch_add(by.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Times a load, on the cache's clock if it has one, and on the system's 
+/// monotonic clock if it doesn't.
+/// 
+pub(crate) enum Stopwatch {
+    Clock(Arc<dyn Clock>, Duration),
+    System(Instant),
+}
+
+impl Stopwatch {
+    pub(crate) fn start(clock: Option<&Arc<dyn Clock>>) -> Self {
+        match clock {
+            Some(clock) => Self::Clock(clock.clone(), clock.now()),
+            None        => Self::System(Instant::now()),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        match self {
+            Self::Clock(clock, start) => clock.now().saturating_sub(*start),
+            Self::System(start)       => start.elapsed(),
+        }
+    }
+}
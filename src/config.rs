@@ -0,0 +1,250 @@
+//! Cache settings as data, for services that configure their caches from
+//! files. With the `serde` feature a `CacheConfig` can be deserialized, from
+//! TOML for instance:
+//! 
+//! ```toml
+//! capacity   = 10000
+//! max_weight = 1048576
+//! ttl_secs   = 300
+//! admission  = true
+//! policy     = "lfu-da"
+//! ```
+//! 
+//! Only `capacity` is required. Unknown settings are an error rather than
+//! ignored, so a misspelt one is noticed.
+//! 
+
+use std::hash::Hash;
+use std::time::Duration;
+
+use crate::{ConfigError, LfuCache, LfuCacheBuilder, Policy};
+
+/// The settings of an `LfuCache`, see `LfuCache::from_config()`.
+/// 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct CacheConfig {
+    /// The maximum number of items.
+    pub capacity   : usize,
+    /// The weight limit, see `LfuCache::set_weigher()`. It needs a weigher,
+    /// see `LfuCache::from_config_with_weigher()`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_weight : Option<usize>,
+    /// The TTL of every item, in seconds, see `LfuCache::set_default_ttl()`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ttl_secs   : Option<u64>,
+    /// Whether admission control is on, see `LfuCache::enable_admission()`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub admission  : bool,
+    /// Whether the cache keeps stats, see `LfuCache::enable_stats()`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stats      : bool,
+    /// The eviction policy.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub policy     : Policy,
+}
+
+impl CacheConfig {
+    /// Returns the settings of a cache with the given capacity and none of
+    /// the opt-in features, like `LfuCache::new()` creates.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            max_weight : None,
+            ttl_secs   : None,
+            admission  : false,
+            stats      : false,
+            policy     : Policy::Lfu,
+        }
+    }
+
+    /// Returns an error if the settings don't go together. `weighed` says
+    /// whether a weigher comes with them.
+    /// 
+    fn validate(&self, weighed: bool) -> Result<(), ConfigError> {
+        if self.capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        if self.ttl_secs == Some(0) {
+            return Err(ConfigError::ZeroTtl);
+        }
+        match (self.max_weight, weighed) {
+            (Some(_), false) => return Err(ConfigError::NoWeigher),
+            (None,    true)  => return Err(ConfigError::NoWeightLimit),
+            _                => (),
+        }
+        match self.policy {
+            Policy::Gdsf { scale: 0 } => Err(ConfigError::ZeroScale),
+            Policy::Gdsf { .. } if !weighed => Err(ConfigError::NoWeigher),
+            Policy::Decay { half_life } if half_life.is_zero() => {
+                Err(ConfigError::ZeroHalfLife)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn builder(&self) -> LfuCacheBuilder {
+        let mut builder = LfuCacheBuilder::new(self.capacity)
+                              .policy(self.policy);
+
+        if self.admission { builder = builder.admission(); }
+        if self.stats     { builder = builder.stats(); }
+
+        if let Some(secs) = self.ttl_secs {
+            builder = builder.expire_after_write(Duration::from_secs(secs));
+        }
+        builder
+    }
+}
+
+impl<K, V> LfuCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Creates a cache with the settings in `config`, after checking that
+    /// they go together. Weighing items takes a weigher, so a config with a
+    /// `max_weight` or `Policy::Gdsf` is turned down, see
+    /// `from_config_with_weigher()`.
+    /// 
+    pub fn from_config(config: &CacheConfig) -> Result<Self, ConfigError> {
+        config.validate(false)?;
+
+        Ok(config.builder().build())
+    }
+
+    /// Like `from_config()`, for a config with a `max_weight`, which `weigh`
+    /// is held to, see `set_weigher()`.
+    /// 
+    pub fn from_config_with_weigher<F>(config: &CacheConfig, weigh: F)
+        -> Result<Self, ConfigError>
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        config.validate(true)?;
+
+        let max_weight = config.max_weight.expect("checked by validate()");
+        let mut cache  = config.builder().build();
+
+        cache.set_weigher(max_weight, weigh);
+        Ok(cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_checked() {
+        let config = |f: fn(&mut CacheConfig)| {
+            let mut config = CacheConfig::new(10);
+            f(&mut config);
+            config
+        };
+        let plain = |config: &CacheConfig| {
+            LfuCache::<u32, u32>::from_config(config).map(|_| ())
+        };
+        let weighed = |config: &CacheConfig| {
+            LfuCache::<u32, u32>::from_config_with_weigher(config, |_, _| 1)
+                .map(|_| ())
+        };
+        assert_eq!(plain(&CacheConfig::new(10)), Ok(()));
+        assert_eq!(plain(&config(|c| c.capacity = 0)),
+                   Err(ConfigError::ZeroCapacity));
+        assert_eq!(plain(&config(|c| c.ttl_secs = Some(0))),
+                   Err(ConfigError::ZeroTtl));
+        assert_eq!(plain(&config(|c| c.max_weight = Some(5))),
+                   Err(ConfigError::NoWeigher));
+        assert_eq!(plain(&config(|c| c.policy = Policy::Gdsf { scale: 8 })),
+                   Err(ConfigError::NoWeigher));
+        assert_eq!(weighed(&CacheConfig::new(10)),
+                   Err(ConfigError::NoWeightLimit));
+        assert_eq!(weighed(&config(|c| {
+                       c.max_weight = Some(5);
+                       c.policy     = Policy::Gdsf { scale: 0 };
+                   })),
+                   Err(ConfigError::ZeroScale));
+        assert_eq!(plain(&config(|c| {
+                       c.policy = Policy::Decay { half_life: Duration::ZERO };
+                   })),
+                   Err(ConfigError::ZeroHalfLife));
+        assert_eq!(ConfigError::NoWeigher.to_string(),
+                   "weighing items needs a weigher");
+    }
+
+    #[cfg(feature = "serde")]
+    fn parse(toml: &str) -> CacheConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn caches_follow_their_config() {
+        // The bare minimum is a plain cache.
+        let config = parse("capacity = 2");
+
+        assert_eq!(config, CacheConfig::new(2));
+
+        let mut cache = LfuCache::from_config(&config).unwrap();
+
+        for key in 0..3 { cache.insert(key, key); }
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains_key(&0));
+        assert_eq!(cache.stats(), None);
+
+        // Stats, a TTL and a policy.
+        let config = parse(r#"
+            capacity = 4
+            ttl_secs = 60
+            stats    = true
+            policy   = "lfu-da"
+        "#);
+        assert_eq!(config.ttl_secs, Some(60));
+        assert_eq!(config.policy, Policy::LfuDa);
+
+        let mut cache = LfuCache::from_config(&config).unwrap();
+
+        cache.insert("a", 1);
+        cache.get(&"a");
+        assert_eq!(cache.stats().map(|stats| stats.hits), Some(1));
+        assert!(matches!(cache.remaining_ttl(&"a"),
+                         crate::RemainingTtl::Expires(ttl)
+                             if ttl <= Duration::from_secs(60)));
+
+        // A weight limit, held to by the weigher that comes with it.
+        let config = parse(r#"
+            capacity   = 100
+            max_weight = 10
+            policy     = { gdsf = { scale = 16 } }
+        "#);
+        assert_eq!(LfuCache::<u32, String>::from_config(&config).err(),
+                   Some(ConfigError::NoWeigher));
+
+        let mut cache = LfuCache::from_config_with_weigher(&config, |_, v| {
+            String::len(v)
+        }).unwrap();
+
+        for key in 0..5 { cache.insert(key, "abc".to_string()); }
+        assert_eq!(cache.len(), 3);
+        assert!(cache.weight() <= 10);
+
+        // Admission control turns away a newcomer to a full cache that the
+        // LFU item has been used more than.
+        let config = parse("capacity = 1\nadmission = true");
+        let mut cache = LfuCache::from_config(&config).unwrap();
+
+        cache.insert(1, 1);
+        for _ in 0..5 { cache.get(&1); }
+        cache.insert(2, 2);
+        assert!(cache.contains_key(&1));
+
+        // Misspelt or unsupported settings don't go unnoticed.
+        for toml in ["capcity = 2", "capacity = 2\ntti_secs = 5",
+                     "ttl_secs = 5"]
+        {
+            assert!(toml::from_str::<CacheConfig>(toml).is_err(), "{toml}");
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! The `Counter` trait, for the type an `LfuCache` keeps each item's
+//! frequency count in. It's `usize` unless another is given: a cache of many
+//! small items can keep its counts in a `u16` or a `u8` to save room. A
+//! smaller count saturates at its maximum, and an item with a saturated count
+//! stays in the queue for it, moving to the back on each access, as the items
+//! in one queue are ordered by recency.
+//! 
+
+use core::fmt::Debug;
+use core::hash::Hash;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// An unsigned integer type an `LfuCache` can keep frequency counts in. It's
+/// implemented for `u8`, `u16`, `u32`, `u64` and `usize`.
+/// 
+pub trait Counter: sealed::Sealed + Copy + Eq + Ord + Hash + Debug
+                 + Send + Sync + 'static
+{
+    /// The highest count, where increments stop.
+    const MAX: Self;
+
+    /// Returns the count as a `usize`, saturating if it doesn't fit.
+    /// 
+    fn to_usize(self) -> usize;
+
+    /// Returns `n` as a count, saturating at `MAX`.
+    /// 
+    fn from_usize(n: usize) -> Self;
+
+    /// Returns the count plus one, or `None` if it's saturated.
+    /// 
+    fn checked_incr(self) -> Option<Self>;
+
+    /// Returns the count plus one, or `MAX` if it's saturated.
+    /// 
+    fn saturating_incr(self) -> Self {
+        self.checked_incr().unwrap_or(Self::MAX)
+    }
+}
+
+macro_rules! counters {
+    ($($t:ty),*) => { $(
+        impl sealed::Sealed for $t {}
+
+        impl Counter for $t {
+            const MAX: Self = <$t>::MAX;
+
+            fn to_usize(self) -> usize {
+                usize::try_from(self).unwrap_or(usize::MAX)
+            }
+
+            fn from_usize(n: usize) -> Self {
+                Self::try_from(n).unwrap_or(Self::MAX)
+            }
+
+            fn checked_incr(self) -> Option<Self> {
+                self.checked_add(1)
+            }
+        }
+    )* };
+}
+
+counters!(u8, u16, u32, u64, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_saturate() {
+        assert_eq!(254u8.checked_incr(), Some(255));
+        assert_eq!(255u8.checked_incr(), None);
+        assert_eq!(255u8.saturating_incr(), 255);
+        assert_eq!(u16::from_usize(70_000), u16::MAX);
+        assert_eq!(u16::from_usize(7), 7);
+        assert_eq!(u64::MAX.to_usize(), usize::MAX);
+        assert_eq!(<u8 as Counter>::MAX.to_usize(), 255);
+    }
+}
@@ -0,0 +1,418 @@
+//! `DedupLfuCache`, for caches where many keys map to equal values, like the
+//! same rendered page under several URLs. Each distinct value is stored once,
+//! as an `Arc` in an intern table, and the cache's items share it. The table
+//! counts the items holding each value, and drops the value when the last of
+//! them leaves the cache, however many clones of it are held elsewhere.
+//! 
+//! The weight of the cache counts each distinct value once, plus
+//! `ENTRY_WEIGHT` for each item, which stands for its key and its pointer to
+//! the value. `set_max_weight()` limits the cache by that weight.
+//! 
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+
+use crate::{DefaultState, EvictionOrder, InsertResult, LfuCache};
+
+/// The weight each item adds to the weight of its value, see `weight()`.
+/// 
+pub const ENTRY_WEIGHT: usize = 1;
+
+/// Gives the weight of a value.
+/// 
+type ValueWeigher<V> = Box<dyn Fn(&V) -> usize + Send + Sync>;
+
+/// A Least Frequently Used cache that stores each distinct value once, see
+/// the `dedup` module.
+/// 
+/// ```
+/// use lfu_cache::DedupLfuCache;
+/// 
+/// let mut cache = DedupLfuCache::with_weigher(100, String::len);
+/// 
+/// for key in 0..100 {
+///     cache.insert(key, String::from(["even", "odd"][key % 2]));
+/// }
+/// assert_eq!(cache.distinct_values(), 2);
+/// assert_eq!(cache.weight(), "even".len() + "odd".len() + 100);
+/// ```
+pub struct DedupLfuCache<K, V, S = DefaultState> {
+    cache      : LfuCache<K, Arc<V>, S>,
+    interned   : HashMap<Arc<V>, usize>,
+    weigh      : ValueWeigher<V>,
+    weight     : usize,
+    max_weight : Option<usize>,
+}
+
+impl<K, V> DedupLfuCache<K, V> {
+    /// Creates a new cache with the given capacity, in which every distinct
+    /// value weighs 1.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self::with_weigher(capacity, |_| 1)
+    }
+
+    /// Creates a new cache with the given capacity, in which `weigh` gives
+    /// the weight of each distinct value.
+    /// 
+    pub fn with_weigher<F>(capacity: usize, weigh: F) -> Self
+    where
+        F: Fn(&V) -> usize + Send + Sync + 'static,
+    {
+        Self {
+            cache      : LfuCache::new(capacity),
+            interned   : HashMap::new(),
+            weigh      : Box::new(weigh),
+            weight     : 0,
+            max_weight : None,
+        }
+    }
+}
+
+impl<K, V, S> DedupLfuCache<K, V, S> {
+    /// Returns the number of items in the cache.
+    /// 
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the capacity of the cache.
+    /// 
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// Returns how many distinct values the items hold between them.
+    /// 
+    pub fn distinct_values(&self) -> usize {
+        self.interned.len()
+    }
+
+    /// Returns the weight of the cache: the weights of its distinct values,
+    /// each counted once, plus `ENTRY_WEIGHT` for each item.
+    /// 
+    pub fn weight(&self) -> usize {
+        self.weight + self.len() * ENTRY_WEIGHT
+    }
+
+    /// Returns an estimate of the memory the cache takes up, in bytes, like
+    /// `LfuCache::memory_footprint()` does, along with the intern table and
+    /// one copy of each distinct value.
+    /// 
+    pub fn memory_footprint(&self) -> usize {
+        use std::mem::size_of;
+
+        // An `Arc` allocation holds the two reference counts and the value.
+        let table  = self.interned.capacity() 
+                     * (size_of::<(Arc<V>, usize)>() + 1);
+        let values = self.interned.len() * size_of::<(usize, usize, V)>();
+
+        self.cache.memory_footprint() - size_of::<LfuCache<K, Arc<V>, S>>()
+            + size_of::<Self>() + table + values
+    }
+
+    /// Returns an iterator over the keys in eviction order: the key
+    /// `pop_lfu()` would remove first comes first.
+    /// 
+    pub fn eviction_order(&self) -> EvictionOrder<'_, K> {
+        self.cache.eviction_order()
+    }
+}
+
+impl<K, V, S> DedupLfuCache<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns `true` if the cache holds the key, without counting an access.
+    /// 
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns the frequency count of the key, or `None` if it isn't cached.
+    /// 
+    pub fn frequency(&self, key: &K) -> Option<usize> {
+        self.cache.frequency(key)
+    }
+
+    /// Returns the interned copy of `value` for an item to hold, adding it
+    /// to the table if it's new.
+    /// 
+    fn intern(&mut self, value: V) -> Arc<V> {
+        if let Some(items) = self.interned.get_mut(&value) {
+            *items += 1;
+            return self.interned.get_key_value(&value).unwrap().0.clone();
+        }
+        let shared = Arc::new(value);
+
+        self.weight += (self.weigh)(&shared);
+        self.interned.insert(shared.clone(), 1);
+        shared
+    }
+
+    /// Lets go of an item's value, dropping it from the table if no other
+    /// item holds it.
+    /// 
+    fn release(&mut self, value: &Arc<V>) {
+        let Some(items) = self.interned.get_mut(&**value) else { return };
+
+        *items -= 1;
+
+        if *items == 0 {
+            self.weight -= (self.weigh)(value);
+            self.interned.remove(&**value);
+        }
+    }
+}
+
+impl<K, V, S> DedupLfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Limits the cache by its weight, see `weight()`, as well as by its
+    /// number of items. Items are evicted in LFU order until the weight is at
+    /// most `max_weight`, so evicting an item whose value others share only
+    /// takes off its `ENTRY_WEIGHT`. An item heavier than the limit on its
+    /// own is turned away by `insert()`. The items already cached are evicted
+    /// down to the limit now.
+    /// 
+    pub fn set_max_weight(&mut self, max_weight: usize) {
+        self.max_weight = Some(max_weight);
+        self.shed_weight();
+    }
+
+    /// Inserts a key-value pair into the cache, sharing the stored copy of
+    /// the value if another item already holds one equal to it. If the cache
+    /// is full, or a new item would take it over its weight limit, LFU items
+    /// are evicted to make room for a new key.
+    /// 
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity() == 0 { return; }
+
+        if let Some(max) = self.max_weight {
+            if (self.weigh)(&value).saturating_add(ENTRY_WEIGHT) > max {
+                return;
+            }
+        }
+        let hash   = self.cache.hash_key(&key);
+        let cached = self.cache.contains_hashed(hash, &key);
+        let value  = self.intern(value);
+
+        // Victims are popped here rather than by the cache, so their values
+        // can be released. The new value is interned first, so it's kept
+        // even if the last item holding it goes.
+        if !cached {
+            if self.len() >= self.capacity() { self.pop_lfu(); }
+
+            if let Some(max) = self.max_weight {
+                while self.weight() + ENTRY_WEIGHT > max 
+                      && self.pop_lfu().is_some() {}
+            }
+        }
+        match self.cache.write_hashed(hash, key, value.clone(), None).1 {
+            InsertResult::Replaced(old) => self.release(&old),
+            InsertResult::Inserted      => {},
+            _                           => self.release(&value),
+        }
+        // A replaced value may be heavier than the one it replaced.
+        self.shed_weight();
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    /// 
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.cache.get(key).map(|value| &**value)
+    }
+
+    /// Returns the shared value corresponding to the key, counting an access
+    /// like `get()` does.
+    /// 
+    pub fn get_shared(&mut self, key: &K) -> Option<Arc<V>> {
+        self.cache.get(key).cloned()
+    }
+
+    /// Removes the key from the cache and returns its value, if it was
+    /// present. Other items may still share the value.
+    /// 
+    pub fn remove(&mut self, key: &K) -> Option<Arc<V>> {
+        let value = self.cache.remove(key)?;
+
+        self.release(&value);
+        Some(value)
+    }
+
+    /// Removes the Least Frequently Used item from the cache and returns it.
+    /// 
+    pub fn pop_lfu(&mut self) -> Option<(K, Arc<V>)> {
+        let (key, value) = self.cache.pop_lfu()?;
+
+        self.release(&value);
+        Some((key, value))
+    }
+
+    /// Evicts LFU items until the cache is within its weight limit.
+    /// 
+    fn shed_weight(&mut self) {
+        let Some(max) = self.max_weight else { return };
+
+        while self.weight() > max && self.pop_lfu().is_some() {}
+    }
+
+    /// Removes all items from the cache, and their values from the table.
+    /// 
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.interned.clear();
+        self.weight = 0;
+    }
+}
+
+impl<K, V, S> std::fmt::Debug for DedupLfuCache<K, V, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DedupLfuCache")
+         .field("len", &self.len())
+         .field("distinct_values", &self.distinct_values())
+         .field("weight", &self.weight())
+         .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_values_are_stored_once() {
+        let pages = ["a".repeat(1000), "b".repeat(2000), "c".repeat(3000)];
+        let mut cache = DedupLfuCache::with_weigher(1000, String::len);
+
+        for key in 0..1000 { cache.insert(key, pages[key % 3].clone()); }
+
+        assert_eq!(cache.len(), 1000);
+        assert_eq!(cache.distinct_values(), 3);
+        assert_eq!(cache.weight(), 6000 + 1000 * ENTRY_WEIGHT);
+        assert!(Arc::ptr_eq(&cache.get_shared(&0).unwrap(),
+                            &cache.get_shared(&999).unwrap()));
+
+        // Next to a cache holding a copy per key, the copies don't show.
+        let mut copies = LfuCache::new(1000);
+
+        for key in 0..1000 { copies.insert(key, [key as u8 % 3; 512]); }
+
+        let mut shared = DedupLfuCache::new(1000);
+
+        for key in 0..1000 { shared.insert(key, [key as u8 % 3; 512]); }
+
+        assert!(copies.memory_footprint() > 512 * 1000);
+        assert!(shared.memory_footprint() < copies.memory_footprint() / 2);
+    }
+
+    #[test]
+    fn values_go_with_their_last_item() {
+        let mut cache = DedupLfuCache::with_weigher(4, String::len);
+
+        cache.insert(1, "red".to_string());
+        cache.insert(2, "red".to_string());
+        cache.insert(3, "blue".to_string());
+        cache.get(&3);
+
+        // Replacing one of the reds keeps the value for the other.
+        cache.insert(1, "green".to_string());
+        assert_eq!(cache.distinct_values(), 3);
+
+        // Evicting the other drops it.
+        cache.insert(4, "blue".to_string());
+        cache.insert(5, "blue".to_string());
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.distinct_values(), 2);
+        assert_eq!(cache.weight(), "green".len() + "blue".len() + 4);
+
+        assert_eq!(cache.remove(&1).as_deref().map(String::as_str),
+                   Some("green"));
+        assert_eq!(cache.pop_lfu().map(|(key, _)| key), Some(4));
+        assert_eq!(cache.distinct_values(), 1);
+
+        cache.clear();
+        assert_eq!(cache.distinct_values(), 0);
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn max_weight_counts_shared_values_once() {
+        let mut cache = DedupLfuCache::with_weigher(100, String::len);
+
+        cache.set_max_weight(20);
+
+        for key in 1..=3 { cache.insert(key, "a".repeat(10)); }
+
+        cache.get(&1);
+        cache.get(&2);
+        cache.insert(4, "b".repeat(6));
+        assert_eq!(cache.weight(), 20);
+
+        // Evicting 3 only takes off its entry, as 1 and 2 still hold its 
+        // value, so 4 goes too.
+        cache.insert(5, "c".repeat(2));
+        assert_eq!(cache.eviction_order().copied().collect::<Vec<_>>(), 
+                   [5, 1, 2]);
+        assert_eq!(cache.weight(), 10 + 2 + 3 * ENTRY_WEIGHT);
+
+        // An item heavier than the limit on its own leaves the cache be.
+        cache.insert(6, "d".repeat(20));
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.contains_key(&6));
+
+        // Lowering the limit evicts down to it.
+        cache.set_max_weight(14);
+        assert_eq!(cache.eviction_order().copied().collect::<Vec<_>>(), 
+                   [1, 2]);
+        assert_eq!(cache.weight(), 10 + 2 * ENTRY_WEIGHT);
+
+        // So does a heavier value for a cached key: 1, the last item holding
+        // the first value, goes, and the value with it.
+        cache.insert(2, "e".repeat(3));
+        assert_eq!(cache.eviction_order().copied().collect::<Vec<_>>(), [2]);
+        assert_eq!(cache.distinct_values(), 1);
+        assert_eq!(cache.weight(), 3 + ENTRY_WEIGHT);
+    }
+
+    #[test]
+    fn clones_held_outside_dont_keep_values() {
+        let mut cache = DedupLfuCache::with_weigher(2, String::len);
+
+        cache.insert(1, "red".to_string());
+        cache.insert(2, "blue".to_string());
+
+        // The caller's clone outlives both the removal and the eviction.
+        let red  = cache.get_shared(&1).unwrap();
+        let blue = cache.get_shared(&2).unwrap();
+
+        cache.remove(&1);
+        cache.insert(3, "green".to_string());
+        cache.get(&3);
+        cache.get(&3);
+        cache.insert(4, "green".to_string());
+
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains_key(&2));
+        assert_eq!(cache.distinct_values(), 1);
+        assert_eq!(cache.weight(), "green".len() + 2 * ENTRY_WEIGHT);
+
+        // A new item with an equal value doesn't get the one let go of.
+        drop(blue);
+        cache.insert(5, "red".to_string());
+        assert!(!Arc::ptr_eq(&cache.get_shared(&5).unwrap(), &red));
+        assert_eq!(cache.distinct_values(), 2);
+    }
+}
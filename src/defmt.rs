@@ -0,0 +1,61 @@
+//! `defmt::Format` for the caches, so they can be logged on targets that log
+//! through `defmt`. A cache is shown as a summary: its length and capacity,
+//! and how many frequency buckets its items are in, with the lowest and
+//! highest frequencies. Keys and values aren't shown, so they needn't
+//! implement `Format` themselves.
+//! 
+//! The stats and event types derive `Format` where they're defined.
+//! 
+
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hash};
+
+use ::defmt::{write, Format, Formatter};
+
+use crate::ArrayLfuCache;
+#[cfg(feature = "std")]
+use crate::LfuCache;
+
+/// Writes the summary of a cache, given its buckets' frequencies in
+/// ascending order.
+/// 
+fn summarize(f        : Formatter<'_>,
+             name     : &str,
+             len      : usize,
+             capacity : usize,
+             freqs    : impl Iterator<Item = usize>)
+{
+    let (mut buckets, mut min, mut max) = (0, 0, 0);
+
+    for freq in freqs {
+        if buckets == 0 { min = freq; }
+        buckets += 1;
+        max      = freq;
+    }
+    write!(f, "{=str} {{ len: {=usize}, capacity: {=usize}, buckets: {=usize}, \
+               frequencies: {=usize}..={=usize} }}",
+           name, len, capacity, buckets, min, max);
+}
+
+impl<K, V, const N: usize> Format for ArrayLfuCache<K, V, N>
+where
+    K: Eq,
+{
+    fn format(&self, f: Formatter<'_>) {
+        summarize(f, "ArrayLfuCache", self.len(), self.capacity(),
+                  self.frequencies());
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> Format for LfuCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn format(&self, f: Formatter<'_>) {
+        let freqs = self.buckets_in(..).map(|bucket| bucket.frequency());
+
+        summarize(f, "LfuCache", self.len(), self.capacity(), freqs);
+    }
+}
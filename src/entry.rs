@@ -0,0 +1,340 @@
+//! Handles on a cache's items and buckets: guards that pin an item and can
+//! outlive a single call, read-only views of a frequency bucket, views of
+//! the items at either end of the eviction order, guards that put off
+//! counting an access until the value turns out to be used, and cursors that
+//! walk the items in eviction order, changing them as they go.
+//! 
+
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use linked_vector::{HNode, LinkedVector};
+
+use crate::cache::Value;
+use crate::hashed::HashedMap;
+use crate::queues::Queued;
+use crate::iter::BucketIter;
+use crate::{Counter, DefaultState, LfuCache};
+
+/// Protects an item of an `LfuCache` from eviction while it's alive, see 
+/// `LfuCache::guard()`. It doesn't borrow the cache, so it can be handed to 
+/// other threads or tasks, and cloning it pins the item until every clone is
+/// dropped.
+/// 
+#[derive(Clone, Debug)]
+pub struct EntryGuard<K> {
+    pub(crate) key  : K,
+    pub(crate) _pin : Arc<()>,
+}
+
+impl<K> EntryGuard<K> {
+    /// Returns the key of the pinned item.
+    /// 
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+/// A view of one frequency queue of an `LfuCache`, returned by 
+/// `LfuCache::peek_bucket()` and `LfuCache::buckets_in()`.
+/// 
+pub struct Bucket<'a, K, V, S = DefaultState, C = usize> {
+    pub(crate) frequency : usize,
+    pub(crate) queue     : &'a LinkedVector<Queued<K>>,
+    pub(crate) map       : &'a HashedMap<K, Value<V, C>, S>,
+}
+
+impl<'a, K, V, S, C: Counter> Bucket<'a, K, V, S, C> {
+    /// Returns the frequency count shared by the bucket's items.
+    /// 
+    pub fn frequency(&self) -> usize {
+        self.frequency
+    }
+
+    /// Returns the number of items in the bucket.
+    /// 
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns `true` if the bucket holds no items. Buckets handed out by the
+    /// cache never are.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns an iterator over the bucket's items in queue order, next to be
+    /// evicted first. Each value is looked up in the map as it's reached.
+    /// 
+    pub fn iter(&self) -> BucketIter<'a, K, V, S, C> {
+        BucketIter { keys: self.queue.iter(), map: self.map }
+    }
+}
+
+/// A view of an item of an `LfuCache` that borrows the cache, returned by
+/// `LfuCache::lfu_entry()` and `LfuCache::mfu_entry()`. Reading or changing
+/// the value through it doesn't count as an access; `bump()` does. Its item 
+/// is live when the view is made, and stays in the cache until `remove()`, 
+/// even if its TTL runs out in the meantime.
+/// 
+pub struct OccupiedEntry<'a, K, V, S = DefaultState, C = usize> {
+    pub(crate) cache : &'a mut LfuCache<K, V, S, C>,
+    pub(crate) key   : K,
+}
+
+impl<'a, K, V, S, C: Counter> OccupiedEntry<'a, K, V, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Returns the item's key.
+    /// 
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns a reference to the item's value.
+    /// 
+    pub fn get(&self) -> &V {
+        self.cache.peek_value(&self.key).unwrap()
+    }
+
+    /// Returns a mutable reference to the item's value.
+    /// 
+    pub fn get_mut(&mut self) -> &mut V {
+        self.cache.peek_value_mut(&self.key).unwrap()
+    }
+
+    /// Converts the view into a mutable reference to the item's value that
+    /// lives as long as the cache's borrow.
+    /// 
+    pub fn into_mut(self) -> &'a mut V {
+        self.cache.peek_value_mut(&self.key).unwrap()
+    }
+
+    /// Returns the item's frequency count.
+    /// 
+    pub fn frequency(&self) -> usize {
+        self.cache.frequency(&self.key).unwrap()
+    }
+
+    /// Counts an access to the item, as `get()` on the cache would, moving it
+    /// up the eviction order.
+    /// 
+    pub fn bump(&mut self) {
+        self.cache.observe_live(&self.key);
+    }
+
+    /// Removes the item from the cache and returns its key and value.
+    /// 
+    pub fn remove(self) -> (K, V) {
+        self.cache.remove_live(&self.key).unwrap()
+    }
+}
+
+impl<K, V, S, C: Counter> fmt::Debug for OccupiedEntry<'_, K, V, S, C>
+where
+    K: Eq + Hash + Clone + fmt::Debug,
+    V: fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry").field("key", &self.key)
+                                       .field("value", self.get())
+                                       .finish()
+    }
+}
+
+/// A value looked up in an `LfuCache` whose access hasn't been counted yet,
+/// returned by `LfuCache::get_deferred()`. It derefs to the value. The access
+/// is counted, as `get()` would count it, by `commit()`, and by dropping the
+/// guard if `commit_on_drop()` was called and the thread isn't panicking.
+/// Otherwise, and after `cancel()`, the item is as if it hadn't been looked 
+/// up.
+/// 
+pub struct AccessGuard<'a, K, V, S = DefaultState, C = usize>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Counter,
+{
+    pub(crate) cache   : &'a mut LfuCache<K, V, S, C>,
+    pub(crate) key     : K,
+    pub(crate) on_drop : bool,
+}
+
+impl<K, V, S, C> AccessGuard<'_, K, V, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Counter,
+{
+    /// Returns the item's key.
+    /// 
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Has dropping the guard count the access, unless the thread is
+    /// panicking, so that it only needs calling `cancel()` where the value
+    /// goes unused.
+    /// 
+    pub fn commit_on_drop(mut self) -> Self {
+        self.on_drop = true;
+        self
+    }
+
+    /// Counts the access, moving the item up the eviction order.
+    /// 
+    pub fn commit(mut self) {
+        self.on_drop = false;
+        self.cache.get(&self.key);
+    }
+
+    /// Drops the guard without counting the access.
+    /// 
+    pub fn cancel(mut self) {
+        self.on_drop = false;
+    }
+}
+
+impl<K, V, S, C> Deref for AccessGuard<'_, K, V, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Counter,
+{
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.cache.peek_value(&self.key).unwrap()
+    }
+}
+
+impl<K, V, S, C> Drop for AccessGuard<'_, K, V, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Counter,
+{
+    fn drop(&mut self) {
+        if self.on_drop && !std::thread::panicking() {
+            self.cache.get(&self.key);
+        }
+    }
+}
+
+impl<K, V, S, C> fmt::Debug for AccessGuard<'_, K, V, S, C>
+where
+    K: Eq + Hash + Clone + fmt::Debug,
+    V: fmt::Debug,
+    S: BuildHasher,
+    C: Counter,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessGuard").field("key", &self.key)
+                                     .field("value", &**self)
+                                     .field("commit_on_drop", &self.on_drop)
+                                     .finish()
+    }
+}
+
+impl<K: fmt::Debug, V, S, C: Counter> fmt::Debug for Bucket<'_, K, V, S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.queue.iter().map(|q| &q.key).collect::<Vec<_>>();
+
+        f.debug_struct("Bucket").field("frequency", &self.frequency)
+                                .field("keys", &keys)
+                                .finish()
+    }
+}
+
+/// A cursor over the items of an `LfuCache` in eviction order, returned by
+/// `LfuCache::cursor_entries_mut()`. It starts on the LFU item, and moves on
+/// with `move_next()`, or with `remove_current()` and `bump_current()`,
+/// which move it past the item they change. It holds the handles of its 
+/// item's place in the queues, so moving it takes constant time.
+/// 
+/// Bumping an item moves it up the eviction order, so the cursor comes to it
+/// again if it moved past items the cursor hasn't got to yet. A walk that 
+/// bumps every item it comes to doesn't end.
+/// 
+pub struct EntryCursorMut<'a, K, V, S = DefaultState, C = usize> {
+    pub(crate) cache : &'a mut LfuCache<K, V, S, C>,
+    pub(crate) at    : Option<(HNode, HNode)>,
+}
+
+impl<K, V, S, C> EntryCursorMut<'_, K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    C: Counter,
+{
+    /// Returns the item the cursor is on, with a mutable reference to its 
+    /// value and its frequency count, or `None` once the cursor is past the
+    /// last item. Values changed this way keep the weight they were written
+    /// with.
+    /// 
+    pub fn current(&mut self) -> Option<(&K, &mut V, usize)> {
+        Some(self.cache.entry_at(self.at?))
+    }
+
+    /// Moves the cursor to the next item in eviction order. Once it's past
+    /// the last item, this does nothing.
+    /// 
+    pub fn move_next(&mut self) {
+        self.at = self.at.and_then(|at| self.cache.position_after(at));
+    }
+}
+
+impl<K, V, S, C> EntryCursorMut<'_, K, V, S, C>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+    C: Counter,
+{
+    /// Removes the item the cursor is on and returns its key and value, and
+    /// moves the cursor to the item after it. Returns `None` once the cursor
+    /// is past the last item, or if the item had expired, in which case it's
+    /// dropped as `remove()` would drop it.
+    /// 
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let at  = self.at?;
+        let key = self.cache.key_at(at).clone();
+
+        // The next item's handles are taken first. Only the removed key's
+        // node, and its queue if it's left empty, go away.
+        self.at = self.cache.position_after(at);
+        self.cache.remove_entry(&key)
+    }
+
+    /// Counts an access to the item the cursor is on, as `get()` on the cache
+    /// would, and moves the cursor to the item that was after it. Once the 
+    /// cursor is past the last item, this does nothing.
+    /// 
+    pub fn bump_current(&mut self) {
+        let Some(at) = self.at else { return };
+        let key      = self.cache.key_at(at).clone();
+
+        // The bump only moves the bumped key, so the next item keeps its
+        // handles.
+        self.at = self.cache.position_after(at);
+        self.cache.observe(&key);
+    }
+}
+
+impl<K, V, S, C> fmt::Debug for EntryCursorMut<'_, K, V, S, C>
+where
+    K: Eq + Hash + fmt::Debug,
+    S: BuildHasher,
+    C: Counter,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = self.at.map(|at| self.cache.key_at(at));
+
+        f.debug_struct("EntryCursorMut").field("current", &key).finish()
+    }
+}
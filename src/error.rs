@@ -0,0 +1,188 @@
+//! The errors of the fallible parts of the API. `CacheError` covers every way
+//! an operation on a cache can fail, so callers that don't care which one can
+//! pass them all on with `?`. The operations that can only fail one way
+//! return a focused error instead, `VersionMismatch` or `ConfigError`, which
+//! converts into a `CacheError`.
+//! 
+
+use core::fmt;
+
+/// Why an operation on a cache failed.
+/// 
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheError {
+    /// The capacity is 0, so nothing could be cached.
+    ZeroCapacity,
+    /// The item was turned away, by admission control, or because every
+    /// item is pinned or the capacity is 0.
+    Rejected,
+    /// The item's weight is over the limit for one item.
+    TooLarge { weight: usize },
+    /// The item's version isn't the one expected.
+    VersionMismatch(VersionMismatch),
+    /// The cache's settings don't go together.
+    #[cfg(feature = "std")]
+    Config(ConfigError),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroCapacity         => {
+                f.write_str("a cache with a capacity of 0 can't hold a value")
+            },
+            Self::Rejected             => f.write_str("the item was rejected"),
+            Self::TooLarge { weight }  => {
+                write!(f, "an item weighing {weight} is over the limit")
+            },
+            Self::VersionMismatch(err) => err.fmt(f),
+            #[cfg(feature = "std")]
+            Self::Config(err)          => err.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for CacheError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::VersionMismatch(err) => Some(err),
+            #[cfg(feature = "std")]
+            Self::Config(err)          => Some(err),
+            _                          => None,
+        }
+    }
+}
+
+impl From<VersionMismatch> for CacheError {
+    fn from(err: VersionMismatch) -> Self {
+        Self::VersionMismatch(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ConfigError> for CacheError {
+    fn from(err: ConfigError) -> Self {
+        Self::Config(err)
+    }
+}
+
+/// The error returned by `LfuCache::insert_if_version()` when the item's
+/// version isn't the one expected.
+/// 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// The version the caller expected.
+    pub expected : u64,
+    /// The item's actual version, or 0 if the key isn't cached.
+    pub actual   : u64,
+}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected version {}, found version {}",
+               self.expected, self.actual)
+    }
+}
+
+impl core::error::Error for VersionMismatch {}
+
+/// Why a `CacheConfig` was turned down, see `LfuCache::from_config()`.
+/// 
+#[cfg(feature = "std")]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The capacity is 0, so nothing could be cached.
+    ZeroCapacity,
+    /// The TTL is 0, so everything would expire as soon as it's written.
+    ZeroTtl,
+    /// There's a weight limit, or `Policy::Gdsf`, but no weigher.
+    NoWeigher,
+    /// There's a weigher but no weight limit.
+    NoWeightLimit,
+    /// `Policy::Gdsf` has a scale of 0.
+    ZeroScale,
+    /// `Policy::Decay` has a half-life of 0.
+    ZeroHalfLife,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ZeroCapacity  => "the capacity must be at least 1",
+            Self::ZeroTtl       => "the TTL must be at least 1 second",
+            Self::NoWeigher     => "weighing items needs a weigher",
+            Self::NoWeightLimit => "a weigher needs a max_weight",
+            Self::ZeroScale     => "the gdsf scale must be at least 1",
+            Self::ZeroHalfLife  => "the decay half-life must be more than 0",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::error::Error for ConfigError {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{CacheConfig, InsertResult, LfuCache};
+
+    #[test]
+    fn errors_pass_on_with_the_question_mark() {
+        fn update(cache: &mut LfuCache<u32, u32>, version: u64)
+            -> Result<u64, CacheError>
+        {
+            Ok(cache.insert_if_version(1, 1, version)?)
+        }
+        let mut cache = LfuCache::try_new(2).unwrap();
+
+        assert_eq!(update(&mut cache, 0), Ok(1));
+        assert_eq!(update(&mut cache, 0),
+                   Err(CacheError::VersionMismatch(VersionMismatch {
+                       expected: 0, actual: 1,
+                   })));
+        assert!(matches!(LfuCache::<u32, u32>::try_new(0),
+                         Err(CacheError::ZeroCapacity)));
+
+        let config = CacheConfig::new(0);
+        let err    = LfuCache::<u32, u32>::from_config(&config)
+                         .map_err(CacheError::from).err().unwrap();
+
+        assert_eq!(err, CacheError::Config(ConfigError::ZeroCapacity));
+        assert!(core::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn insert_results_become_errors() {
+        let mut cache = LfuCache::new(2);
+
+        cache.set_weigher(10, |_: &u32, value: &usize| *value);
+        cache.set_max_item_fraction(0.5);
+
+        assert_eq!(cache.insert_checked(1, 3).into_result(), Ok(None));
+        assert_eq!(cache.insert_checked(1, 4).into_result(), Ok(Some(3)));
+        assert_eq!(cache.insert_checked(2, 8).into_result(),
+                   Err(CacheError::TooLarge { weight: 8 }));
+        assert_eq!(InsertResult::<u32>::Rejected.into_result(),
+                   Err(CacheError::Rejected));
+    }
+
+    #[test]
+    fn messages() {
+        let version = VersionMismatch { expected: 3, actual: 4 };
+
+        for (err, message) in [
+            (CacheError::ZeroCapacity,
+             "a cache with a capacity of 0 can't hold a value"),
+            (CacheError::Rejected, "the item was rejected"),
+            (CacheError::TooLarge { weight: 12 },
+             "an item weighing 12 is over the limit"),
+            (version.into(), "expected version 3, found version 4"),
+            (ConfigError::NoWeigher.into(), "weighing items needs a weigher"),
+        ] {
+            assert_eq!(err.to_string(), message);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! `StaticLfuCache`, for a process-wide cache in a `static`. A cache can't be
+//! built in a const context, so this holds what it takes to build one, and 
+//! builds it behind a `OnceLock` the first time it's used.
+//! 
+
+use std::hash::{BuildHasher, Hash};
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use crate::{DefaultState, LfuCache, SyncLfuCache};
+
+/// A `SyncLfuCache` that can be put in a `static` and is built on first use.
+/// It derefs to the `SyncLfuCache`, so it's used just like one.
+/// 
+/// ```
+/// use lfu_cache::StaticLfuCache;
+/// 
+/// static SQUARES: StaticLfuCache<u64, u64> = StaticLfuCache::new(1024);
+/// 
+/// fn square(n: u64) -> u64 {
+///     SQUARES.get_or_insert_with(n, || n * n)
+/// }
+/// 
+/// let threads = (0..4).map(|_| std::thread::spawn(|| square(12)))
+///                     .collect::<Vec<_>>();
+/// 
+/// for thread in threads {
+///     assert_eq!(thread.join().unwrap(), 144);
+/// }
+/// assert_eq!(SQUARES.len(), 1);
+/// ```
+pub struct StaticLfuCache<K, V, S = DefaultState> {
+    cache    : OnceLock<SyncLfuCache<K, V, S>>,
+    capacity : usize,
+    init     : fn(usize) -> LfuCache<K, V, S>,
+}
+
+impl<K, V> StaticLfuCache<K, V> 
+where
+    K: Eq + Hash + Clone,
+{
+    /// Creates a cache that will have the given capacity once it's built.
+    /// 
+    pub const fn new(capacity: usize) -> Self {
+        Self::with_init(capacity, LfuCache::new)
+    }
+}
+
+impl<K, V, S> StaticLfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Creates a cache that's built by calling `init` with `capacity`, for a
+    /// cache configured with `LfuCacheBuilder`, say. 
+    /// 
+    pub const fn with_init(capacity : usize, 
+                           init     : fn(usize) -> LfuCache<K, V, S>) 
+        -> Self 
+    {
+        Self { cache: OnceLock::new(), capacity, init }
+    }
+
+    /// Returns the cache, building it first if this is its first use. If 
+    /// several threads get here at once, only one builds it, and the others 
+    /// wait for it.
+    /// 
+    pub fn cache(&self) -> &SyncLfuCache<K, V, S> {
+        self.cache.get_or_init(|| {
+            SyncLfuCache::from_cache((self.init)(self.capacity))
+        })
+    }
+
+    /// Returns `true` once the cache has been built.
+    /// 
+    pub fn is_initialized(&self) -> bool {
+        self.cache.get().is_some()
+    }
+}
+
+impl<K, V, S> Deref for StaticLfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    type Target = SyncLfuCache<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.cache()
+    }
+}
@@ -0,0 +1,176 @@
+//! A small, seedable, non-cryptographic hasher. Given the same seed it always
+//! produces the same hashes, so a hash map built with it behaves the same way
+//! from one run to the next. It's based on the multiply-and-rotate scheme of
+//! FxHash, with a final mixing step so both the high and low bits of the hash
+//! are usable by the hash map.
+//! 
+//! It makes no attempt to resist hash flooding.
+//! 
+
+use core::hash::{BuildHasher, Hasher};
+
+/// The hasher the caches use when none is given: the standard library's
+/// randomly seeded `RandomState`, or `SeededState` with the `fast-hash`
+/// feature. `SeededState` is much faster on short keys, but it's
+/// the same for every run and so no defense against hash flooding.
+/// 
+#[cfg(all(feature = "std", not(feature = "fast-hash")))]
+pub type DefaultState = std::collections::hash_map::RandomState;
+
+/// The hasher the caches use when none is given: the standard library's
+/// randomly seeded `RandomState`, or `SeededState` with the `fast-hash`
+/// feature. `SeededState` is much faster on short keys, but it's
+/// the same for every run and so no defense against hash flooding.
+/// 
+#[cfg(feature = "fast-hash")]
+pub type DefaultState = SeededState;
+
+/// The multiplier used by FxHash.
+/// 
+const MUL: u64 = 0x517c_c1b7_2722_0a95;
+
+/// Builds `SeededHasher`s that all start from the same seed.
+/// 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeededState {
+    seed: u64,
+}
+
+impl SeededState {
+    /// Creates a new `SeededState` with the given seed.
+    /// 
+    pub const fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = SeededHasher;
+
+    fn build_hasher(&self) -> SeededHasher {
+        SeededHasher { hash: self.seed }
+    }
+}
+
+/// The hasher built by `SeededState`.
+/// 
+#[derive(Clone, Debug)]
+pub struct SeededHasher {
+    hash: u64,
+}
+
+impl SeededHasher {
+    #[inline]
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(MUL);
+    }
+}
+
+impl Hasher for SeededHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        let mut rest = chunks.remainder();
+
+        if !rest.is_empty() {
+            // The same word as zero padding the rest to 8 bytes, but put 
+            // together from fixed size reads. A copy of a variable length
+            // is a call to `memcpy` that costs more than the hashing does on
+            // short keys.
+            let mut word  = 0;
+            let mut shift = 0;
+
+            if rest.len() >= 4 {
+                word  = u32::from_le_bytes(rest[..4].try_into().unwrap()) 
+                        as u64;
+                shift = 32;
+                rest  = &rest[4..];
+            }
+            if rest.len() >= 2 {
+                let half = u16::from_le_bytes(rest[..2].try_into().unwrap());
+                word  |= (half as u64) << shift;
+                shift += 16;
+                rest   = &rest[2..];
+            }
+            if let Some(&byte) = rest.first() {
+                word |= (byte as u64) << shift;
+            }
+            self.add(word);
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        // The 64 bit finalizer from MurmurHash3.
+        let mut h = self.hash;
+        h ^= h >> 33;
+        h  = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+        h  = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h ^ (h >> 33)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_determines_hash() {
+        let a = SeededState::new(1);
+        let b = SeededState::new(2);
+
+        assert_eq!(a.hash_one("key"), SeededState::new(1).hash_one("key"));
+        assert_ne!(a.hash_one("key"), b.hash_one("key"));
+        assert_ne!(a.hash_one(1u64), a.hash_one(2u64));
+    }
+
+    #[test]
+    fn short_writes_are_zero_padded() {
+        let bytes = *b"0123456789abcde";
+
+        for len in 0..bytes.len() {
+            let mut padded = [0; 16];
+            padded[..len].copy_from_slice(&bytes[..len]);
+
+            let mut hasher = SeededState::new(3).build_hasher();
+            hasher.write(&bytes[..len]);
+
+            let mut expected = SeededState::new(3).build_hasher();
+
+            for word in padded[..len.div_ceil(8) * 8].chunks(8) {
+                expected.add(u64::from_le_bytes(word.try_into().unwrap()));
+            }
+            assert_eq!(hasher.finish(), expected.finish(), "len {len}");
+        }
+    }
+}
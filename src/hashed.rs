@@ -0,0 +1,392 @@
+//! The map of an `LfuCache`, from its keys to their value records. It's a
+//! hash table that keeps each key's 64-bit hash in the key's slot, so that
+//! it grows without hashing the keys again and a probe only compares keys
+//! whose hashes match in full. The cache hashes a key once per operation and
+//! hands the hash to the `_hashed` methods, and to admission control and the
+//! ghosts of adaptive capacity, which use the same hasher.
+//! 
+//! The frequency queues keep each key's hash alongside it too, so an item the
+//! cache reaches through a queue rather than by the caller's key, like the 
+//! victim of an eviction, is found without hashing its key. An insert into a
+//! full cache hashes the new key and nothing else.
+//! 
+
+#[cfg(test)]
+use std::alloc::Layout;
+use std::borrow::Borrow;
+#[cfg(test)]
+use std::cell::Cell;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Index;
+
+use hashbrown::hash_table::{self, HashTable};
+use hashbrown::TryReserveError;
+
+/// A slot of the table: a key, its hash, and its value.
+/// 
+#[derive(Clone, Debug)]
+pub(crate) struct Slot<K, V> {
+    hash  : u64,
+    key   : K,
+    value : V,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct HashedMap<K, V, S> {
+    table  : HashTable<Slot<K, V>>,
+    hasher : S,
+}
+
+#[cfg(test)]
+thread_local! {
+    static REFUSE_GROWTH: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Makes `try_reserve()` on this thread fail wherever the table would have to
+/// grow, as it would if the allocator refused.
+/// 
+#[cfg(test)]
+pub(crate) fn refuse_growth(on: bool) {
+    REFUSE_GROWTH.with(|refuse| refuse.set(on));
+}
+
+/// Returns the test of whether a slot holds `key`, whose hash is `hash`.
+/// 
+fn holds<'a, K, V, Q>(hash: u64, key: &'a Q) -> impl Fn(&Slot<K, V>) -> bool
+                                                   + 'a
+where
+    K: Borrow<Q>,
+    Q: Eq + ?Sized,
+{
+    move |slot| slot.hash == hash && slot.key.borrow() == key
+}
+
+impl<K, V, S> HashedMap<K, V, S> {
+    pub(crate) fn with_capacity_and_hasher(capacity: usize, hasher: S)
+        -> Self
+    {
+        Self { table: HashTable::with_capacity(capacity), hasher }
+    }
+
+    pub(crate) fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Makes room for `additional` more keys, or returns the error growing
+    /// the table met with, leaving the table as it was.
+    /// 
+    pub(crate) fn try_reserve(&mut self, additional: usize) 
+        -> Result<(), TryReserveError> 
+    {
+        #[cfg(test)]
+        if REFUSE_GROWTH.with(Cell::get) 
+           && self.len() + additional > self.capacity() 
+        {
+            return Err(TryReserveError::AllocError { 
+                layout: Layout::new::<Slot<K, V>>() 
+            });
+        }
+        self.table.try_reserve(additional, |slot| slot.hash)
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.table.iter())
+    }
+
+    /// Returns the keys and values with the keys' hashes.
+    /// 
+    pub(crate) fn iter_hashed(&self)
+        -> impl Iterator<Item = (u64, &K, &V)>
+    {
+        self.table.iter().map(|slot| (slot.hash, &slot.key, &slot.value))
+    }
+
+    #[cfg(any(test, feature = "debug-verify"))]
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.table.iter().map(|slot| &slot.value)
+    }
+
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.table.iter_mut().map(|slot| &mut slot.value)
+    }
+
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.table.drain().map(|slot| (slot.key, slot.value))
+    }
+
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.table.retain(|slot| f(&slot.key, &mut slot.value));
+    }
+
+    /// Returns the index of the bucket holding the key whose hash is `hash`
+    /// and whose value passes `f`, without comparing keys.
+    /// 
+    pub(crate) fn find_index_by(&self, hash: u64, f: impl Fn(&V) -> bool) 
+        -> Option<usize> 
+    {
+        self.table.find_bucket_index(hash, |slot| {
+            slot.hash == hash && f(&slot.value)
+        })
+    }
+
+    /// Returns the hash, key and value in the bucket at `index`, if it's 
+    /// full. A key stays in its bucket until it's removed or the table grows.
+    /// 
+    pub(crate) fn get_at(&self, index: usize) -> Option<(u64, &K, &V)> {
+        self.table.get_bucket(index)
+                  .map(|slot| (slot.hash, &slot.key, &slot.value))
+    }
+
+    pub(crate) fn get_at_mut(&mut self, index: usize) 
+        -> Option<(&K, &mut V)> 
+    {
+        self.table.get_bucket_mut(index)
+                  .map(|slot| (&slot.key, &mut slot.value))
+    }
+}
+
+impl<K, V, S: BuildHasher> HashedMap<K, V, S> {
+    /// Returns the hash of a key, as the map files it.
+    /// 
+    pub(crate) fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        self.hasher.hash_one(key)
+    }
+
+    pub(crate) fn get_hashed<Q>(&self, hash: u64, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.table.find(hash, holds(hash, key)).map(|slot| &slot.value)
+    }
+
+    /// Returns the index of the key's bucket, see `get_at()`.
+    /// 
+    pub(crate) fn find_index_hashed<Q>(&self, hash: u64, key: &Q) 
+        -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.table.find_bucket_index(hash, holds(hash, key))
+    }
+
+    pub(crate) fn get_mut_hashed<Q>(&mut self, hash: u64, key: &Q)
+        -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.table.find_mut(hash, holds(hash, key)).map(|slot| &mut slot.value)
+    }
+
+    pub(crate) fn remove_entry_hashed<Q>(&mut self, hash: u64, key: &Q)
+        -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let (slot, _) = self.table.find_entry(hash, holds(hash, key))
+                                  .ok()?
+                                  .remove();
+        Some((slot.key, slot.value))
+    }
+
+    /// Adds a key that isn't in the map, and returns its value.
+    /// 
+    pub(crate) fn insert_unique_hashed(&mut self, hash: u64, key: K, value: V)
+        -> &mut V
+    {
+        let slot = Slot { hash, key, value };
+
+        &mut self.table.insert_unique(hash, slot, |slot| slot.hash)
+                       .into_mut()
+                       .value
+    }
+
+    /// Inserts a key-value pair, returning the value it replaced.
+    /// 
+    #[cfg(test)]
+    pub(crate) fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Eq + Hash,
+    {
+        self.insert_hashed(self.hash(&key), key, value)
+    }
+
+    /// Like `insert()`, for a key whose hash is `hash`.
+    /// 
+    pub(crate) fn insert_hashed(&mut self, hash: u64, key: K, value: V) 
+        -> Option<V>
+    where
+        K: Eq,
+    {
+        match self.get_mut_hashed(hash, &key) {
+            Some(old) => Some(std::mem::replace(old, value)),
+            None      => {
+                self.insert_unique_hashed(hash, key, value);
+                None
+            },
+        }
+    }
+
+    pub(crate) fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_hashed(self.hash(key), key)
+    }
+
+    pub(crate) fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_mut_hashed(self.hash(key), key)
+    }
+
+    pub(crate) fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let hash = self.hash(key);
+
+        self.table.find(hash, holds(hash, key))
+                  .map(|slot| (&slot.key, &slot.value))
+    }
+
+    pub(crate) fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.remove_entry_hashed(self.hash(key), key).map(|(_, value)| value)
+    }
+}
+
+impl<K, V, S, Q> Index<&Q> for HashedMap<K, V, S>
+where
+    K: Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not in the map")
+    }
+}
+
+/// An iterator over the keys and values of a `HashedMap`.
+/// 
+pub(crate) struct Iter<'a, K, V>(hash_table::Iter<'a, Slot<K, V>>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|slot| (&slot.key, &slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::hash::Hasher;
+
+    thread_local! {
+        static HASHED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// A key that counts the times it's hashed.
+    /// 
+    #[derive(Clone, PartialEq, Eq)]
+    struct Counted(u32);
+
+    impl Hash for Counted {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            HASHED.with(|hashed| hashed.set(hashed.get() + 1));
+            self.0.hash(state);
+        }
+    }
+
+    fn hashed() -> usize {
+        HASHED.with(Cell::get)
+    }
+
+    #[test]
+    fn keys_are_hashed_once() {
+        let mut map = HashedMap::with_capacity_and_hasher(
+                          0, std::hash::RandomState::new());
+
+        // Growing the table from nothing doesn't hash the keys again.
+        for key in 0..1000 { map.insert(Counted(key), key); }
+
+        assert_eq!(hashed(), 1000);
+        assert!(map.capacity() >= 1000);
+
+        let hash = map.hash(&Counted(7));
+
+        assert_eq!(map.get_hashed(hash, &Counted(7)), Some(&7));
+        *map.get_mut_hashed(hash, &Counted(7)).unwrap() += 1;
+        assert_eq!(map[&Counted(7)], 8);
+
+        assert_eq!(map.insert(Counted(7), 70), Some(8));
+        assert_eq!(map.remove(&Counted(7)), Some(70));
+        assert!(!map.contains_key(&Counted(7)));
+        assert_eq!(map.remove_entry_hashed(hash, &Counted(7)).map(|e| e.1),
+                   None);
+
+        // A bucket is reached by its index without hashing the key.
+        let hash  = map.hash(&Counted(8));
+        let index = map.find_index_hashed(hash, &Counted(8)).unwrap();
+
+        assert_eq!(map.get_at(index).map(|(_, key, value)| (key.0, *value)),
+                   Some((8, 8)));
+        assert_eq!(map.find_index_by(hash, |value| *value == 8), Some(index));
+        *map.get_at_mut(index).unwrap().1 += 1;
+        assert_eq!(map.get_at(index).map(|(_, _, value)| *value), Some(9));
+
+        map.retain(|key, _| key.0 % 2 == 0);
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.iter().count(), 500);
+        assert!(map.iter_hashed().all(|(hash, key, _)| {
+            map.get_hashed(hash, key).is_some()
+        }));
+        assert_eq!(hashed(), 1006);
+    }
+}
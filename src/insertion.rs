@@ -0,0 +1,73 @@
+//! The order in which keys entered the cache. Each key is given the next
+//! number of a monotonic sequence when it's inserted, and keeps it for as long
+//! as it stays, however its frequency count changes. The cache keeps the index
+//! in step with every insertion and removal.
+//! 
+
+use std::collections::{btree_map, BTreeMap, HashMap};
+use std::hash::Hash;
+
+#[derive(Clone, Debug)]
+pub(crate) struct InsertionIndex<K> {
+    seqs : HashMap<K, u64>,
+    keys : BTreeMap<u64, K>,
+    next : u64,
+}
+
+impl<K> InsertionIndex<K>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Creates an index of the keys, numbered in the order they're given.
+    /// 
+    pub(crate) fn new<'a>(keys: impl Iterator<Item = &'a K>) -> Self
+    where
+        K: 'a,
+    {
+        let mut index = Self {
+            seqs : HashMap::new(),
+            keys : BTreeMap::new(),
+            next : 0,
+        };
+        keys.for_each(|key| index.insert(key));
+        index
+    }
+
+    /// Gives a new key the next sequence number.
+    /// 
+    pub(crate) fn insert(&mut self, key: &K) {
+        let seq = self.next;
+
+        self.next += 1;
+        self.seqs.insert(key.clone(), seq);
+        self.keys.insert(seq, key.clone());
+    }
+}
+
+impl<K> InsertionIndex<K>
+where
+    K: Eq + Hash,
+{
+    pub(crate) fn remove(&mut self, key: &K) {
+        if let Some(seq) = self.seqs.remove(key) {
+            self.keys.remove(&seq);
+        }
+    }
+
+    /// Forgets every key. The sequence carries on from where it was.
+    /// 
+    pub(crate) fn clear(&mut self) {
+        self.seqs.clear();
+        self.keys.clear();
+    }
+
+    pub(crate) fn get(&self, key: &K) -> Option<u64> {
+        self.seqs.get(key).copied()
+    }
+
+    /// Returns the indexed keys, oldest first.
+    /// 
+    pub(crate) fn keys(&self) -> btree_map::Values<'_, u64, K> {
+        self.keys.values()
+    }
+}
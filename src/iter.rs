@@ -0,0 +1,523 @@
+//! The iterators returned by `LfuCache` and its buckets. They're named types
+//! so they can be kept in struct fields and named in trait impls. None of them
+//! change the frequency counts of the items they visit.
+//! 
+
+use std::collections::btree_map;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+use linked_vector::LinkedVector;
+
+#[cfg(feature = "debug-audit")]
+use std::collections::vec_deque;
+
+use crate::cache::Value;
+use crate::entry::Bucket;
+use crate::hashed::{self, HashedMap};
+use crate::queues::{LfuOrder, Queued, Queues};
+use crate::{Counter, DefaultState};
+
+#[cfg(feature = "debug-audit")]
+use crate::cache::AuditRecord;
+
+/// An iterator over the items of an `LfuCache` in eviction order, see
+/// `LfuCache::iter()`.
+/// 
+pub struct Iter<'a, K, V, S = DefaultState, C = usize> {
+    pub(crate) order : LfuOrder<'a, K>,
+    pub(crate) map   : &'a HashedMap<K, Value<V, C>, S>,
+}
+
+impl<'a, K, V, S, C: Counter> Iterator for Iter<'a, K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = self.map;
+        self.order.next().map(|(_, key)| (key, &map[key].value))
+    }
+}
+
+impl<K, V, S, C: Counter> Clone for Iter<'_, K, V, S, C> {
+    fn clone(&self) -> Self {
+        Self { order: self.order, map: self.map }
+    }
+}
+
+impl<K, V, S, C: Counter> fmt::Debug for Iter<'_, K, V, S, C>
+where
+    K: Eq + Hash + fmt::Debug,
+    V: fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the items of an `LfuCache` in eviction order, with their
+/// frequency counts, see `LfuCache::entries()`.
+/// 
+pub struct Entries<'a, K, V, S = DefaultState, C = usize> {
+    pub(crate) order : LfuOrder<'a, K>,
+    pub(crate) map   : &'a HashedMap<K, Value<V, C>, S>,
+}
+
+impl<'a, K, V, S, C: Counter> Iterator for Entries<'a, K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a V, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = self.map;
+
+        self.order.next().map(|(_, key)| {
+            let vrec = &map[key];
+            (key, &vrec.value, vrec.count.to_usize())
+        })
+    }
+}
+
+impl<K, V, S, C: Counter> Clone for Entries<'_, K, V, S, C> {
+    fn clone(&self) -> Self {
+        Self { order: self.order, map: self.map }
+    }
+}
+
+impl<K, V, S, C: Counter> fmt::Debug for Entries<'_, K, V, S, C>
+where
+    K: Eq + Hash + fmt::Debug,
+    V: fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the items of an `LfuCache` in arbitrary order, see
+/// `LfuCache::iter_unordered()`.
+/// 
+pub struct IterUnordered<'a, K, V, C = usize> {
+    pub(crate) items      : hashed::Iter<'a, K, Value<V, C>>,
+    pub(crate) generation : u64,
+}
+
+impl<'a, K, V, C: Counter> Iterator for IterUnordered<'a, K, V, C> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let generation = self.generation;
+
+        self.items.find(|(_, vrec)| vrec.gen >= generation)
+                  .map(|(key, vrec)| (key, &vrec.value))
+    }
+}
+
+impl<K, V, C: Counter> Clone for IterUnordered<'_, K, V, C> {
+    fn clone(&self) -> Self {
+        Self { items: self.items.clone(), generation: self.generation }
+    }
+}
+
+impl<K, V, C: Counter> fmt::Debug for IterUnordered<'_, K, V, C>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the items of an `LfuCache` in arbitrary order, with their
+/// frequency counts, see `LfuCache::entries_unordered()`.
+/// 
+pub struct EntriesUnordered<'a, K, V, C = usize> {
+    pub(crate) items      : hashed::Iter<'a, K, Value<V, C>>,
+    pub(crate) generation : u64,
+}
+
+impl<'a, K, V, C: Counter> Iterator for EntriesUnordered<'a, K, V, C> {
+    type Item = (&'a K, &'a V, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let generation = self.generation;
+
+        self.items.find(|(_, vrec)| vrec.gen >= generation)
+                  .map(|(key, vrec)| {
+                      (key, &vrec.value, vrec.count.to_usize())
+                  })
+    }
+}
+
+impl<K, V, C: Counter> Clone for EntriesUnordered<'_, K, V, C> {
+    fn clone(&self) -> Self {
+        Self { items: self.items.clone(), generation: self.generation }
+    }
+}
+
+impl<K, V, C: Counter> fmt::Debug for EntriesUnordered<'_, K, V, C>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator that moves the items out of an `LfuCache` in eviction order,
+/// with their frequency counts, see `LfuCache::into_entries()`.
+/// 
+pub struct IntoEntries<K, V, S = DefaultState, C = usize> {
+    pub(crate) queues : Queues<K>,
+    pub(crate) map    : HashedMap<K, Value<V, C>, S>,
+    pub(crate) left   : usize,
+}
+
+impl<K, V, S, C: Counter> Iterator for IntoEntries<K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = (K, V, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The queues only hold the current items, not those left behind by
+        // `invalidate_all()`. Their copies of the keys are dropped in favor of
+        // the keys the items were inserted with.
+        loop {
+            let queue = &mut self.queues.front_mut()?.1;
+
+            let Some(Queued { hash, key }) = queue.pop_front() else {
+                self.queues.pop_front();
+                continue;
+            };
+            let (key, vrec) = self.map.remove_entry_hashed(hash, &key)
+                                      .unwrap();
+
+            self.left -= 1;
+            return Some((key, vrec.value, vrec.count.to_usize()));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<K, V, S, C: Counter> ExactSizeIterator for IntoEntries<K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{}
+
+impl<K, V, S, C: Counter> fmt::Debug for IntoEntries<K, V, S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoEntries").field("left", &self.left)
+                                     .finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the keys of an `LfuCache` in the order they entered it,
+/// see `LfuCache::keys_in_insertion_order()`.
+/// 
+pub struct KeysInInsertionOrder<'a, K> {
+    pub(crate) keys: Option<btree_map::Values<'a, u64, K>>,
+}
+
+impl<'a, K> Iterator for KeysInInsertionOrder<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.keys.as_mut()?.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.as_ref().map_or((0, Some(0)), |keys| keys.size_hint())
+    }
+}
+
+impl<K> ExactSizeIterator for KeysInInsertionOrder<'_, K> {}
+
+impl<K> Clone for KeysInInsertionOrder<'_, K> {
+    fn clone(&self) -> Self {
+        Self { keys: self.keys.clone() }
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for KeysInInsertionOrder<'_, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the keys of a cache in eviction order, see
+/// `LfuCache::eviction_order()`.
+/// 
+pub struct EvictionOrder<'a, K> {
+    pub(crate) order: LfuOrder<'a, K>,
+}
+
+impl<'a, K> Iterator for EvictionOrder<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.order.next().map(|(_, key)| key)
+    }
+}
+
+impl<K> Clone for EvictionOrder<'_, K> {
+    fn clone(&self) -> Self {
+        Self { order: self.order }
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for EvictionOrder<'_, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the values of an `LfuCache` in eviction order, see
+/// `LfuCache::values_lfu_first()`.
+/// 
+pub struct ValuesLfuFirst<'a, K, V, S, C = usize> {
+    pub(crate) order : LfuOrder<'a, K>,
+    pub(crate) map   : &'a HashedMap<K, Value<V, C>, S>,
+}
+
+impl<'a, K, V, S, C: Counter> Iterator for ValuesLfuFirst<'a, K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = self.map;
+        self.order.next().map(|(_, key)| &map[key].value)
+    }
+}
+
+impl<K, V, S, C: Counter> Clone for ValuesLfuFirst<'_, K, V, S, C> {
+    fn clone(&self) -> Self {
+        Self { order: self.order, map: self.map }
+    }
+}
+
+impl<K, V, S, C: Counter> fmt::Debug for ValuesLfuFirst<'_, K, V, S, C>
+where
+    K: Eq + Hash,
+    V: fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the items of an `LfuCache` in eviction order, with
+/// mutable references to the values, see
+/// `LfuCache::iter_mut_by_eviction_order()`.
+/// 
+pub struct EvictionOrderMut<'a, K, V> {
+    // The pointers are to different values in the cache's map, which stays
+    // mutably borrowed for `'a`.
+    pub(crate) items : std::vec::IntoIter<(&'a K, *mut V, usize)>,
+    pub(crate) _map  : PhantomData<&'a mut V>,
+}
+
+impl<'a, K, V> Iterator for EvictionOrderMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.items.next().map(|(key, value, freq)| {
+            // SAFETY: Each key is in one queue only, so each pointer is to a
+            //         different value, and it's handed out once, as it leaves
+            //         `items`. The map is mutably borrowed for `'a`, so it 
+            //         can't move or drop the value while the reference lives.
+            (key, unsafe { &mut *value }, freq)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for EvictionOrderMut<'_, K, V> {}
+
+// SAFETY: The iterator stands in for a `&'a K` and a `&'a mut V` to each of
+//         its items, so it can go where those can.
+unsafe impl<K: Sync, V: Send> Send for EvictionOrderMut<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for EvictionOrderMut<'_, K, V> {}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for EvictionOrderMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items = self.items.as_slice().iter().map(|&(key, value, freq)| {
+            // SAFETY: The items still in `items` haven't been handed out, so
+            //         nothing else refers to their values, and the shared 
+            //         references end with the call, while the map is still 
+            //         borrowed.
+            (key, unsafe { &*value }, freq)
+        });
+        f.debug_list().entries(items).finish()
+    }
+}
+
+/// An iterator over mutable references to the values of an `LfuCache` in
+/// eviction order, see `LfuCache::values_lfu_first_mut()`.
+/// 
+pub struct ValuesLfuFirstMut<'a, K, V> {
+    pub(crate) items: EvictionOrderMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesLfuFirstMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.items.next().map(|(_, value, _)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesLfuFirstMut<'_, K, V> {}
+
+impl<K, V: fmt::Debug> fmt::Debug for ValuesLfuFirstMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self.items.items.as_slice().iter().map(|&(_, value, _)| {
+            // SAFETY: Like the items `EvictionOrderMut`'s `Debug` shows, these
+            //         haven't been handed out, and the map is still borrowed.
+            unsafe { &*value }
+        });
+        f.debug_list().entries(values).finish()
+    }
+}
+
+/// An iterator over the buckets of an `LfuCache` with frequency counts in a
+/// range, see `LfuCache::buckets_in()`.
+/// 
+pub struct Buckets<'a, K, V, S, C = usize> {
+    pub(crate) queues  : linked_vector::Iter<'a, (usize, 
+                                                  LinkedVector<Queued<K>>)>,
+    pub(crate) range   : (Bound<usize>, Bound<usize>),
+    pub(crate) map     : &'a HashedMap<K, Value<V, C>, S>,
+    pub(crate) started : bool,
+}
+
+impl<'a, K, V, S, C: Counter> Iterator for Buckets<'a, K, V, S, C> {
+    type Item = Bucket<'a, K, V, S, C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The frequencies are in ascending order, so the ones in range are
+        // all together.
+        for (freq, queue) in &mut self.queues {
+            if !self.range.contains(freq) {
+                if self.started { return None; }
+                continue;
+            }
+            self.started = true;
+
+            if !queue.is_empty() {
+                return Some(Bucket { frequency: *freq, queue, map: self.map });
+            }
+        }
+        None
+    }
+}
+
+impl<K, V, S, C: Counter> fmt::Debug for Buckets<'_, K, V, S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buckets").field("range", &self.range)
+                                 .finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the items of a bucket in queue order, see
+/// `Bucket::iter()`.
+/// 
+pub struct BucketIter<'a, K, V, S, C = usize> {
+    pub(crate) keys : linked_vector::Iter<'a, Queued<K>>,
+    pub(crate) map  : &'a HashedMap<K, Value<V, C>, S>,
+}
+
+impl<'a, K, V, S, C: Counter> Iterator for BucketIter<'a, K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = self.map;
+        self.keys.next().map(|Queued { hash, key }| {
+            (key, &map.get_hashed(*hash, key).unwrap().value)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<K, V, S, C: Counter> ExactSizeIterator for BucketIter<'_, K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{}
+
+impl<K, V, S, C: Counter> fmt::Debug for BucketIter<'_, K, V, S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BucketIter").field("remaining", &self.keys.len())
+                                    .finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the records in the audit log of an `LfuCache`, see
+/// `LfuCache::audit_log()` and `LfuCache::audit_for()`.
+/// 
+#[cfg(feature = "debug-audit")]
+pub struct AuditRecords<'a, K> {
+    pub(crate) records : Option<vec_deque::Iter<'a, AuditRecord<K>>>,
+    pub(crate) key     : Option<&'a K>,
+}
+
+#[cfg(feature = "debug-audit")]
+impl<'a, K: PartialEq> Iterator for AuditRecords<'a, K> {
+    type Item = &'a AuditRecord<K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.key;
+
+        self.records.as_mut()?.find(|rec| {
+            key.is_none_or(|key| rec.event.key().is_none_or(|k| k == key))
+        })
+    }
+}
+
+#[cfg(feature = "debug-audit")]
+impl<K> Clone for AuditRecords<'_, K> {
+    fn clone(&self) -> Self {
+        Self { records: self.records.clone(), key: self.key }
+    }
+}
+
+#[cfg(feature = "debug-audit")]
+impl<K: PartialEq + fmt::Debug> fmt::Debug for AuditRecords<'_, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
@@ -0,0 +1,361 @@
+//! A readable JSON dump of a cache, for attaching to bug reports or diffing
+//! between environments, see `LfuCache::export_json()`. Unlike the compact
+//! states the cache exports for warming up, it holds the values too. The
+//! document is pretty-printed and laid out like this:
+//! 
+//! ```json
+//! {
+//!   "version": 1,
+//!   "capacity": 4,
+//!   "len": 2,
+//!   "stats": null,
+//!   "entries": [
+//!     {
+//!       "key": "b",
+//!       "frequency": 3,
+//!       "value": 2
+//!     },
+//!     {
+//!       "key": "a",
+//!       "frequency": 1,
+//!       "value": 1
+//!     }
+//!   ]
+//! }
+//! ```
+//! 
+//! An entry with a priority boost also has a `"priority"` field, after its
+//! frequency, see `LfuCache::set_priority()`.
+//! 
+//! `version` is the version of the layout, `stats` is `null` unless the
+//! cache's stats are enabled, and `entries` lists the items by descending
+//! frequency count, and in reverse eviction order among equal counts, so 
+//! that in a plain cache the item `pop_lfu()` would remove first comes last.
+//! A cache with log buckets or priority boosts can queue an item with a 
+//! lower count ahead of one with a higher count; its entries are still 
+//! listed by count.
+//! 
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hash};
+use std::io::{Read, Write};
+
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{CacheStats, LfuCache};
+
+/// The version of the layout written by `export_json()`, and the only one
+/// `import_json()` reads.
+/// 
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document<K, V> {
+    version  : u32,
+    capacity : usize,
+    len      : usize,
+    stats    : Option<CacheStats>,
+    entries  : Vec<Entry<K, V>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry<K, V> {
+    key       : K,
+    frequency : usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    priority  : u8,
+    value     : V,
+}
+
+fn is_zero(priority: &u8) -> bool {
+    *priority == 0
+}
+
+impl<K, V, S> LfuCache<K, V, S>
+where
+    K: Eq + Hash + Serialize,
+    V: Serialize,
+    S: BuildHasher,
+{
+    /// Writes the cache to `writer` as a pretty-printed JSON document, with
+    /// its capacity, its stats and its items, by descending frequency count,
+    /// each with its count and priority boost. The layout is described in
+    /// the `json` module's documentation. Frequency counts aren't changed.
+    /// 
+    pub fn export_json<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        let mut entries = self.eviction_order().filter_map(|key| {
+            Some(Entry { key,
+                         frequency : self.frequency(key)?,
+                         priority  : self.priority(key)?,
+                         value     : self.peek_value(key)? })
+        }).collect::<Vec<_>>();
+
+        // The sort is stable, so eviction order is kept within each count.
+        entries.reverse();
+        entries.sort_by_key(|entry| Reverse(entry.frequency));
+
+        let document = Document {
+            version  : VERSION,
+            capacity : self.capacity(),
+            len      : entries.len(),
+            stats    : self.stats().copied(),
+            entries,
+        };
+        serde_json::to_writer_pretty(writer, &document)
+    }
+}
+
+impl<K, V> LfuCache<K, V>
+where
+    K: Eq + Hash + Clone + DeserializeOwned,
+    V: DeserializeOwned,
+{
+    /// Reads a cache written by `export_json()`. The items get back their
+    /// frequency counts, their priority boosts and, if the exported cache 
+    /// was a plain one, their places in the eviction order, and the stats are
+    /// restored if they were there. The new cache is a plain one.
+    /// 
+    /// Returns an error if the document isn't valid JSON, doesn't have the
+    /// layout `export_json()` writes, or doesn't describe a cache: if `len`
+    /// doesn't match the entries, there are more entries than `capacity`, a
+    /// key is listed twice, or the entries aren't in descending order of
+    /// frequency, which is at least 1.
+    /// 
+    pub fn import_json<R: Read>(reader: R) -> serde_json::Result<Self> {
+        let document: Document<K, V> = serde_json::from_reader(reader)?;
+
+        validate(&document).map_err(serde_json::Error::custom)?;
+
+        let boosted = document.entries.iter().rev()
+                              .filter(|entry| entry.priority > 0)
+                              .map(|entry| (entry.key.clone(), entry.priority))
+                              .collect::<Vec<_>>();
+
+        let entries = document.entries.into_iter().rev().map(|entry| {
+            (entry.key, entry.value, entry.frequency)
+        });
+        let mut cache = Self::bulk_load(document.capacity, entries);
+
+        for (key, priority) in boosted {
+            cache.set_priority(&key, priority);
+        }
+
+        if let Some(stats) = document.stats { cache.restore_stats(stats); }
+
+        Ok(cache)
+    }
+}
+
+/// Checks that the document describes a cache `bulk_load()` can rebuild as it
+/// was exported.
+/// 
+fn validate<K, V>(document: &Document<K, V>) -> Result<(), String>
+where
+    K: Eq + Hash,
+{
+    let entries = &document.entries;
+
+    if document.version != VERSION {
+        return Err(format!("unsupported version {}", document.version));
+    }
+    if document.len != entries.len() {
+        return Err(format!("len is {}, but there are {} entries",
+                           document.len, entries.len()));
+    }
+    if entries.len() > document.capacity {
+        return Err(format!("{} entries don't fit in a capacity of {}",
+                           entries.len(), document.capacity));
+    }
+    if entries.iter().any(|entry| entry.frequency == 0) {
+        return Err("an entry has a frequency of 0".into());
+    }
+    if entries.windows(2).any(|pair| pair[0].frequency < pair[1].frequency) {
+        return Err("the entries aren't in descending order of frequency"
+                   .into());
+    }
+    let mut keys = HashSet::with_capacity(entries.len());
+
+    if !entries.iter().all(|entry| keys.insert(&entry.key)) {
+        return Err("a key is listed more than once".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export<K, V>(cache: &LfuCache<K, V>) -> String
+    where
+        K: Eq + Hash + Serialize,
+        V: Serialize,
+    {
+        let mut json = Vec::new();
+
+        cache.export_json(&mut json).unwrap();
+        String::from_utf8(json).unwrap()
+    }
+
+    fn import(json: &str) -> serde_json::Result<LfuCache<String, u32>> {
+        LfuCache::import_json(json.as_bytes())
+    }
+
+    #[test]
+    fn layout_is_stable() {
+        let mut cache = LfuCache::new(4);
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.get(&"b");
+        cache.get(&"b");
+
+        // The layout in the module's documentation.
+        assert_eq!(export(&cache), r#"{
+  "version": 1,
+  "capacity": 4,
+  "len": 2,
+  "stats": null,
+  "entries": [
+    {
+      "key": "b",
+      "frequency": 3,
+      "value": 2
+    },
+    {
+      "key": "a",
+      "frequency": 1,
+      "value": 1
+    }
+  ]
+}"#);
+        cache.enable_stats();
+        cache.get(&"c");
+
+        assert!(export(&cache).contains(r#"
+  "stats": {
+    "hits": 0,
+    "misses": 1,
+    "inserts": 0,
+    "updates": 0,
+    "evictions": 0,
+    "rejections": 0,
+    "too_large": 0,
+    "grows": 0,
+    "shrinks": 0
+  },
+"#));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut cache = LfuCache::new(5);
+
+        cache.enable_stats();
+
+        for (i, key) in ["a", "b", "c", "d", "e"].into_iter().enumerate() {
+            cache.insert(key.to_string(), i as u32);
+        }
+        for key in ["c", "a", "c", "e", "d", "c"] {
+            cache.get(&key.to_string());
+        }
+        cache.get(&"z".to_string());
+
+        let copy = import(&export(&cache)).unwrap();
+
+        assert_eq!(export(&copy), export(&cache));
+        assert_eq!(copy.capacity(), 5);
+        assert_eq!(copy.stats(), cache.stats());
+        assert!(copy.eviction_order().eq(cache.eviction_order()));
+
+        for (key, value) in cache.iter() {
+            assert_eq!(copy.peek_value(key), Some(value));
+            assert_eq!(copy.frequency(key), cache.frequency(key));
+        }
+        let plain = import(&export(&LfuCache::<String, u32>::new(2))).unwrap();
+
+        assert_eq!(plain.stats(), None);
+        assert!(plain.is_empty());
+    }
+
+    #[test]
+    fn round_trip_with_log_buckets() {
+        // x (count 7) and y (count 4) share a bucket, with x queued first.
+        let mut cache = crate::LfuCacheBuilder::new(4).log_buckets(2).build();
+
+        cache.insert("x".to_string(), 1);
+        for _ in 0..6 { cache.get(&"x".to_string()); }
+        cache.insert("y".to_string(), 2);
+        for _ in 0..3 { cache.get(&"y".to_string()); }
+        cache.insert("z".to_string(), 3);
+
+        let json = export(&cache);
+        let copy = import(&json).unwrap();
+
+        assert!(json.find("\"x\"") < json.find("\"y\""));
+        assert_eq!(export(&copy), json);
+        for (key, value) in cache.iter() {
+            assert_eq!(copy.peek_value(key), Some(value));
+            assert_eq!(copy.frequency(key), cache.frequency(key));
+        }
+        assert!(copy.eviction_order().map(String::as_str).eq(["z", "y", "x"]));
+    }
+
+    #[test]
+    fn round_trip_with_priorities() {
+        let mut cache = LfuCache::new(4);
+
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        cache.insert("c".to_string(), 3);
+        cache.get(&"b".to_string());
+        cache.set_priority(&"a".to_string(), 3);
+
+        let json = export(&cache);
+        let copy = import(&json).unwrap();
+
+        assert!(json.contains(r#""frequency": 1,
+      "priority": 3,"#));
+        assert_eq!(export(&copy), json);
+        assert_eq!(copy.priority(&"a".to_string()), Some(3));
+        assert_eq!(copy.priority(&"b".to_string()), Some(0));
+        assert!(copy.eviction_order().eq(cache.eviction_order()));
+        assert!(copy.eviction_order().map(String::as_str).eq(["c", "b", "a"]));
+    }
+
+    #[test]
+    fn import_checks_the_shape() {
+        let entry = |key: &str, freq: usize| {
+            format!(r#"{{ "key": "{key}", "frequency": {freq}, "value": 0 }}"#)
+        };
+        let doc = |version: u32, cap: usize, len: usize, entries: &[String]| {
+            format!(r#"{{ "version": {version}, "capacity": {cap}, 
+                          "len": {len}, "stats": null, 
+                          "entries": [{}] }}"#, entries.join(","))
+        };
+        let fails = |json: &str, msg: &str| {
+            let e = import(json).map(|_| ()).unwrap_err().to_string();
+            assert!(e.contains(msg), "{e:?} doesn't mention {msg:?}");
+        };
+        let (a2, b1) = (entry("a", 2), entry("b", 1));
+
+        assert_eq!(import(&doc(1, 2, 2, &[a2.clone(), b1.clone()]))
+                         .unwrap().frequency(&"a".to_string()), Some(2));
+
+        fails(&doc(2, 2, 2, &[a2.clone(), b1.clone()]), "unsupported version");
+        fails(&doc(1, 2, 1, &[a2.clone(), b1.clone()]), "len is 1");
+        fails(&doc(1, 1, 2, &[a2.clone(), b1.clone()]), "don't fit");
+        fails(&doc(1, 2, 2, &[b1.clone(), a2.clone()]), "descending order");
+        fails(&doc(1, 2, 2, &[a2.clone(), entry("b", 0)]), "frequency of 0");
+        fails(&doc(1, 2, 2, &[a2.clone(), entry("a", 1)]), "more than once");
+        fails(r#"{ "version": 1, "capacity": 2, "len": 0 }"#,
+              "missing field `entries`");
+        fails(&doc(1, 2, 0, &[]).replace("null", "null, \"extra\": 0"),
+              "unknown field `extra`");
+        fails(&doc(1, 2, 1, &[a2.replace("0 }", "\"zero\" }")]),
+              "invalid type");
+    }
+}
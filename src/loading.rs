@@ -0,0 +1,245 @@
+//! A read-through cache that owns its loader. Instead of passing a closure to
+//! every lookup, the loader is given once, and `get()` calls it on a miss.
+//! 
+
+use std::hash::{BuildHasher, Hash};
+
+use crate::clock::Stopwatch;
+use crate::{DefaultState, LfuCache, LoadStats};
+
+/// An `LfuCache` that loads missing values with the loader it was created
+/// with.
+/// 
+pub struct LoadingCache<K, V, F, S = DefaultState> {
+    cache  : LfuCache<K, V, S>,
+    loader : F,
+    stats  : LoadStats,
+}
+
+impl<K, V, E, F> LoadingCache<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    F: FnMut(&K) -> Result<V, E>,
+{
+    /// Creates a loading cache with the given capacity.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `capacity` is 0, since the cache has to hold what it loads.
+    /// 
+    pub fn new(capacity: usize, loader: F) -> Self {
+        Self::from_cache(LfuCache::new(capacity), loader)
+    }
+}
+
+impl<K, V, E, F, S> LoadingCache<K, V, F, S>
+where
+    K: Eq + Hash + Clone,
+    F: FnMut(&K) -> Result<V, E>,
+    S: BuildHasher,
+{
+    /// Puts `loader` in charge of filling `cache`, which keeps its items and
+    /// whatever features it has enabled.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the cache's capacity is 0.
+    /// 
+    pub fn from_cache(cache: LfuCache<K, V, S>, loader: F) -> Self {
+        assert!(cache.capacity() > 0, "a loading cache needs a capacity");
+
+        Self { cache, loader, stats: LoadStats::default() }
+    }
+
+    /// Returns a reference to the key's value, loading and inserting it if
+    /// the key isn't cached. A loader error is passed on, and nothing is
+    /// inserted.
+    /// 
+    pub fn get(&mut self, key: &K) -> Result<&V, E> {
+        // One lookup, so an item that expires while it's being looked up is
+        // loaded again rather than found and then missed.
+        let (loader, stats) = (&mut self.loader, &mut self.stats);
+        let watch           = self.cache.stopwatch();
+
+        self.cache.get_or_try_insert_with(key.clone(), || {
+            Self::load(loader, stats, watch, key)
+        })
+    }
+
+    /// Removes the key from the cache, returning its value if it was cached.
+    /// The next `get()` loads it again.
+    /// 
+    pub fn invalidate(&mut self, key: &K) -> Option<V> {
+        self.cache.remove(key)
+    }
+
+    /// Reloads the key's value. A cached item gets the new value in place,
+    /// keeping its frequency count, see `LfuCache::replace_value()`; a key
+    /// that isn't cached is inserted. If the loader fails, the cache is left
+    /// as it was.
+    /// 
+    pub fn refresh(&mut self, key: &K) -> Result<(), E> {
+        let watch = self.cache.stopwatch();
+        let value = Self::load(&mut self.loader, &mut self.stats, watch, key)?;
+
+        if self.cache.contains_key(key) {
+            self.cache.replace_value(key, value);
+        } else {
+            self.cache.insert(key.clone(), value);
+        }
+        Ok(())
+    }
+
+    /// Returns the loader's stats. Loads are timed on the cache's clock, if
+    /// it has one.
+    /// 
+    pub fn load_stats(&self) -> &LoadStats {
+        &self.stats
+    }
+
+    /// Sets the loader's stats back to zero.
+    /// 
+    pub fn reset_load_stats(&mut self) {
+        self.stats = LoadStats::default();
+    }
+
+    /// Returns the cache.
+    /// 
+    pub fn cache(&self) -> &LfuCache<K, V, S> {
+        &self.cache
+    }
+
+    /// Returns the cache, for the rest of its methods.
+    /// 
+    pub fn cache_mut(&mut self) -> &mut LfuCache<K, V, S> {
+        &mut self.cache
+    }
+
+    /// Calls the loader, timing and counting the call.
+    /// 
+    fn load(loader : &mut F, 
+            stats  : &mut LoadStats, 
+            watch  : Stopwatch, 
+            key    : &K) 
+        -> Result<V, E> 
+    {
+        let result = loader(key);
+
+        stats.record(watch.elapsed(), result.is_ok());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn loads_once_until_evicted() {
+        let mut calls = HashMap::new();
+        let mut cache = LoadingCache::new(2, |key: &u32| {
+            *calls.entry(*key).or_insert(0) += 1;
+            Ok::<_, ()>(key * 10)
+        });
+        for _ in 0..3 {
+            assert_eq!(cache.get(&1), Ok(&10));
+            assert_eq!(cache.get(&2), Ok(&20));
+        }
+        assert_eq!(cache.load_stats().loads, 2);
+
+        // Key 3 evicts key 1, which is loaded again.
+        cache.get(&3).unwrap();
+        cache.get(&1).unwrap();
+        assert_eq!(cache.invalidate(&1), Some(10));
+        cache.get(&1).unwrap();
+        assert_eq!(cache.load_stats().loads, 5);
+        drop(cache);
+        assert_eq!(calls, HashMap::from([(1, 3), (2, 1), (3, 1)]));
+    }
+
+    #[test]
+    fn loads_are_timed_on_the_cache_clock() {
+        use crate::ManualClock;
+        use std::time::Duration;
+
+        let clock = ManualClock::new();
+        let mut inner = LfuCache::new(2);
+
+        inner.set_clock(clock.clone());
+
+        let mut cache = LoadingCache::from_cache(inner, |key: &u64| {
+            clock.advance(Duration::from_millis(*key));
+            if *key == 0 { Err(()) } else { Ok(*key) }
+        });
+        cache.get(&3).unwrap();
+        cache.get(&3).unwrap();
+        cache.get(&0).unwrap_err();
+        cache.refresh(&200).unwrap();
+
+        let stats = cache.load_stats();
+
+        assert_eq!((stats.loads, stats.failures), (2, 1));
+        assert_eq!(stats.load_time, Duration::from_millis(203));
+        assert_eq!(stats.max_load_time, Duration::from_millis(200));
+        assert_eq!(stats.histogram, [1, 0, 1, 0, 1, 0, 0]);
+
+        cache.reset_load_stats();
+        assert_eq!(cache.load_stats().calls(), 0);
+    }
+
+    #[test]
+    fn expiring_mid_lookup_reloads() {
+        use crate::Clock;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::time::Duration;
+
+        // A clock that moves a millisecond every time it's read.
+        #[derive(Debug)]
+        struct Ticking(AtomicU64);
+
+        impl Clock for Ticking {
+            fn now(&self) -> Duration {
+                Duration::from_millis(self.0.fetch_add(1, Ordering::Relaxed))
+            }
+        }
+        for ttl in 1..60 {
+            let mut inner = LfuCache::new(2);
+
+            inner.set_clock(Ticking(AtomicU64::new(0)));
+            inner.insert_with_ttl(1, 0, Duration::from_millis(ttl));
+
+            let mut cache = LoadingCache::from_cache(inner, |key: &u64| {
+                Ok::<_, ()>(*key)
+            });
+            for _ in 0..ttl {
+                assert!(matches!(cache.get(&1), Ok(&0) | Ok(&1)));
+            }
+            assert_eq!(cache.get(&1), Ok(&1));
+            assert!(cache.load_stats().loads >= 1);
+        }
+    }
+
+    #[test]
+    fn refresh_replaces_in_place() {
+        let mut version = 0;
+        let mut cache   = LoadingCache::new(4, |key: &u32| {
+            version += 1;
+            if *key == 0 { Err("no such key") } else { Ok((*key, version)) }
+        });
+        cache.get(&1).unwrap();
+        cache.get(&1).unwrap();
+        cache.refresh(&1).unwrap();
+        assert_eq!(cache.cache().frequency(&1), Some(2));
+        assert_eq!(cache.get(&1), Ok(&(1, 2)));
+
+        cache.refresh(&2).unwrap();
+        assert_eq!(cache.cache().frequency(&2), Some(1));
+        assert_eq!(cache.get(&0), Err("no such key"));
+        assert_eq!(cache.refresh(&0), Err("no such key"));
+        assert_eq!(cache.cache().len(), 2);
+
+        let stats = cache.load_stats();
+        assert_eq!((stats.loads, stats.failures), (3, 2));
+    }
+}
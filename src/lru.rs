@@ -0,0 +1,160 @@
+//! Conversions between `LfuCache` and the `lru` crate's `LruCache`, for
+//! running the two side by side or moving from one to the other. With the
+//! `lru-interop` feature an `LruCache` converts into an `LfuCache`, see
+//! `From<LruCache>`, and back, see `LfuCache::into_lru()`.
+//! 
+//! Neither cache has what the other orders its items by, so the order items
+//! would be evicted in is what's kept. An `LruCache`'s items come in at a
+//! frequency count of 1, least recently used first, so the item it would
+//! have evicted first is the LFU item. An `LfuCache`'s items go out in
+//! eviction order, so the item it would have evicted first is the least
+//! recently used one, and frequency counts become recency. Capacities carry
+//! over.
+//! 
+
+use std::hash::{BuildHasher, Hash};
+use std::num::NonZeroUsize;
+
+use ::lru::LruCache;
+
+use crate::{Counter, LfuCache};
+
+impl<K, V> From<LruCache<K, V>> for LfuCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Creates a cache with the capacity and items of `lru`, whose items all
+    /// have a frequency count of 1 and are evicted in the order `lru` would
+    /// have evicted them. The map is sized for the items rather than the
+    /// capacity, so an unbounded `LruCache` converts too.
+    /// 
+    fn from(lru: LruCache<K, V>) -> Self {
+        let mut cache = LfuCache::new(lru.len());
+
+        cache.set_capacity(lru.cap().get());
+
+        // The owned iterator pops the least recently used item first.
+        for (key, value) in lru { cache.insert(key, value); }
+        cache
+    }
+}
+
+impl<K, V, S, C> LfuCache<K, V, S, C>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    C: Counter,
+{
+    /// Consumes the cache, returning an `LruCache` with its capacity and
+    /// items, in which the item `pop_lfu()` would have removed first is the
+    /// least recently used one. A capacity of 0 becomes 1, the least an
+    /// `LruCache` has. Pinned items a cache may hold past its capacity are
+    /// dropped, least protected first.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCache;
+    /// 
+    /// let mut cache = LfuCache::new(3);
+    /// 
+    /// for key in 0..3 { cache.insert(key, key * 10); }
+    /// cache.get(&0);
+    /// 
+    /// let mut lru = cache.into_lru();
+    /// 
+    /// assert_eq!(lru.cap().get(), 3);
+    /// assert_eq!(lru.pop_lru(), Some((1, 10)));
+    /// ```
+    pub fn into_lru(self) -> LruCache<K, V> {
+        let capacity = NonZeroUsize::new(self.capacity())
+                                    .unwrap_or(NonZeroUsize::MIN);
+
+        // An unbounded cache is grown to the items, then bounded, so a large
+        // capacity isn't set aside up front.
+        let mut lru = LruCache::unbounded();
+
+        for (key, value, _) in self.into_entries() { lru.put(key, value); }
+
+        lru.resize(capacity);
+        lru
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_order_becomes_eviction_order() {
+        let mut lru = LruCache::new(NonZeroUsize::new(5).unwrap());
+
+        for key in 0..5 { lru.put(key, key * 10); }
+
+        // 1 and 3 become the most recently used, in that order.
+        lru.get(&1);
+        lru.get(&3);
+
+        let mut cache = LfuCache::from(lru);
+
+        assert_eq!(cache.capacity(), 5);
+        assert_eq!(cache.len(), 5);
+        assert_eq!(cache.frequency(&3), Some(1));
+        assert_eq!(cache.pop_lfu(), Some((0, 0)));
+        assert_eq!(cache.pop_lfu(), Some((2, 20)));
+
+        // The next insertion into a full cache evicts 4.
+        cache.insert(5, 50);
+        cache.insert(6, 60);
+        cache.insert(7, 70);
+
+        assert!(!cache.contains_key(&4));
+        assert_eq!(cache.pop_lfu(), Some((1, 10)));
+
+        // An unbounded cache isn't allocated for its capacity.
+        let mut lru = LruCache::unbounded();
+
+        lru.put("a", 1);
+
+        let cache = LfuCache::from(lru);
+
+        assert_eq!(cache.capacity(), usize::MAX);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn eviction_order_becomes_lru_order() {
+        let mut cache = LfuCache::new(4);
+
+        for key in 0..4 { cache.insert(key, key * 10); }
+        for _ in 0..3 { cache.get(&0); }
+        cache.get(&2);
+
+        let mut lru = cache.into_lru();
+
+        assert_eq!(lru.cap().get(), 4);
+        assert_eq!(lru.len(), 4);
+        assert_eq!(lru.peek_mru(), Some((&0, &0)));
+
+        // A new item pushes out the least protected one, 1.
+        lru.put(4, 40);
+
+        assert!(!lru.contains(&1));
+        assert_eq!(lru.pop_lru(), Some((3, 30)));
+        assert_eq!(lru.pop_lru(), Some((2, 20)));
+        assert_eq!(lru.pop_lru(), Some((0, 0)));
+
+        // A round trip keeps the eviction order.
+        let mut cache = LfuCache::new(3);
+
+        for key in 0..3 { cache.insert(key, key); }
+        cache.get(&0);
+
+        let mut back = LfuCache::from(cache.into_lru());
+
+        assert_eq!(back.capacity(), 3);
+        assert_eq!(back.pop_lfu(), Some((1, 1)));
+        assert_eq!(back.pop_lfu(), Some((2, 2)));
+        assert_eq!(back.pop_lfu(), Some((0, 0)));
+
+        assert_eq!(LfuCache::<u32, u32>::new(0).into_lru().cap().get(), 1);
+    }
+}
@@ -0,0 +1,355 @@
+//! `NamespacedLfuCache`, for several logical caches that should share one
+//! budget, like users, posts and sessions. Each namespace is a typed view of
+//! the shared cache, see `NamespacedLfuCache::namespace()`, whose keys are
+//! tagged with the namespace internally. The items of all namespaces compete
+//! in one set of frequency queues, so a hot namespace takes up more of the
+//! capacity and a cold one gives way to it.
+//! 
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+use crate::{DefaultState, LfuCache};
+
+/// A value of any namespace, as a `NamespacedLfuCache` holds it.
+/// 
+type AnyValue = Box<dyn Any + Send>;
+
+/// The bookkeeping of one namespace: the type of its values, for checking
+/// views against, and its number of items.
+/// 
+#[derive(Clone, Copy, Debug)]
+struct Space {
+    type_id   : TypeId,
+    type_name : &'static str,
+    len       : usize,
+}
+
+/// A Least Frequently Used cache divided into namespaces that share its
+/// capacity, see the `namespace` module.
+/// 
+/// ```
+/// use lfu_cache::NamespacedLfuCache;
+/// 
+/// let mut cache = NamespacedLfuCache::new(100);
+/// 
+/// cache.namespace::<String>("users").insert(1, "ann".to_string());
+/// cache.namespace::<Vec<u32>>("posts").insert(1, vec![7, 8]);
+/// 
+/// assert_eq!(cache.namespace::<String>("users").get(&1).unwrap(), "ann");
+/// assert_eq!(cache.namespace::<Vec<u32>>("posts").len(), 1);
+/// assert_eq!(cache.len(), 2);
+/// ```
+pub struct NamespacedLfuCache<K, S = DefaultState> {
+    cache  : LfuCache<(usize, K), AnyValue, S>,
+    ids    : HashMap<Box<str>, usize>,
+    spaces : Vec<Space>,
+}
+
+impl<K> NamespacedLfuCache<K> {
+    /// Creates a new cache with the given capacity, shared by all its
+    /// namespaces.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, DefaultState::default())
+    }
+}
+
+impl<K, S> NamespacedLfuCache<K, S> {
+    /// Creates a new cache with the given capacity that uses `hash_builder`
+    /// to hash keys.
+    /// 
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            cache  : LfuCache::with_hasher(capacity, hash_builder),
+            ids    : HashMap::new(),
+            spaces : Vec::new(),
+        }
+    }
+
+    /// Returns the number of items in the cache, in all namespaces.
+    /// 
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the maximum number of items the cache holds, in all
+    /// namespaces.
+    /// 
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// Returns the number of items in the namespace, 0 if it's never been
+    /// used.
+    /// 
+    pub fn namespace_len(&self, name: &str) -> usize {
+        self.ids.get(name).map_or(0, |&id| self.spaces[id].len)
+    }
+
+    /// Returns the names of the namespaces that have been used, in the order
+    /// they were first used.
+    /// 
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        let mut names = vec![""; self.spaces.len()];
+
+        for (name, &id) in &self.ids { names[id] = name; }
+        names.into_iter()
+    }
+}
+
+impl<K, S> NamespacedLfuCache<K, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Returns a view of the namespace `name`, whose values are `V`s. The
+    /// namespace is created the first time it's asked for, and its name is
+    /// bound to `V` from then on.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the namespace holds values of another type.
+    /// 
+    pub fn namespace<V: Any + Send>(&mut self, name: &str)
+        -> Namespace<'_, K, V, S>
+    {
+        let id = match self.ids.get(name) {
+            Some(&id) => id,
+            None      => {
+                self.spaces.push(Space {
+                    type_id   : TypeId::of::<V>(),
+                    type_name : type_name::<V>(),
+                    len       : 0,
+                });
+                self.ids.insert(name.into(), self.spaces.len() - 1);
+                self.spaces.len() - 1
+            },
+        };
+        let space = self.spaces[id];
+
+        assert!(space.type_id == TypeId::of::<V>(),
+                "the namespace {name:?} holds {}, not {}",
+                space.type_name, type_name::<V>());
+
+        Namespace { cache: self, id, _values: PhantomData }
+    }
+
+    /// Removes every item in the namespace, and returns how many there were.
+    /// The other namespaces' items keep their frequency counts. This visits
+    /// each item in the cache once.
+    /// 
+    pub fn clear_namespace(&mut self, name: &str) -> usize {
+        let Some(&id) = self.ids.get(name) else { return 0 };
+
+        self.spaces[id].len = 0;
+        self.cache.retain_mut(|(space, _), _, _| *space != id)
+    }
+
+    /// Removes the Least Frequently Used item, of whatever namespace, and
+    /// returns the name of its namespace and its key.
+    /// 
+    pub fn pop_lfu(&mut self) -> Option<(&str, K)> {
+        let ((id, key), _) = self.cache.pop_lfu()?;
+
+        self.spaces[id].len -= 1;
+
+        let name = self.ids.iter().find(|(_, &other)| other == id)
+                                  .map(|(name, _)| &**name)?;
+        Some((name, key))
+    }
+
+    /// Removes all items from the cache, in all namespaces.
+    /// 
+    pub fn clear(&mut self) {
+        self.cache.clear();
+
+        for space in &mut self.spaces { space.len = 0; }
+    }
+}
+
+impl<K, S> std::fmt::Debug for NamespacedLfuCache<K, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NamespacedLfuCache")
+         .field("len", &self.len())
+         .field("capacity", &self.capacity())
+         .field("namespaces", &self.spaces.len())
+         .finish_non_exhaustive()
+    }
+}
+
+/// A typed view of one namespace of a `NamespacedLfuCache`, see
+/// `NamespacedLfuCache::namespace()`. Its methods only see the namespace's
+/// items, but an insertion into a full cache evicts the LFU item of any
+/// namespace.
+/// 
+/// Keys are tagged with the namespace by pairing them with its id, so
+/// lookups clone the key.
+/// 
+pub struct Namespace<'a, K, V, S = DefaultState> {
+    cache   : &'a mut NamespacedLfuCache<K, S>,
+    id      : usize,
+    _values : PhantomData<fn() -> V>,
+}
+
+impl<K, V, S> Namespace<'_, K, V, S> {
+    /// Returns the number of items in the namespace.
+    /// 
+    pub fn len(&self) -> usize {
+        self.cache.spaces[self.id].len
+    }
+
+    /// Returns `true` if the namespace holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, V, S> Namespace<'_, K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Any + Send,
+    S: BuildHasher,
+{
+    /// Returns the key tagged with the namespace.
+    /// 
+    fn tagged(&self, key: &K) -> (usize, K) {
+        (self.id, key.clone())
+    }
+
+    /// Returns `true` if the namespace holds the key, without counting an
+    /// access.
+    /// 
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.cache.cache.contains_key(&self.tagged(key))
+    }
+
+    /// Returns the frequency count of the key, or `None` if the namespace
+    /// doesn't hold it.
+    /// 
+    pub fn frequency(&self, key: &K) -> Option<usize> {
+        self.cache.cache.frequency(&self.tagged(key))
+    }
+
+    /// Returns a reference to the value corresponding to the key, counting
+    /// an access.
+    /// 
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let key = self.tagged(key);
+
+        self.cache.cache.get(&key).and_then(|value| value.downcast_ref())
+    }
+
+    /// Inserts a key-value pair into the namespace. If the cache is full, the
+    /// LFU item of any namespace is evicted to make room for a new key.
+    /// 
+    pub fn insert(&mut self, key: K, value: V) {
+        let key   = (self.id, key);
+        let cache = &mut *self.cache;
+
+        if cache.capacity() == 0 { return; }
+
+        // The victim is popped here rather than by the cache, so its
+        // namespace's count can be kept.
+        if !cache.cache.contains_key(&key) {
+            if cache.len() >= cache.capacity() { cache.pop_lfu(); }
+
+            cache.spaces[self.id].len += 1;
+        }
+        cache.cache.insert(key, Box::new(value));
+    }
+
+    /// Removes the key from the namespace and returns its value, if it was
+    /// present.
+    /// 
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let value = self.cache.cache.remove(&self.tagged(key))?;
+
+        self.cache.spaces[self.id].len -= 1;
+        value.downcast().ok().map(|value| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hot_namespace_squeezes_out_a_cold_one() {
+        let mut cache = NamespacedLfuCache::new(10);
+
+        for key in 0..5 {
+            cache.namespace::<String>("users").insert(key, key.to_string());
+            cache.namespace::<u64>("posts").insert(key, key * 100);
+        }
+        assert_eq!(cache.namespace_len("users"), 5);
+        assert_eq!(cache.namespace_len("posts"), 5);
+
+        // Posts are read, users aren't, and new posts keep coming.
+        for key in 5..20 {
+            let mut posts = cache.namespace::<u64>("posts");
+
+            for old in 0..key { posts.get(&old); }
+            posts.insert(key, key * 100);
+        }
+        let mut users = cache.namespace::<String>("users");
+
+        assert!(users.is_empty());
+        assert_eq!(users.get(&0), None);
+
+        let mut posts = cache.namespace::<u64>("posts");
+
+        assert_eq!(posts.len(), 10);
+        assert_eq!(posts.get(&0), Some(&0));
+        assert_eq!(cache.len(), 10);
+        assert!(cache.namespaces().eq(["users", "posts"]));
+    }
+
+    #[test]
+    fn namespaces_are_invalidated_on_their_own() {
+        let mut cache = NamespacedLfuCache::new(10);
+
+        // The same key in two namespaces is two items.
+        cache.namespace::<&str>("sessions").insert("k", "session");
+        cache.namespace::<u32>("users").insert("k", 1);
+        cache.namespace::<u32>("users").insert("j", 2);
+        cache.namespace::<u32>("users").get(&"k");
+
+        assert_eq!(cache.namespace::<&str>("sessions").get(&"k"),
+                   Some(&"session"));
+        cache.namespace::<u32>("users").insert("k", 11);
+
+        assert_eq!(cache.clear_namespace("sessions"), 1);
+        assert_eq!(cache.clear_namespace("sessions"), 0);
+        assert_eq!(cache.clear_namespace("nowhere"), 0);
+
+        let mut users = cache.namespace::<u32>("users");
+
+        assert_eq!(users.len(), 2);
+        assert_eq!(users.frequency(&"k"), Some(3));
+        assert_eq!(users.remove(&"k"), Some(11));
+        assert!(!users.contains_key(&"k") && users.contains_key(&"j"));
+        assert_eq!(cache.namespace_len("users"), 1);
+        assert!(!cache.namespace::<&str>("sessions").contains_key(&"k"));
+        assert_eq!(cache.pop_lfu(), Some(("users", "j")));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "holds u32, not")]
+    fn namespaces_keep_their_type() {
+        let mut cache = NamespacedLfuCache::new(2);
+
+        cache.namespace::<u32>("users").insert(1, 1);
+        cache.namespace::<String>("users");
+    }
+}
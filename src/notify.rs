@@ -0,0 +1,133 @@
+//! Eviction notifications, for handling evicted items away from the cache's
+//! call stack. The cache sends an `Evicted` message down a bounded channel for
+//! each item it lets go of, with the reason why, and an `EvictionReceiver` 
+//! takes them at its own pace, on the same thread or another.
+//! 
+//! Sending never blocks. When the channel is full the message is dropped,
+//! value and all, and counted, see `EvictionReceiver::dropped()`.
+//! 
+
+use std::sync::atomic::Ordering::Relaxed;
+use std::time::Duration;
+
+use crate::shim::{sync_channel, Arc, AtomicU64, Receiver, SyncSender};
+use crate::shim::TrySendError;
+
+/// Why an item left the cache.
+/// 
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EvictionReason {
+    /// It was evicted to keep the cache within its capacity or weight limit.
+    Capacity,
+    /// Its TTL ran out.
+    Expired,
+    /// It was removed on request by a method that doesn't hand it back, like
+    /// `invalidate_tag()` or `retain_mut()`, or a write too heavy for the
+    /// cache dropped it. Items that are handed back, by `remove()`, 
+    /// `pop_lfu()`, `truncate()`, `evict_while()`, `drain_frequency()` and 
+    /// the like, aren't sent.
+    Removed,
+    /// A write of its key replaced its value.
+    Replaced,
+    /// It was dropped by `clear()`.
+    Cleared,
+}
+
+/// An item the cache evicted, as received by an `EvictionReceiver`.
+/// 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Evicted<K, V> {
+    /// The item's key.
+    pub key    : K,
+    /// The item's value.
+    pub value  : V,
+    /// Why it left the cache.
+    pub reason : EvictionReason,
+}
+
+/// Receives the items evicted from a cache, see
+/// `LfuCache::eviction_receiver()`. Once the cache is dropped, or has been
+/// given another receiver, the messages still in the channel can be taken and
+/// then `recv()` returns `None`.
+/// 
+pub struct EvictionReceiver<K, V> {
+    rx      : Receiver<Evicted<K, V>>,
+    dropped : Arc<AtomicU64>,
+}
+
+impl<K, V> EvictionReceiver<K, V> {
+    /// Takes the next message if there is one, without waiting.
+    /// 
+    pub fn try_recv(&self) -> Option<Evicted<K, V>> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Waits for the next message. Returns `None` once the cache has stopped
+    /// sending and the channel is empty.
+    /// 
+    pub fn recv(&self) -> Option<Evicted<K, V>> {
+        self.rx.recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next message.
+    /// 
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Evicted<K, V>> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// Returns an iterator over the messages in the channel now, which
+    /// doesn't wait for more.
+    /// 
+    pub fn try_iter(&self) -> impl Iterator<Item = Evicted<K, V>> + '_ {
+        self.rx.try_iter()
+    }
+
+    /// Returns how many messages have been dropped because the channel was
+    /// full.
+    /// 
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Relaxed)
+    }
+}
+
+impl<K, V> std::fmt::Debug for EvictionReceiver<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvictionReceiver").field("dropped", &self.dropped())
+                                          .finish_non_exhaustive()
+    }
+}
+
+/// The cache's end of the channel.
+/// 
+pub(crate) struct EvictionSender<K, V> {
+    tx      : SyncSender<Evicted<K, V>>,
+    dropped : Arc<AtomicU64>,
+}
+
+impl<K, V> EvictionSender<K, V> {
+    /// Creates a channel that holds up to `bound` messages.
+    /// 
+    pub(crate) fn channel(bound: usize)
+        -> (EvictionSender<K, V>, EvictionReceiver<K, V>)
+    {
+        let (tx, rx) = sync_channel(bound);
+        let dropped  = Arc::new(AtomicU64::new(0));
+
+        (Self { tx, dropped: dropped.clone() },
+         EvictionReceiver { rx, dropped })
+    }
+
+    /// Sends a message, or counts it as dropped if the channel is full. A
+    /// receiver that's gone away drops it without counting.
+    /// 
+    pub(crate) fn send(&self, key: K, value: V, reason: EvictionReason) {
+        let msg = Evicted { key, value, reason };
+
+        if let Err(TrySendError::Full(_)) = self.tx.try_send(msg) {
+            self.dropped.fetch_add(1, Relaxed);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! An ordered index of string-like keys, so that every key starting with a
+//! given prefix can be found with a range lookup instead of a scan of the
+//! whole cache. The cache keeps it in step with every insertion and removal.
+//! 
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+#[derive(Clone, Debug)]
+pub(crate) struct PrefixIndex<K> {
+    keys   : BTreeMap<Box<str>, K>,
+    as_str : fn(&K) -> &str,
+}
+
+impl<K> PrefixIndex<K>
+where
+    K: Clone,
+{
+    /// Creates an index of the keys, reading each one's string with `as_str`.
+    /// 
+    pub(crate) fn new<'a>(as_str : fn(&K) -> &str,
+                          keys   : impl Iterator<Item = &'a K>) -> Self
+    where
+        K: 'a,
+    {
+        let mut index = Self { keys: BTreeMap::new(), as_str };
+
+        keys.for_each(|key| index.insert(key));
+        index
+    }
+
+    pub(crate) fn insert(&mut self, key: &K) {
+        self.keys.insert((self.as_str)(key).into(), key.clone());
+    }
+
+    /// Returns the keys that start with `prefix`.
+    /// 
+    pub(crate) fn with_prefix(&self, prefix: &str) -> Vec<K> {
+        self.keys.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+                 .take_while(|(s, _)| s.starts_with(prefix))
+                 .map(|(_, key)| key.clone())
+                 .collect()
+    }
+
+    /// Returns the indexed keys, in order.
+    /// 
+    #[cfg(test)]
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &K> {
+        self.keys.values()
+    }
+}
+
+impl<K> PrefixIndex<K> {
+    pub(crate) fn remove(&mut self, key: &K) {
+        self.keys.remove((self.as_str)(key));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
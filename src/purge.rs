@@ -0,0 +1,162 @@
+//! A tokio task that purges a `SyncLfuCache` of expired items on a timer, so
+//! that items with TTLs don't linger until they're next looked up. The task
+//! holds the cache's lock only for the length of each `purge_expired()`, and
+//! stops when its handle is shut down or dropped.
+//! 
+
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+use crate::{Clock, SyncLfuCache};
+
+/// A clock that follows tokio's clock, starting from when it was created. It
+/// stops when tokio's time is paused and moves when it's advanced, which lets
+/// tests of TTLs run in virtual time.
+/// 
+#[derive(Clone, Copy, Debug)]
+pub struct TokioClock {
+    start: Instant,
+}
+
+impl TokioClock {
+    /// Creates a clock that starts now.
+    /// 
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for TokioClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for TokioClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// The counters kept by a purge task, see `PurgeTaskHandle::stats()`.
+/// 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PurgeStats {
+    /// The number of times the task has purged the cache.
+    pub ticks       : u64,
+    /// The number of items purged over all ticks.
+    pub purged      : u64,
+    /// The number of items purged by the last tick.
+    pub last_purged : u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    ticks       : AtomicU64,
+    purged      : AtomicU64,
+    last_purged : AtomicU64,
+}
+
+/// The handle of a task started by `spawn_purge_task()`. Dropping it stops
+/// the task after the tick it's on, if any.
+/// 
+#[derive(Debug)]
+pub struct PurgeTaskHandle {
+    stop     : Option<oneshot::Sender<()>>,
+    task     : JoinHandle<()>,
+    counters : Arc<Counters>,
+}
+
+impl PurgeTaskHandle {
+    /// Returns the task's counters so far.
+    /// 
+    pub fn stats(&self) -> PurgeStats {
+        PurgeStats {
+            ticks       : self.counters.ticks.load(Relaxed),
+            purged      : self.counters.purged.load(Relaxed),
+            last_purged : self.counters.last_purged.load(Relaxed),
+        }
+    }
+
+    /// Returns `true` once the task has stopped.
+    /// 
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stops the task, waiting for it to finish the tick it's on, and returns
+    /// its final counters.
+    /// 
+    /// # Panics
+    /// 
+    /// Resumes the task's panic, if it panicked.
+    /// 
+    pub async fn shutdown(mut self) -> PurgeStats {
+        self.stop.take();
+
+        if let Err(e) = (&mut self.task).await {
+            if e.is_panic() { std::panic::resume_unwind(e.into_panic()); }
+        }
+        self.stats()
+    }
+}
+
+impl Drop for PurgeTaskHandle {
+    fn drop(&mut self) {
+        // Dropping the sender is what tells the task to stop.
+        self.stop.take();
+    }
+}
+
+/// Spawns a tokio task that calls `purge_expired()` on the cache every
+/// `interval`, the first time after one interval has passed. Ticks that are
+/// missed while the runtime is busy are skipped rather than made up. The
+/// cache's clock decides which items have expired; give it a `TokioClock` to
+/// have TTLs follow tokio's time.
+/// 
+/// # Panics
+/// 
+/// Panics if `interval` is zero, or if it's called outside a tokio runtime.
+/// 
+pub fn spawn_purge_task<K, V, S>(cache    : Arc<SyncLfuCache<K, V, S>>,
+                                 interval : Duration)
+    -> PurgeTaskHandle
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + 'static,
+    S: BuildHasher + Send + 'static,
+{
+    assert!(!interval.is_zero(), "the purge interval must be non-zero");
+
+    let (stop, mut stopped) = oneshot::channel::<()>();
+    let counters            = Arc::new(Counters::default());
+
+    let task = tokio::spawn({
+        let counters = counters.clone();
+
+        async move {
+            let mut timer = time::interval_at(Instant::now() + interval,
+                                              interval);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = timer.tick()  => {},
+                }
+                let purged = cache.lock().purge_expired() as u64;
+
+                counters.ticks.fetch_add(1, Relaxed);
+                counters.purged.fetch_add(purged, Relaxed);
+                counters.last_purged.store(purged, Relaxed);
+            }
+        }
+    });
+    PurgeTaskHandle { stop: Some(stop), task, counters }
+}
@@ -0,0 +1,353 @@
+//! The frequency queues shared by the caches backed by linked vectors: a
+//! linked vector of `(frequency, queue)` pairs in ascending frequency order,
+//! where each queue holds the keys with that frequency count, least recently
+//! used first. These functions keep the queues and the handles in the value
+//! records in step; the caches themselves only differ in how they map keys to
+//! value records.
+//! 
+
+use linked_vector::*;
+
+use crate::cache::Value;
+
+/// The frequency queues.
+/// 
+pub(crate) type Queues<K> = LinkedVector<(usize, LinkedVector<Queued<K>>)>;
+
+/// A key in a queue, with its hash, so that an item reached through the 
+/// queues, like the victim of an eviction, is found in the map without 
+/// hashing its key again. `BTreeLfuCache` doesn't hash keys, and leaves it 0.
+/// 
+#[derive(Debug)]
+pub(crate) struct Queued<K> {
+    pub(crate) hash : u64,
+    pub(crate) key  : K,
+}
+
+impl<K: Clone> Clone for Queued<K> {
+    fn clone(&self) -> Self {
+        Self { hash: self.hash, key: self.key.clone() }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.hash = source.hash;
+        self.key.clone_from(&source.key);
+    }
+}
+
+/// Pushes a key to the back of the queue for the given frequency, creating
+/// the queue if need be, and sets the handles of its value record. Finding the
+/// queue takes a walk past the queues for lower frequencies, so it's quickest
+/// for new keys with a frequency of 1.
+/// 
+pub(crate) fn push<K, V, C>(freq_qs : &mut Queues<K>, 
+                            key     : Queued<K>, 
+                            freq    : usize, 
+                            vrec    : &mut Value<V, C>) 
+{
+    let mut hnext = freq_qs.front_node();
+
+    while let Some(hfreq) = hnext {
+        if freq_qs.get(hfreq).0 >= freq { break; }
+        hnext = freq_qs.next_node(hfreq);
+    }
+    // Get the handle of the queue with the frequency, or make one.
+    let hfreq = match hnext {
+        Some(hfreq) if freq_qs.get(hfreq).0 == freq => hfreq,
+        Some(hfreq) => freq_qs.insert(hfreq, (freq, LinkedVector::new())),
+        None        => freq_qs.push_back((freq, LinkedVector::new())),
+    };
+    // Set the frequency queue locator handles of the value record and push
+    // its key to the queue.
+    vrec.hfreq = hfreq;
+    vrec.hpos  = freq_qs.get_mut(hfreq).1.push_back(key);
+}
+
+/// Removes the key of the given value record from its queue, and the queue
+/// too if that leaves it empty.
+/// 
+pub(crate) fn unlink<K, V, C>(freq_qs: &mut Queues<K>, vrec: &Value<V, C>) 
+    -> Queued<K> 
+{
+    let queue = freq_qs.get_mut(vrec.hfreq);
+    let key   = queue.1.remove(vrec.hpos);
+
+    // Don't leave an empty queue behind.
+    if queue.1.is_empty() {
+        freq_qs.remove(vrec.hfreq);
+    }
+    key
+}
+
+/// Pops the key of the Least Frequently Used item. An emptied frequency 1
+/// queue is kept around, since the insert that usually follows an eviction
+/// will want it.
+/// 
+pub(crate) fn pop_lfu<K>(freq_qs: &mut Queues<K>) -> Option<Queued<K>> {
+    skip_empty_front(freq_qs);
+
+    let hqueue = freq_qs.front_node()?;
+
+    // Get the first queue.
+    let queue = freq_qs.get_mut(hqueue);
+    let key   = queue.1.pop_front();
+
+    // If the queue is empty, remove it if it's not the first one.
+    if queue.0 != 1 && queue.1.is_empty() {
+        freq_qs.remove(hqueue);
+    }
+    key
+}
+
+/// Pops the key of the Least Frequently Used item and pushes a clone of 
+/// `key`, whose hash is `hash`, to the back of the queue for the frequency 
+/// `freq()` gives, which is passed the popped key's frequency, and returns 
+/// that frequency. The popped key's queue is kept for `key` if it's the queue
+/// for that frequency, or if the pop emptied it and it can be relabeled, 
+/// which saves dropping one queue and making another on each insert into a 
+/// full cache. The popped key's own storage is reused too: the clone is made
+/// with `clone_from()` into it, and a key alone in a queue that's kept is 
+/// overwritten in its node rather than popped and pushed. The queues end up
+/// as `pop_lfu()` followed by `push()` would leave them, less any emptied 
+/// frequency 1 queue.
+/// 
+pub(crate) fn replace_lfu<K: Clone, V, C>(freq_qs : &mut Queues<K>, 
+                                          hash    : u64,
+                                          key     : &K, 
+                                          vrec    : &mut Value<V, C>,
+                                          freq    : impl FnOnce(usize) 
+                                                        -> usize)
+    -> Option<usize>
+{
+    skip_empty_front(freq_qs);
+
+    let hqueue = freq_qs.front_node()?;
+    let queue  = freq_qs.get(hqueue);
+    let popped = queue.0;
+    let alone  = queue.1.len() == 1;
+    let freq   = freq(popped);
+    let next   = freq_qs.next_node(hqueue).map(|hnext| freq_qs.get(hnext).0);
+
+    if popped == freq || alone && next.is_none_or(|next| next > freq) {
+        let queue = freq_qs.get_mut(hqueue);
+
+        queue.0    = freq;
+        vrec.hfreq = hqueue;
+
+        if alone {
+            let hpos = queue.1.front_node().unwrap();
+
+            let slot = queue.1.get_mut(hpos);
+
+            slot.hash = hash;
+            slot.key.clone_from(key);
+            vrec.hpos = hpos;
+        } else {
+            let mut slot = queue.1.pop_front().unwrap();
+
+            slot.hash = hash;
+            slot.key.clone_from(key);
+            vrec.hpos = queue.1.push_back(slot);
+        }
+    } else {
+        let mut slot = freq_qs.get_mut(hqueue).1.pop_front().unwrap();
+
+        slot.hash = hash;
+        slot.key.clone_from(key);
+
+        if alone { freq_qs.remove(hqueue); }
+        push(freq_qs, slot, freq, vrec);
+    }
+    Some(popped)
+}
+
+/// Drops the emptied frequency 1 queue `pop_lfu()` keeps, if it wasn't
+/// reused, so the front queue has the LFU item.
+/// 
+fn skip_empty_front<K>(freq_qs: &mut Queues<K>) {
+    if freq_qs.front().is_some_and(|q| q.1.is_empty()) {
+        freq_qs.pop_front();
+    }
+}
+
+/// Increments the frequency of the given key.
+/// 
+pub(crate) fn incr_freq<K, V, C>(freq_qs : &mut Queues<K>, 
+                                 vrec    : &mut Value<V, C>) 
+{
+    let hqueue = vrec.hfreq;
+    let freq   = freq_qs.get(hqueue).0;
+
+    // A key alone in its queue can take the queue with it if there's no queue
+    // for freq + 1 already, which keeps the handles as they are and saves 
+    // making one queue and dropping another.
+    let next = freq_qs.next_node(hqueue).map(|hnext| freq_qs.get(hnext).0);
+
+    if freq_qs.get(hqueue).1.len() == 1 && next != Some(freq + 1) {
+        freq_qs.get_mut(hqueue).0 += 1;
+        return;
+    }
+    // Get a cursor to the frequency queue referenced by vrec.
+    let mut curs   = freq_qs.cursor_mut(vrec.hfreq);
+    let     hqueue = curs.node();
+    let     freq   = curs.0;
+
+    // Remove the key from it's current queue (cursor implements DerefMut).
+    let key = curs.1.remove(vrec.hpos);
+
+    if curs.move_next().is_some() && curs.0 == freq + 1 {
+        // If the next queue is the one we want, add the key to it.
+        vrec.hfreq = curs.node();
+        vrec.hpos  = curs.1.push_back(key);
+    } else {
+        // If the first queue wasn't for freq + 1, create a new one.
+        let mut newq = (freq + 1, LinkedVector::new());
+
+        curs.move_to(hqueue);
+
+        // Add the key to it and update the Value record's handles.
+        vrec.hpos  = newq.1.push_back(key);
+        vrec.hfreq = curs.insert_after(newq);
+    }
+    curs.move_to(hqueue);
+
+    // If the former queue is empty, remove it.
+    if curs.1.is_empty() {
+        curs.remove();
+    }
+}
+
+/// Moves the given key to the back of the queue for `freq`, which is at 
+/// least its current frequency, creating the queue if need be. Finding it 
+/// takes a walk past the queues in between.
+/// 
+pub(crate) fn raise_freq<K, V, C>(freq_qs : &mut Queues<K>, 
+                                  vrec    : &mut Value<V, C>, 
+                                  freq    : usize) 
+{
+    let     last   = freq_qs.back().map(|queue| queue.0);
+    let mut curs   = freq_qs.cursor_mut(vrec.hfreq);
+    let     hqueue = curs.node();
+    let     key    = curs.1.remove(vrec.hpos);
+
+    // Stop at the queue for `freq` if there is one, or else at the last one
+    // below it. A raise past every queue, as under LRFU, goes straight to the
+    // back.
+    let mut found = curs.0 == freq;
+
+    if !found && last.is_some_and(|last| last <= freq) {
+        curs.move_to_back();
+        found = curs.0 == freq;
+    }
+    while !found && curs.move_next().is_some() {
+        if curs.0 >= freq {
+            found = curs.0 == freq;
+            if !found { curs.move_prev(); }
+            break;
+        }
+    }
+    if found {
+        vrec.hfreq = curs.node();
+        vrec.hpos  = curs.1.push_back(key);
+    } else {
+        let mut newq = (freq, LinkedVector::new());
+
+        vrec.hpos  = newq.1.push_back(key);
+        vrec.hfreq = curs.insert_after(newq);
+    }
+    curs.move_to(hqueue);
+
+    if curs.1.is_empty() {
+        curs.remove();
+    }
+}
+
+/// Walks the frequency queues front to back, and each queue front to back,
+/// yielding every key along with its frequency count. This is the order items
+/// are evicted in.
+/// 
+pub(crate) fn lfu_order<K>(freq_qs: &Queues<K>) -> LfuOrder<'_, K> {
+    let hfreq = freq_qs.front_node();
+    let hpos  = hfreq.and_then(|hfreq| freq_qs.get(hfreq).1.front_node());
+
+    LfuOrder { freq_qs, hfreq, hpos }
+}
+
+/// Returns the handles of the first key in eviction order, those of its
+/// queue and of its node in the queue.
+/// 
+pub(crate) fn first<K>(freq_qs: &Queues<K>) -> Option<(HNode, HNode)> {
+    first_from(freq_qs, freq_qs.front_node())
+}
+
+/// Returns the handles of the key after the one at `at` in eviction order.
+/// 
+pub(crate) fn after<K>(freq_qs: &Queues<K>, at: (HNode, HNode)) 
+    -> Option<(HNode, HNode)> 
+{
+    let (hfreq, hpos) = at;
+
+    match freq_qs.get(hfreq).1.next_node(hpos) {
+        Some(hpos) => Some((hfreq, hpos)),
+        None       => first_from(freq_qs, freq_qs.next_node(hfreq)),
+    }
+}
+
+/// Returns the handles of the first key of the queues from `hfreq` on,
+/// passing over an empty one.
+/// 
+fn first_from<K>(freq_qs: &Queues<K>, mut hfreq: Option<HNode>) 
+    -> Option<(HNode, HNode)> 
+{
+    while let Some(hqueue) = hfreq {
+        if let Some(hpos) = freq_qs.get(hqueue).1.front_node() {
+            return Some((hqueue, hpos));
+        }
+        hfreq = freq_qs.next_node(hqueue);
+    }
+    None
+}
+
+/// Returns the key at the far end of the eviction order: the one used most 
+/// recently of those with the highest frequency count.
+/// 
+pub(crate) fn mfu<K>(freq_qs: &Queues<K>) -> Option<&K> {
+    freq_qs.iter().rev().find_map(|(_, queue)| queue.back())
+                        .map(|queued| &queued.key)
+}
+
+/// The iterator returned by `lfu_order()`. It only holds handles, so it can
+/// be copied to look ahead.
+/// 
+pub(crate) struct LfuOrder<'a, K> {
+    freq_qs : &'a Queues<K>,
+    hfreq   : Option<HNode>,
+    hpos    : Option<HNode>,
+}
+
+impl<'a, K> Iterator for LfuOrder<'a, K> {
+    type Item = (usize, &'a K);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (freq, queue) = self.freq_qs.get(self.hfreq?);
+
+            if let Some(hpos) = self.hpos {
+                self.hpos = queue.next_node(hpos);
+                return Some((*freq, &queue.get(hpos).key));
+            }
+            self.hfreq = self.freq_qs.next_node(self.hfreq?);
+            self.hpos  = self.hfreq.and_then(|hfreq| {
+                self.freq_qs.get(hfreq).1.front_node()
+            });
+        }
+    }
+}
+
+impl<K> Clone for LfuOrder<'_, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for LfuOrder<'_, K> {}
@@ -0,0 +1,30 @@
+//! A small, seedable pseudo-random number generator (SplitMix64) for the
+//! parts of the cache that make random choices. Seeding it makes those
+//! choices repeatable, which is what tests and simulations need.
+//! 
+
+#[derive(Clone, Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns `true` with a probability of `1 / n`.
+    /// 
+    pub(crate) fn one_in(&mut self, n: u64) -> bool {
+        n <= 1 || self.next_u64().is_multiple_of(n)
+    }
+}
@@ -0,0 +1,134 @@
+//! Picking cached items at random, for spot checks like validating a few of
+//! them against the origin every so often. Every item is as likely to be
+//! picked as any other, whatever its frequency count, so a sample isn't
+//! skewed toward the sparsely populated queues the way picking a random
+//! queue first would be. The RNG is the caller's, from the `rand` crate.
+//! 
+
+use rand::seq::index;
+use rand::{Rng, RngExt};
+
+use crate::{Counter, LfuCache};
+
+impl<K, V, S, C: Counter> LfuCache<K, V, S, C> {
+    /// Returns an item picked at random, or `None` if the cache is empty.
+    /// Each item is picked with the same probability. Frequency counts
+    /// aren't changed. The map is walked up to the item picked, so this takes
+    /// time in proportion to the number of items.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCache;
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// 
+    /// let mut cache = LfuCache::new(10);
+    /// let mut rng   = StdRng::seed_from_u64(1);
+    /// 
+    /// for key in 0..5 { cache.insert(key, key * 10); }
+    /// 
+    /// let (key, value) = cache.random_entry(&mut rng).unwrap();
+    /// 
+    /// assert_eq!(*value, key * 10);
+    /// assert_eq!(cache.frequency(key), Some(1));
+    /// ```
+    pub fn random_entry(&self, rng: &mut impl Rng) -> Option<(&K, &V)> {
+        if self.is_empty() { return None; }
+
+        let pick = rng.random_range(0..self.len());
+
+        self.iter_unordered().nth(pick)
+    }
+
+    /// Returns `n` different items picked at random, or every item if there
+    /// are no more than `n`, in no particular order. Each set of `n` items is
+    /// picked with the same probability. Frequency counts aren't changed. The
+    /// map is walked once, up to the last item picked.
+    /// 
+    pub fn random_entries(&self, n: usize, rng: &mut impl Rng)
+        -> Vec<(&K, &V)>
+    {
+        let len       = self.len();
+        let mut picks = index::sample(rng, len, n.min(len)).into_vec();
+        let mut items = self.iter_unordered().enumerate();
+
+        picks.sort_unstable();
+        picks.into_iter().filter_map(|pick| {
+            items.find(|&(i, _)| i == pick).map(|(_, item)| item)
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Returns the chi-square statistic of the counts, against the same
+    /// expected count for each.
+    /// 
+    fn chi_square(counts: &[u32], expected: f64) -> f64 {
+        counts.iter().map(|&count| (count as f64 - expected).powi(2))
+                     .sum::<f64>() / expected
+    }
+
+    /// Returns a cache of 8 items, with very different frequency counts, and
+    /// some items left behind by `invalidate_all()`.
+    /// 
+    fn skewed() -> LfuCache<usize, usize> {
+        let mut cache = LfuCache::new(40);
+
+        for key in 100..140 { cache.insert(key, key); }
+        cache.invalidate_all();
+
+        for key in 0..8 {
+            cache.insert(key, key);
+            for _ in 0..key * key { cache.get(&key); }
+        }
+        assert!(cache.pending_reclaim() > 0);
+        cache
+    }
+
+    #[test]
+    fn items_are_picked_evenly() {
+        let cache   = skewed();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut hit = [0; 8];
+
+        for _ in 0..80_000 {
+            let (&key, &value) = cache.random_entry(&mut rng).unwrap();
+
+            assert_eq!(key, value);
+            hit[key] += 1;
+        }
+        // The 0.1% critical value for 7 degrees of freedom is 24.3.
+        assert!(chi_square(&hit, 10_000.0) < 24.3, "{hit:?}");
+        assert_eq!(cache.frequency(&0), Some(1));
+
+        assert_eq!(LfuCache::<u32, u32>::new(4).random_entry(&mut rng), None);
+    }
+
+    #[test]
+    fn samples_are_even_and_without_repeats() {
+        let cache   = skewed();
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut hit = [0; 8];
+
+        for _ in 0..20_000 {
+            let mut keys = cache.random_entries(3, &mut rng)
+                                .into_iter()
+                                .map(|(&key, _)| key)
+                                .collect::<Vec<_>>();
+            for &key in &keys { hit[key] += 1; }
+
+            keys.sort();
+            keys.dedup();
+            assert_eq!(keys.len(), 3);
+        }
+        assert!(chi_square(&hit, 7_500.0) < 24.3, "{hit:?}");
+
+        // Asking for more than there are returns them all.
+        assert_eq!(cache.random_entries(20, &mut rng).len(), 8);
+        assert!(cache.random_entries(0, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+//! `LfuSet`, for when all that's needed is which keys have been seen and how
+//! often, in bounded memory. It's an `LfuCache` whose values are `()`, which
+//! take up no room in the value records.
+//! 
+
+use std::hash::{BuildHasher, Hash};
+
+use crate::{DefaultState, EvictionOrder, LfuCache};
+
+/// A set of keys that holds up to its capacity, evicting the Least
+/// Frequently Used key to make room for a new one.
+/// 
+pub struct LfuSet<K, S = DefaultState> {
+    cache: LfuCache<K, (), S>,
+}
+
+impl<K> LfuSet<K> {
+    /// Creates a new LFU set with the given capacity.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self { cache: LfuCache::new(capacity) }
+    }
+}
+
+impl<K, S> LfuSet<K, S> {
+    /// Creates a new LFU set with the given capacity that uses `hash_builder`
+    /// to hash keys.
+    /// 
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self { cache: LfuCache::with_hasher(capacity, hash_builder) }
+    }
+
+    /// Returns the number of keys in the set.
+    /// 
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if the set is empty.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Returns the maximum number of keys the set holds.
+    /// 
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// Returns an iterator over the keys in eviction order, like
+    /// `eviction_order()`. Frequency counts aren't changed.
+    /// 
+    pub fn iter(&self) -> SetIter<'_, K> {
+        SetIter { keys: self.cache.eviction_order() }
+    }
+
+    /// Returns an iterator over the keys in eviction order: the key
+    /// `pop_lfu()` would remove first comes first.
+    /// 
+    pub fn eviction_order(&self) -> EvictionOrder<'_, K> {
+        self.cache.eviction_order()
+    }
+}
+
+impl<K, S> LfuSet<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns `true` if the key is in the set, without counting an access.
+    /// 
+    pub fn peek_contains(&self, key: &K) -> bool {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns the frequency count of the key, or `None` if it isn't in the
+    /// set.
+    /// 
+    pub fn frequency(&self, key: &K) -> Option<usize> {
+        self.cache.frequency(key)
+    }
+}
+
+impl<K, S> LfuSet<K, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Adds the key to the set, evicting the LFU key if the set is full, and
+    /// returns `true` if it wasn't there before. A key that's already in the
+    /// set has its frequency count incremented instead.
+    /// 
+    pub fn insert(&mut self, key: K) -> bool {
+        // New keys start at version 1, and 0 means nothing was stored.
+        self.cache.insert_versioned(key, ()) == 1
+    }
+
+    /// Returns `true` if the key is in the set, counting an access to it.
+    /// 
+    pub fn contains(&mut self, key: &K) -> bool {
+        self.cache.get(key).is_some()
+    }
+
+    /// Removes the key from the set, returning `true` if it was there.
+    /// 
+    pub fn remove(&mut self, key: &K) -> bool {
+        self.cache.remove(key).is_some()
+    }
+
+    /// Removes the Least Frequently Used key from the set and returns it. Of
+    /// the keys with the lowest frequency count, the one that was accessed
+    /// least recently is chosen.
+    /// 
+    pub fn pop_lfu(&mut self) -> Option<K> {
+        self.cache.pop_lfu().map(|(key, _)| key)
+    }
+
+    /// Removes all keys from the set.
+    /// 
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+impl<K, S> Clone for LfuSet<K, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        Self { cache: self.cache.clone() }
+    }
+}
+
+/// An iterator over the keys of an `LfuSet` in eviction order, see
+/// `LfuSet::iter()`.
+/// 
+#[derive(Clone, Debug)]
+pub struct SetIter<'a, K> {
+    keys: EvictionOrder<'a, K>,
+}
+
+impl<'a, K> Iterator for SetIter<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.keys.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The cache's trace tests, with the values left out. A `get` expects 1
+    // if the key is in the set and 0 if it isn't.
+
+    fn run(cmd: &[&str], data: &[&[i32]], exp: &[i32]) {
+        let mut set = None;
+
+        for ((cmd, data), exp) in cmd.iter().zip(data).zip(exp) {
+            match *cmd {
+                "LfuCache" => set = Some(LfuSet::new(data[0] as usize)),
+                "put"      => { set.as_mut().unwrap().insert(data[0]); },
+                "get"      => {
+                    let set = set.as_mut().unwrap();
+                    assert_eq!(set.contains(&data[0]), *exp == 1);
+                },
+                _ => panic!("Bad command!"),
+            }
+        }
+    }
+
+    #[test]
+    fn trace_1() {
+        let null = i32::MIN;
+        let cmd  = ["LfuCache","put","put","get","put","get",
+                    "get","put","get","get","get"];
+        let data: [&[i32]; 11] = [&[2],&[1],&[2],&[1],&[3],&[2],&[3],
+                                  &[4],&[1],&[3],&[4]];
+        let exp  = [null,null,null,1,null,0,1,null,0,1,1];
+
+        run(&cmd, &data, &exp);
+    }
+
+    #[test]
+    fn trace_2() {
+        let null = i32::MIN;
+
+        run(&["LfuCache","put","get"], &[&[0],&[0],&[0]], &[null,null,0]);
+    }
+
+    #[test]
+    fn trace_4() {
+        let null = i32::MIN;
+        let cmd  = ["LfuCache","put","put","put","put","put","get","put","get","get","put","get","put","put","put","get","put","get","get","get","get","put","put","get","get","get","put","put","get","put","get","put","get","get","get","put","put","put","get","put","get","get","put","put","get","put","put","put","put","get","put","put","get","put","put","get","put","put","put","put","put","get","put","put","get","put","get","get","get","put","get","get","put","put","put","put","get","put","put","put","put","get","get","get","put","put","put","get","put","put","put","get","put","put","put","get","get","get","put","put","put","put","get","put","put","put","put","put","put","put"];
+        let data: [&[i32]; 110] = [&[10],&[10],&[3],&[6],&[10],&[9],&[13],&[2],&[2],&[3],&[5],&[8],&[9],&[5],&[1],&[11],&[9],&[7],&[5],&[8],&[9],&[4],&[9],&[9],&[10],&[10],&[6],&[3],&[3],&[10],&[8],&[2],&[1],&[5],&[4],&[11],&[12],&[5],&[13],&[7],&[8],&[12],&[3],&[2],&[5],&[2],&[13],&[8],&[1],&[6],&[9],&[8],&[5],&[6],&[1],&[10],&[4],&[7],&[11],&[8],&[9],&[5],&[3],&[11],&[12],&[4],&[3],&[9],&[6],&[3],&[1],&[10],&[3],&[10],&[1],&[11],&[3],&[3],&[3],&[10],&[3],&[8],&[7],&[5],&[13],&[2],&[11],&[12],&[9],&[2],&[3],&[1],&[12],&[9],&[6],&[4],&[5],&[5],&[8],&[11],&[5],&[9],&[1],&[2],&[7],&[4],&[7],&[9],&[13],&[11]];
+        let exp  = [null,null,null,null,null,null,0,null,1,1,null,0,null,null,null,0,null,0,1,0,1,null,null,1,1,1,null,null,1,null,0,null,1,1,1,null,null,null,0,null,0,1,null,null,1,null,null,null,null,1,null,null,1,null,null,1,null,null,null,null,null,1,null,null,0,null,1,1,1,null,1,1,null,null,null,null,1,null,null,null,null,1,0,1,null,null,null,0,null,null,null,1,null,null,null,1,1,1,null,null,null,null,1,null,null,null,null,null,null,null];
+
+        run(&cmd, &data, &exp);
+    }
+
+    #[test]
+    fn set_methods() {
+        let mut set = LfuSet::new(2);
+
+        assert!(set.insert("a"));
+        assert!(!set.insert("a"));
+        assert!(set.insert("b"));
+        assert!(set.contains(&"b"));
+        assert!(set.peek_contains(&"b"));
+        assert_eq!(set.frequency(&"a"), Some(2));
+        assert_eq!(set.frequency(&"b"), Some(2));
+
+        // "a" was used least recently of the two.
+        assert!(set.insert("c"));
+        assert!(!set.peek_contains(&"a"));
+        assert_eq!(set.eviction_order().collect::<Vec<_>>(), [&"c", &"b"]);
+        assert_eq!(set.pop_lfu(), Some("c"));
+        assert!(set.remove(&"b"));
+        assert!(!set.remove(&"b"));
+        assert!(set.is_empty());
+        assert!(!LfuSet::new(0).insert(1));
+    }
+}
@@ -0,0 +1,146 @@
+//! The synchronization primitives of the thread-safe parts of the crate,
+//! `SyncLfuCache` and the eviction channel. They're the standard library's,
+//! or loom's when the crate is built with `--cfg loom`, so that the tests in
+//! `tests/loom.rs` can check those parts under every interleaving of their
+//! threads. Loom has no bounded channel, so under loom the eviction channel
+//! is a small one built on its mutex and condition variable.
+//! 
+
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::AtomicU64;
+#[cfg(not(loom))]
+pub(crate) use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{Arc, Condvar, Mutex, MutexGuard};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::AtomicU64;
+#[cfg(loom)]
+pub(crate) use channel::{sync_channel, Receiver, SyncSender};
+
+pub(crate) use std::sync::mpsc::TrySendError;
+
+/// Declares a thread-local, one that loom keeps per modeled thread under
+/// `--cfg loom`.
+/// 
+#[cfg(not(loom))]
+macro_rules! thread_locals {
+    ($($tt:tt)*) => { std::thread_local!($($tt)*); };
+}
+
+#[cfg(loom)]
+macro_rules! thread_locals {
+    ($($tt:tt)*) => { loom::thread_local!($($tt)*); };
+}
+
+pub(crate) use thread_locals;
+
+/// A bounded channel with the parts of `std::sync::mpsc`'s API the eviction
+/// channel uses, for loom, which has none.
+/// 
+#[cfg(loom)]
+mod channel {
+    use std::collections::VecDeque;
+    use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+    use std::sync::mpsc::TrySendError;
+    use std::time::Duration;
+
+    use super::{Arc, Condvar, Mutex};
+
+    struct State<T> {
+        queue    : VecDeque<T>,
+        sender   : bool,
+        receiver : bool,
+    }
+
+    struct Shared<T> {
+        state : Mutex<State<T>>,
+        ready : Condvar,
+        bound : usize,
+    }
+
+    pub(crate) struct SyncSender<T>(Arc<Shared<T>>);
+
+    pub(crate) struct Receiver<T>(Arc<Shared<T>>);
+
+    pub(crate) fn sync_channel<T>(bound: usize)
+        -> (SyncSender<T>, Receiver<T>)
+    {
+        let shared = Arc::new(Shared {
+            state : Mutex::new(State {
+                queue    : VecDeque::new(),
+                sender   : true,
+                receiver : true,
+            }),
+            ready : Condvar::new(),
+            bound,
+        });
+        (SyncSender(shared.clone()), Receiver(shared))
+    }
+
+    impl<T> SyncSender<T> {
+        pub(crate) fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+            let mut state = self.0.state.lock().unwrap();
+
+            if !state.receiver {
+                return Err(TrySendError::Disconnected(msg));
+            }
+            if state.queue.len() >= self.0.bound {
+                return Err(TrySendError::Full(msg));
+            }
+            state.queue.push_back(msg);
+            self.0.ready.notify_one();
+            Ok(())
+        }
+    }
+
+    impl<T> Drop for SyncSender<T> {
+        fn drop(&mut self) {
+            self.0.state.lock().unwrap().sender = false;
+            self.0.ready.notify_one();
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub(crate) fn try_recv(&self) -> Result<T, TryRecvError> {
+            let mut state = self.0.state.lock().unwrap();
+
+            match state.queue.pop_front() {
+                Some(msg)            => Ok(msg),
+                None if state.sender => Err(TryRecvError::Empty),
+                None                 => Err(TryRecvError::Disconnected),
+            }
+        }
+
+        pub(crate) fn recv(&self) -> Result<T, RecvError> {
+            let mut state = self.0.state.lock().unwrap();
+
+            loop {
+                if let Some(msg) = state.queue.pop_front() { return Ok(msg); }
+                if !state.sender { return Err(RecvError); }
+
+                state = self.0.ready.wait(state).unwrap();
+            }
+        }
+
+        /// Loom has no clock, so this waits without a timeout.
+        /// 
+        pub(crate) fn recv_timeout(&self, _timeout: Duration)
+            -> Result<T, RecvTimeoutError>
+        {
+            self.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        }
+
+        pub(crate) fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+            std::iter::from_fn(|| self.try_recv().ok())
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.0.state.lock().unwrap().receiver = false;
+        }
+    }
+}
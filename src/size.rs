@@ -0,0 +1,283 @@
+//! Weighing items by their size in memory, for caches that should hold a
+//! number of bytes rather than a number of items, see
+//! `LfuCache::weigh_by_size()`. Keys and values report the memory they own on
+//! the heap through the `HeapSize` trait, which is implemented for the
+//! standard types a cache usually holds, and can be implemented for others.
+//! 
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash};
+use std::mem::{size_of, size_of_val};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use linked_vector::HNode;
+
+use crate::cache::Value;
+use crate::{LfuCache, LfuCacheBuilder};
+
+/// Reports the memory a value owns on the heap, in bytes, not counting the
+/// value itself. A `String` owns its buffer, a `Vec` its buffer and whatever
+/// its elements own, and a `u64` nothing.
+/// 
+/// Estimates are fine: the cache only uses them to decide what to evict.
+/// 
+/// ```
+/// use lfu_cache::HeapSize;
+/// 
+/// struct Page { url: String, body: Vec<u8> }
+/// 
+/// impl HeapSize for Page {
+///     fn heap_size(&self) -> usize {
+///         self.url.heap_size() + self.body.heap_size()
+///     }
+/// }
+/// ```
+pub trait HeapSize {
+    /// Returns the number of bytes the value owns on the heap.
+    /// 
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! owns_nothing {
+    ($($t:ty),*) => { $(
+        impl HeapSize for $t {
+            fn heap_size(&self) -> usize { 0 }
+        }
+    )* };
+}
+
+owns_nothing!(u8, u16, u32, u64, u128, usize,
+              i8, i16, i32, i64, i128, isize,
+              f32, f64, bool, char, (), str);
+
+/// A reference owns nothing: what it points to belongs to someone else.
+/// 
+impl<T: ?Sized> HeapSize for &T {
+    fn heap_size(&self) -> usize { 0 }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(HeapSize::heap_size).sum()
+    }
+}
+
+impl<T: HeapSize, const N: usize> HeapSize for [T; N] {
+    fn heap_size(&self) -> usize {
+        self.as_slice().heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.as_slice().heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for VecDeque<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>()
+            + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        // A slot holds a key and a value, plus a control byte.
+        self.capacity() * (size_of::<(K, V)>() + 1)
+            + self.iter().map(|(k, v)| k.heap_size() + v.heap_size())
+                         .sum::<usize>()
+    }
+}
+
+impl<T: HeapSize + ?Sized> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+/// The pointee is counted in full, as if the cache were its only owner.
+/// 
+impl<T: HeapSize + ?Sized> HeapSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        2 * size_of::<usize>() + size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+/// The pointee is counted in full, as if the cache were its only owner.
+/// 
+impl<T: HeapSize + ?Sized> HeapSize for Rc<T> {
+    fn heap_size(&self) -> usize {
+        2 * size_of::<usize>() + size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+impl<A: HeapSize, B: HeapSize, C: HeapSize> HeapSize for (A, B, C) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size() + self.2.heap_size()
+    }
+}
+
+/// The memory each item takes up in the cache's own structures, whatever its
+/// key and value own: a map slot, with the hash, the key, the value record
+/// and a control byte, and a node in a frequency queue, with a copy of the key
+/// and two links. It's the same reckoning as `LfuCache::memory_footprint()`'s.
+/// 
+fn entry_overhead<K, V>() -> usize {
+    size_of::<(u64, K, Value<V>)>() + 1 
+        + size_of::<(Option<K>, HNode, HNode)>()
+}
+
+/// Returns an item's weight in bytes, see `LfuCache::weigh_by_size()`.
+/// 
+fn size_of_entry<K: HeapSize, V: HeapSize>(key: &K, value: &V) -> usize {
+    // The queue's copy of the key owns as much as the map's.
+    entry_overhead::<K, V>() + 2 * key.heap_size() + value.heap_size()
+}
+
+impl<K, V, S> LfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone + HeapSize + 'static,
+    V: HeapSize + 'static,
+    S: BuildHasher,
+{
+    /// Limits the cache to about `max_bytes` of memory, by weighing each item
+    /// at its size: what its key and value take up in the cache's structures,
+    /// what they own on the heap according to `HeapSize`, and a fixed
+    /// overhead for the item's bookkeeping. `weight()` then gives the bytes
+    /// the items take up, but not the cache's spare capacity.
+    /// 
+    /// This is `set_weigher()` with a weigher that does the sums, so values
+    /// are re-weighed when they're replaced, but not when they're changed in
+    /// place.
+    /// 
+    pub fn weigh_by_size(&mut self, max_bytes: usize) {
+        self.set_weigher(max_bytes, size_of_entry);
+    }
+}
+
+/// An `LfuCacheBuilder` whose cache will be weighed by size, see
+/// `LfuCacheBuilder::weigh_by_size()`.
+/// 
+#[derive(Clone, Debug)]
+pub struct SizeWeighedBuilder<S> {
+    builder   : LfuCacheBuilder<S>,
+    max_bytes : usize,
+}
+
+impl<S> LfuCacheBuilder<S> {
+    /// Has the cache hold about `max_bytes` of items, see
+    /// `LfuCache::weigh_by_size()`. The weigher depends on the key and value
+    /// types, so this comes after the other options.
+    /// 
+    /// ```
+    /// use lfu_cache::LfuCacheBuilder;
+    /// 
+    /// let mut cache = LfuCacheBuilder::new(1000).weigh_by_size(4096)
+    ///                                           .build();
+    /// 
+    /// cache.insert(1, "x".repeat(5000));
+    /// assert!(cache.is_empty());
+    /// 
+    /// cache.insert(2, "x".repeat(1000));
+    /// assert!(cache.weight() > 1000);
+    /// ```
+    pub fn weigh_by_size(self, max_bytes: usize) -> SizeWeighedBuilder<S> {
+        SizeWeighedBuilder { builder: self, max_bytes }
+    }
+}
+
+impl<S> SizeWeighedBuilder<S> {
+    /// Creates the cache.
+    /// 
+    pub fn build<K, V>(self) -> LfuCache<K, V, S>
+    where
+        K: Eq + Hash + Clone + HeapSize + 'static,
+        V: HeapSize + 'static,
+        S: BuildHasher,
+    {
+        let mut cache = self.builder.build();
+
+        cache.weigh_by_size(self.max_bytes);
+        cache
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DefaultState;
+
+    #[test]
+    fn sizes_of_std_types() {
+        let mut bytes = Vec::with_capacity(10);
+
+        bytes.extend_from_slice(b"abc");
+
+        assert_eq!(bytes.heap_size(), 10);
+        assert_eq!(String::with_capacity(7).heap_size(), 7);
+        assert_eq!(vec![String::from("ab"), String::from("cde")].heap_size(),
+                   2 * size_of::<String>() + 5);
+        assert_eq!(Box::new(5u32).heap_size(), 4);
+        assert_eq!(Box::<str>::from("hello").heap_size(), 5);
+        assert_eq!(Some(String::from("xy")).heap_size(), 2);
+        assert_eq!((7u8, "borrowed").heap_size(), 0);
+    }
+
+    #[test]
+    fn weight_follows_the_bytes() {
+        let overhead = entry_overhead::<u32, String>();
+        let mut cache = LfuCacheBuilder::new(100).weigh_by_size(usize::MAX)
+                                                 .build();
+        let bytes = |cache: &LfuCache<u32, String>| {
+            cache.iter().map(|(_, value)| value.capacity()).sum::<usize>()
+        };
+
+        for key in 0..10 { cache.insert(key, "x".repeat(key as usize * 10)); }
+
+        assert_eq!(cache.weight(), 10 * overhead + 450);
+        assert_eq!(cache.weight(), 10 * overhead + bytes(&cache));
+
+        // Replacements are re-weighed, and removals take their bytes along.
+        cache.insert(9, String::new());
+        cache.insert(3, "y".repeat(1000));
+        cache.remove(&5);
+        cache.pop_lfu();
+
+        assert_eq!(cache.weight(), 8 * overhead + bytes(&cache));
+
+        // The limit holds in bytes.
+        let mut cache = LfuCache::with_hasher(100, DefaultState::default());
+        let overhead  = entry_overhead::<u32, Vec<u8>>();
+
+        cache.weigh_by_size(4 * (overhead + 100));
+
+        for key in 0..20 { cache.insert(key, vec![key as u8; 100]); }
+
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.weight(), 4 * (overhead + 100));
+
+        cache.insert(20, vec![0; 250]);
+        assert!(cache.contains_key(&20) && cache.len() < 4);
+        assert!(cache.weight() <= 4 * (overhead + 100));
+    }
+}
@@ -0,0 +1,94 @@
+//! A Count-Min sketch of access frequencies, used to decide whether a new key
+//! is worth evicting an existing one for (TinyLFU admission). It can estimate
+//! the popularity of keys that aren't in the cache, or that have been evicted,
+//! in a fixed amount of memory.
+//! 
+//! Counters are periodically halved so that keys which were popular long ago
+//! don't keep their estimate forever.
+//! 
+
+/// The number of rows, each indexed by a different hash of the key.
+/// 
+const DEPTH: usize = 4;
+
+#[derive(Clone, Debug)]
+pub(crate) struct FrequencySketch {
+    counters  : Vec<u8>,
+    mask      : usize,
+    additions : usize,
+    period    : usize,
+}
+
+impl FrequencySketch {
+    /// Creates a sketch sized for a cache of the given capacity.
+    /// 
+    pub(crate) fn new(capacity: usize) -> Self {
+        let width = capacity.max(16).next_power_of_two();
+        Self {
+            counters  : vec![0; width * DEPTH],
+            mask      : width - 1,
+            additions : 0,
+            period    : width * 10,
+        }
+    }
+
+    /// Records one access to the key with the given hash.
+    /// 
+    pub(crate) fn increment(&mut self, hash: u64) {
+        for i in self.indexes(hash) {
+            self.counters[i] = self.counters[i].saturating_add(1);
+        }
+        self.additions += 1;
+
+        if self.additions == self.period {
+            self.age();
+        }
+    }
+
+    /// Returns the estimated number of recent accesses to the key with the
+    /// given hash. It can overestimate, but never underestimates.
+    /// 
+    pub(crate) fn estimate(&self, hash: u64) -> u8 {
+        self.indexes(hash).into_iter().map(|i| self.counters[i]).min().unwrap()
+    }
+
+    /// Halves every counter.
+    /// 
+    fn age(&mut self) {
+        for counter in &mut self.counters {
+            *counter /= 2;
+        }
+        self.additions /= 2;
+    }
+
+    /// Returns the index of the key's counter in each row.
+    /// 
+    fn indexes(&self, hash: u64) -> [usize; DEPTH] {
+        let h1    = hash as usize;
+        let h2    = (hash >> 32) as usize | 1;
+        let width = self.mask + 1;
+
+        core::array::from_fn(|row| {
+            row * width + (h1.wrapping_add(row.wrapping_mul(h2)) & self.mask)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_and_ages() {
+        let mut sketch = FrequencySketch::new(16);
+
+        for _ in 0..5 {
+            sketch.increment(0x1234_5678_9abc_def0);
+        }
+        assert_eq!(sketch.estimate(0x1234_5678_9abc_def0), 5);
+        assert_eq!(sketch.estimate(0x0fed_cba9_8765_4321), 0);
+
+        sketch.age();
+        assert_eq!(sketch.estimate(0x1234_5678_9abc_def0), 2);
+    }
+}
@@ -0,0 +1,53 @@
+//! Point-in-time views of a cache, for readers that shouldn't see the writes
+//! made after they started. A snapshot holds its own clones of the keys and
+//! values, so with `Arc` values (or keys) taking one only costs a reference
+//! count per item, and it's unaffected by anything the cache does later.
+//! 
+
+use std::sync::Arc;
+
+/// An immutable copy of the items of an `LfuCache`, in eviction order, see
+/// `LfuCache::snapshot_shared()`. Cloning it shares the items, so it can be
+/// handed to more than one reader, or thread, cheaply.
+/// 
+#[derive(Debug)]
+pub struct LfuSnapshot<K, V> {
+    entries: Arc<[(K, V, usize)]>,
+}
+
+impl<K, V> LfuSnapshot<K, V> {
+    pub(crate) fn new(entries: Vec<(K, V, usize)>) -> Self {
+        Self { entries: entries.into() }
+    }
+
+    /// Returns an iterator over the items in the eviction order they had when
+    /// the snapshot was taken, next to be evicted first.
+    /// 
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, value, _)| (key, value))
+    }
+
+    /// Like `iter()`, but with each item's frequency count too.
+    /// 
+    pub fn iter_with_frequency(&self) -> impl Iterator<Item = (&K, &V, usize)> {
+        self.entries.iter().map(|(key, value, freq)| (key, value, *freq))
+    }
+
+    /// Returns the number of items in the snapshot.
+    /// 
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the snapshot holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K, V> Clone for LfuSnapshot<K, V> {
+    fn clone(&self) -> Self {
+        Self { entries: self.entries.clone() }
+    }
+}
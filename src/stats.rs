@@ -0,0 +1,127 @@
+//! What a cache reports about itself: its counters, and snapshots of its
+//! shape for logs and tooling.
+//! 
+
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+/// Counters of what the cache has been doing, kept once 
+/// `LfuCache::enable_stats()` has been called.
+/// 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CacheStats {
+    /// Calls to `get()` that found the key.
+    pub hits       : u64,
+    /// Calls to `get()` that didn't.
+    pub misses     : u64,
+    /// Keys added to the cache by `insert()`.
+    pub inserts    : u64,
+    /// Calls to `insert()` that replaced the value of a cached key.
+    pub updates    : u64,
+    /// Items removed to make room for new ones.
+    pub evictions  : u64,
+    /// New keys turned away by admission control.
+    pub rejections : u64,
+    /// Writes turned away because the value was too heavy, see 
+    /// `LfuCache::set_max_item_fraction()`.
+    pub too_large  : u64,
+    /// Capacity increases made by `LfuCache::adapt()`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub grows      : u64,
+    /// Capacity decreases made by `LfuCache::adapt()`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shrinks    : u64,
+}
+
+/// A summary of how long loads have taken: the calls cache misses made to a
+/// loader, such as the closure given to `LfuCache::get_or_insert_with()` or a
+/// `LoadingCache`'s loader.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadStats {
+    /// Calls to the loader that returned a value.
+    pub loads         : u64,
+    /// Calls to the loader that returned an error.
+    pub failures      : u64,
+    /// The time spent in the loader, over all calls.
+    pub load_time     : Duration,
+    /// The longest a call has taken.
+    pub max_load_time : Duration,
+    /// The number of calls that took less than each of `LoadStats::BOUNDS`,
+    /// but at least the one before, with the last bucket for calls that took
+    /// longer than all of them.
+    pub histogram     : [u64; 7],
+}
+
+#[cfg(feature = "std")]
+impl LoadStats {
+    /// The upper bounds of the histogram's buckets, but for the last.
+    /// 
+    pub const BOUNDS: [Duration; 6] = [
+        Duration::from_micros(100),
+        Duration::from_millis(1),
+        Duration::from_millis(10),
+        Duration::from_millis(100),
+        Duration::from_secs(1),
+        Duration::from_secs(10),
+    ];
+
+    /// Returns the number of calls to the loader.
+    /// 
+    pub fn calls(&self) -> u64 {
+        self.loads + self.failures
+    }
+
+    /// Counts a call to the loader that took `elapsed`.
+    /// 
+    pub(crate) fn record(&mut self, elapsed: Duration, ok: bool) {
+        if ok { self.loads += 1; } else { self.failures += 1; }
+
+        let bucket = Self::BOUNDS.partition_point(|&bound| bound <= elapsed);
+
+        self.load_time     += elapsed;
+        self.max_load_time  = self.max_load_time.max(elapsed);
+        self.histogram[bucket] += 1;
+    }
+}
+
+/// A snapshot of the shape of a cache, returned by `LfuCache::dump_state()`.
+/// It holds copies of the keys, but never the values.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheState<K> {
+    pub capacity : usize,
+    pub len      : usize,
+    /// The frequency queues, from lowest frequency to highest.
+    pub buckets  : Vec<BucketState<K>>,
+    /// The cache's stats, if they're enabled.
+    pub stats    : Option<CacheStats>,
+}
+
+/// One frequency queue in a `CacheState`.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BucketState<K> {
+    pub frequency : usize,
+    /// The keys in the queue, next to be evicted first.
+    pub keys      : Vec<K>,
+}
+
+/// The keys of a cache and their frequency counts, but not the values, for
+/// warming up a cache after a restart. Returned by 
+/// `LfuCache::export_warm_state()` and taken by `apply_warm_state()`.
+/// 
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WarmState<K> {
+    /// The keys and their frequency counts, next to be evicted first.
+    pub entries: Vec<(K, usize)>,
+}
@@ -0,0 +1,461 @@
+//! A cache in front of a persistent store. Writes go to the store either
+//! right away ("write-through"), or once their items leave the cache or are
+//! flushed ("write-back"), which saves the store from seeing every update to
+//! a hot key.
+//! 
+
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, PoisonError};
+
+use crate::{DefaultState, EvictionReason, EvictionReceiver, LfuCache};
+use crate::LfuCacheBuilder;
+
+/// A persistent store that a `StoreCache` writes to.
+/// 
+pub trait Store<K, V> {
+    /// The error the store's operations fail with.
+    /// 
+    type Error;
+
+    /// Stores the key's value, replacing any it had.
+    /// 
+    fn write(&mut self, key: &K, value: &V) -> Result<(), Self::Error>;
+
+    /// Deletes the key's value, if it has one.
+    /// 
+    fn delete(&mut self, key: &K) -> Result<(), Self::Error>;
+}
+
+/// When a `StoreCache` writes to its store.
+/// 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Every insert and removal goes to the store before it changes the cache.
+    /// 
+    WriteThrough,
+    /// Inserts and removals only mark their keys dirty. A dirty item is
+    /// written to the store when it leaves the cache, however it leaves, and
+    /// everything dirty is written by `StoreCache::flush_dirty()`.
+    /// 
+    WriteBack,
+}
+
+/// A store that keeps everything in a hash map, for tests and as an example.
+/// 
+#[derive(Clone, Debug)]
+pub struct MemoryStore<K, V> {
+    items: HashMap<K, V>,
+}
+
+impl<K, V> MemoryStore<K, V> {
+    /// Creates an empty store.
+    /// 
+    pub fn new() -> Self {
+        Self { items: HashMap::new() }
+    }
+
+    /// Returns the stored items.
+    /// 
+    pub fn items(&self) -> &HashMap<K, V> {
+        &self.items
+    }
+}
+
+impl<K, V> Default for MemoryStore<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Store<K, V> for MemoryStore<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    type Error = Infallible;
+
+    fn write(&mut self, key: &K, value: &V) -> Result<(), Infallible> {
+        self.items.insert(key.clone(), value.clone());
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &K) -> Result<(), Infallible> {
+        self.items.remove(key);
+        Ok(())
+    }
+}
+
+/// An `LfuCache` that fronts a `Store`, see `WriteMode`. The store's errors
+/// are returned from the methods that write to it, and leave the cache as it
+/// was.
+/// 
+/// The cache is only reachable read-only through `cache()`, so that every
+/// change to it goes through here and reaches the store. In write-back mode
+/// the cache's eviction receiver is taken, see `LfuCache::eviction_receiver()`,
+/// to catch the dirty items the cache lets go of.
+/// 
+pub struct StoreCache<K, V, T, S = DefaultState> {
+    cache   : LfuCache<K, V, S>,
+    store   : T,
+    mode    : WriteMode,
+    dirty   : HashSet<K>,
+    deleted : HashSet<K>,
+    evicted : HashMap<K, V>,
+    // Only reached through `get_mut()`, so the lock is never taken; it keeps
+    // the store cache `Sync`.
+    leaving : Option<Mutex<EvictionReceiver<K, V>>>,
+}
+
+impl<K, V, T, S> StoreCache<K, V, T, S>
+where
+    K: Eq + Hash + Clone,
+    T: Store<K, V>,
+    S: BuildHasher,
+{
+    /// Puts `cache` in front of `store`. The items already in the cache are
+    /// taken to be in the store too.
+    /// 
+    pub fn new(mut cache: LfuCache<K, V, S>, store: T, mode: WriteMode) 
+        -> Self 
+    {
+        // Every item but the new one can leave in a single insert, and the
+        // channel is drained after each.
+        let leaving = (mode == WriteMode::WriteBack).then(|| {
+            let bound = cache.capacity().max(cache.len()) + 1;
+
+            Mutex::new(cache.eviction_receiver(bound))
+        });
+        Self {
+            cache,
+            store,
+            mode,
+            dirty   : HashSet::new(),
+            deleted : HashSet::new(),
+            evicted : HashMap::new(),
+            leaving,
+        }
+    }
+
+    /// Inserts a key-value pair into the cache. In write-through mode it's
+    /// written to the store first. In write-back mode a dirty item that has
+    /// to be evicted to make room is written first, and a new item the cache
+    /// turns away, by admission control, because every item is pinned, or 
+    /// because it's too large, is written right away rather than lost. Dirty
+    /// items the insert pushes out some other way, like several at once for
+    /// a weigher, or expired ones, are written once it's done. If one of 
+    /// those writes fails, the error is returned and the items stay dirty 
+    /// for the next `flush_dirty()`.
+    /// 
+    pub fn insert(&mut self, key: K, value: V) -> Result<(), T::Error> {
+        if self.mode == WriteMode::WriteThrough {
+            self.store.write(&key, &value)?;
+            self.cache.insert(key, value);
+            return Ok(());
+        }
+        self.catch_evicted();
+
+        // An item too large to cache isn't stored, and the old value would be
+        // stale.
+        if self.cache.would_be_too_large(&key, &value) {
+            self.store.write(&key, &value)?;
+            self.cache.remove(&key);
+            self.dirty.remove(&key);
+            self.deleted.remove(&key);
+            self.evicted.remove(&key);
+            return Ok(());
+        }
+        if !self.cache.contains_key(&key) {
+            // Don't lose the value if the cache turns the new key away.
+            if self.cache.would_reject(&key) {
+                self.store.write(&key, &value)?;
+                self.deleted.remove(&key);
+                return Ok(());
+            }
+            if self.cache.len() >= self.cache.capacity() {
+                let victim = self.cache.eviction_candidate()
+                                       .filter(|k| self.dirty.contains(*k))
+                                       .cloned();
+                if let Some(victim) = victim {
+                    self.flush(&victim)?;
+                }
+            }
+        }
+        self.deleted.remove(&key);
+        self.dirty.insert(key.clone());
+        self.cache.insert(key, value);
+        self.catch_evicted();
+        self.write_evicted().map(drop)
+    }
+
+    /// Removes the key from the cache and returns its value, if it was
+    /// present. The key is deleted from the store too, right away in
+    /// write-through mode, and by the next `flush_dirty()` in write-back mode.
+    /// 
+    pub fn remove(&mut self, key: &K) -> Result<Option<V>, T::Error> {
+        match self.mode {
+            WriteMode::WriteThrough => self.store.delete(key)?,
+            WriteMode::WriteBack    => {
+                self.catch_evicted();
+                self.dirty.remove(key);
+                self.evicted.remove(key);
+                self.deleted.insert(key.clone());
+            },
+        }
+        Ok(self.cache.remove(key))
+    }
+
+    /// Returns a reference to the value corresponding to the key, counting
+    /// the access like `LfuCache::get()` does.
+    /// 
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        // An item this expires is caught by the next call.
+        self.catch_evicted();
+        self.cache.get(key)
+    }
+
+    /// Writes every dirty item to the store, and applies the removals made
+    /// since the last flush, returning how many writes and deletes it made.
+    /// If the store fails, what was flushed so far stays flushed, and the
+    /// rest stays dirty for the next call.
+    /// 
+    pub fn flush_dirty(&mut self) -> Result<usize, T::Error> {
+        let mut flushed = 0;
+
+        self.catch_evicted();
+
+        for key in self.deleted.iter().cloned().collect::<Vec<_>>() {
+            self.store.delete(&key)?;
+            self.deleted.remove(&key);
+            flushed += 1;
+        }
+        flushed += self.write_evicted()?;
+
+        for key in self.dirty.iter().cloned().collect::<Vec<_>>() {
+            if self.flush(&key)? { flushed += 1; }
+        }
+        Ok(flushed)
+    }
+
+    /// Returns `true` if the key's item has changes the store hasn't seen,
+    /// whether it's still cached or it left before they could be written.
+    /// 
+    pub fn is_dirty(&self, key: &K) -> bool {
+        self.dirty.contains(key) || self.evicted.contains_key(key)
+    }
+
+    /// Returns the cache.
+    /// 
+    pub fn cache(&self) -> &LfuCache<K, V, S> {
+        &self.cache
+    }
+
+    /// Returns the store.
+    /// 
+    pub fn store(&self) -> &T {
+        &self.store
+    }
+
+    /// Returns the write mode.
+    /// 
+    pub fn mode(&self) -> WriteMode {
+        self.mode
+    }
+
+    /// Writes a dirty item to the store and marks it clean, returning `true`
+    /// if it was written. A key that's no longer cached was caught on its 
+    /// way out, and is only marked clean here.
+    /// 
+    fn flush(&mut self, key: &K) -> Result<bool, T::Error> {
+        let Some(value) = self.cache.peek_value(key) else {
+            self.dirty.remove(key);
+            return Ok(false);
+        };
+        self.store.write(key, value)?;
+        self.dirty.remove(key);
+        Ok(true)
+    }
+
+    /// Takes the dirty items the cache has let go of from its eviction 
+    /// channel, to be written by `write_evicted()`. Replaced values are 
+    /// stale, and clean items are in the store already.
+    /// 
+    fn catch_evicted(&mut self) {
+        let Some(leaving) = &mut self.leaving else { return };
+        let leaving = leaving.get_mut().unwrap_or_else(PoisonError::into_inner);
+
+        while let Some(item) = leaving.try_recv() {
+            if item.reason != EvictionReason::Replaced 
+               && self.dirty.remove(&item.key) 
+            {
+                self.evicted.insert(item.key, item.value);
+            }
+        }
+    }
+
+    /// Writes the dirty items that left the cache to the store, returning
+    /// how many there were. If the store fails, the ones not yet written are
+    /// kept for the next try.
+    /// 
+    fn write_evicted(&mut self) -> Result<usize, T::Error> {
+        let keys = self.evicted.keys().cloned().collect::<Vec<_>>();
+
+        for key in &keys {
+            self.store.write(key, &self.evicted[key])?;
+            self.evicted.remove(key);
+        }
+        Ok(keys.len())
+    }
+}
+
+impl<S> LfuCacheBuilder<S> {
+    /// Creates the cache in front of `store`, see `StoreCache`.
+    /// 
+    pub fn build_with_store<K, V, T>(self, store: T, mode: WriteMode)
+        -> StoreCache<K, V, T, S>
+    where
+        K: Eq + Hash + Clone,
+        T: Store<K, V>,
+        S: BuildHasher,
+    {
+        StoreCache::new(self.build(), store, mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A store that fails while `down` is set.
+    /// 
+    #[derive(Default)]
+    struct FlakyStore {
+        items : MemoryStore<u32, u32>,
+        down  : bool,
+    }
+
+    impl Store<u32, u32> for FlakyStore {
+        type Error = &'static str;
+
+        fn write(&mut self, key: &u32, value: &u32) -> Result<(), Self::Error> {
+            if self.down { return Err("down"); }
+            self.items.write(key, value).map_err(|e| match e {})
+        }
+
+        fn delete(&mut self, key: &u32) -> Result<(), Self::Error> {
+            if self.down { return Err("down"); }
+            self.items.delete(key).map_err(|e| match e {})
+        }
+    }
+
+    #[test]
+    fn write_through_errors_leave_cache_alone() {
+        let mut cache = LfuCacheBuilder::new(4)
+                            .build_with_store(FlakyStore::default(),
+                                              WriteMode::WriteThrough);
+        cache.insert(1, 10).unwrap();
+        assert_eq!(cache.store().items.items()[&1], 10);
+
+        cache.store.down = true;
+        assert_eq!(cache.insert(1, 11), Err("down"));
+        assert_eq!(cache.insert(2, 20), Err("down"));
+        assert_eq!(cache.remove(&1), Err("down"));
+        assert_eq!(cache.get(&1), Some(&10));
+        assert_eq!(cache.cache().len(), 1);
+
+        cache.store.down = false;
+        assert_eq!(cache.remove(&1), Ok(Some(10)));
+        assert!(cache.store().items.items().is_empty());
+        assert_eq!(cache.flush_dirty(), Ok(0));
+    }
+
+    #[test]
+    fn write_back_flushes_on_evict() {
+        let mut cache = LfuCacheBuilder::new(2)
+                            .build_with_store(MemoryStore::new(),
+                                              WriteMode::WriteBack);
+        cache.insert(1, 10).unwrap();
+        cache.insert(1, 11).unwrap();
+        cache.insert(2, 20).unwrap();
+        assert!(cache.store().items().is_empty());
+
+        // Key 2 is evicted for key 3, so it's written first.
+        cache.insert(3, 30).unwrap();
+        assert_eq!(cache.store().items(), &HashMap::from([(2, 20)]));
+        assert!(!cache.is_dirty(&2));
+        assert!(cache.is_dirty(&1) && cache.is_dirty(&3));
+
+        assert_eq!(cache.remove(&3).unwrap(), Some(30));
+        assert_eq!(cache.remove(&2).unwrap(), None);
+        assert_eq!(cache.flush_dirty().unwrap(), 3);
+        assert_eq!(cache.store().items(), &HashMap::from([(1, 11)]));
+        assert_eq!(cache.flush_dirty().unwrap(), 0);
+    }
+
+    #[test]
+    fn write_back_catches_items_a_weigher_evicts() {
+        let mut inner = LfuCache::new(10);
+
+        inner.set_weigher(10, |_, value: &u32| *value as usize);
+
+        let mut cache = StoreCache::new(inner, MemoryStore::new(), 
+                                        WriteMode::WriteBack);
+        cache.insert(1, 4).unwrap();
+        cache.insert(2, 4).unwrap();
+
+        // Key 3 takes the room of both.
+        cache.insert(3, 9).unwrap();
+        assert_eq!(cache.store().items(), &HashMap::from([(1, 4), (2, 4)]));
+        assert!(!cache.is_dirty(&1) && !cache.is_dirty(&2));
+
+        // One too large for the cache goes straight to the store.
+        cache.insert(3, 11).unwrap();
+        assert!(!cache.cache().contains_key(&3) && !cache.is_dirty(&3));
+        assert_eq!(cache.store().items()[&3], 11);
+        assert_eq!(cache.flush_dirty(), Ok(0));
+    }
+
+    #[test]
+    fn write_back_catches_expired_items() {
+        let clock     = crate::ManualClock::new();
+        let mut inner = LfuCacheBuilder::new(4)
+                            .expire_after_write(Duration::from_secs(1))
+                            .build();
+        inner.set_clock(clock.clone());
+
+        let mut cache = StoreCache::new(inner, MemoryStore::new(), 
+                                        WriteMode::WriteBack);
+        cache.insert(1, 10).unwrap();
+        cache.insert(2, 20).unwrap();
+        clock.advance(Duration::from_secs(2));
+
+        // Key 1 is dropped by the miss, and key 2 is flushed from the cache.
+        assert_eq!(cache.get(&1), None);
+        assert!(cache.is_dirty(&1));
+        assert_eq!(cache.flush_dirty(), Ok(2));
+        assert_eq!(cache.store().items(), &HashMap::from([(1, 10), (2, 20)]));
+        assert!(!cache.is_dirty(&1) && !cache.is_dirty(&2));
+    }
+
+    #[test]
+    fn write_back_errors_leave_cache_alone() {
+        let mut cache = StoreCache::new(LfuCache::new(1),
+                                        FlakyStore::default(),
+                                        WriteMode::WriteBack);
+        cache.insert(1, 10).unwrap();
+        cache.store.down = true;
+
+        // Evicting key 1 would lose its value.
+        assert_eq!(cache.insert(2, 20), Err("down"));
+        assert_eq!(cache.flush_dirty(), Err("down"));
+        assert_eq!(cache.get(&1), Some(&10));
+        assert!(cache.is_dirty(&1));
+
+        cache.store.down = false;
+        cache.insert(2, 20).unwrap();
+        assert_eq!(cache.store().items.items(), &HashMap::from([(1, 10)]));
+        assert_eq!(cache.flush_dirty(), Ok(1));
+    }
+}
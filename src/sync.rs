@@ -0,0 +1,525 @@
+//! A thread-safe wrapper around `LfuCache`. The cache sits behind a mutex, and
+//! loads of missing keys are coalesced: when several threads miss the same key
+//! at once, only the first runs its loader, and the rest wait for its result
+//! instead of running their own ("cache stampede" protection). A load in
+//! flight doesn't hold the cache's lock, so other keys can be read and written
+//! while it runs.
+//! 
+//! Callbacks the cache runs while it's locked, like a weigher or a loader 
+//! given to one of its methods, mustn't use it again. The lock remembers the
+//! thread holding it, so a callback that does panics instead of deadlocking.
+//! 
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::PoisonError;
+
+use crate::shim::{Arc, AtomicU64, Condvar, Mutex, MutexGuard};
+use crate::{DefaultState, EvictionReceiver, LfuCache};
+
+/// An `LfuCache` that can be shared between threads. Values are handed out as
+/// clones, so `V` is usually cheap to clone, like an `Arc`.
+/// 
+pub struct SyncLfuCache<K, V, S = DefaultState> {
+    cache    : Mutex<LfuCache<K, V, S>>,
+    inflight : Mutex<HashMap<K, Arc<Flight<V>>>>,
+    holder   : AtomicU64,
+}
+
+/// Exclusive access to the cache inside a `SyncLfuCache`, see 
+/// `SyncLfuCache::lock()`. The lock is released when it's dropped.
+/// 
+pub struct CacheGuard<'a, K, V, S = DefaultState> {
+    cache  : MutexGuard<'a, LfuCache<K, V, S>>,
+    holder : &'a AtomicU64,
+}
+
+impl<K, V, S> Deref for CacheGuard<'_, K, V, S> {
+    type Target = LfuCache<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cache
+    }
+}
+
+impl<K, V, S> DerefMut for CacheGuard<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.cache
+    }
+}
+
+impl<K, V, S> Drop for CacheGuard<'_, K, V, S> {
+    fn drop(&mut self) {
+        // Cleared while the lock is still held.
+        self.holder.store(NOBODY, Relaxed);
+    }
+}
+
+/// The holder of a lock that isn't held.
+/// 
+const NOBODY: u64 = 0;
+
+/// Returns a number for the current thread that no other thread has.
+/// 
+fn thread_number() -> u64 {
+    // Numbers are handed out across loom's models too, so this one is std's.
+    static NEXT: std::sync::atomic::AtomicU64 = 
+        std::sync::atomic::AtomicU64::new(NOBODY + 1);
+
+    crate::shim::thread_locals! {
+        static NUMBER: u64 = NEXT.fetch_add(1, Relaxed);
+    }
+    NUMBER.with(|number| *number)
+}
+
+/// A load in progress. The thread running the loader sets the outcome, and
+/// the threads waiting on the same key read it once notified.
+/// 
+struct Flight<V> {
+    outcome : Mutex<Outcome<V>>,
+    done    : Condvar,
+}
+
+enum Outcome<V> {
+    Loading,
+    Loaded(V),
+    Failed(Box<dyn Any + Send>),
+    Panicked,
+}
+
+impl<V> Flight<V> {
+    fn new() -> Self {
+        Self { outcome: Mutex::new(Outcome::Loading), done: Condvar::new() }
+    }
+
+    fn finish(&self, outcome: Outcome<V>) {
+        *self.outcome.lock().unwrap() = outcome;
+        self.done.notify_all();
+    }
+}
+
+/// Lands the loader's flight if it unwinds, so that the threads waiting on it
+/// are woken up instead of waiting forever.
+/// 
+struct Landing<'a, K, V, S>
+where
+    K: Eq + Hash,
+{
+    owner  : &'a SyncLfuCache<K, V, S>,
+    key    : &'a K,
+    flight : &'a Flight<V>,
+}
+
+impl<K, V, S> Drop for Landing<'_, K, V, S>
+where
+    K: Eq + Hash,
+{
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.flight.finish(Outcome::Panicked);
+        }
+        let mut inflight = self.owner.inflight.lock().unwrap();
+
+        // A waiter that passed over the landed flight may have put its own 
+        // in its place.
+        if inflight.get(self.key).is_some_and(|f| ptr::eq(&**f, self.flight)) {
+            inflight.remove(self.key);
+        }
+    }
+}
+
+impl<K, V> SyncLfuCache<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Creates a new thread-safe LFU cache with the given capacity.
+    /// 
+    pub fn new(capacity: usize) -> Self {
+        Self::from_cache(LfuCache::new(capacity))
+    }
+}
+
+impl<K, V, S> SyncLfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    S: BuildHasher,
+{
+    /// Wraps an existing cache, keeping its items and whatever features it
+    /// has enabled.
+    /// 
+    pub fn from_cache(cache: LfuCache<K, V, S>) -> Self {
+        Self {
+            cache    : Mutex::new(cache),
+            inflight : Mutex::new(HashMap::new()),
+            holder   : AtomicU64::new(NOBODY),
+        }
+    }
+
+    /// Locks the cache for direct access to the rest of its methods. A panic
+    /// while it's locked doesn't poison it, since the cache's methods leave
+    /// it whole when a callback panics.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the current thread already holds the lock, as it does while
+    /// the cache runs a callback, rather than deadlocking. The cache is left
+    /// as it was before the call that ran the callback.
+    /// 
+    pub fn lock(&self) -> CacheGuard<'_, K, V, S> {
+        let thread = thread_number();
+
+        // Only this thread ever stores its own number.
+        assert!(self.holder.load(Relaxed) != thread, 
+                "a SyncLfuCache was used by a callback it was running: its \
+                 lock is already held by this thread");
+
+        let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+
+        self.holder.store(thread, Relaxed);
+        CacheGuard { cache, holder: &self.holder }
+    }
+
+    /// Inserts a key-value pair into the cache.
+    /// 
+    pub fn insert(&self, key: K, value: V) {
+        self.lock().insert(key, value);
+    }
+
+    /// Removes the key from the cache and returns its value, if it was present.
+    /// 
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.lock().remove(key)
+    }
+
+    /// Returns the number of items in the cache.
+    /// 
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Starts sending the items the cache evicts to a channel, and returns 
+    /// its receiving end, see `LfuCache::eviction_receiver()`.
+    /// 
+    pub fn eviction_receiver(&self, bound: usize) -> EvictionReceiver<K, V> {
+        self.lock().eviction_receiver(bound)
+    }
+
+    /// Unwraps the cache.
+    /// 
+    pub fn into_inner(self) -> LfuCache<K, V, S> {
+        self.cache.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K, V, S> SyncLfuCache<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    /// Returns a clone of the value corresponding to the key.
+    /// 
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.lock().get(key).cloned()
+    }
+
+    /// Returns a clone of the key's value if it's cached and fresh, without
+    /// counting an access.
+    /// 
+    fn peek(&self, key: &K) -> Option<V> {
+        let cache = self.lock();
+
+        cache.contains_key(key).then(|| cache.peek_value(key).cloned())
+                               .flatten()
+    }
+
+    /// Returns a clone of the key's value, calling `f` to load and insert it
+    /// if it's missing. Concurrent misses on the same key are coalesced: `f`
+    /// runs on one thread only, and the others wait for its value.
+    /// 
+    /// # Panics
+    /// 
+    /// If the loader panics, the panic is resumed on its thread, and the
+    /// threads that were waiting on it panic as well. Later calls try again.
+    /// 
+    pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> V {
+        match self.get_or_try_insert_with(key, || Ok::<_, Infallible>(f())) {
+            Ok(value) => value,
+            Err(e)    => match e {},
+        }
+    }
+
+    /// Like `get_or_insert_with()`, but the loader can fail. Its error is
+    /// returned to the threads that were waiting on it too, as clones, and
+    /// nothing is inserted. Later calls try again.
+    /// 
+    /// If the threads waiting on a key were called with differing error types,
+    /// those whose type doesn't match the loader's load the key again, with
+    /// their misses coalesced the same way.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics like `get_or_insert_with()` if the loader panics.
+    /// 
+    pub fn get_or_try_insert_with<E>(&self,
+                                     key : K,
+                                     f   : impl FnOnce() -> Result<V, E>)
+        -> Result<V, E>
+    where
+        E: Clone + Send + 'static,
+    {
+        if let Some(value) = self.get(&key) { return Ok(value); }
+
+        // A flight that failed with an error of another type than `E` stays
+        // in the map until its loader is done with it, and is passed over.
+        let mut passed = None::<Arc<Flight<V>>>;
+
+        let flight = loop {
+            let flight = {
+                let mut inflight = self.inflight.lock().unwrap();
+
+                match inflight.get(&key) {
+                    Some(flight) if !passed.as_ref().is_some_and(|p| {
+                        ptr::eq(&**p, &**flight)
+                    }) => flight.clone(),
+                    _ => {
+                        // The value may have landed since the lookup above,
+                        // which counted the miss already.
+                        if let Some(value) = self.peek(&key) { 
+                            return Ok(value); 
+                        }
+                        let flight = Arc::new(Flight::new());
+                        inflight.insert(key.clone(), flight.clone());
+                        break flight;
+                    },
+                }
+            };
+            let mut outcome = flight.outcome.lock().unwrap();
+
+            while let Outcome::Loading = *outcome {
+                outcome = flight.done.wait(outcome).unwrap();
+            }
+            match &*outcome {
+                Outcome::Loaded(value) => return Ok(value.clone()),
+                Outcome::Panicked      => panic!("the loader panicked"),
+                Outcome::Failed(e)     => {
+                    if let Some(e) = e.downcast_ref::<E>() {
+                        return Err(e.clone());
+                    }
+                },
+                Outcome::Loading       => unreachable!(),
+            }
+            drop(outcome);
+            passed = Some(flight);
+        };
+        let _land  = Landing { owner: self, key: &key, flight: &flight };
+        let watch  = self.lock().start_load();
+        let result = f();
+
+        self.lock().finish_load(watch, result.is_ok());
+
+        match result {
+            Ok(value) => {
+                self.lock().insert(key.clone(), value.clone());
+                flight.finish(Outcome::Loaded(value.clone()));
+                Ok(value)
+            },
+            Err(e) => {
+                flight.finish(Outcome::Failed(Box::new(e.clone())));
+                Err(e)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+    use std::thread;
+
+    const THREADS: usize = 32;
+
+    /// Blocks until every thread but the loader's is waiting on the key.
+    /// 
+    fn await_waiters(cache: &SyncLfuCache<u32, u32>, key: u32) {
+        loop {
+            let waiting = cache.inflight.lock().unwrap().get(&key)
+                                        .map_or(0, Arc::strong_count);
+
+            // The map, the loader, and the waiters each hold the flight.
+            if waiting == THREADS + 1 { return; }
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn concurrent_misses_load_once() {
+        let cache = SyncLfuCache::new(8);
+        let loads = AtomicUsize::new(0);
+
+        let values = thread::scope(|s| {
+            let handles = (0..THREADS).map(|_| s.spawn(|| {
+                cache.get_or_insert_with(7, || {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    await_waiters(&cache, 7);
+                    42
+                })
+            })).collect::<Vec<_>>();
+
+            handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(values, [42; THREADS]);
+        assert_eq!(cache.get(&7), Some(42));
+        assert!(cache.inflight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_load_reaches_waiters() {
+        let cache = SyncLfuCache::new(8);
+        let loads = AtomicUsize::new(0);
+
+        let results = thread::scope(|s| {
+            let handles = (0..THREADS).map(|_| s.spawn(|| {
+                cache.get_or_try_insert_with(7, || {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    await_waiters(&cache, 7);
+                    Err("unavailable")
+                })
+            })).collect::<Vec<_>>();
+
+            handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(results, [Err("unavailable"); THREADS]);
+        assert!(cache.is_empty());
+
+        // The failure isn't remembered.
+        assert_eq!(cache.get_or_try_insert_with(7, || Ok::<_, ()>(1)), Ok(1));
+    }
+
+    #[test]
+    fn waiters_of_another_error_type_load_once() {
+        let cache = SyncLfuCache::new(8);
+        let loads = AtomicUsize::new(0);
+
+        // The first load fails after everyone's waiting, cast as `&str` or 
+        // `u8` by whichever thread ran it. The other half load again, once.
+        let load = || {
+            if loads.fetch_add(1, Ordering::SeqCst) > 0 { return Ok(1); } 
+
+            await_waiters(&cache, 7);
+            Err(())
+        };
+        let (cache, load) = (&cache, &load);
+
+        let results = thread::scope(|s| {
+            let handles = (0..THREADS).map(|i| s.spawn(move || {
+                if i % 2 == 0 {
+                    cache.get_or_try_insert_with(7, || load().map_err(|_| "a"))
+                         .map_err(|e| e.to_string())
+                } else {
+                    cache.get_or_try_insert_with(7, || load().map_err(|_| 0u8))
+                         .map_err(|e| e.to_string())
+                }
+            })).collect::<Vec<_>>();
+
+            handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), THREADS / 2);
+        assert!(results.iter().flatten().all(|&value| value == 1));
+        assert!(cache.inflight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_miss_is_counted_once() {
+        let cache = SyncLfuCache::new(8);
+
+        cache.lock().enable_stats();
+        assert_eq!(cache.get_or_insert_with(7, || 1), 1);
+        assert_eq!(cache.get_or_insert_with(7, || 2), 1);
+
+        let stats = cache.lock().stats().copied().unwrap();
+
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn panicked_load_reaches_waiters() {
+        let cache = SyncLfuCache::new(8);
+
+        let panics = thread::scope(|s| {
+            let handles = (0..THREADS).map(|_| s.spawn(|| {
+                cache.get_or_insert_with(7, || {
+                    await_waiters(&cache, 7);
+                    panic!("backend down");
+                })
+            })).collect::<Vec<_>>();
+
+            handles.into_iter().map(|h| h.join()).filter(Result::is_err).count()
+        });
+        assert_eq!(panics, THREADS);
+        assert_eq!(cache.get_or_insert_with(7, || 1), 1);
+    }
+
+    /// Runs `f`, which must panic because it used the cache from a callback.
+    /// 
+    fn assert_reentered(f: impl FnOnce()) {
+        let e = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+        let e = e.downcast_ref::<&str>().unwrap();
+
+        assert!(e.contains("used by a callback it was running"), "{e}");
+    }
+
+    #[test]
+    fn callbacks_cant_reenter() {
+        static CACHE: OnceLock<SyncLfuCache<u32, u32>> = OnceLock::new();
+
+        // A weigher that looks at the cache it's weighing for.
+        let mut inner = LfuCache::new(4);
+
+        inner.set_weigher(100, |_, value| {
+            if *value == 0 { CACHE.get().unwrap().len(); }
+            1
+        });
+        let cache = CACHE.get_or_init(|| SyncLfuCache::from_cache(inner));
+
+        cache.insert(1, 1);
+        cache.insert(2, 2);
+        assert_reentered(|| cache.insert(3, 0));
+
+        // A loader run with the cache locked.
+        assert_reentered(|| {
+            cache.lock().get_or_insert_with(4, || cache.get(&1).unwrap());
+        });
+
+        // The cache is as it was, and usable.
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&3), None);
+        assert_eq!(cache.get(&4), None);
+        assert_eq!(cache.lock().weight(), 2);
+
+        cache.insert(3, 3);
+        assert_eq!(cache.get_or_insert_with(4, || 4), 4);
+        assert_eq!(cache.get(&1), Some(1));
+        assert_eq!(cache.len(), 4);
+
+        // A loader run without the lock held can use the cache.
+        assert_eq!(cache.get_or_insert_with(5, || cache.get(&1).unwrap()), 1);
+    }
+}
@@ -0,0 +1,83 @@
+//! An index from tags to the cached keys carrying them, and back, so that
+//! everything with a given tag can be invalidated at once. The cache keeps it
+//! in step with every removal, evictions included, so tags never outlive the
+//! entries they were given to.
+//! 
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+#[derive(Clone, Debug)]
+pub(crate) struct TagIndex<K> {
+    keys : HashMap<Box<str>, HashSet<K>>,
+    tags : HashMap<K, Vec<Box<str>>>,
+}
+
+impl<K> TagIndex<K>
+where
+    K: Eq + Hash + Clone,
+{
+    pub(crate) fn new() -> Self {
+        Self { keys: HashMap::new(), tags: HashMap::new() }
+    }
+
+    /// Gives the key the tags, replacing any it had.
+    /// 
+    pub(crate) fn set(&mut self, key: &K, tags: Vec<Box<str>>) {
+        self.remove(key);
+
+        if tags.is_empty() { return; }
+
+        for tag in &tags {
+            self.keys.entry(tag.clone()).or_default().insert(key.clone());
+        }
+        self.tags.insert(key.clone(), tags);
+    }
+
+    /// Returns the keys with the tag.
+    /// 
+    pub(crate) fn keys(&self, tag: &str) -> Vec<K> {
+        self.keys.get(tag).map_or_else(Vec::new, |keys| {
+            keys.iter().cloned().collect()
+        })
+    }
+}
+
+impl<K> TagIndex<K>
+where
+    K: Eq + Hash,
+{
+    /// Forgets the key's tags.
+    /// 
+    pub(crate) fn remove(&mut self, key: &K) {
+        let Some(tags) = self.tags.remove(key) else { return };
+
+        for tag in tags {
+            let keys = self.keys.get_mut(&tag).unwrap();
+            keys.remove(key);
+
+            if keys.is_empty() {
+                self.keys.remove(&tag);
+            }
+        }
+    }
+
+    /// Returns the number of distinct tags in use.
+    /// 
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns the keys that have tags.
+    /// 
+    #[cfg(test)]
+    pub(crate) fn tagged(&self) -> impl Iterator<Item = &K> {
+        self.tags.keys()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.keys.clear();
+        self.tags.clear();
+    }
+}
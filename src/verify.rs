@@ -0,0 +1,220 @@
+//! The shadow model behind the `debug-verify` feature. With the feature on,
+//! every `LfuCache` keeps a slow, obviously correct model of itself: a map of
+//! each key to the version of its value, its frequency count and the tick of
+//! its last use, with the items ordered by frequency and then by last use.
+//! The outcomes of `get()`, `insert()`, `remove()`, `pop_lfu()` and `clear()`
+//! are checked against what the model predicts: which item was hit, what its
+//! version and frequency count are now, which item was evicted, and `len()`.
+//! The first divergence panics with both sides.
+//! 
+//! The model only follows plain LFU: no admission control, probabilistic
+//! increments, logarithmic buckets, other policies, weights, pins, eviction
+//! preferences or TTLs. While the cache uses any of those, or after any other
+//! method changes its items, the model is lost. It's rebuilt from the cache
+//! before the next operation it can check.
+//! 
+
+#[cfg(test)]
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// An item as the model has it.
+/// 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Item {
+    pub(crate) version : u64,
+    pub(crate) freq    : usize,
+    last               : u64,
+}
+
+/// An item's version and frequency count, or `None` for an absent key.
+/// 
+pub(crate) type State = Option<(u64, usize)>;
+
+#[derive(Clone)]
+pub(crate) struct Shadow<K> {
+    items  : HashMap<K, Item>,
+    order  : BTreeMap<(usize, u64), K>,
+    tick   : u64,
+    synced : bool,
+    // Set once the cache holds a key, which only `K: Clone` caches can.
+    clone  : Option<fn(&K) -> K>,
+}
+
+impl<K> Shadow<K> {
+    /// Creates the model of an empty cache.
+    /// 
+    pub(crate) fn new() -> Self {
+        Self {
+            items  : HashMap::new(),
+            order  : BTreeMap::new(),
+            tick   : 0,
+            synced : true,
+            clone  : None,
+        }
+    }
+
+    /// Marks the model as out of step with the cache.
+    /// 
+    pub(crate) fn lose(&mut self) {
+        self.synced = false;
+    }
+
+    /// Marks the model as in step again, once an operation's outcome has been
+    /// checked.
+    /// 
+    pub(crate) fn confirm(&mut self) {
+        self.synced = true;
+    }
+
+    pub(crate) fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Empties the model along with the cache.
+    /// 
+    pub(crate) fn clear(&mut self) {
+        self.items.clear();
+        self.order.clear();
+        self.synced = true;
+    }
+}
+
+impl<K> Shadow<K>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Lets the model clone keys, once the cache is about to hold one.
+    /// 
+    pub(crate) fn learn_clone(&mut self) {
+        self.clone = Some(K::clone);
+    }
+
+    /// A write of the key, into a cache of `capacity`. Returns its new state
+    /// and the item evicted to make room for it, if any.
+    /// 
+    pub(crate) fn insert(&mut self, key: &K, capacity: usize)
+        -> (State, Option<(K, Item)>)
+    {
+        if capacity == 0 { return (None, None); }
+
+        self.learn_clone();
+
+        if let Some(item) = self.take(key) {
+            self.add(key.clone(), item.version + 1, item.freq + 1);
+            return (Some((item.version + 1, item.freq + 1)), None);
+        }
+        let victim = if self.len() >= capacity { self.pop_lfu() } else { None };
+
+        self.add(key.clone(), 1, 1);
+        (Some((1, 1)), victim)
+    }
+}
+
+impl<K> Shadow<K>
+where
+    K: Eq + Hash,
+{
+    /// Clones a key the cache holds.
+    /// 
+    pub(crate) fn dup(&self, key: &K) -> K {
+        (self.clone.expect("a key was cached without `learn_clone()`"))(key)
+    }
+
+    /// Rebuilds the model from the cache's items, given in eviction order with
+    /// their versions and frequency counts.
+    /// 
+    pub(crate) fn sync(&mut self, items: impl Iterator<Item = (K, u64, usize)>)
+    {
+        self.items.clear();
+        self.order.clear();
+
+        for (key, version, freq) in items { self.add(key, version, freq); }
+
+        self.synced = true;
+    }
+
+    /// Returns the first item the model has that `present()` says the cache
+    /// doesn't.
+    /// 
+    pub(crate) fn missing(&self, present: impl Fn(&K) -> bool) 
+        -> Option<Item> 
+    {
+        self.items.iter().find(|(key, _)| !present(key)).map(|(_, item)| *item)
+    }
+
+    /// A hit on the key. Returns its new state.
+    /// 
+    pub(crate) fn get(&mut self, key: &K) -> State {
+        let item = self.take(key)?;
+
+        self.add(self.dup(key), item.version, item.freq + 1);
+        Some((item.version, item.freq + 1))
+    }
+
+    /// Removes the key. Returns the state it had.
+    /// 
+    pub(crate) fn remove(&mut self, key: &K) -> State {
+        self.take(key).map(|item| (item.version, item.freq))
+    }
+
+    /// Removes the item with the lowest frequency count, the least recently
+    /// used of those.
+    /// 
+    pub(crate) fn pop_lfu(&mut self) -> Option<(K, Item)> {
+        let (_, key) = self.order.pop_first()?;
+        let item     = self.items.remove(&key).unwrap();
+
+        Some((key, item))
+    }
+
+    fn add(&mut self, key: K, version: u64, freq: usize) {
+        self.tick += 1;
+        self.order.insert((freq, self.tick), self.dup(&key));
+        self.items.insert(key, Item { version, freq, last: self.tick });
+    }
+
+    fn take(&mut self, key: &K) -> Option<Item> {
+        let item = self.items.remove(key)?;
+
+        self.order.remove(&(item.freq, item.last));
+        Some(item)
+    }
+}
+
+/// Panics if the cache and the model disagree on `what`, after `op`.
+/// 
+#[track_caller]
+pub(crate) fn check<T>(op: &str, what: &str, model: T, cache: T)
+where
+    T: Debug + PartialEq,
+{
+    assert!(model == cache,
+            "debug-verify: {op} diverged from the model\n\
+             \x20 {what} in the model: {model:?}\n\
+             \x20 {what} in the cache: {cache:?}");
+}
+
+#[cfg(test)]
+thread_local! {
+    static FAULT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Makes the cache on this thread skip frequency increments, to show that the
+/// model notices.
+/// 
+#[cfg(test)]
+pub(crate) fn inject_fault(on: bool) {
+    FAULT.with(|fault| fault.set(on));
+}
+
+#[cfg(test)]
+pub(crate) fn fault() -> bool {
+    FAULT.with(|fault| fault.get())
+}
@@ -0,0 +1,89 @@
+//! `JsLfuCache`, an `LfuCache` for JavaScript through `wasm-bindgen`, such as
+//! in a browser extension. Its keys are strings and its values any JS value.
+//! In JS it's constructed with `new JsLfuCache(capacity)`, and 
+//! `JSON.stringify()` turns it into its keys and their frequency counts,
+//! hottest first, through `toJSON()`.
+//! 
+//! On `wasm32-unknown-unknown` the standard library's `Instant` panics, so
+//! with this feature the cache tells time with `performance.now()` there
+//! instead, see the `clock` module.
+//! 
+
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::LfuCache;
+
+/// An `LfuCache` of JS values by string keys, for JavaScript.
+/// 
+#[wasm_bindgen]
+pub struct JsLfuCache {
+    cache: LfuCache<String, JsValue>,
+}
+
+#[wasm_bindgen]
+impl JsLfuCache {
+    /// Creates a cache that holds up to `capacity` items.
+    /// 
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: usize) -> Self {
+        Self { cache: LfuCache::new(capacity) }
+    }
+
+    /// Inserts a key-value pair, evicting the LFU item if the cache is full.
+    /// 
+    pub fn insert(&mut self, key: String, value: JsValue) {
+        self.cache.insert(key, value);
+    }
+
+    /// Returns the key's value, counting an access, or `undefined` if it
+    /// isn't cached.
+    /// 
+    pub fn get(&mut self, key: String) -> Option<JsValue> {
+        self.cache.get(&key).cloned()
+    }
+
+    /// Removes the key and returns its value, or `undefined` if it wasn't
+    /// cached.
+    /// 
+    pub fn remove(&mut self, key: String) -> Option<JsValue> {
+        self.cache.remove(&key)
+    }
+
+    /// Returns the number of items in the cache.
+    /// 
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns `true` if the cache holds no items.
+    /// 
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Removes all items from the cache.
+    /// 
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Returns an array of `{ key, frequency }` objects, one for each item,
+    /// hottest first: the item `pop_lfu()` would remove first comes last.
+    /// 
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> Array {
+        let entries = self.cache.entries().collect::<Vec<_>>();
+
+        entries.into_iter().rev().map(|(key, _, freq)| {
+            let entry = Object::new();
+
+            // Setting a property of a plain object can't fail.
+            Reflect::set(&entry, &"key".into(), &key.into()).unwrap();
+            Reflect::set(&entry, &"frequency".into(), &freq.into()).unwrap();
+            JsValue::from(entry)
+        })
+        .collect()
+    }
+}
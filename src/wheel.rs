@@ -0,0 +1,465 @@
+//! The expiry index: a hierarchical timer wheel that files the keys of the
+//! items with TTLs by the time they expire. Time is counted in ticks of a
+//! millisecond. The wheel has six levels of 64 slots: a slot of the first
+//! level holds the keys due in one tick, a slot of the second those due in a
+//! span of 64 ticks, and so on out to about two years, past which keys wait
+//! in an overflow list.
+//! 
+//! As the wheel turns, the keys in each slot it reaches are either due or
+//! cascade down to the finer slots, and slots with nothing in them are
+//! skipped. So finding the expired keys takes time in proportion to their
+//! number, plus a step for each slot that holds any keys. Each key has one
+//! entry, which is moved when the key is rescheduled and removed when it
+//! leaves the cache.
+//! 
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// Each level has 2 to this power slots.
+const BITS   : u32   = 6;
+const SLOTS  : usize = 1 << BITS;
+const LEVELS : usize = 6;
+
+/// The lists past the slots: the keys too far off for the wheel, and the
+/// keys whose tick has come.
+const OVERFLOW : usize = LEVELS * SLOTS;
+const READY    : usize = OVERFLOW + 1;
+
+/// The end of a list.
+const NIL : usize = usize::MAX;
+
+/// Returns the tick `at` falls in.
+/// 
+fn tick(at: Duration) -> u64 {
+    u64::try_from(at.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// A key's entry in the wheel, linked into the list it's filed in.
+/// 
+#[derive(Clone, Debug)]
+struct Node<K> {
+    key  : K,
+    at   : Duration,
+    list : usize,
+    prev : usize,
+    next : usize,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TimerWheel<K> {
+    nodes    : Vec<Option<Node<K>>>,
+    free     : Vec<usize>,
+    index    : HashMap<K, usize>,
+    heads    : Vec<usize>,
+
+    // A bit for each slot of each level that holds any keys.
+    occupied : [u64; LEVELS],
+
+    // The tick the wheel has turned to. Keys due by then are ready.
+    now      : u64,
+}
+
+impl<K> TimerWheel<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            nodes    : Vec::new(),
+            free     : Vec::new(),
+            index    : HashMap::new(),
+            heads    : vec![NIL; READY + 1],
+            occupied : [0; LEVELS],
+            now      : 0,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Forgets every key. The wheel stays where it has turned to.
+    /// 
+    pub(crate) fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.index.clear();
+        self.heads.fill(NIL);
+        self.occupied = [0; LEVELS];
+    }
+
+    /// Returns every entry, in no particular order.
+    /// 
+    #[cfg(test)]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, Duration)> {
+        self.nodes.iter().flatten().map(|node| (&node.key, node.at))
+    }
+
+    /// Returns the entries due by `end`, along with some due up to a slot's
+    /// span later, for the caller to sift. Only the lists that can hold keys
+    /// due by then are visited.
+    /// 
+    pub(crate) fn due_by(&self, end: Duration)
+        -> impl Iterator<Item = (&K, Duration)>
+    {
+        let end       = tick(end);
+        let mut lists = vec![READY];
+
+        // The slots ahead of the cursors start later at each level.
+        'levels: for level in 0..LEVELS {
+            let mut slots = self.ahead(level);
+
+            while slots != 0 {
+                let slot = slots.trailing_zeros() as usize;
+
+                if self.start(level, slot) > end { break 'levels; }
+
+                lists.push(level * SLOTS + slot);
+                slots &= slots - 1;
+            }
+        }
+        if self.overflow_start().is_some_and(|start| start <= end) {
+            lists.push(OVERFLOW);
+        }
+        lists.into_iter().flat_map(|list| self.entries(list))
+    }
+
+    /// Returns the entries in a list.
+    /// 
+    fn entries(&self, list: usize) -> impl Iterator<Item = (&K, Duration)> {
+        let next = |i: usize| Some(i).filter(|&i| i != NIL);
+
+        std::iter::successors(next(self.heads[list]), move |&i| {
+            next(self.node(i).next)
+        })
+        .map(|i| (&self.node(i).key, self.node(i).at))
+    }
+
+    fn node(&self, i: usize) -> &Node<K> {
+        self.nodes[i].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, i: usize) -> &mut Node<K> {
+        self.nodes[i].as_mut().unwrap()
+    }
+
+    /// Returns the bits of the slots of `level` that are past its cursor and
+    /// hold keys.
+    /// 
+    fn ahead(&self, level: usize) -> u64 {
+        let cursor = (self.now >> (BITS * level as u32)) as usize & (SLOTS - 1);
+
+        self.occupied[level] & (u64::MAX << cursor << 1)
+    }
+
+    /// Returns the first tick of a slot past the cursor of its level.
+    /// 
+    fn start(&self, level: usize, slot: usize) -> u64 {
+        let shift = BITS * level as u32;
+
+        ((self.now >> shift >> BITS << BITS) | slot as u64) << shift
+    }
+
+    /// Returns the tick at which the wheel comes full circle and the keys in
+    /// the overflow list are filed again, if there are any.
+    /// 
+    fn overflow_start(&self) -> Option<u64> {
+        const SPAN: u32 = BITS * LEVELS as u32;
+
+        if self.heads[OVERFLOW] == NIL { return None; }
+
+        ((self.now >> SPAN) + 1).checked_mul(1 << SPAN)
+    }
+
+    /// Returns the list a key due at `at` is filed in: the slot of the
+    /// highest level whose digit of the tick differs from the cursor's.
+    /// 
+    fn list_for(&self, at: Duration) -> usize {
+        let at = tick(at);
+
+        if at <= self.now { return READY; }
+
+        let level = ((u64::BITS - 1 - (at ^ self.now).leading_zeros())
+                     / BITS) as usize;
+
+        if level >= LEVELS { return OVERFLOW; }
+
+        level * SLOTS + ((at >> (BITS * level as u32)) as usize & (SLOTS - 1))
+    }
+
+    fn link(&mut self, i: usize, list: usize) {
+        let head = self.heads[list];
+        let node = self.node_mut(i);
+
+        node.list = list;
+        node.prev = NIL;
+        node.next = head;
+
+        if head != NIL { self.node_mut(head).prev = i; }
+
+        self.heads[list] = i;
+
+        if list < OVERFLOW {
+            self.occupied[list / SLOTS] |= 1 << (list % SLOTS);
+        }
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let Node { list, prev, next, .. } = *self.node(i);
+
+        match prev {
+            NIL  => self.heads[list] = next,
+            prev => self.node_mut(prev).next = next,
+        }
+        if next != NIL { self.node_mut(next).prev = prev; }
+
+        if list < OVERFLOW && self.heads[list] == NIL {
+            self.occupied[list / SLOTS] &= !(1 << (list % SLOTS));
+        }
+    }
+
+    /// Turns the wheel to the tick `to`, filing the keys of each slot it
+    /// reaches on the way again, relative to the slot's first tick. Those
+    /// whose tick has come are ready, and the rest cascade down a level or
+    /// more.
+    /// 
+    fn advance(&mut self, to: u64) {
+        while self.now < to {
+            let next = (0..LEVELS).find_map(|level| {
+                let slots = self.ahead(level);
+                let slot  = slots.trailing_zeros() as usize;
+
+                (slots != 0).then(|| (level * SLOTS + slot,
+                                      self.start(level, slot)))
+            })
+            .or_else(|| self.overflow_start().map(|start| (OVERFLOW, start)));
+
+            // Until the next slot that holds keys, there's nothing to do.
+            let Some((list, start)) = next.filter(|&(_, at)| at <= to) else {
+                self.now = to;
+                break;
+            };
+            self.now = start;
+
+            let mut i = self.heads[list];
+
+            while i != NIL {
+                let next = self.node(i).next;
+
+                self.unlink(i);
+                self.link(i, self.list_for(self.node(i).at));
+                i = next;
+            }
+        }
+    }
+}
+
+impl<K> TimerWheel<K>
+where
+    K: Eq + Hash + Clone,
+{
+    /// Returns the time the key is due at, if it's in the wheel.
+    /// 
+    #[cfg(test)]
+    pub(crate) fn get(&self, key: &K) -> Option<Duration> {
+        self.index.get(key).map(|&i| self.node(i).at)
+    }
+
+    /// Files the key as due at `at`, in place of its entry if it has one.
+    /// 
+    pub(crate) fn schedule(&mut self, key: K, at: Duration) {
+        let i = match self.index.get(&key) {
+            Some(&i) => {
+                self.unlink(i);
+                self.node_mut(i).at = at;
+                i
+            },
+            None => {
+                let node = Some(Node { key  : key.clone(),
+                                       at,
+                                       list : NIL,
+                                       prev : NIL,
+                                       next : NIL });
+                let i = match self.free.pop() {
+                    Some(i) => { self.nodes[i] = node; i },
+                    None    => { self.nodes.push(node); self.nodes.len() - 1 },
+                };
+                self.index.insert(key, i);
+                i
+            },
+        };
+        self.link(i, self.list_for(at));
+    }
+}
+
+impl<K> TimerWheel<K>
+where
+    K: Eq + Hash,
+{
+    /// Removes the key's entry, returning `true` if it had one.
+    /// 
+    pub(crate) fn cancel(&mut self, key: &K) -> bool {
+        let Some(&i) = self.index.get(key) else { return false };
+
+        self.release(i);
+        true
+    }
+
+    /// Takes up to `max_items` of the entries due by `cutoff` out of the
+    /// wheel, turning it to `cutoff` first.
+    /// 
+    pub(crate) fn take_due(&mut self, cutoff: Duration, max_items: usize)
+        -> Vec<(K, Duration)>
+    {
+        self.advance(tick(cutoff));
+
+        // The keys due later in the same tick stay ready.
+        let mut due = Vec::new();
+        let mut i   = self.heads[READY];
+
+        while i != NIL && due.len() < max_items {
+            let next = self.node(i).next;
+
+            if self.node(i).at <= cutoff {
+                let node = self.release(i);
+
+                due.push((node.key, node.at));
+            }
+            i = next;
+        }
+        due
+    }
+
+    /// Returns `true` if any entries are due by `cutoff`, turning the wheel
+    /// to it.
+    /// 
+    pub(crate) fn has_due(&mut self, cutoff: Duration) -> bool {
+        self.advance(tick(cutoff));
+        self.entries(READY).any(|(_, at)| at <= cutoff)
+    }
+
+    /// Removes an entry and returns it.
+    /// 
+    fn release(&mut self, i: usize) -> Node<K> {
+        self.unlink(i);
+        self.free.push(i);
+
+        let node = self.nodes[i].take().unwrap();
+
+        self.index.remove(&node.key);
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// Checks that each entry is filed where it belongs, and returns the
+    /// level of each key's slot, or `LEVELS` for the overflow list and
+    /// `LEVELS + 1` for the ready list.
+    /// 
+    fn levels(wheel: &TimerWheel<u32>) -> Vec<(u32, usize)> {
+        let mut levels = (0..=READY).flat_map(|list| {
+            wheel.entries(list).map(move |(&key, at)| {
+                assert_eq!(wheel.list_for(at), list);
+                (key, if list == READY { LEVELS + 1 } else { list / SLOTS })
+            })
+        })
+        .collect::<Vec<_>>();
+
+        for (level, &bits) in wheel.occupied.iter().enumerate() {
+            for slot in 0..SLOTS {
+                assert_eq!(bits & (1 << slot) != 0,
+                           wheel.heads[level * SLOTS + slot] != NIL);
+            }
+        }
+        assert_eq!(levels.len(), wheel.len());
+        levels.sort();
+        levels
+    }
+
+    #[test]
+    fn keys_cascade_down_the_levels() {
+        let mut wheel = TimerWheel::new();
+        let ms        = Duration::from_millis;
+        let top       = 1u64 << (BITS * LEVELS as u32);
+
+        // Around the edges of the first and second levels, and past the
+        // wheel's reach.
+        for (key, at) in [(1, 63), (2, 64), (3, 4095), (4, 4096), (5, 4097),
+                          (6, top - 1), (7, top + 5)]
+        {
+            wheel.schedule(key, ms(at));
+        }
+
+        assert_eq!(levels(&wheel), [(1, 0), (2, 1), (3, 1), (4, 2), (5, 2),
+                                    (6, 5), (7, 6)]);
+        assert!(wheel.take_due(62 * MS, usize::MAX).is_empty());
+        assert_eq!(wheel.take_due(63 * MS, usize::MAX), [(1, 63 * MS)]);
+
+        // Turning over the first level brings the second's slot down.
+        assert!(!wheel.has_due(Duration::from_micros(63_999)));
+        assert_eq!(wheel.take_due(64 * MS, usize::MAX), [(2, 64 * MS)]);
+        assert_eq!(levels(&wheel), [(3, 1), (4, 2), (5, 2), (6, 5), (7, 6)]);
+
+        // And over the second level, the third's, down to the first.
+        assert_eq!(wheel.take_due(4095 * MS, usize::MAX), [(3, 4095 * MS)]);
+        assert_eq!(levels(&wheel), [(4, 2), (5, 2), (6, 5), (7, 6)]);
+        assert_eq!(wheel.take_due(4096 * MS, usize::MAX), [(4, 4096 * MS)]);
+        assert_eq!(levels(&wheel), [(5, 0), (6, 5), (7, 6)]);
+
+        // Empty slots are skipped over, however far the wheel turns.
+        let mut due = wheel.take_due(ms(top), usize::MAX);
+
+        due.sort();
+        assert_eq!(due, [(5, ms(4097)), (6, ms(top - 1))]);
+        assert_eq!(levels(&wheel), [(7, 0)]);
+        assert!(wheel.has_due(ms(top + 5)));
+        assert_eq!(wheel.take_due(Duration::MAX, 1).len(), 1);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn rescheduled_keys_move() {
+        let mut wheel = TimerWheel::new();
+
+        for key in 0..10 { wheel.schedule(key, (key + 1) * 100 * MS); }
+
+        // Sooner, later, out and back in.
+        wheel.schedule(9, 50 * MS);
+        wheel.schedule(0, 5000 * MS);
+        assert!(wheel.cancel(&1));
+        assert!(!wheel.cancel(&1));
+        wheel.schedule(1, 2 * MS);
+
+        assert_eq!(wheel.get(&0), Some(5000 * MS));
+        assert_eq!(wheel.len(), 10);
+
+        let due = wheel.take_due(300 * MS, usize::MAX);
+
+        assert_eq!(due.len(), 3);
+        assert!(due.contains(&(1, 2 * MS)) && due.contains(&(9, 50 * MS)));
+        assert!(due.contains(&(2, 300 * MS)));
+        assert_eq!(wheel.take_due(300 * MS, usize::MAX), []);
+
+        // Keys filed behind the wheel are due right away, to the nanosecond.
+        let at = 300 * MS - Duration::from_nanos(1);
+
+        wheel.schedule(20, at);
+        wheel.schedule(21, 300 * MS + Duration::from_nanos(1));
+        assert_eq!(wheel.take_due(300 * MS, usize::MAX), [(20, at)]);
+
+        let mut soon = wheel.due_by(500 * MS).map(|(&key, _)| key)
+                                             .collect::<Vec<_>>();
+        soon.sort();
+        assert_eq!(soon, [3, 4, 21]);
+        assert_eq!(levels(&wheel).len(), 8);
+    }
+}
@@ -0,0 +1,92 @@
+//! Checks that the caches, and the views and iterators they hand out, are
+//! `Send` and `Sync` whenever what they hold is, and aren't when it isn't.
+//! 
+
+#![cfg(feature = "std")]
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+use lfu_cache::*;
+
+type Bytes = Arc<[u8]>;
+
+// The caches.
+assert_impl_all!(LfuCache<String, Bytes>: Send, Sync);
+assert_impl_all!(Arc<Mutex<LfuCache<String, Bytes>>>: Send, Sync);
+assert_impl_all!(LfuCacheBuilder: Send, Sync);
+assert_impl_all!(SyncLfuCache<String, Bytes>: Send, Sync);
+assert_impl_all!(ApproxLfuCache<String, Bytes>: Send, Sync);
+assert_impl_all!(BTreeLfuCache<String, Bytes>: Send, Sync);
+assert_impl_all!(ArrayLfuCache<u64, Bytes, 8>: Send, Sync);
+assert_impl_all!(LfuSet<String>: Send, Sync);
+assert_impl_all!(StaticLfuCache<String, Bytes>: Send, Sync);
+assert_impl_all!(LfuSnapshot<String, Bytes>: Send, Sync);
+assert_impl_all!(StoreCache<String, Bytes, MemoryStore<String, Bytes>>: Send,
+                                                                       Sync);
+assert_impl_all!(LoadingCache<String, Bytes, fn(&String) -> Result<Bytes, ()>>:
+                 Send, Sync);
+
+// What's `Send` about a cache is what it holds.
+assert_impl_all!(LfuCache<u64, Cell<u64>>: Send);
+assert_not_impl_any!(LfuCache<u64, Cell<u64>>: Sync);
+assert_not_impl_any!(LfuCache<Rc<u64>, u64>: Send, Sync);
+assert_not_impl_any!(LfuCache<u64, Rc<u64>>: Send, Sync);
+assert_impl_all!(SyncLfuCache<u64, Cell<u64>>: Send, Sync);
+assert_not_impl_any!(SyncLfuCache<u64, Rc<u64>>: Send, Sync);
+
+// Views and iterators.
+assert_impl_all!(Iter<'static, String, Bytes>: Send, Sync);
+assert_impl_all!(EvictionOrder<'static, String>: Send, Sync);
+assert_impl_all!(EvictionOrderMut<'static, String, Bytes>: Send, Sync);
+assert_impl_all!(ValuesLfuFirst<'static, String, Bytes, DefaultState>: Send,
+                                                                     Sync);
+assert_impl_all!(ValuesLfuFirstMut<'static, String, Bytes>: Send, Sync);
+assert_impl_all!(Entries<'static, String, Bytes>: Send, Sync);
+assert_impl_all!(IntoEntries<String, Bytes>: Send, Sync);
+assert_impl_all!(Bucket<'static, String, Bytes>: Send, Sync);
+assert_impl_all!(Buckets<'static, String, Bytes, DefaultState>: Send, Sync);
+assert_impl_all!(BucketIter<'static, String, Bytes, DefaultState>: Send, Sync);
+assert_impl_all!(KeysInInsertionOrder<'static, String>: Send, Sync);
+assert_impl_all!(SetIter<'static, String>: Send, Sync);
+assert_impl_all!(OccupiedEntry<'static, String, Bytes>: Send, Sync);
+
+// A mutable iterator hands out `&mut V`, so it can be sent when `V` can, but
+// only shared when `V` can be.
+assert_impl_all!(EvictionOrderMut<'static, u64, Cell<u64>>: Send);
+assert_not_impl_any!(EvictionOrderMut<'static, u64, Cell<u64>>: Sync);
+assert_not_impl_any!(EvictionOrderMut<'static, Rc<u64>, u64>: Send, Sync);
+assert_not_impl_any!(EvictionOrderMut<'static, u64, Rc<u64>>: Send, Sync);
+
+// Guards and notifications.
+assert_impl_all!(EntryGuard<String>: Send, Sync);
+assert_not_impl_any!(EntryGuard<Rc<u64>>: Send, Sync);
+assert_impl_all!(Evicted<String, Bytes>: Send, Sync);
+assert_impl_all!(EvictionReceiver<String, Bytes>: Send);
+
+#[cfg(feature = "tokio")]
+assert_impl_all!(PurgeTaskHandle: Send, Sync);
+
+#[test]
+fn cache_can_be_shared_behind_a_mutex() {
+    let cache = Arc::new(Mutex::new(LfuCache::<String, Bytes>::new(8)));
+
+    let handles = (0..4).map(|i| {
+        let cache = cache.clone();
+
+        std::thread::spawn(move || {
+            let mut cache = cache.lock().unwrap();
+
+            cache.insert(i.to_string(), Bytes::from(vec![i; 4]));
+            cache.get(&i.to_string()).map(|bytes| bytes.len())
+        })
+    }).collect::<Vec<_>>();
+
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), Some(4));
+    }
+    assert_eq!(cache.lock().unwrap().len(), 4);
+}
@@ -0,0 +1,74 @@
+//! Checks that `LfuCache` works as a store of the `cached` crate, through the
+//! `Cached` trait and behind the `#[cached]` macro.
+//! 
+
+#![cfg(feature = "cached")]
+
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use cached::macros::cached;
+use cached::Cached;
+use lfu_cache::LfuCache;
+
+/// Drives any store the same way, looking keys up by `&str`.
+/// 
+fn exercise<C: Cached<String, u64>>(store: &mut C) {
+    assert_eq!(store.cache_set("a".to_string(), 1), None);
+    assert_eq!(store.cache_set("b".to_string(), 2), None);
+    assert_eq!(store.cache_set("a".to_string(), 10), Some(1));
+    assert_eq!(store.cache_get("a"), Some(&10));
+    assert_eq!(store.cache_get("z"), None);
+
+    *store.cache_get_mut("b").unwrap() += 1;
+    assert_eq!(store.cache_get("b"), Some(&3));
+    assert_eq!(*store.cache_get_or_set_with("c".to_string(), || 4), 4);
+    assert_eq!(store.cache_try_get_or_set_with("c".to_string(), || {
+        Err::<u64, ()>(())
+    }), Ok(&4));
+    assert!(store.cache_contains("c"));
+    assert_eq!(store.cache_size(), 3);
+    assert_eq!(store.cache_remove("c"), Some(4));
+    assert_eq!(store.cache_remove_entry("b"), Some(("b".to_string(), 3)));
+    assert_eq!(store.cache_get_or_set_with("d".to_string(), || 5), &5);
+}
+
+#[test]
+fn works_through_the_trait() {
+    let mut cache = LfuCache::new(3);
+
+    cache.enable_stats();
+    exercise(&mut cache);
+
+    assert_eq!(cache.cache_hits(), Some(4));
+    assert_eq!(cache.cache_misses(), Some(3));
+    assert_eq!(cache.cache_capacity(), Some(3));
+
+    // "a" has been used more than "d", which goes to make room.
+    cache.cache_set("e".to_string(), 6);
+    cache.cache_set("f".to_string(), 7);
+    assert_eq!(cache.cache_evictions(), Some(1));
+    assert!(!cache.cache_contains("d"));
+
+    cache.cache_reset();
+    assert_eq!(cache.cache_size(), 0);
+    assert_eq!(cache.cache_hits(), Some(0));
+    assert_eq!(LfuCache::<String, u64>::new(1).cache_hits(), None);
+}
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[cached(ty = "LfuCache<u64, u64>", create = "{ LfuCache::new(2) }")]
+fn square(n: u64) -> u64 {
+    CALLS.fetch_add(1, Relaxed);
+    n * n
+}
+
+#[test]
+fn backs_a_cached_function() {
+    for n in [1, 1, 1, 2, 3, 1, 3, 2] {
+        assert_eq!(square(n), n * n);
+    }
+    // 3 evicts 2, and 2 evicts 3, while 1 is used too often to go.
+    assert_eq!(CALLS.load(Relaxed), 4);
+    assert_eq!(SQUARE.read().cache_size(), 2);
+}
@@ -0,0 +1,94 @@
+//! Formats each of the crate's `defmt::Format` types through a logger that
+//! keeps the encoded frames, so the impls are run and not just compiled.
+//! 
+
+#![cfg(feature = "defmt")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use lfu_cache::{ArrayLfuCache, CacheStats};
+
+static FRAMES : Mutex<Vec<u8>> = Mutex::new(Vec::new());
+static TAKEN  : AtomicBool     = AtomicBool::new(false);
+
+#[defmt::global_logger]
+struct Capture;
+
+unsafe impl defmt::Logger for Capture {
+    fn acquire() {
+        while TAKEN.swap(true, Ordering::Acquire) { std::hint::spin_loop(); }
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        TAKEN.store(false, Ordering::Release);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        FRAMES.lock().unwrap().extend_from_slice(bytes);
+    }
+}
+
+// The logger has to supply time stamps, even though `println!` doesn't use
+// them.
+defmt::timestamp!("{=u64}", 0);
+
+/// Formats the value and returns the frame it was encoded to.
+/// 
+fn emit<T: defmt::Format>(value: &T) -> Vec<u8> {
+    defmt::println!("{}", value);
+
+    std::mem::take(&mut *FRAMES.lock().unwrap())
+}
+
+// Values aren't formatted, so they needn't implement `Format`.
+struct Opaque;
+
+#[test]
+fn formats_every_type() {
+    let mut array = ArrayLfuCache::<u8, Opaque, 4>::new();
+    let empty     = emit(&array);
+
+    array.insert(1, Opaque);
+    array.insert(2, Opaque);
+    array.get(&1);
+
+    assert!(!empty.is_empty());
+    assert_ne!(emit(&array), empty);
+    assert!(!emit(&CacheStats::default()).is_empty());
+
+    #[cfg(feature = "std")]
+    {
+        use lfu_cache::{Evicted, EvictionReason, LfuCache, Policy};
+
+        let mut cache = LfuCache::new(4);
+        let empty     = emit(&cache);
+
+        cache.insert("a", Opaque);
+        cache.get(&"a");
+
+        assert_ne!(emit(&cache), empty);
+        assert!(!emit(&Policy::LfuDa).is_empty());
+        assert!(!emit(&Evicted { key    : 1u8,
+                                 value  : 2u8,
+                                 reason : EvictionReason::Expired })
+                                 .is_empty());
+    }
+    #[cfg(feature = "debug-audit")]
+    {
+        use lfu_cache::AuditEvent;
+
+        let mut cache = lfu_cache::LfuCache::new(2);
+
+        cache.enable_audit_log(4);
+        cache.insert(1u8, Opaque);
+
+        for record in cache.audit_log() {
+            assert!(!emit(&record).is_empty());
+            assert!(!emit(&record.event).is_empty());
+        }
+        assert!(!emit(&AuditEvent::<u8>::Cleared).is_empty());
+    }
+}
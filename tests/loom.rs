@@ -0,0 +1,92 @@
+//! Model checks of the thread-safe wrappers with loom, which runs each test
+//! under every interleaving of its threads. They only build with `--cfg loom`:
+//! 
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --test loom --release
+//! ```
+//! 
+
+#![cfg(all(loom, feature = "std"))]
+
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+
+use lfu_cache::{EvictionReason, SyncLfuCache};
+
+/// Runs `f` under loom, bounding the preemptions per interleaving, which
+/// finds most ordering bugs in a fraction of the time an exhaustive search
+/// takes. `LOOM_MAX_PREEMPTIONS` overrides the bound.
+/// 
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+
+    builder.preemption_bound.get_or_insert(3);
+    builder.check(f);
+}
+
+#[test]
+fn concurrent_misses_load_once() {
+    model(|| {
+        let cache = Arc::new(SyncLfuCache::new(4));
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let threads = (0..2).map(|_| {
+            let (cache, loads) = (cache.clone(), loads.clone());
+
+            thread::spawn(move || {
+                cache.get_or_insert_with(1, || {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    10
+                })
+            })
+        }).collect::<Vec<_>>();
+
+        for thread in threads { assert_eq!(thread.join().unwrap(), 10); }
+
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get(&1), Some(10));
+    });
+}
+
+#[test]
+fn evictions_reach_the_receiver() {
+    model(|| {
+        let cache    = Arc::new(SyncLfuCache::new(1));
+        let receiver = cache.eviction_receiver(4);
+
+        cache.insert(0, 0);
+
+        // Two writers race, each evicting whatever is cached, while the
+        // receiver takes the messages on a thread of its own until the cache
+        // is gone.
+        let reader = thread::spawn(move || {
+            let mut keys = Vec::new();
+
+            while let Some(evicted) = receiver.recv() {
+                assert_eq!(evicted.reason, EvictionReason::Capacity);
+                keys.push(evicted.key);
+            }
+            (keys, receiver.dropped())
+        });
+        let writer = thread::spawn({
+            let cache = cache.clone();
+
+            move || cache.insert(1, 1)
+        });
+        cache.insert(2, 2);
+        writer.join().unwrap();
+
+        let last = Arc::try_unwrap(cache).ok().unwrap().into_inner();
+        let kept = *last.eviction_candidate().unwrap();
+
+        drop(last);
+
+        let (mut keys, dropped) = reader.join().unwrap();
+
+        keys.push(kept);
+        keys.sort();
+        assert_eq!(keys, [0, 1, 2]);
+        assert_eq!(dropped, 0);
+    });
+}
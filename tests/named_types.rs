@@ -0,0 +1,44 @@
+//! Checks that the types returned by `LfuCache` can be named from outside the
+//! crate.
+//! 
+
+#![cfg(feature = "std")]
+
+use lfu_cache::{Bucket, DefaultState, EvictionOrder, Iter, LfuCache};
+use lfu_cache::ValuesLfuFirst;
+
+struct Wrapper<'a> {
+    it: Iter<'a, String, u64>,
+}
+
+struct Views<'a> {
+    order   : EvictionOrder<'a, String>,
+    values  : ValuesLfuFirst<'a, String, u64, DefaultState>,
+    coldest : Option<Bucket<'a, String, u64>>,
+}
+
+#[test]
+fn iterators_can_be_stored() {
+    let mut cache = LfuCache::new(4);
+
+    cache.insert("a".to_string(), 1);
+    cache.insert("b".to_string(), 2);
+    cache.get(&"b".to_string());
+
+    let wrapper = Wrapper { it: cache.iter() };
+    let mut items = wrapper.it.map(|(k, v)| (k.clone(), *v))
+                              .collect::<Vec<_>>();
+
+    items.sort();
+    assert_eq!(items, [("a".to_string(), 1), ("b".to_string(), 2)]);
+
+    let views = Views {
+        order   : cache.eviction_order(),
+        values  : cache.values_lfu_first(),
+        coldest : cache.peek_bucket(1),
+    };
+    assert_eq!(format!("{:?}", views.order), r#"["a", "b"]"#);
+    assert_eq!(format!("{:?}", views.values), "[1, 2]");
+    assert_eq!(format!("{:?}", views.coldest.unwrap()), 
+               r#"Bucket { frequency: 1, keys: ["a"] }"#);
+}
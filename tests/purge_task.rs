@@ -0,0 +1,61 @@
+//! Checks that the purge task drops expired items on its own, in tokio's
+//! virtual time.
+//! 
+
+#![cfg(feature = "tokio")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use lfu_cache::{spawn_purge_task, LfuCache, PurgeStats, SyncLfuCache};
+use lfu_cache::TokioClock;
+
+fn cache() -> Arc<SyncLfuCache<u32, u32>> {
+    let mut cache = LfuCache::new(16);
+
+    cache.set_clock(TokioClock::new());
+
+    for i in 0..10 {
+        cache.insert_with_ttl(i, i, Duration::from_secs(1 + i as u64 / 5));
+    }
+    cache.insert(10, 10);
+    Arc::new(SyncLfuCache::from_cache(cache))
+}
+
+#[tokio::test(start_paused = true)]
+async fn expired_items_disappear() {
+    let cache  = cache();
+    let handle = spawn_purge_task(cache.clone(), Duration::from_millis(500));
+    let sleep  = |ms| tokio::time::sleep(Duration::from_millis(ms));
+
+    // Ticks at 0.5s, 1s, 1.5s and 2s.
+    sleep(750).await;
+    assert_eq!(cache.len(), 11);
+    assert_eq!(handle.stats().ticks, 1);
+
+    sleep(500).await;
+    assert_eq!(cache.len(), 6);
+    assert_eq!(handle.stats().last_purged, 5);
+
+    sleep(1_000).await;
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.get(&10), Some(10));
+
+    let stats = handle.shutdown().await;
+
+    assert_eq!(stats, PurgeStats { ticks: 4, purged: 10, last_purged: 5 });
+}
+
+#[tokio::test(start_paused = true)]
+async fn dropping_the_handle_stops_the_task() {
+    let cache  = cache();
+    let handle = spawn_purge_task(cache.clone(), Duration::from_millis(500));
+
+    tokio::time::sleep(Duration::from_millis(750)).await;
+    assert!(!handle.is_finished());
+    drop(handle);
+
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    assert_eq!(cache.len(), 11);
+    assert_eq!(Arc::strong_count(&cache), 1);
+}
@@ -0,0 +1,69 @@
+//! Process-wide caches in `static`s, shared by several threads.
+//! 
+
+#![cfg(feature = "std")]
+
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::thread;
+
+use lfu_cache::{LfuCache, LfuCacheBuilder, StaticLfuCache};
+
+static FIBS: StaticLfuCache<u64, u64> = StaticLfuCache::new(128);
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn fib(n: u64) -> u64 {
+    if n < 2 { return n; }
+
+    if let Some(fib) = FIBS.get(&n) { return fib; }
+
+    CALLS.fetch_add(1, Relaxed);
+
+    let fib = fib(n - 1) + fib(n - 2);
+    FIBS.insert(n, fib);
+    fib
+}
+
+#[test]
+fn memoizes_across_threads() {
+    let threads = (0..8).map(|i| thread::spawn(move || fib(60 + i % 2)))
+                        .collect::<Vec<_>>();
+
+    for (i, thread) in threads.into_iter().enumerate() {
+        let exp = [1_548_008_755_920, 2_504_730_781_961][i % 2];
+
+        assert_eq!(thread.join().unwrap(), exp);
+    }
+    // Threads racing on the same `n` can each work it out, but not many 
+    // times over.
+    assert_eq!(FIBS.len(), 60);
+    assert!(CALLS.load(Relaxed) < 60 * 8);
+}
+
+fn with_stats(capacity: usize) -> LfuCache<String, usize> {
+    LfuCacheBuilder::new(capacity).stats().build()
+}
+
+static WORDS: StaticLfuCache<String, usize> = 
+    StaticLfuCache::with_init(2, with_stats);
+
+#[test]
+fn is_built_once_on_first_use() {
+    assert!(!WORDS.is_initialized());
+
+    thread::scope(|s| {
+        for word in ["a", "bb", "a", "ccc", "a"] {
+            s.spawn(move || {
+                WORDS.get_or_insert_with(word.to_string(), || word.len())
+            });
+        }
+    });
+    assert!(WORDS.is_initialized());
+    assert_eq!(WORDS.cache().lock().capacity(), 2);
+
+    // Each of the three words missed at least once, in the one cache.
+    let stats = WORDS.lock().stats().cloned().unwrap();
+
+    assert!(stats.misses >= 3);
+    assert!(WORDS.len() <= 2);
+}
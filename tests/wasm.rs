@@ -0,0 +1,82 @@
+//! Tests of `JsLfuCache` with wasm-bindgen-test. They only build for wasm32,
+//! and run under node with wasm-bindgen's test runner:
+//! 
+//! ```text
+//! CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER=wasm-bindgen-test-runner \
+//!     cargo test --target wasm32-unknown-unknown --features wasm --test wasm
+//! ```
+//! 
+
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use std::time::Duration;
+
+use js_sys::{Array, Reflect, JSON};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use lfu_cache::{JsLfuCache, LfuCache, SystemClock};
+
+#[wasm_bindgen_test]
+fn basic_operations() {
+    let mut cache = JsLfuCache::new(2);
+
+    assert!(cache.is_empty());
+    cache.insert("a".into(), JsValue::from(1));
+    cache.insert("b".into(), JsValue::from_str("two"));
+
+    assert_eq!(cache.get("a".into()), Some(JsValue::from(1)));
+    assert_eq!(cache.len(), 2);
+
+    // "b" is the LFU item, so it makes room for "c".
+    cache.insert("c".into(), JsValue::NULL);
+
+    assert_eq!(cache.get("b".into()), None);
+    assert_eq!(cache.get("c".into()), Some(JsValue::NULL));
+    assert_eq!(cache.remove("a".into()), Some(JsValue::from(1)));
+    assert_eq!(cache.remove("a".into()), None);
+    assert_eq!(cache.len(), 1);
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[wasm_bindgen_test]
+fn json_lists_keys_and_frequencies_hottest_first() {
+    let mut cache = JsLfuCache::new(4);
+
+    cache.insert("cold".into(), JsValue::TRUE);
+    cache.insert("hot".into(), JsValue::FALSE);
+    for _ in 0..2 { cache.get("hot".into()); }
+
+    let entries = cache.to_json();
+    let field   = |i: u32, name: &str| {
+        Reflect::get(&entries.get(i), &name.into()).unwrap()
+    };
+    assert_eq!(entries.length(), 2);
+    assert_eq!(field(0, "key"), "hot");
+    assert_eq!(field(0, "frequency"), 3);
+    assert_eq!(field(1, "key"), "cold");
+    assert_eq!(field(1, "frequency"), 1);
+
+    // `JSON.stringify()` goes through `toJSON()`.
+    let json = JSON::stringify(&JsValue::from(cache)).unwrap();
+
+    assert_eq!(String::from(json), 
+               r#"[{"key":"hot","frequency":3},{"key":"cold","frequency":1}]"#);
+    assert!(Array::is_array(&entries));
+}
+
+#[wasm_bindgen_test]
+fn time_based_features_work() {
+    let mut cache = LfuCache::new(4);
+
+    cache.set_clock(SystemClock::new());
+    cache.enable_stats();
+    cache.insert_with_ttl(1, 1, Duration::from_secs(60));
+
+    assert!(cache.remaining_ttl(&1).duration().is_some());
+    assert_eq!(cache.get_or_insert_with(2, || 2), &2);
+    assert_eq!(cache.purge_expired(), 0);
+    assert!(!cache.run_pending_tasks().work_remains);
+}